@@ -1,39 +1,22 @@
 use std::sync::Arc;
 use std::f32::consts::*;
+use std::path::Path;
 
 use clap::*;
 
-use light::cameras::{Camera, PerspectiveCamera, OrthographicCamera, HemisphereCamera, SphereCamera, PerspectiveLensCamera};
 use light::film::Film;
 use light::filters::{BoxFilter, GaussianFilter, CachingFilter};
+use light::color::Color;
+use light::integrators::{Integrator, AOIntegrator, BDPTIntegrator, DebugIntegrator, DebugMode, PathIntegrator, SPPMIntegrator, VolPathIntegrator, WhittedIntegrator};
+use light::loaders::{load_obj, load_ply, load_pbrt, load_scene, SceneDescription, CameraDescription, CameraKind};
+use light::lights::{DistantLight, InfiniteAreaLight};
+use light::materials::{Material, MatteMaterial};
 use light::scene::Scene;
+use light::textures::{CheckerboardTexture, ConstantTexture, UVMapping2D};
 use light::shapes::{Sphere, Disc, Cylinder, Paraboloid, Plane, Cone};
 use light::renderer::{render, RendererSetup};
-use light::geometry::{Vector, Trans};
-use light::sampler::{SamplerFactory2D, Sampler2D, CentersSampler2D, LHCSampler2D};
-
-
-struct SamplerFactory {
-    n : usize,
-}
-
-impl SamplerFactory {
-    pub fn new(n : usize) -> SamplerFactory {
-        SamplerFactory { n: n }
-    }
-}
-
-impl SamplerFactory2D for SamplerFactory {
-    fn get_sampler(&self) -> Box<dyn Sampler2D> {
-        if self.n == 1 {
-            Box::new(CentersSampler2D::new())
-        } else {
-            Box::new(LHCSampler2D::new(self.n))
-            // let w = (self.n as f32).sqrt().round() as usize;
-            // Box::new(StrataSampler2D::new(w, w))
-        }
-    }
-}
+use light::geometry::{Transform, Point, Vector, Trans};
+use light::sampler::Sampler2DKind;
 
 
 fn get_app<'a, 'b>() -> App<'a, 'b> {
@@ -82,101 +65,301 @@ fn get_app<'a, 'b>() -> App<'a, 'b> {
                 .takes_value(true)
                 .default_value("10")
                 .required_if("camera", "ortho"))
+        .arg(Arg::with_name("eye")
+                .long("eye")
+                .value_name("X,Y,Z")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .validator(validate_triple)
+                .default_value("0,0,0"))
+        .arg(Arg::with_name("look-at")
+                .long("look-at")
+                .value_name("X,Y,Z")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .validator(validate_triple)
+                .default_value("0,0,1"))
+        .arg(Arg::with_name("up")
+                .long("up")
+                .value_name("X,Y,Z")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .validator(validate_triple)
+                .default_value("0,1,0"))
         .arg(Arg::with_name("samples")
                 .long("samples")
                 .value_name("N")
                 .takes_value(true)
                 .default_value("16"))
+        .arg(Arg::with_name("integrator")
+                .long("integrator")
+                .value_name("TYPE")
+                .takes_value(true)
+                .possible_values(&["path", "volpath", "bdpt", "sppm", "whitted", "ao", "debug"])
+                .default_value("path"))
+        .arg(Arg::with_name("max-depth")
+                .long("max-depth")
+                .value_name("N")
+                .takes_value(true)
+                .default_value("5"))
+        .arg(Arg::with_name("rr-depth")
+                .long("rr-depth")
+                .value_name("N")
+                .takes_value(true)
+                .default_value("3"))
+        .arg(Arg::with_name("iterations")
+                .long("iterations")
+                .value_name("N")
+                .takes_value(true)
+                .default_value("64"))
+        .arg(Arg::with_name("photons")
+                .long("photons")
+                .value_name("N")
+                .takes_value(true))
+        .arg(Arg::with_name("radius")
+                .long("radius")
+                .value_name("R")
+                .takes_value(true))
+        .arg(Arg::with_name("write-frequency")
+                .long("write-frequency")
+                .value_name("N")
+                .takes_value(true)
+                .default_value("8"))
+        .arg(Arg::with_name("ao-samples")
+                .long("ao-samples")
+                .value_name("N")
+                .takes_value(true)
+                .default_value("16"))
+        .arg(Arg::with_name("ao-distance")
+                .long("ao-distance")
+                .value_name("D")
+                .takes_value(true))
+        .arg(Arg::with_name("debug-mode")
+                .long("debug-mode")
+                .value_name("MODE")
+                .takes_value(true)
+                .possible_values(&["normal", "uv", "dpdu", "dpdv", "dndu", "dndv", "depth", "primitive"])
+                .default_value("normal"))
         .arg(Arg::with_name("output")
                 .long("output")
                 .value_name("PNG")
                 .takes_value(true)
                 .default_value("out/test.png"))
+        .arg(Arg::with_name("scene")
+                .long("scene")
+                .value_name("FILE|PBRT")
+                .takes_value(true))
+        .arg(Arg::with_name("mesh")
+                .long("mesh")
+                .value_name("OBJ|PLY")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1))
 }
 
-fn get_renderer_setup() -> Option<RendererSetup> {
-    match get_app().get_matches_safe() {
-        Err(e) => {
-            println!("{:}", e);
-            None
+fn parse_triple(s : &str) -> std::result::Result<(f32, f32, f32), String> {
+    let v = s.split(',').map(|c| c.trim().parse::<f32>()).collect::<std::result::Result<Vec<f32>, _>>();
+    match v {
+        Ok(ref v) if v.len() == 3 => Ok((v[0], v[1], v[2])),
+        _ => Err(format!("expected X,Y,Z but found '{}'", s)),
+    }
+}
+
+fn validate_triple(s : String) -> std::result::Result<(), String> {
+    parse_triple(&s).map(|_| ())
+}
+
+// Settings given explicitly on the command line win over the scene file, which
+// in turn wins over the command line defaults.
+fn get_renderer_setup(matches : &ArgMatches, desc : SceneDescription) -> std::result::Result<(RendererSetup, Scene), String> {
+    let explicit = |name : &str| matches.occurrences_of(name) > 0;
+
+    let film_size = match (matches.value_of("resolution").unwrap(), desc.film) {
+        (_, Some(size)) if !explicit("resolution") => Some(size),
+        ("4k", _)    => Some((3840, 2160)),
+        ("2k", _)    => Some((1920, 1080)),
+        ("1080p", _) => Some((1920, 1080)),
+        ("720p", _)  => Some((1280, 720)),
+        ("VGA", _)   => Some((640, 480)),
+        ("QVGA", _)  => Some((320, 240)),
+        _            => None
+    }.unwrap();
+
+    let film = Film::new(film_size.0, film_size.1);
+
+    let filter = match (matches.value_of("filter").unwrap(), desc.filter) {
+        (_, Some(f)) if !explicit("filter") => Some(f),
+        ("box", _)      => Some(CachingFilter::new(&BoxFilter::new(0.5f32, 0.5f32))),
+        ("gaussian", _) => Some(CachingFilter::new(&GaussianFilter::new(1.4f32, 1.4f32, 0.25f32))),
+        _               => None,
+    }.unwrap();
+
+    let camera_options = ["camera", "fov", "lens-radius", "focal-distance", "scale"];
+    let mut camera = match desc.camera {
+        Some(c) if !camera_options.iter().any(|o| explicit(o)) => c,
+        _ => {
+            let value = |name : &str| matches.value_of(name).unwrap().parse::<f32>().unwrap();
+            let kind = match matches.value_of("camera").unwrap() {
+                "perspective"      => CameraKind::Perspective { fov_y: value("fov") * PI / 180f32 },
+                "perspective-lens" => CameraKind::PerspectiveLens { fov_y: value("fov") * PI / 180f32, lens_radius: value("lens-radius"), focal_distance: value("focal-distance") },
+                "ortho"            => CameraKind::Orthographic { scale: value("scale") },
+                "hemisphere"       => CameraKind::Hemisphere,
+                _                  => CameraKind::Sphere,
+            };
+            CameraDescription::new(kind)
         },
-        Ok(matches) => {
-            let film_size = match matches.value_of("resolution").unwrap() {
-                "4k"    => Some((3840, 2160)),
-                "2k"    => Some((1920, 1080)),
-                "1080p" => Some((1920, 1080)),
-                "720p"  => Some((1280, 720)),
-                "VGA"   => Some((640, 480)),
-                "QVGA"  => Some((320, 240)),
-                _       => None
-            }.unwrap();
-
-            let film = Film::new(film_size.0, film_size.1);
-
-            let filter = match matches.value_of("filter").unwrap() {
-                "box"      => Some(CachingFilter::new(&BoxFilter::new(0.5f32, 0.5f32))),
-                "gaussian" => Some(CachingFilter::new(&GaussianFilter::new(1.4f32, 1.4f32, 0.25f32))),
-                _          => None,
-            }.unwrap();
-
-            let camera : Arc<dyn Camera> = match matches.value_of("camera").unwrap() {
-                "perspective" => {
-                    let fov = matches.value_of("fov").unwrap().parse::<f32>().unwrap() * PI / 180f32;
-                    Arc::new(PerspectiveCamera::new(fov, film.width as f32 / film.height as f32))
-                },
-                "perspective-lens" => {
-                    let fov = matches.value_of("fov").unwrap().parse::<f32>().unwrap() * PI / 180f32;
-                    let lr = matches.value_of("lens-radius").unwrap().parse::<f32>().unwrap();
-                    let fd = matches.value_of("focal-distance").unwrap().parse::<f32>().unwrap();
-                    Arc::new(PerspectiveLensCamera::new(fov, film.width as f32 / film.height as f32, lr, fd))
-                },
-                "ortho" => {
-                    let scale = matches.value_of("scale").unwrap().parse::<f32>().unwrap();
-                    Arc::new(OrthographicCamera::new(scale, film.width as f32 / film.height as f32))
-                },
-                "hemisphere" => Arc::new(HemisphereCamera::new()),
-                _            => Arc::new(SphereCamera::new()),
+    };
+
+    if ["eye", "look-at", "up"].iter().any(|o| explicit(o)) {
+        let triple = |name : &str| parse_triple(matches.value_of(name).unwrap()).unwrap();
+        let (eye, target, up) = (triple("eye"), triple("look-at"), triple("up"));
+        camera.transform = Transform::look_at(&Point::new(eye.0, eye.1, eye.2), &Point::new(target.0, target.1, target.2), &Vector::new(up.0, up.1, up.2))
+            .ok_or_else(|| String::from("degenerate camera: --eye and --look-at must be distinct finite points and --up a non-zero vector"))?;
+    }
+
+    let camera = camera.build(film.width as f32 / film.height as f32);
+
+    let sampler = match desc.sampler {
+        Some(s) if !explicit("samples") => s,
+        _ => match matches.value_of("samples").unwrap().parse::<usize>().unwrap() {
+            1 => Sampler2DKind::Centers,
+            n => Sampler2DKind::LHC(n),
+        },
+    };
+
+    let max_depth = matches.value_of("max-depth").unwrap().parse::<u32>().unwrap();
+    let rr_depth = matches.value_of("rr-depth").unwrap().parse::<u32>().unwrap();
+    let integrator : Arc<dyn Integrator> = match matches.value_of("integrator").unwrap() {
+        "path"    => Arc::new(PathIntegrator::new(max_depth, rr_depth)),
+        "volpath" => Arc::new(VolPathIntegrator::new(max_depth, rr_depth)),
+        "bdpt"    => Arc::new(BDPTIntegrator::new(camera.clone(), max_depth)),
+        "ao"      => {
+            let n_samples = matches.value_of("ao-samples").unwrap().parse::<usize>().unwrap();
+            let max_distance = matches.value_of("ao-distance").map_or(f32::INFINITY, |d| d.parse::<f32>().unwrap());
+            Arc::new(AOIntegrator::new(n_samples, max_distance))
+        },
+        "debug"   => {
+            let mode = match matches.value_of("debug-mode").unwrap() {
+                "uv"        => DebugMode::UV,
+                "dpdu"      => DebugMode::Dpdu,
+                "dpdv"      => DebugMode::Dpdv,
+                "dndu"      => DebugMode::Dndu,
+                "dndv"      => DebugMode::Dndv,
+                "depth"     => DebugMode::Depth,
+                "primitive" => DebugMode::Primitive,
+                _           => DebugMode::Normal,
             };
+            Arc::new(DebugIntegrator::new(mode))
+        },
+        _         => Arc::new(WhittedIntegrator::new(max_depth)),
+    };
+
+    let output_filename = String::from(matches.value_of("output").unwrap());
+
+    Ok((RendererSetup::new(film, filter, camera, Arc::new(sampler), integrator, output_filename), desc.scene))
+}
+
+// The photon mapper renders by itself rather than through the renderer.  It
+// traces a photon per pixel each iteration, and starts with a search radius
+// of a hundredth of the scene's size, unless told otherwise.
+fn get_sppm(matches : &ArgMatches, setup : &RendererSetup, scene : &Scene) -> SPPMIntegrator {
+    let iterations = matches.value_of("iterations").unwrap().parse::<u32>().unwrap();
+    let photons = match matches.value_of("photons") {
+        Some(n) => n.parse::<usize>().unwrap(),
+        None    => (setup.film.width * setup.film.height) as usize,
+    };
+    let max_depth = matches.value_of("max-depth").unwrap().parse::<u32>().unwrap();
+    let radius = match matches.value_of("radius") {
+        Some(r) => r.parse::<f32>().unwrap(),
+        None    => scene.bounds.bounding_sphere().1 / 50f32,
+    };
+    let write_frequency = matches.value_of("write-frequency").unwrap().parse::<u32>().unwrap();
+    SPPMIntegrator::new(iterations, photons, max_depth, radius, write_frequency)
+}
 
-            let sampler_factory = Arc::new(SamplerFactory::new(matches.value_of("samples").unwrap().parse::<usize>().unwrap()));
-                    
-            let output_filename = String::from(matches.value_of("output").unwrap());
+fn build_scene(matches : &ArgMatches) -> std::result::Result<SceneDescription, String> {
+    let mut desc = match matches.value_of("scene") {
+        Some(path) => {
+            let p = Path::new(path);
+            if p.extension().is_some_and(|e| e.eq_ignore_ascii_case("pbrt")) {
+                load_pbrt(p).map_err(|e| format!("{}: {}", path, e))?
+            } else {
+                load_scene(p).map_err(|e| format!("{}: {}", path, e))?
+            }
+        },
+        None => SceneDescription { scene: demo_scene(), film: None, filter: None, sampler: None, camera: None },
+    };
 
-            Some(RendererSetup::new(film, filter, camera, sampler_factory, output_filename))
+    if let Some(paths) = matches.values_of("mesh") {
+        for path in paths {
+            let p = Path::new(path);
+            if p.extension().is_some_and(|e| e.eq_ignore_ascii_case("ply")) {
+                let mesh = load_ply(p).map_err(|e| format!("{}: {}", path, e))?;
+                desc.scene.add_mesh(&mesh, Arc::new(MatteMaterial::default()));
+            } else {
+                let objects = load_obj(p).map_err(|e| format!("{}: {}", path, e))?;
+                for o in objects.iter() {
+                    let primitives = desc.scene.add_mesh(&o.mesh, Arc::new(MatteMaterial::default()));
+                    desc.scene.add_group(&o.name, primitives);
+                }
+            }
         }
     }
+
+    Ok(desc)
 }
 
-fn build_scene() -> Scene {
+// The shape gallery rendered when no scene file is given; scenes/demo.scene
+// describes the same scene.
+fn demo_scene() -> Scene {
     let mut scene = Scene::new();
+    let checks = CheckerboardTexture::new(Box::new(UVMapping2D::new(8f32, 8f32, 0f32, 0f32)), Arc::new(ConstantTexture::new(Color::white())), Arc::new(ConstantTexture::new(Color::gray(0.25f32))));
+    let m : Arc<dyn Material> = Arc::new(MatteMaterial::new(Arc::new(checks)));
 
     for z in vec![7f32].into_iter() { // , 10f32, 20f32, 40f32].into_iter() {
-        scene.add(Arc::new(Sphere::unit().translate(&Vector::new( -5f32, 0.8f32, z))));
-        scene.add(Arc::new(Sphere::new_partial(0.5f32, (-0.3f32, 0.3f32), PI).rotate(FRAC_PI_2, &Vector::unit_x()).translate(&Vector::new(-5f32, -0.8f32, z))));
+        scene.add(Arc::new(Sphere::unit().translate(&Vector::new( -5f32, 0.8f32, z))), m.clone());
+        scene.add(Arc::new(Sphere::new_partial(0.5f32, (-0.3f32, 0.3f32), PI).rotate(FRAC_PI_2, &Vector::unit_x()).translate(&Vector::new(-5f32, -0.8f32, z))), m.clone());
 
-        scene.add(Arc::new(Cylinder::unit().translate(&Vector::new( -3f32, 0.8f32, z))));
-        scene.add(Arc::new(Cylinder::new_partial(0.5f32, 1f32, PI).rotate(FRAC_PI_2, &Vector::unit_x()).translate(&Vector::new(-3f32, -0.8f32, z))));
+        scene.add(Arc::new(Cylinder::unit().translate(&Vector::new( -3f32, 0.8f32, z))), m.clone());
+        scene.add(Arc::new(Cylinder::new_partial(0.5f32, 1f32, PI).rotate(FRAC_PI_2, &Vector::unit_x()).translate(&Vector::new(-3f32, -0.8f32, z))), m.clone());
 
-        scene.add(Arc::new(Disc::new_annulus(0.1f32, 0.5f32).translate(&Vector::new(-1f32, 0.8f32, z))));
-        scene.add(Arc::new(Disc::new_partial_annulus(0.1f32, 0.5f32, PI * 1.5f32).rotate(FRAC_PI_3, &Vector::unit_x()).translate(&Vector::new(-1f32, -0.8f32, z))));
+        scene.add(Arc::new(Disc::new_annulus(0.1f32, 0.5f32).translate(&Vector::new(-1f32, 0.8f32, z))), m.clone());
+        scene.add(Arc::new(Disc::new_partial_annulus(0.1f32, 0.5f32, PI * 1.5f32).rotate(FRAC_PI_3, &Vector::unit_x()).translate(&Vector::new(-1f32, -0.8f32, z))), m.clone());
         
-        scene.add(Arc::new(Plane::unit().translate(&Vector::new(1f32, 0.8f32, z))));
-        scene.add(Arc::new(Plane::unit().rotate(FRAC_PI_3, &Vector::unit_x()).translate(&Vector::new(1f32, -0.8f32, z))));
+        scene.add(Arc::new(Plane::unit().translate(&Vector::new(1f32, 0.8f32, z))), m.clone());
+        scene.add(Arc::new(Plane::unit().rotate(FRAC_PI_3, &Vector::unit_x()).translate(&Vector::new(1f32, -0.8f32, z))), m.clone());
 
-        scene.add(Arc::new(Cone::unit().rotate(-FRAC_PI_2, &Vector::unit_x()).translate(&Vector::new(3f32, 0.3f32, z))));
-        scene.add(Arc::new(Cone::new_partial(0.5f32, 1f32, 0.2f32, 0.8f32, PI * 1.5f32).rotate(PI, &Vector::unit_z()).rotate(-FRAC_PI_2, &Vector::unit_x()).translate(&Vector::new(3f32, -1.3f32, z))));
+        scene.add(Arc::new(Cone::unit().rotate(-FRAC_PI_2, &Vector::unit_x()).translate(&Vector::new(3f32, 0.3f32, z))), m.clone());
+        scene.add(Arc::new(Cone::new_partial(0.5f32, 1f32, 0.2f32, 0.8f32, PI * 1.5f32).rotate(PI, &Vector::unit_z()).rotate(-FRAC_PI_2, &Vector::unit_x()).translate(&Vector::new(3f32, -1.3f32, z))), m.clone());
 
-        scene.add(Arc::new(Paraboloid::unit().rotate(-FRAC_PI_2, &Vector::unit_x()).translate(&Vector::new(5f32, 0.3f32, z))));
-        scene.add(Arc::new(Paraboloid::new_partial(0.5f32, 1f32, 0.2f32, 0.8f32, PI * 1.5f32).rotate(PI, &Vector::unit_z()).rotate(-FRAC_PI_2, &Vector::unit_x()).translate(&Vector::new(5f32, -1.3f32, z))));
+        scene.add(Arc::new(Paraboloid::unit().rotate(-FRAC_PI_2, &Vector::unit_x()).translate(&Vector::new(5f32, 0.3f32, z))), m.clone());
+        scene.add(Arc::new(Paraboloid::new_partial(0.5f32, 1f32, 0.2f32, 0.8f32, PI * 1.5f32).rotate(PI, &Vector::unit_z()).rotate(-FRAC_PI_2, &Vector::unit_x()).translate(&Vector::new(5f32, -1.3f32, z))), m.clone());
     }
 
+    // a sun from over the camera's left shoulder and a dim sky, so that
+    // every integrator has something to light the scene with
+    let sun = DistantLight::new(Color::gray(2.5f32)).look_at(&Point::origin(), &Point::new(0.4f32, -0.6f32, 1f32), &Vector::unit_y()).unwrap();
+    scene.add_light(Box::new(sun));
+    scene.add_light(Box::new(InfiniteAreaLight::constant(Color::gray(0.3f32))));
+
     scene
 }
 
 fn main() {
     env_logger::init();
 
-    if let Some(setup) = get_renderer_setup() {
-        render(setup, build_scene());
+    match get_app().get_matches_safe() {
+        Err(e) => println!("{:}", e),
+        Ok(matches) => {
+            match build_scene(&matches).and_then(|desc| get_renderer_setup(&matches, desc)) {
+                Err(e) => println!("{}", e),
+                Ok((setup, scene)) => {
+                    match matches.value_of("integrator").unwrap() {
+                        "sppm" => get_sppm(&matches, &setup, &scene).render(setup, scene),
+                        _      => render(setup, scene),
+                    }
+                },
+            }
+        }
     }
 }
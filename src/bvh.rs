@@ -0,0 +1,330 @@
+use crate::geometry::{BoundingBox, Ray, Point, Vector};
+use crate::shapes::ShapeIntersection;
+
+const MAX_PRIMS_IN_NODE : usize = 4;
+const BUCKET_COUNT      : usize = 12;
+
+#[derive(Copy, Clone, Debug)]
+struct PrimitiveInfo {
+    index    : usize,
+    bounds   : BoundingBox,
+    centroid : Point,
+}
+
+// Nodes are stored depth-first, so the first child of an interior node always
+// immediately follows its parent and only the second child needs an offset.
+#[derive(Copy, Clone, Debug)]
+struct LinearNode {
+    bounds : BoundingBox,
+    offset : usize,
+    count  : usize,
+    axis   : usize,
+}
+
+pub struct BVH {
+    nodes   : Vec<LinearNode>,
+    indices : Vec<usize>,
+}
+
+impl BVH {
+    pub fn new(bounds : &[BoundingBox]) -> BVH {
+        let mut info : Vec<PrimitiveInfo> = bounds.iter().enumerate().map(|(ix, b)| {
+            PrimitiveInfo { index: ix, bounds: *b, centroid: b.centroid() }
+        }).collect();
+
+        let mut bvh = BVH {
+            nodes:   Vec::with_capacity(2 * bounds.len()),
+            indices: Vec::with_capacity(bounds.len()),
+        };
+
+        if !info.is_empty() {
+            bvh.build(&mut info);
+        }
+
+        bvh
+    }
+
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    fn build(&mut self, info : &mut [PrimitiveInfo]) -> usize {
+        let node_ix = self.nodes.len();
+        self.nodes.push(LinearNode { bounds: BoundingBox::empty(), offset: 0, count: 0, axis: 0 });
+
+        let mut bounds = BoundingBox::empty();
+        let mut centroid_bounds = BoundingBox::empty();
+        for i in info.iter() {
+            bounds.add_self_bounding_box(&i.bounds);
+            centroid_bounds.add_self_point(&i.centroid);
+        }
+
+        let n = info.len();
+        let axis = centroid_bounds.maximum_extent();
+        let (lo, hi) = (centroid_bounds.min()[axis], centroid_bounds.max()[axis]);
+
+        if n == 1 || lo == hi {
+            return self.make_leaf(node_ix, bounds, info);
+        }
+
+        let mid = if n <= 2 {
+            info.sort_by(|a, b| a.centroid[axis].total_cmp(&b.centroid[axis]));
+            n / 2
+        } else {
+            let bucket_of = |p : &Point| {
+                let b = (BUCKET_COUNT as f32 * centroid_bounds.offset(p)[axis]) as usize;
+                b.min(BUCKET_COUNT - 1)
+            };
+
+            let mut counts = [0usize; BUCKET_COUNT];
+            let mut bucket_bounds = [BoundingBox::empty(); BUCKET_COUNT];
+            for i in info.iter() {
+                let b = bucket_of(&i.centroid);
+                counts[b] += 1;
+                bucket_bounds[b].add_self_bounding_box(&i.bounds);
+            }
+
+            // cost of splitting after bucket s, relative to a traversal step of 1
+            let mut min_cost = f32::INFINITY;
+            let mut min_bucket = 0;
+            for s in 0..(BUCKET_COUNT - 1) {
+                let mut b0 = BoundingBox::empty();
+                let mut b1 = BoundingBox::empty();
+                let mut c0 = 0;
+                let mut c1 = 0;
+                for b in 0..=s {
+                    b0.add_self_bounding_box(&bucket_bounds[b]);
+                    c0 += counts[b];
+                }
+                for b in (s + 1)..BUCKET_COUNT {
+                    b1.add_self_bounding_box(&bucket_bounds[b]);
+                    c1 += counts[b];
+                }
+                let cost = 1f32 + ((c0 as f32) * b0.surface_area() + (c1 as f32) * b1.surface_area()) / bounds.surface_area();
+                if cost < min_cost {
+                    min_cost = cost;
+                    min_bucket = s;
+                }
+            }
+
+            if n <= MAX_PRIMS_IN_NODE && min_cost >= n as f32 {
+                return self.make_leaf(node_ix, bounds, info);
+            }
+
+            let mut mid = 0;
+            for i in 0..n {
+                if bucket_of(&info[i].centroid) <= min_bucket {
+                    info.swap(i, mid);
+                    mid += 1;
+                }
+            }
+
+            if mid == 0 || mid == n {
+                info.sort_by(|a, b| a.centroid[axis].total_cmp(&b.centroid[axis]));
+                n / 2
+            } else {
+                mid
+            }
+        };
+
+        let (left, right) = info.split_at_mut(mid);
+        self.build(left);
+        let second = self.build(right);
+
+        self.nodes[node_ix] = LinearNode { bounds: bounds, offset: second, count: 0, axis: axis };
+        node_ix
+    }
+
+    fn make_leaf(&mut self, node_ix : usize, bounds : BoundingBox, info : &[PrimitiveInfo]) -> usize {
+        let offset = self.indices.len();
+        self.indices.extend(info.iter().map(|i| i.index));
+        self.nodes[node_ix] = LinearNode { bounds: bounds, offset: offset, count: info.len(), axis: 0 };
+        node_ix
+    }
+
+    pub fn intersect<F>(&self, r : &Ray, mut f : F) -> Option<(usize, ShapeIntersection)>
+        where F : FnMut(usize, &Ray) -> Option<ShapeIntersection>
+    {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let inv_dir = Vector::new(1f32 / r.direction.x, 1f32 / r.direction.y, 1f32 / r.direction.z);
+        let dir_is_neg = [inv_dir.x < 0f32, inv_dir.y < 0f32, inv_dir.z < 0f32];
+
+        let mut ray = *r;
+        let mut closest : Option<(usize, ShapeIntersection)> = None;
+
+        let mut stack : Vec<usize> = Vec::with_capacity(64);
+        let mut current = 0;
+
+        loop {
+            let node = &self.nodes[current];
+            if intersects_box(&node.bounds, &ray, &inv_dir, &dir_is_neg) {
+                if node.count > 0 {
+                    for &ix in self.indices[node.offset..(node.offset + node.count)].iter() {
+                        if let Some(i) = f(ix, &ray) {
+                            // ties go to the lowest index, matching a front-to-back linear scan
+                            let closer = match closest {
+                                None             => true,
+                                Some((cix, ref c)) => i.time < c.time || (i.time == c.time && ix < cix),
+                            };
+                            if closer {
+                                ray.t_max = i.time;
+                                closest = Some((ix, i));
+                            }
+                        }
+                    }
+                } else if dir_is_neg[node.axis] {
+                    stack.push(current + 1);
+                    current = node.offset;
+                    continue;
+                } else {
+                    stack.push(node.offset);
+                    current += 1;
+                    continue;
+                }
+            }
+
+            match stack.pop() {
+                None     => break,
+                Some(ix) => current = ix,
+            }
+        }
+
+        closest
+    }
+
+    // Whether f reports a hit for any of the primitives r may meet, stopping
+    // at the first rather than searching for the closest.
+    pub fn intersects<F>(&self, r : &Ray, mut f : F) -> bool
+        where F : FnMut(usize, &Ray) -> bool
+    {
+        if self.nodes.is_empty() {
+            return false;
+        }
+
+        let inv_dir = Vector::new(1f32 / r.direction.x, 1f32 / r.direction.y, 1f32 / r.direction.z);
+        let dir_is_neg = [inv_dir.x < 0f32, inv_dir.y < 0f32, inv_dir.z < 0f32];
+
+        let mut stack : Vec<usize> = Vec::with_capacity(64);
+        let mut current = 0;
+
+        loop {
+            let node = &self.nodes[current];
+            if intersects_box(&node.bounds, r, &inv_dir, &dir_is_neg) {
+                if node.count > 0 {
+                    if self.indices[node.offset..(node.offset + node.count)].iter().any(|&ix| f(ix, r)) {
+                        return true;
+                    }
+                } else if dir_is_neg[node.axis] {
+                    stack.push(current + 1);
+                    current = node.offset;
+                    continue;
+                } else {
+                    stack.push(node.offset);
+                    current += 1;
+                    continue;
+                }
+            }
+
+            match stack.pop() {
+                None     => return false,
+                Some(ix) => current = ix,
+            }
+        }
+    }
+}
+
+// Same slab test as BoundingBox::intersects, with the reciprocal direction and
+// slab order hoisted out of the traversal loop.
+fn intersects_box(b : &BoundingBox, r : &Ray, inv_dir : &Vector, dir_is_neg : &[bool; 3]) -> bool {
+    let (lo, hi) = (b.min(), b.max());
+    let near = |axis : usize| if dir_is_neg[axis] { hi[axis] } else { lo[axis] };
+    let far  = |axis : usize| if dir_is_neg[axis] { lo[axis] } else { hi[axis] };
+
+    let mut t0 = (near(0) - r.origin.x) * inv_dir.x;
+    let mut t1 = (far(0)  - r.origin.x) * inv_dir.x;
+    let ty0    = (near(1) - r.origin.y) * inv_dir.y;
+    let ty1    = (far(1)  - r.origin.y) * inv_dir.y;
+
+    if t0 > ty1 || ty0 > t1 { return false; }
+    if ty0 > t0 { t0 = ty0; }
+    if ty1 < t1 { t1 = ty1; }
+
+    let tz0 = (near(2) - r.origin.z) * inv_dir.z;
+    let tz1 = (far(2)  - r.origin.z) * inv_dir.z;
+
+    if t0 > tz1 || tz0 > t1 { return false; }
+    if tz0 > t0 { t0 = tz0; }
+    if tz1 < t1 { t1 = tz1; }
+
+    t0 <= r.t_max && t1 >= r.t_min
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use rand::prelude::*;
+    use rand::rngs::StdRng;
+
+    use crate::geometry::Trans;
+    use crate::shapes::{Shape, Sphere};
+
+    #[test]
+    fn test_bvh_matches_linear_scan() {
+        let mut rng = StdRng::seed_from_u64(1);
+
+        let shapes : Vec<Arc<dyn Shape>> = (0..500).map(|_| {
+            let s = Sphere::new(rng.gen_range(0.1f32, 1f32)).translate(&Vector::new(rng.gen_range(-20f32, 20f32), rng.gen_range(-20f32, 20f32), rng.gen_range(5f32, 40f32)));
+            Arc::new(s) as Arc<dyn Shape>
+        }).collect();
+        let bounds : Vec<BoundingBox> = shapes.iter().map(|s| s.world_bound()).collect();
+        let bvh = BVH::new(&bounds);
+        assert_eq!(bvh.len(), shapes.len());
+
+        for _ in 0..2000 {
+            let d = Vector::new(rng.gen_range(-0.6f32, 0.6f32), rng.gen_range(-0.6f32, 0.6f32), 1f32).normalize();
+            let r = Ray::new(&Point::origin(), &d);
+
+            let mut linear : Option<(usize, f32)> = None;
+            for (ix, s) in shapes.iter().enumerate() {
+                if let Some(i) = s.intersect(&r) {
+                    if linear.map_or(true, |(_, t)| i.time < t) {
+                        linear = Some((ix, i.time));
+                    }
+                }
+            }
+
+            let fast = bvh.intersect(&r, |ix, ray| shapes[ix].intersect(ray)).map(|(ix, i)| (ix, i.time));
+            assert_eq!(linear, fast);
+            assert_eq!(linear.is_some(), bvh.intersects(&r, |ix, ray| shapes[ix].intersect(ray).is_some()));
+        }
+    }
+
+    #[test]
+    fn test_non_finite_bounds() {
+        // such as a mesh with an infinite vertex, which gives a NaN centroid;
+        // building mustn't panic.  Centroids spread over more than f32::MAX all
+        // land in the first bucket, so the NaN ones reach the fallback sort
+        let (inf, max) = (f32::INFINITY, f32::MAX);
+        let bounds : Vec<BoundingBox> = [(-max, -max), (inf, inf), (0f32, 1f32), (-inf, inf), (2f32, 3f32), (max, max)].iter().map(|&(lo, hi)| {
+            BoundingBox::for_points(&[Point::new(lo, 0f32, 0f32), Point::new(hi, 1f32, 1f32)])
+        }).collect();
+        assert_eq!(BVH::new(&bounds).len(), bounds.len());
+    }
+
+    #[test]
+    fn test_empty_bvh() {
+        let bvh = BVH::new(&[]);
+        assert!(bvh.is_empty());
+        assert!(bvh.intersect(&Ray::z_axis(), |_, _| None).is_none());
+        assert!(!bvh.intersects(&Ray::z_axis(), |_, _| true));
+    }
+}
@@ -1,5 +1,45 @@
-use crate::geometry::{Ray, HasTransform, TransMut};
+use crate::color::Color;
+use crate::geometry::{Point, Ray, Vector, HasTransform, TransMut};
+use crate::lights::VisibilityTester;
+use crate::shapes::SurfaceContext;
 
 pub trait Camera : HasTransform + TransMut + Send + Sync {
     fn cast(&self, x : f32, y : f32) -> Ray;
+
+    // The densities with which cast() chooses the origin of r, by area of
+    // the lens, and its direction, by solid angle.  Zero for cameras that
+    // cannot be found by paths traced from the lights.
+    fn pdf_we(&self, _r : &Ray) -> (f32, f32) {
+        (0f32, 0f32)
+    }
+
+    // Samples a point on the lens that sees the point of context, for
+    // connecting paths traced from the lights to the camera.  None if the
+    // point is out of view, or the camera cannot be found this way.
+    fn sample_wi(&self, _context : &SurfaceContext, _u : (f32, f32)) -> Option<ImportanceSample> {
+        None
+    }
+}
+
+// The importance arriving at a point from a point sampled on the lens, in
+// direction wi, and the camera coordinates, as given to cast(), at which the
+// point is seen.
+#[derive(Copy, Clone, Debug)]
+pub struct ImportanceSample {
+    pub wi         : Vector,
+    pub we         : Color,
+    pub pdf        : f32,
+    pub film       : (f32, f32),
+    pub visibility : VisibilityTester,
+}
+
+impl ImportanceSample {
+    pub fn new(wi : Vector, we : Color, pdf : f32, film : (f32, f32), visibility : VisibilityTester) -> ImportanceSample {
+        ImportanceSample { wi: wi, we: we, pdf: pdf, film: film, visibility: visibility }
+    }
+
+    // The point sampled on the lens.
+    pub fn p(&self) -> Point {
+        self.visibility.ray.at_time(1f32)
+    }
 }
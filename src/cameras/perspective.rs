@@ -1,18 +1,31 @@
+use std::f32::consts::PI;
+
+use crate::color::Color;
 use crate::geometry::{Ray, Vector, Point, Transform, HasTransform, TransMut};
-use crate::cameras::Camera;
+use crate::cameras::{Camera, ImportanceSample};
+use crate::lights::VisibilityTester;
+use crate::sampler::to_disc_concentric;
+use crate::shapes::SurfaceContext;
 
 pub struct PerspectiveCamera {
     transform : Transform,
     pub fov_y : f32,
     fov_x_tan : f32,
     fov_y_tan : f32,
+    importance : PerspectiveImportance,
 }
 
 impl PerspectiveCamera {
     pub fn new(fov_y : f32, aspect_ratio : f32) -> PerspectiveCamera {
         let fov_y_tan = (fov_y / 2f32).tan();
         let fov_x_tan = fov_y_tan * aspect_ratio;
-        PerspectiveCamera { transform: Transform::identity(), fov_y: fov_y, fov_x_tan: fov_x_tan, fov_y_tan: fov_y_tan }
+        PerspectiveCamera {
+            transform: Transform::identity(),
+            fov_y: fov_y,
+            fov_x_tan: fov_x_tan,
+            fov_y_tan: fov_y_tan,
+            importance: PerspectiveImportance::new(fov_x_tan, fov_y_tan, 0f32, 1f32),
+        }
     }
 }
 
@@ -21,6 +34,14 @@ impl Camera for PerspectiveCamera {
         let d = Vector::new(x * self.fov_x_tan, y * self.fov_y_tan, 1f32).normalize();
         Ray::new(&Point::origin(), &d).from(self)
     }
+
+    fn pdf_we(&self, r : &Ray) -> (f32, f32) {
+        self.importance.pdf_we(&r.to(self))
+    }
+
+    fn sample_wi(&self, context : &SurfaceContext, u : (f32, f32)) -> Option<ImportanceSample> {
+        self.importance.sample_wi(self, context, u)
+    }
 }
 
 impl HasTransform for PerspectiveCamera {
@@ -34,3 +55,108 @@ impl TransMut for PerspectiveCamera {
         self.transform = *t + self.transform;
     }
 }
+
+// The importance perspective cameras give to rays, after pbrt-v3, for an
+// image plane spanning ±fov_x_tan by ±fov_y_tan at unit distance, seen
+// through a thin lens of lens_radius focused at focal_distance, or through a
+// pinhole if the radius is zero.  Each camera position on the film is given
+// equal importance, so that light traced from the lights lands on the film
+// as it would be found by rays cast from it.
+#[derive(Copy, Clone, Debug)]
+pub struct PerspectiveImportance {
+    fov_x_tan      : f32,
+    fov_y_tan      : f32,
+    lens_radius    : f32,
+    focal_distance : f32,
+}
+
+impl PerspectiveImportance {
+    pub fn new(fov_x_tan : f32, fov_y_tan : f32, lens_radius : f32, focal_distance : f32) -> PerspectiveImportance {
+        PerspectiveImportance { fov_x_tan: fov_x_tan, fov_y_tan: fov_y_tan, lens_radius: lens_radius, focal_distance: focal_distance }
+    }
+
+    fn lens_area(&self) -> f32 {
+        if self.lens_radius > 0f32 { PI * self.lens_radius * self.lens_radius } else { 1f32 }
+    }
+
+    // The importance of camera space ray r, with the camera coordinates at
+    // which it is seen, or None if it is out of view.
+    pub fn we(&self, r : &Ray) -> Option<(f32, (f32, f32))> {
+        let d = r.direction.normalize();
+        let cos_theta = d.z;
+        if cos_theta <= 0f32 {
+            return None;
+        }
+
+        // rays through one point on the plane of focus share a film position
+        let distance = if self.lens_radius > 0f32 { self.focal_distance } else { 1f32 };
+        let p_focus = r.origin + d * (distance / cos_theta);
+        let (x, y) = (p_focus.x / (distance * self.fov_x_tan), p_focus.y / (distance * self.fov_y_tan));
+        if x.abs() > 1f32 || y.abs() > 1f32 {
+            return None;
+        }
+
+        let area = 4f32 * self.fov_x_tan * self.fov_y_tan;
+        let cos2_theta = cos_theta * cos_theta;
+        Some((1f32 / (area * self.lens_area() * cos2_theta * cos2_theta), (x, y)))
+    }
+
+    // The densities of Camera::pdf_we() for camera space ray r.
+    pub fn pdf_we(&self, r : &Ray) -> (f32, f32) {
+        if self.we(r).is_none() {
+            return (0f32, 0f32);
+        }
+        let cos_theta = r.direction.normalize().z;
+        let area = 4f32 * self.fov_x_tan * self.fov_y_tan;
+        (1f32 / self.lens_area(), 1f32 / (area * cos_theta * cos_theta * cos_theta))
+    }
+
+    // Camera::sample_wi() for a camera with transform t.
+    pub fn sample_wi<T : HasTransform>(&self, t : &T, context : &SurfaceContext, u : (f32, f32)) -> Option<ImportanceSample> {
+        let (x, y) = to_disc_concentric(u);
+        let p_lens = Point::new(x * self.lens_radius, y * self.lens_radius, 0f32).from(t);
+        let n_lens = Vector::unit_z().from(t).normalize();
+
+        let wi = p_lens - context.p;
+        let dist2 = wi.magnitude_squared();
+        if dist2 == 0f32 {
+            return None;
+        }
+        let wi = wi.normalize();
+        let pdf = dist2 / (n_lens.dot(&wi).abs() * self.lens_area());
+
+        let (we, film) = self.we(&Ray::new(&p_lens, &-wi).to(t))?;
+        Some(ImportanceSample::new(wi, Color::gray(we), pdf, film, VisibilityTester::new(context, &p_lens)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Normal;
+
+    #[test]
+    fn test_importance() {
+        let mut camera = PerspectiveCamera::new(PI / 2f32, 2f32);
+        camera.transform_self(&Transform::translation(&Vector::new(1f32, 0f32, 0f32)));
+        let r = camera.cast(0.3f32, -0.5f32);
+
+        // a point seen by the ray sees the camera where the ray left it
+        let p = r.at_time(3f32);
+        let context = SurfaceContext::new(p, Normal::new(0f32, 0f32, -1f32), (0f32, 0f32), (Vector::unit_x(), Vector::unit_y()), (Normal::zero(), Normal::zero()));
+        let s = camera.sample_wi(&context, (0.5f32, 0.5f32)).unwrap();
+        assert!((s.film.0 - 0.3f32).abs() < 1e-5f32 && (s.film.1 + 0.5f32).abs() < 1e-5f32);
+        assert!((s.p() - r.origin).magnitude() < 1e-5f32);
+
+        // the image plane at unit distance is 4 by 2
+        let cos_theta = r.direction.normalize().z;
+        let (pdf_pos, pdf_dir) = camera.pdf_we(&r);
+        assert_eq!(pdf_pos, 1f32);
+        assert!((pdf_dir - 1f32 / (8f32 * cos_theta * cos_theta * cos_theta)).abs() < 1e-5f32);
+
+        // nothing behind the camera or outside its view is seen
+        assert_eq!(camera.pdf_we(&camera.cast(1.5f32, 0f32)), (0f32, 0f32));
+        let behind = SurfaceContext { p: Point::new(1f32, 0f32, -1f32), .. context };
+        assert!(camera.sample_wi(&behind, (0.5f32, 0.5f32)).is_none());
+    }
+}
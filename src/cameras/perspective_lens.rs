@@ -1,5 +1,6 @@
 use crate::geometry::{Ray, Vector, Point, Transform, HasTransform, TransMut};
-use crate::cameras::Camera;
+use crate::cameras::{Camera, ImportanceSample, PerspectiveImportance};
+use crate::shapes::SurfaceContext;
 use crate::sampler::{UniformSampler2D, Sampler2D, to_disc_concentric};
 
 #[derive(Debug)]
@@ -10,6 +11,7 @@ pub struct PerspectiveLensCamera {
     fov_y_tan      : f32,
     lens_radius    : f32,
     focal_distance : f32,
+    importance     : PerspectiveImportance,
 }
 
 impl PerspectiveLensCamera {
@@ -24,6 +26,7 @@ impl PerspectiveLensCamera {
             fov_y_tan:      fov_y_tan,
             lens_radius:    lens_radius,
             focal_distance: focal_distance,
+            importance:     PerspectiveImportance::new(fov_x_tan, fov_y_tan, lens_radius, focal_distance),
         }
     }
 }
@@ -50,6 +53,14 @@ impl Camera for PerspectiveLensCamera {
         let lens_dir    = focal_point - lens_origin;
         Ray::new(&lens_origin, &lens_dir.normalize()).from(self)
     }
+
+    fn pdf_we(&self, r : &Ray) -> (f32, f32) {
+        self.importance.pdf_we(&r.to(self))
+    }
+
+    fn sample_wi(&self, context : &SurfaceContext, u : (f32, f32)) -> Option<ImportanceSample> {
+        self.importance.sample_wi(self, context, u)
+    }
 }
 
 impl HasTransform for PerspectiveLensCamera {
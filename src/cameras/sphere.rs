@@ -13,26 +13,26 @@ impl SphereCamera {
 
 impl Camera for SphereCamera {
     fn cast(&self, x : f32, y : f32) -> Ray {
-        let h = x * core::f32::consts::PI;
-        let v = y * core::f32::consts::FRAC_PI_2;
-
-        let d = Vector::new(h.sin() * v.cos(), v.sin(), h.cos() * v.cos());
-        Ray::new(&Point::origin(), &d).from(self)
-
+        Ray::new(&Point::origin(), &lat_long_direction(x, y)).from(self)
+    }
+}
 
-/*                                       h  h.sin()  h.cos()      v  v.sin()  v.cos()
-            [-1, -1] -> ( 0, -1, 0)  -pi/2       -1        0  -pi/2       -1        0
-            [-1,  0] -> (-1,  0, 0)  -pi/2       -1        0      0        0        1
-            [-1,  1] -> ( 0,  1, 0)  -pi/2       -1        0   pi/2        1        0
-            [ 0, -1] -> ( 0, -1, 0)      0        0        1  -pi/2       -1        0
-            [ 0,  0] -> ( 0,  0, 1)      0        0        1      0        0        1
-            [ 0,  1] -> ( 0,  1, 0)      0        0        1   pi/2        1        0
-            [ 1, -1] -> ( 0, -1  0)   pi/2        1        0  -pi/2       -1        0
-            [ 1,  0] -> ( 1,  0, 0)   pi/2        1        0      0        0        1
-            [ 1,  1] -> ( 0,  1, 0)   pi/2        1        0   pi/2        1        0
-*/
+// The latitude-longitude mapping of the sphere camera, from film coordinates
+// in [-1, 1]² to directions.  x runs around the horizon, from -z through -x,
+// +z (at x = 0) and +x back to -z, and y from straight down (-y) to straight
+// up (+y).
+pub fn lat_long_direction(x : f32, y : f32) -> Vector {
+    let h = x * core::f32::consts::PI;
+    let v = y * core::f32::consts::FRAC_PI_2;
+    Vector::new(h.sin() * v.cos(), v.sin(), h.cos() * v.cos())
+}
 
-    }
+// The film coordinates lat_long_direction() maps to the direction of d.
+pub fn lat_long_coordinates(d : &Vector) -> (f32, f32) {
+    let d = d.normalize();
+    let h = d.x.atan2(d.z);
+    let v = d.y.clamp(-1f32, 1f32).asin();
+    (h / core::f32::consts::PI, v / core::f32::consts::FRAC_PI_2)
 }
 
 impl HasTransform for SphereCamera {
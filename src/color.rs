@@ -0,0 +1,210 @@
+use std::default::Default;
+use std::ops::{Index, Add, Sub, Mul, Div};
+use std::fmt::{Display, Formatter, Result};
+
+// Linear RGB, with the sRGB primaries.
+#[derive(Copy, Clone, Debug)]
+pub struct Color {
+    pub r : f32,
+    pub g : f32,
+    pub b : f32,
+}
+
+impl Color {
+    pub fn new(r : f32, g : f32, b : f32) -> Color {
+        Color { r: r, g: g, b: b }
+    }
+
+    pub fn gray(v : f32) -> Color {
+        Color::new(v, v, v)
+    }
+
+    pub fn black() -> Color {
+        Color::gray(0f32)
+    }
+
+    pub fn white() -> Color {
+        Color::gray(1f32)
+    }
+
+    pub fn is_black(&self) -> bool {
+        self.r == 0f32 && self.g == 0f32 && self.b == 0f32
+    }
+
+    // The Y (luminance) component of the equivalent XYZ colour.
+    pub fn luminance(&self) -> f32 {
+        0.212671f32 * self.r + 0.715160f32 * self.g + 0.072169f32 * self.b
+    }
+
+    pub fn max_component(&self) -> f32 {
+        self.r.max(self.g).max(self.b)
+    }
+
+    pub fn clamp(&self, lo : f32, hi : f32) -> Color {
+        Color::new(self.r.clamp(lo, hi), self.g.clamp(lo, hi), self.b.clamp(lo, hi))
+    }
+
+    pub fn sqrt(&self) -> Color {
+        Color::new(self.r.sqrt(), self.g.sqrt(), self.b.sqrt())
+    }
+
+    pub fn exp(&self) -> Color {
+        Color::new(self.r.exp(), self.g.exp(), self.b.exp())
+    }
+
+    // 8-bit sRGB-encoded components, clamped to the displayable range.
+    pub fn to_srgb8(&self) -> [u8; 3] {
+        let encode = |v : f32| {
+            let v = v.clamp(0f32, 1f32);
+            let e = if v <= 0.0031308f32 { 12.92f32 * v } else { 1.055f32 * v.powf(1f32 / 2.4f32) - 0.055f32 };
+            (e * 255f32).round() as u8
+        };
+        [encode(self.r), encode(self.g), encode(self.b)]
+    }
+
+    pub fn mul_s(&self, s : f32) -> Color {
+        Color::new(self.r * s, self.g * s, self.b * s)
+    }
+
+    pub fn mul_self_s(&mut self, s : f32) {
+        self.r = self.r * s;
+        self.g = self.g * s;
+        self.b = self.b * s
+    }
+
+    pub fn div_s(&self, s : f32) -> Color {
+        Color::new(self.r / s, self.g / s, self.b / s)
+    }
+
+    pub fn div_self_s(&mut self, s : f32) {
+        self.r = self.r / s;
+        self.g = self.g / s;
+        self.b = self.b / s
+    }
+
+    pub fn add_c(&self, o : &Color) -> Color {
+        Color::new(self.r + o.r, self.g + o.g, self.b + o.b)
+    }
+
+    pub fn add_self_c(&mut self, o : &Color) {
+        self.r = self.r + o.r;
+        self.g = self.g + o.g;
+        self.b = self.b + o.b
+    }
+
+    pub fn sub_c(&self, o : &Color) -> Color {
+        Color::new(self.r - o.r, self.g - o.g, self.b - o.b)
+    }
+
+    pub fn sub_self_c(&mut self, o : &Color) {
+        self.r = self.r - o.r;
+        self.g = self.g - o.g;
+        self.b = self.b - o.b
+    }
+
+    pub fn mul_c(&self, o : &Color) -> Color {
+        Color::new(self.r * o.r, self.g * o.g, self.b * o.b)
+    }
+
+    pub fn mul_self_c(&mut self, o : &Color) {
+        self.r = self.r * o.r;
+        self.g = self.g * o.g;
+        self.b = self.b * o.b
+    }
+
+    // Component-wise division, where dividing by zero gives zero.
+    pub fn div_c(&self, o : &Color) -> Color {
+        let d = |a : f32, b : f32| if b == 0f32 { 0f32 } else { a / b };
+        Color::new(d(self.r, o.r), d(self.g, o.g), d(self.b, o.b))
+    }
+}
+
+impl Index<usize> for Color {
+    type Output = f32;
+    fn index(&self, index : usize) -> &f32 {
+        match index {
+            0 => &self.r,
+            1 => &self.g,
+            _ => &self.b,
+        }
+    }
+}
+
+impl Display for Color {
+    fn fmt(&self, f : &mut Formatter) -> Result {
+        writeln!(f, "rgb({}, {}, {})", self.r, self.g, self.b)
+    }
+}
+
+impl PartialEq for Color {
+    fn eq(&self, other: &Color) -> bool {
+        (self.r - other.r).abs() < 1e-3f32 && (self.g - other.g).abs() < 1e-3f32 && (self.b - other.b).abs() < 1e-3f32
+    }
+}
+
+impl Default for Color {
+    fn default() -> Color {
+        Color::black()
+    }
+}
+
+impl Add<Color> for Color {
+    type Output=Color;
+    fn add(self, c : Color) -> Color {
+        self.add_c(&c)
+    }
+}
+
+impl Sub<Color> for Color {
+    type Output=Color;
+    fn sub(self, c : Color) -> Color {
+        self.sub_c(&c)
+    }
+}
+
+impl Mul<Color> for Color {
+    type Output=Color;
+    fn mul(self, c : Color) -> Color {
+        self.mul_c(&c)
+    }
+}
+
+impl Mul<f32> for Color {
+    type Output=Color;
+    fn mul(self, s : f32) -> Color {
+        self.mul_s(s)
+    }
+}
+
+impl Mul<Color> for f32 {
+    type Output=Color;
+    fn mul(self, c : Color) -> Color {
+        c.mul_s(self)
+    }
+}
+
+impl Div<f32> for Color {
+    type Output=Color;
+    fn div(self, s : f32) -> Color {
+        self.div_s(s)
+    }
+}
+
+#[test]
+fn test_arithmetic() {
+    let a = Color::new(0.5f32, 1f32, 2f32);
+    let b = Color::new(2f32, 0f32, 0.5f32);
+    assert_eq!(a * b, Color::new(1f32, 0f32, 1f32));
+    assert_eq!(a + b, Color::new(2.5f32, 1f32, 2.5f32));
+    assert_eq!(2f32 * a - b, Color::new(-1f32, 2f32, 3.5f32));
+    assert_eq!(a.div_c(&b), Color::new(0.25f32, 0f32, 4f32));
+    assert!((Color::white().luminance() - 1f32).abs() < 1e-5);
+}
+
+#[test]
+fn test_srgb8() {
+    assert_eq!(Color::black().to_srgb8(), [0, 0, 0]);
+    assert_eq!(Color::new(1f32, 2f32, -1f32).to_srgb8(), [255, 255, 0]);
+    // 18% grey encodes to roughly the middle of the 8-bit range
+    assert_eq!(Color::gray(0.18f32).to_srgb8(), [118, 118, 118]);
+}
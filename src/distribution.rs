@@ -0,0 +1,164 @@
+// Piecewise-constant distributions for importance sampling, after pbrt-v3.
+
+// A function on [0, 1] that is constant over each of n equal pieces, and its
+// cumulative distribution.  Negative values are treated as zero.
+#[derive(Clone, Debug)]
+pub struct Distribution1D {
+    func     : Vec<f32>,
+    cdf      : Vec<f32>,
+    func_int : f32,
+}
+
+impl Distribution1D {
+    pub fn new(func : &[f32]) -> Distribution1D {
+        assert!(!func.is_empty(), "distribution needs at least one value");
+        let n = func.len();
+        let func : Vec<f32> = func.iter().map(|f| f.max(0f32)).collect();
+
+        let mut cdf = Vec::with_capacity(n + 1);
+        cdf.push(0f32);
+        for i in 0..n {
+            let c = cdf[i] + func[i] / (n as f32);
+            cdf.push(c);
+        }
+
+        // a function that is zero everywhere is sampled uniformly
+        let func_int = cdf[n];
+        if func_int == 0f32 {
+            for (i, c) in cdf.iter_mut().enumerate() {
+                *c = (i as f32) / (n as f32);
+            }
+        } else {
+            for c in cdf.iter_mut() {
+                *c /= func_int;
+            }
+        }
+
+        Distribution1D { func: func, cdf: cdf, func_int: func_int }
+    }
+
+    pub fn count(&self) -> usize {
+        self.func.len()
+    }
+
+    // The integral of the function over [0, 1].
+    pub fn integral(&self) -> f32 {
+        self.func_int
+    }
+
+    // Maps u to a point x in [0, 1) distributed as the function is, returning
+    // x, its density and the piece it is in.
+    pub fn sample_continuous(&self, u : f32) -> (f32, f32, usize) {
+        let offset = self.find(u);
+        let mut du = u - self.cdf[offset];
+        let width = self.cdf[offset + 1] - self.cdf[offset];
+        if width > 0f32 {
+            du /= width;
+        }
+        let pdf = if self.func_int > 0f32 { self.func[offset] / self.func_int } else { 1f32 };
+        let x = ((offset as f32 + du) / (self.count() as f32)).min(1f32 - f32::EPSILON);
+        (x, pdf, offset)
+    }
+
+    // Picks one of the pieces in proportion to its value, returning it and its
+    // probability.
+    pub fn sample_discrete(&self, u : f32) -> (usize, f32) {
+        let offset = self.find(u);
+        (offset, self.discrete_pdf(offset))
+    }
+
+    pub fn discrete_pdf(&self, ix : usize) -> f32 {
+        if self.func_int > 0f32 {
+            self.func[ix] / (self.func_int * self.count() as f32)
+        } else {
+            1f32 / (self.count() as f32)
+        }
+    }
+
+    // The density of sample_continuous() at x.
+    pub fn pdf(&self, x : f32) -> f32 {
+        let ix = ((x * self.count() as f32) as usize).min(self.count() - 1);
+        if self.func_int > 0f32 { self.func[ix] / self.func_int } else { 1f32 }
+    }
+
+    // The last piece whose cdf is at most u.
+    fn find(&self, u : f32) -> usize {
+        let ix = self.cdf.partition_point(|&c| c <= u);
+        ix.saturating_sub(1).min(self.count() - 1)
+    }
+}
+
+// A function on [0, 1]² that is constant over each of nu × nv pieces, given
+// row by row, sampled by choosing a row from the marginal distribution and
+// then a point along it.
+#[derive(Clone, Debug)]
+pub struct Distribution2D {
+    conditional : Vec<Distribution1D>,
+    marginal    : Distribution1D,
+}
+
+impl Distribution2D {
+    pub fn new(func : &[f32], nu : usize, nv : usize) -> Distribution2D {
+        assert_eq!(func.len(), nu * nv, "distribution size must match its values");
+        let conditional : Vec<Distribution1D> = func.chunks(nu).map(Distribution1D::new).collect();
+        let marginal = Distribution1D::new(&conditional.iter().map(|d| d.integral()).collect::<Vec<f32>>());
+        Distribution2D { conditional: conditional, marginal: marginal }
+    }
+
+    // Maps u to a point distributed as the function is, returning it and its
+    // density.
+    pub fn sample_continuous(&self, (u0, u1) : (f32, f32)) -> ((f32, f32), f32) {
+        let (v, pdf_v, row) = self.marginal.sample_continuous(u1);
+        let (u, pdf_u, _) = self.conditional[row].sample_continuous(u0);
+        ((u, v), pdf_u * pdf_v)
+    }
+
+    // The density of sample_continuous() at (u, v).
+    pub fn pdf(&self, (u, v) : (f32, f32)) -> f32 {
+        let row = ((v * self.marginal.count() as f32) as usize).min(self.marginal.count() - 1);
+        self.conditional[row].pdf(u) * self.marginal.pdf(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distribution_1d() {
+        let d = Distribution1D::new(&[1f32, 3f32, 0f32, 4f32]);
+        assert_eq!(d.integral(), 2f32);
+
+        // half of u lands in the last piece, none in the empty one
+        let (x, pdf, ix) = d.sample_continuous(0.75f32);
+        assert_eq!(ix, 3);
+        assert!((x - 0.875f32).abs() < 1e-6f32);
+        assert_eq!(pdf, 2f32);
+        assert_eq!(d.pdf(x), pdf);
+        assert_eq!(d.sample_continuous(0.2f32).2, 1);
+        assert_eq!(d.sample_discrete(0.49f32), (1, 0.375f32));
+        assert_eq!(d.discrete_pdf(2), 0f32);
+
+        let zero = Distribution1D::new(&[0f32, 0f32]);
+        assert_eq!(zero.sample_continuous(0.25f32), (0.25f32, 1f32, 0));
+    }
+
+    #[test]
+    fn test_distribution_2d() {
+        let d = Distribution2D::new(&[1f32, 1f32,  0f32, 2f32], 2, 2);
+        let n = 64;
+        let mut in_empty = 0;
+        for i in 0..n {
+            for j in 0..n {
+                let u = ((i as f32 + 0.5f32) / n as f32, (j as f32 + 0.5f32) / n as f32);
+                let ((x, y), pdf) = d.sample_continuous(u);
+                assert!((pdf - d.pdf((x, y))).abs() < 1e-5f32);
+                if x < 0.5f32 && y >= 0.5f32 {
+                    in_empty += 1;
+                }
+            }
+        }
+        assert_eq!(in_empty, 0);
+        assert!((d.pdf((0.75f32, 0.75f32)) - 2f32).abs() < 1e-5f32);
+    }
+}
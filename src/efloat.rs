@@ -0,0 +1,153 @@
+use std::ops::{Add, Sub, Mul, Div, Neg};
+
+// A float that keeps track of the error in computing it, as an interval
+// [low, high] that surely contains the value exact arithmetic would have
+// given.  Each operation rounds the bounds outwards, so that they stay
+// conservative however many operations follow.
+#[derive(Copy, Clone, Debug)]
+pub struct EFloat {
+    pub v : f32,
+    low   : f32,
+    high  : f32,
+}
+
+impl EFloat {
+    // v, which is within err of the exact value.
+    pub fn new(v : f32, err : f32) -> EFloat {
+        if err == 0f32 {
+            EFloat { v: v, low: v, high: v }
+        } else {
+            EFloat { v: v, low: (v - err).next_down(), high: (v + err).next_up() }
+        }
+    }
+
+    fn interval(v : f32, low : f32, high : f32) -> EFloat {
+        EFloat { v: v, low: low, high: high }
+    }
+
+    pub fn lower_bound(&self) -> f32 {
+        self.low
+    }
+
+    pub fn upper_bound(&self) -> f32 {
+        self.high
+    }
+
+    // The furthest the exact value can be from v.
+    pub fn absolute_error(&self) -> f32 {
+        (self.high - self.v).abs().max((self.v - self.low).abs()).next_up()
+    }
+
+    pub fn sqrt(&self) -> EFloat {
+        EFloat::interval(self.v.sqrt(), self.low.max(0f32).sqrt().next_down(), self.high.sqrt().next_up())
+    }
+
+    pub fn abs(&self) -> EFloat {
+        if self.low >= 0f32 {
+            *self
+        } else if self.high <= 0f32 {
+            -*self
+        } else {
+            EFloat::interval(self.v.abs(), 0f32, (-self.low).max(self.high))
+        }
+    }
+}
+
+impl From<f32> for EFloat {
+    fn from(v : f32) -> EFloat {
+        EFloat::new(v, 0f32)
+    }
+}
+
+impl Add for EFloat {
+    type Output = EFloat;
+
+    fn add(self, e : EFloat) -> EFloat {
+        EFloat::interval(self.v + e.v, (self.low + e.low).next_down(), (self.high + e.high).next_up())
+    }
+}
+
+impl Sub for EFloat {
+    type Output = EFloat;
+
+    fn sub(self, e : EFloat) -> EFloat {
+        EFloat::interval(self.v - e.v, (self.low - e.high).next_down(), (self.high - e.low).next_up())
+    }
+}
+
+impl Mul for EFloat {
+    type Output = EFloat;
+
+    fn mul(self, e : EFloat) -> EFloat {
+        let prod = [self.low * e.low, self.high * e.low, self.low * e.high, self.high * e.high];
+        let low = prod.iter().fold(f32::INFINITY, |a, &b| a.min(b));
+        let high = prod.iter().fold(f32::NEG_INFINITY, |a, &b| a.max(b));
+        EFloat::interval(self.v * e.v, low.next_down(), high.next_up())
+    }
+}
+
+impl Div for EFloat {
+    type Output = EFloat;
+
+    // Dividing by an interval that spans zero could give anything at all.
+    fn div(self, e : EFloat) -> EFloat {
+        if e.low < 0f32 && e.high > 0f32 {
+            return EFloat::interval(self.v / e.v, f32::NEG_INFINITY, f32::INFINITY);
+        }
+        let quot = [self.low / e.low, self.high / e.low, self.low / e.high, self.high / e.high];
+        let low = quot.iter().fold(f32::INFINITY, |a, &b| a.min(b));
+        let high = quot.iter().fold(f32::NEG_INFINITY, |a, &b| a.max(b));
+        EFloat::interval(self.v / e.v, low.next_down(), high.next_up())
+    }
+}
+
+impl Neg for EFloat {
+    type Output = EFloat;
+
+    fn neg(self) -> EFloat {
+        EFloat::interval(-self.v, -self.high, -self.low)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contains(e : &EFloat, x : f64) -> bool {
+        e.lower_bound() as f64 <= x && x <= e.upper_bound() as f64
+    }
+
+    #[quickcheck]
+    fn prop_add_bounds_exact(a : f32, b : f32) -> bool {
+        contains(&(EFloat::from(a) + EFloat::from(b)), a as f64 + b as f64)
+    }
+
+    #[quickcheck]
+    fn prop_sub_bounds_exact(a : f32, b : f32) -> bool {
+        contains(&(EFloat::from(a) - EFloat::from(b)), a as f64 - b as f64)
+    }
+
+    #[quickcheck]
+    fn prop_mul_bounds_exact(a : f32, b : f32, c : f32) -> bool {
+        contains(&(EFloat::from(a) * EFloat::from(b) * EFloat::from(c)), a as f64 * b as f64 * c as f64)
+    }
+
+    #[quickcheck]
+    fn prop_div_bounds_exact(a : f32, b : f32) -> bool {
+        b == 0f32 || contains(&(EFloat::from(a) / EFloat::from(b)), a as f64 / b as f64)
+    }
+
+    #[test]
+    fn test_error() {
+        let e = EFloat::new(2f32, 0.5f32);
+        assert!(e.lower_bound() <= 1.5f32 && e.upper_bound() >= 2.5f32);
+        assert!(e.absolute_error() >= 0.5f32);
+        assert_eq!(EFloat::from(3f32).lower_bound(), 3f32);
+        assert!(contains(&e.sqrt(), 1.5f64.sqrt()) && contains(&e.sqrt(), 2.5f64.sqrt()));
+
+        let spans_zero = EFloat::new(0f32, 1f32);
+        assert_eq!((EFloat::from(1f32) / spans_zero).upper_bound(), f32::INFINITY);
+        assert_eq!(spans_zero.abs().lower_bound(), 0f32);
+        assert_eq!((-e).upper_bound(), -e.lower_bound());
+    }
+}
@@ -1,12 +1,16 @@
 use std::path::Path;
 use std::default::Default;
 use std::fs::File;
+use std::io::BufWriter;
 
+use image::hdr::HDREncoder;
 use image::png::PNGEncoder;
-use image::ColorType;
+use image::{ColorType, Rgb};
+
+use crate::color::Color;
 
 pub struct Pixel {
-    sum : f32,
+    sum : Color,
     weight_sum : f32
 }
 
@@ -14,11 +18,12 @@ pub struct Film {
     pub width : u32,
     pub height : u32,
     pixels : Vec<Pixel>,
+    splats : Vec<Color>,
 }
 
 impl Pixel {
     fn new() -> Pixel {
-        Pixel { sum: 0f32, weight_sum: 0f32 }
+        Pixel { sum: Color::black(), weight_sum: 0f32 }
     }
 }
 
@@ -35,7 +40,8 @@ impl Film {
         Film {
             width: width, 
             height: height, 
-            pixels: v
+            pixels: v,
+            splats: vec![Color::black(); (width * height) as usize],
         }
     }
 
@@ -54,19 +60,57 @@ impl Film {
     }
 
     #[inline]
-    pub fn splat(&mut self, x : u32, y : u32, sum : f32, weight_sum : f32) {
+    pub fn splat(&mut self, x : u32, y : u32, sum : Color, weight_sum : f32) {
         let p = self.get_pixel_mut(x, y);
-        p.sum = p.sum + sum;
+        p.sum.add_self_c(&sum);
         p.weight_sum = p.weight_sum + weight_sum;
     }
 
+    // Adds light that reached the film at (x, y), in pixels, from a path
+    // traced from a light rather than through the pixel being rendered, so
+    // it may land anywhere.  Splats are not filtered or weighted, so c should
+    // already be divided by the number of samples taken per pixel.
+    pub fn add_splat(&mut self, x : f32, y : f32, c : Color) {
+        if x < 0f32 || y < 0f32 || x >= self.width as f32 || y >= self.height as f32 {
+            return;
+        }
+        let (x, y) = (x as u32, y as u32);
+        let ix = ((self.height - y - 1) * self.width + x) as usize;
+        self.splats[ix].add_self_c(&c);
+    }
+
+    fn color(p : &Pixel, splat : &Color) -> Color {
+        let c = if p.weight_sum == 0f32 { Color::black() } else { p.sum / p.weight_sum };
+        c + *splat
+    }
+
+    // Pixels hold linear radiance.  A path ending in .hdr is written as a
+    // Radiance image, keeping it linear, for use as an environment map; any
+    // other is written as an sRGB-encoded PNG.
     pub fn save(&self, path : &Path) -> Result<(), &str> {
-        let pixels : Vec<u8> = self.pixels.iter().map(|p| (p.sum / p.weight_sum).round() as u8).collect();
+        if path.extension().is_some_and(|e| e == "hdr") {
+            return self.save_hdr(path);
+        }
+
+        let pixels : Vec<u8> = self.pixels.iter().zip(self.splats.iter()).flat_map(|(p, s)| Film::color(p, s).to_srgb8().to_vec()).collect();
 
         let file = File::create(path).unwrap();
         let encoder = PNGEncoder::new(file);
 
-        match encoder.encode(pixels.as_slice(), self.width as u32, self.height as u32, ColorType::Gray(8)) {
+        match encoder.encode(pixels.as_slice(), self.width as u32, self.height as u32, ColorType::RGB(8)) {
+            Ok(_)  => Ok(()),
+            Err(_) => Err("save failed"),
+        }
+    }
+
+    fn save_hdr(&self, path : &Path) -> Result<(), &str> {
+        let pixels : Vec<Rgb<f32>> = self.pixels.iter().zip(self.splats.iter()).map(|(p, s)| {
+            let c = Film::color(p, s);
+            Rgb { data: [c.r, c.g, c.b] }
+        }).collect();
+
+        let file = File::create(path).map_err(|_| "save failed")?;
+        match HDREncoder::new(BufWriter::new(file)).encode(&pixels, self.width as usize, self.height as usize) {
             Ok(_)  => Ok(()),
             Err(_) => Err("save failed"),
         }
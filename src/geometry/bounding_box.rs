@@ -1,7 +1,7 @@
 use std::default::Default;
 use std::fmt::{Display, Formatter, Result};
 
-use crate::geometry::{Point, Ray, HasTransform};
+use crate::geometry::{Point, Vector, Ray, HasTransform};
 
 #[derive(Copy, Clone, Debug)]
 pub struct BoundingBox {
@@ -35,6 +35,54 @@ impl BoundingBox {
         self.empty
     }
 
+    pub fn min(&self) -> Point {
+        self.min
+    }
+
+    pub fn max(&self) -> Point {
+        self.max
+    }
+
+    pub fn centroid(&self) -> Point {
+        self.min + ((self.max - self.min) * 0.5f32)
+    }
+
+    pub fn diagonal(&self) -> Vector {
+        if self.empty {
+            Vector::zero()
+        } else {
+            self.max - self.min
+        }
+    }
+
+    // The centre and radius of a sphere enclosing the box.
+    pub fn bounding_sphere(&self) -> (Point, f32) {
+        if self.empty {
+            (Point::origin(), 0f32)
+        } else {
+            (self.centroid(), self.diagonal().magnitude() * 0.5f32)
+        }
+    }
+
+    pub fn maximum_extent(&self) -> usize {
+        let d = self.diagonal();
+        if d.x > d.y && d.x > d.z {
+            0
+        } else if d.y > d.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn offset(&self, p : &Point) -> Vector {
+        let mut o = *p - self.min;
+        if self.max.x > self.min.x { o.x /= self.max.x - self.min.x; }
+        if self.max.y > self.min.y { o.y /= self.max.y - self.min.y; }
+        if self.max.z > self.min.z { o.z /= self.max.z - self.min.z; }
+        o
+    }
+
     pub fn range_x(&self) -> Option<(f32, f32)> {
         if self.empty {
             None
@@ -141,7 +189,7 @@ impl BoundingBox {
             0f32
         } else {
             let d = self.max - self.min;
-            2f32 * (d.x * d.y + d.x * d.y + d.y * d.z)
+            2f32 * (d.x * d.y + d.x * d.z + d.y * d.z)
         }
     }
 
@@ -165,37 +213,29 @@ impl BoundingBox {
         }
     }
 
-    pub fn intersects(&self, r : &Ray) -> bool {
-        if self.empty { return false; }
+    // Returns the parametric distances at which the ray enters and exits the
+    // box, clipped to the ray's extent.
+    pub fn intersects(&self, r : &Ray) -> Option<(f32, f32)> {
+        if self.empty { return None; }
 
-        let mut tmin : f32 = (self.min.x - r.origin.x) / r.direction.x;
-        let mut tmax : f32 = (self.max.x - r.origin.x) / r.direction.x;
-        if tmin > tmax {
-            std::mem::swap(&mut tmin, &mut tmax);
-        }
+        let mut t0 = r.t_min;
+        let mut t1 = r.t_max;
 
-        let ty1 = (self.min.y - r.origin.y) / r.direction.y;
-        let ty2 = (self.max.y - r.origin.y) / r.direction.y;
-        if ty1 < ty2 {
-            tmin = tmin.max(ty1);
-            tmax = tmax.min(ty2);
-        } else {
-            tmin = tmin.max(ty2);
-            tmax = tmax.min(ty1);
-        };
-        if tmin > tmax { return false; }
-
-        let tz1 = (self.min.z - r.origin.z) / r.direction.z;
-        let tz2 = (self.max.z - r.origin.z) / r.direction.z;
-        if tz1 < tz2 {
-            tmin = tmin.max(tz1);
-            tmax = tmax.min(tz2);
-        } else {
-            tmin = tmin.max(tz2);
-            tmax = tmax.min(tz1);
-        };
+        for axis in 0..3 {
+            let inv_dir = 1f32 / r.direction[axis];
+            let mut t_near = (self.min[axis] - r.origin[axis]) * inv_dir;
+            let mut t_far  = (self.max[axis] - r.origin[axis]) * inv_dir;
+            if t_near > t_far {
+                std::mem::swap(&mut t_near, &mut t_far);
+            }
 
-        tmax >= tmin && tmin >= 0f32
+            // comparisons against NaN (origin on a slab, direction parallel to it) leave the range unchanged
+            if t_near > t0 { t0 = t_near; }
+            if t_far < t1 { t1 = t_far; }
+            if t0 > t1 { return None; }
+        }
+
+        Some((t0, t1))
     }
 
     pub fn to<T : HasTransform>(&self, t : &T) -> BoundingBox {
@@ -234,3 +274,28 @@ impl Default for BoundingBox {
         BoundingBox::empty()
     }
 }
+
+#[test]
+fn test_surface_area() {
+    let b = BoundingBox::for_points(&[Point::origin(), Point::new(1f32, 2f32, 3f32)]);
+    assert_eq!(b.surface_area(), 22f32);
+    assert_eq!(b.volume(), 6f32);
+}
+
+#[test]
+fn test_maximum_extent() {
+    assert_eq!(BoundingBox::for_points(&[Point::origin(), Point::new(3f32, 2f32, 1f32)]).maximum_extent(), 0);
+    assert_eq!(BoundingBox::for_points(&[Point::origin(), Point::new(1f32, 3f32, 2f32)]).maximum_extent(), 1);
+    assert_eq!(BoundingBox::for_points(&[Point::origin(), Point::new(1f32, 2f32, 3f32)]).maximum_extent(), 2);
+}
+
+#[test]
+fn test_intersects() {
+    let b = BoundingBox::for_points(&[Point::new(-1f32, -1f32, 2f32), Point::new(1f32, 1f32, 4f32)]);
+
+    assert_eq!(b.intersects(&Ray::z_axis()), Some((2f32, 4f32)));
+    assert_eq!(b.intersects(&Ray::x_axis()), None);
+    assert_eq!(b.intersects(&Ray::new_bounded(&Point::origin(), &Vector::unit_z(), 0f32, 1f32)), None);
+    assert_eq!(b.intersects(&Ray::new_bounded(&Point::origin(), &Vector::unit_z(), 0f32, 3f32)), Some((2f32, 3f32)));
+    assert_eq!(b.intersects(&Ray::new(&Point::new(0f32, 0f32, 3f32), &Vector::unit_z())), Some((0f32, 1f32)));
+}
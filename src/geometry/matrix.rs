@@ -3,6 +3,7 @@ use std::ops::{Add, Sub, Mul, Div, Index};
 use std::fmt::{Display, Formatter, Result};
 
 use crate::geometry::{Vector, Normal, Point};
+use crate::math::gamma;
 
 #[derive(Copy, Clone, Debug)]
 pub struct Matrix {
@@ -94,6 +95,58 @@ impl Matrix {
         self.m.swap(11, 14);
     }
 
+    pub fn is_finite(&self) -> bool {
+        self.m.iter().all(|x| x.is_finite())
+    }
+
+    // Gauss-Jordan elimination with partial pivoting, done in f64 so that
+    // near-singular scene transforms keep their precision.  A matrix with an
+    // infinite or NaN element has no inverse.
+    pub fn inverse(&self) -> Option<Matrix> {
+        if !self.is_finite() {
+            return None;
+        }
+
+        let mut a = [[0f64; 8]; 4];
+        for r in 0..4 {
+            for c in 0..4 {
+                a[r][c] = self[r * 4 + c] as f64;
+            }
+            a[r][4 + r] = 1f64;
+        }
+
+        for c in 0..4 {
+            let pivot = (c..4).max_by(|&i, &j| a[i][c].abs().total_cmp(&a[j][c].abs())).unwrap();
+            if a[pivot][c] == 0f64 {
+                return None;
+            }
+            a.swap(c, pivot);
+
+            let p = a[c][c];
+            for x in a[c].iter_mut() {
+                *x /= p;
+            }
+
+            let pivot_row = a[c];
+            for (r, row) in a.iter_mut().enumerate() {
+                if r != c {
+                    let f = row[c];
+                    for (x, y) in row.iter_mut().zip(pivot_row.iter()) {
+                        *x -= f * y;
+                    }
+                }
+            }
+        }
+
+        let mut m = [0f32; 16];
+        for r in 0..4 {
+            for c in 0..4 {
+                m[r * 4 + c] = a[r][4 + c] as f32;
+            }
+        }
+        Some(Matrix::new(&m))
+    }
+
     pub fn mul_m(&self, o : &Matrix) -> Matrix {
         Matrix::new(&[self[ 0] * o.m[ 0] + self[ 1] * o.m[ 4] + self[ 2] * o.m[ 8] + self[ 3] * o.m[12],
                       self[ 0] * o.m[ 1] + self[ 1] * o.m[ 5] + self[ 2] * o.m[ 9] + self[ 3] * o.m[13],
@@ -206,6 +259,27 @@ impl Matrix {
         }
     }
 
+    // As mul_v, with a bound on the rounding error in each component.
+    pub fn mul_v_with_error(&self, v : &Vector) -> (Vector, Vector) {
+        let g = gamma(3);
+        let error = Vector::new(g * ((self[ 0] * v.x).abs() + (self[ 1] * v.y).abs() + (self[ 2] * v.z).abs()),
+                                g * ((self[ 4] * v.x).abs() + (self[ 5] * v.y).abs() + (self[ 6] * v.z).abs()),
+                                g * ((self[ 8] * v.x).abs() + (self[ 9] * v.y).abs() + (self[10] * v.z).abs()));
+        (self.mul_v(v), error)
+    }
+
+    // As mul_p, for an affine matrix, with a bound on the error in each
+    // component: the rounding of the product, and the error already in p,
+    // which is at most error, carried through.
+    pub fn mul_p_with_error(&self, p : &Point, error : &Vector) -> (Point, Vector) {
+        let g = gamma(3);
+        let row = |i : usize| {
+            g * ((self[i] * p.x).abs() + (self[i + 1] * p.y).abs() + (self[i + 2] * p.z).abs() + self[i + 3].abs()) +
+                (1f32 + g) * (self[i].abs() * error.x + self[i + 1].abs() * error.y + self[i + 2].abs() * error.z)
+        };
+        (self.mul_p(p), Vector::new(row(0), row(4), row(8)))
+    }
+
     pub fn premul_p(&self, p : &Point) -> Point {
         let s = self[3] * p.x + self[7] * p.y + self[11] * p.z + self[15];
         
@@ -435,3 +509,39 @@ fn test_normal_math() {
     assert_eq!(Matrix::identity().mul_n(&n), n);
     assert_eq!(Matrix::identity().premul_n(&n), n);
 }
+
+#[test]
+fn test_inverse() {
+    let m = Matrix::translation(&Vector::new(1f32, -2f32, 3f32)) * Matrix::scaling(&Vector::new(2f32, 4f32, 0.5f32));
+    let i = m.inverse().unwrap();
+    let p = Point::new(0.25f32, 7f32, -3f32);
+    let q = i.mul_p(&m.mul_p(&p));
+    assert!((q.x - p.x).abs() < 1e-5 && (q.y - p.y).abs() < 1e-5 && (q.z - p.z).abs() < 1e-5);
+
+    assert!(Matrix::scaling(&Vector::new(1f32, 0f32, 1f32)).inverse().is_none());
+    assert!(Matrix::translation(&Vector::new(f32::NAN, 0f32, 0f32)).inverse().is_none());
+    assert!(Matrix::scaling(&Vector::new(1f32, f32::INFINITY, 1f32)).inverse().is_none());
+}
+
+#[test]
+fn test_error_bounds() {
+    let m = Matrix::translation(&Vector::new(1000f32, -3f32, 0.1f32)) * Matrix::scaling(&Vector::new(0.3f32, 7f32, 1.1f32));
+    let p = Point::new(0.7f32, -1.3f32, 2.9f32);
+    let exact = [0.3f64 as f32 as f64 * 0.7f32 as f64 + 1000f64,
+                 7f64 * -1.3f32 as f64 - 3f64,
+                 1.1f32 as f64 * 2.9f32 as f64 + 0.1f32 as f64];
+
+    let (q, error) = m.mul_p_with_error(&p, &Vector::zero());
+    for ix in 0..3 {
+        assert!((q[ix] as f64 - exact[ix]).abs() <= error[ix] as f64, "component {} out by more than {}", ix, error[ix]);
+        assert!(error[ix] > 0f32);
+    }
+
+    // error already in the point is scaled with it
+    let (_, carried) = m.mul_p_with_error(&p, &Vector::new(0.5f32, 0.5f32, 0.5f32));
+    assert!(carried.x >= 0.15f32 && carried.y >= 3.5f32 && carried.z >= 0.55f32);
+
+    let (v, error) = m.mul_v_with_error(&Vector::new(1f32, 1f32, 1f32));
+    assert_eq!(v, Vector::new(0.3f32, 7f32, 1.1f32));
+    assert!(error.x < 1e-6f32 && error.y < 1e-5f32);
+}
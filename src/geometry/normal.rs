@@ -54,6 +54,10 @@ impl Normal {
         if m != 0f32 { self.div_self_s(m) }
     }
 
+    pub fn to_vector(&self) -> Vector {
+        Vector::new(self.x, self.y, self.z)
+    }
+
     pub fn face_forward(&self, forward : &Vector) -> Normal {
       if self.dot(&forward.to_normal()) < 0f32 { self.reverse() } else { *self }
     }
@@ -112,12 +116,14 @@ impl Normal {
         self.z = self.z - o.z;
     }
 
+    // Normals transform by the inverse transpose, so that they stay
+    // perpendicular to their surface under non-uniform scaling.
     pub fn to<T : HasTransform>(&self, t : &T) -> Normal {
-        t.get_transform().to_object.mul_n(self)
+        t.get_transform().to_world.premul_n(self)
     }
 
     pub fn from<T : HasTransform>(&self, t : &T) -> Normal {
-        t.get_transform().to_world.mul_n(self)
+        t.get_transform().to_object.premul_n(self)
     }
 }
 
@@ -278,3 +284,20 @@ fn test_div() {
 fn face_forward() {
     assert_eq!(Normal::unit_x().reverse().face_forward(&Vector::unit_x()), Normal::unit_x());
 }
+
+#[test]
+fn test_transform() {
+    use crate::geometry::Transform;
+
+    struct Scaled(Transform);
+    impl HasTransform for Scaled {
+        fn get_transform(&self) -> &Transform { &self.0 }
+    }
+
+    // the plane x + y = 0, squashed to a quarter of its width in x
+    let s = Scaled(Transform::scaling(&Vector::new(0.25f32, 1f32, 1f32)));
+    let tangent = Vector::new(1f32, -1f32, 0f32).from(&s);
+    let n = Normal::new(1f32, 1f32, 0f32).from(&s);
+    assert!(n.to_vector().dot(&tangent).abs() < 1e-6);
+    assert_eq!(n.to(&s), Normal::new(1f32, 1f32, 0f32));
+}
@@ -1,5 +1,5 @@
 use std::default::Default;
-use std::ops::{Add, Sub, Mul};
+use std::ops::{Index, Add, Sub, Mul};
 use std::fmt::{Display, Formatter, Result};
 
 use crate::geometry::{Matrix, Vector, HasTransform};
@@ -67,6 +67,27 @@ impl Point {
     pub fn from<T : HasTransform>(&self, t : &T) -> Point {
         t.get_transform().to_world.mul_p(self)
     }
+
+    // As to() and from(), for a point that is within error of where it
+    // should be, returning with it a bound on its error afterwards.
+    pub fn to_with_error<T : HasTransform>(&self, t : &T, error : &Vector) -> (Point, Vector) {
+        t.get_transform().to_object.mul_p_with_error(self, error)
+    }
+
+    pub fn from_with_error<T : HasTransform>(&self, t : &T, error : &Vector) -> (Point, Vector) {
+        t.get_transform().to_world.mul_p_with_error(self, error)
+    }
+}
+
+impl Index<usize> for Point {
+    type Output = f32;
+    fn index(&self, index : usize) -> &f32 {
+        match index {
+            0 => &self.x,
+            1 => &self.y,
+            _ => &self.z,
+        }
+    }
 }
 
 impl Display for Point {
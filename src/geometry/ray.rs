@@ -3,15 +3,30 @@ use std::ops::{Add, Sub, Neg};
 
 use crate::geometry::{Vector, Point, HasTransform};
 
+// The fraction of a segment left off its far end, so that a shadow ray does
+// not hit the surface it is aimed at.  The same value as pbrt's.
+pub const SHADOW_EPSILON : f32 = 0.0001f32;
+
 #[derive(Copy, Clone, Debug)]
 pub struct Ray {
     pub origin    : Point,
     pub direction : Vector,
+    pub t_min     : f32,
+    pub t_max     : f32,
 }
 
 impl Ray {
     pub fn new(origin : &Point, direction : &Vector) -> Ray {
-        Ray { origin: *origin, direction: *direction }
+        Ray { origin: *origin, direction: *direction, t_min: 0f32, t_max: f32::INFINITY }
+    }
+
+    pub fn new_bounded(origin : &Point, direction : &Vector, t_min : f32, t_max : f32) -> Ray {
+        Ray { origin: *origin, direction: *direction, t_min: t_min, t_max: t_max }
+    }
+
+    // A ray from p0 that stops just short of p1, e.g. for shadow rays towards a light.
+    pub fn new_segment(p0 : &Point, p1 : &Point) -> Ray {
+        Ray::new_bounded(p0, &(*p1 - *p0), 0f32, 1f32 - SHADOW_EPSILON)
     }
 
     pub fn x_axis() -> Ray {
@@ -30,8 +45,12 @@ impl Ray {
         self.origin + (self.direction * t)
     }
 
+    pub fn contains(&self, t : f32) -> bool {
+        t >= self.t_min && t <= self.t_max
+    }
+
     pub fn reverse(&self) -> Ray {
-        Ray::new(&self.origin, &-self.direction)
+        Ray { direction: -self.direction, .. *self }
     }
 
     pub fn reverse_self(&mut self) {
@@ -39,41 +58,50 @@ impl Ray {
     }
 
     pub fn to<T : HasTransform>(&self, t : &T) -> Ray {
-        Ray::new(&self.origin.to(t), &self.direction.to(t))
+        Ray { origin: self.origin.to(t), direction: self.direction.to(t), .. *self }
+    }
+
+    // As to(), with bounds on the error in the origin and direction that
+    // transforming them introduces.
+    pub fn to_with_error<T : HasTransform>(&self, t : &T) -> (Ray, Vector, Vector) {
+        let m = &t.get_transform().to_object;
+        let (origin, origin_error) = m.mul_p_with_error(&self.origin, &Vector::zero());
+        let (direction, direction_error) = m.mul_v_with_error(&self.direction);
+        (Ray { origin: origin, direction: direction, .. *self }, origin_error, direction_error)
     }
 
     pub fn from<T : HasTransform>(&self, t : &T) -> Ray {
-        Ray::new(&self.origin.from(t), &self.direction.from(t))
+        Ray { origin: self.origin.from(t), direction: self.direction.from(t), .. *self }
     }
 }
 
 impl Display for Ray {
     fn fmt(&self, f : &mut Formatter) -> Result {
-        writeln!(f, "Ray {{ origin: {}, direction: {} }}", self.origin, self.direction)
+        writeln!(f, "Ray {{ origin: {}, direction: {}, t: [{}, {}] }}", self.origin, self.direction, self.t_min, self.t_max)
     }
 }
 
 impl PartialEq for Ray {
     fn eq(&self, other: &Ray) -> bool {
-        self.origin == other.origin && self.direction == other.direction
+        self.origin == other.origin && self.direction == other.direction && self.t_min == other.t_min && self.t_max == other.t_max
     }
 
     fn ne(&self, other: &Ray) -> bool {
-        self.origin != other.origin || self.direction != other.direction
+        self.origin != other.origin || self.direction != other.direction || self.t_min != other.t_min || self.t_max != other.t_max
     }
 }
 
 impl Add<Vector> for Ray {
     type Output=Ray;
     fn add(self, v : Vector) -> Ray {
-        Ray::new(&self.origin, &(self.direction + v))
+        Ray { direction: self.direction + v, .. self }
     }
 }
 
 impl Sub<Vector> for Ray {
     type Output=Ray;
     fn sub(self, v : Vector) -> Ray {
-        Ray::new(&self.origin, &(self.direction - v))
+        Ray { direction: self.direction - v, .. self }
     }
 }
 
@@ -107,3 +135,16 @@ fn test_reverse() {
 fn test_at_time() {
     assert_eq!(Ray::x_axis().at_time(3f32), Point::new(3f32, 0f32, 0f32));
 }
+
+#[test]
+fn test_extent() {
+    let r = Ray::new_bounded(&Point::origin(), &Vector::unit_x(), 1f32, 2f32);
+    assert!(!r.contains(0.5f32));
+    assert!(r.contains(1.5f32));
+    assert!(!r.contains(2.5f32));
+    assert_eq!(r.reverse().t_max, 2f32);
+
+    let s = Ray::new_segment(&Point::origin(), &Point::new(0f32, 0f32, 4f32));
+    assert!(s.contains(0.5f32));
+    assert!(!s.contains(1f32));
+}
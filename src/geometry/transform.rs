@@ -1,7 +1,7 @@
 use std::default::Default;
 use std::ops::{Add, Neg};
 
-use crate::geometry::{Matrix, Vector, Quaternion};
+use crate::geometry::{Matrix, Vector, Point, Quaternion};
 
 #[derive(Copy, Clone, Debug)]
 pub struct Transform {
@@ -34,6 +34,10 @@ impl Transform {
         Transform::rotation_q(&Quaternion::rotation3(pitch, yaw, roll))
     }
 
+    pub fn from_matrix(m : &Matrix) -> Option<Transform> {
+        m.inverse().map(|i| Transform { to_world: *m, to_object: i })
+    }
+
     pub fn inverse(&self) -> Transform {
         Transform { to_world: self.to_object, to_object: self.to_world }
     }
@@ -42,9 +46,38 @@ impl Transform {
         std::mem::swap(&mut self.to_world, &mut self.to_object);
     }
 
-    // TODO: add look-at transform
-    // https://stackoverflow.com/a/6802424
-    // should be able to reverse order of multiplication and transpose to get the inverse...
+    // Places an object (usually a camera) at eye with its +z axis pointing at
+    // target and its +y axis as close to up as possible.  The rotation part is
+    // orthonormal, so the inverse is its transpose followed by the reversed
+    // translation.  There is no such transform when eye and target coincide,
+    // when up is zero, or when any of them is not finite.
+    pub fn look_at(eye : &Point, target : &Point, up : &Vector) -> Option<Transform> {
+        let finite = [eye.x, eye.y, eye.z, target.x, target.y, target.z, up.x, up.y, up.z].iter().all(|c| c.is_finite());
+        let dir = *target - *eye;
+        if !finite || dir.magnitude() == 0f32 || up.magnitude() == 0f32 {
+            return None;
+        }
+        let dir = dir.normalize();
+        let mut right = up.normalize().cross(&dir);
+        if right.magnitude() == 0f32 {
+            right = dir.coordinate_system().0;
+        }
+        let right = right.normalize();
+        let new_up = dir.cross(&right);
+
+        let to_world = Matrix::new(&[right.x, new_up.x, dir.x, eye.x,
+                                     right.y, new_up.y, dir.y, eye.y,
+                                     right.z, new_up.z, dir.z, eye.z,
+                                        0f32,     0f32,  0f32,  1f32]);
+
+        let e = Vector::new(eye.x, eye.y, eye.z);
+        let to_object = Matrix::new(&[ right.x,  right.y,  right.z, -right.dot(&e),
+                                      new_up.x, new_up.y, new_up.z, -new_up.dot(&e),
+                                         dir.x,    dir.y,    dir.z, -dir.dot(&e),
+                                          0f32,     0f32,     0f32, 1f32]);
+
+        Some(Transform { to_world: to_world, to_object: to_object })
+    }
 
     pub fn compose(&self, t : &Transform) -> Transform {
         Transform { to_world: self.to_world * t.to_world, to_object: t.to_object * self.to_object }
@@ -85,6 +118,12 @@ pub trait HasTransform {
     fn get_transform(&self) -> &Transform;
 }
 
+impl HasTransform for Transform {
+    fn get_transform(&self) -> &Transform {
+        self
+    }
+}
+
 pub trait Trans {
     type Output;
 
@@ -109,6 +148,10 @@ pub trait Trans {
     fn rotate3(&self, pitch : f32, yaw : f32, roll : f32) -> Self::Output {
         self.transform(&Transform::rotation3(pitch, yaw, roll))
     }
+
+    fn look_at(&self, eye : &Point, target : &Point, up : &Vector) -> Option<Self::Output> {
+        Transform::look_at(eye, target, up).map(|t| self.transform(&t))
+    }
 }
 
 pub trait TransMut {
@@ -134,3 +177,30 @@ pub trait TransMut {
         self.transform_self(&Transform::rotation3(pitch, yaw, roll))
     }
 }
+
+#[test]
+fn test_look_at() {
+    let eye = Point::new(1f32, 2f32, 3f32);
+    let t = Transform::look_at(&eye, &Point::new(1f32, 2f32, 13f32), &Vector::new(0f32, 2f32, 0f32)).unwrap();
+    assert!((t.to_world.mul_p(&Point::origin()) - eye).magnitude() < 1e-6);
+    assert!((t.to_world.mul_v(&Vector::unit_z()) - Vector::unit_z()).magnitude() < 1e-6);
+    assert!((t.to_world.mul_v(&Vector::unit_y()) - Vector::unit_y()).magnitude() < 1e-6);
+
+    let t = Transform::look_at(&eye, &Point::new(-4f32, 0f32, 1f32), &Vector::new(0.3f32, 1f32, 0f32)).unwrap();
+    let p = Point::new(-2f32, 0.5f32, 7f32);
+    assert!((t.to_object.mul_p(&t.to_world.mul_p(&p)) - p).magnitude() < 1e-5);
+    let m = t.to_object * t.to_world;
+    for ix in 0..16 {
+        assert!((m[ix] - Matrix::identity()[ix]).abs() < 1e-5);
+    }
+
+    // looking straight along up still gives a usable frame
+    let t = Transform::look_at(&Point::origin(), &Point::new(0f32, 5f32, 0f32), &Vector::unit_y()).unwrap();
+    assert!((t.to_world.mul_v(&Vector::unit_z()) - Vector::unit_y()).magnitude() < 1e-6);
+    assert!(t.to_world.mul_v(&Vector::unit_x()).magnitude().is_finite());
+
+    assert!(Transform::look_at(&eye, &eye, &Vector::unit_y()).is_none());
+    assert!(Transform::look_at(&eye, &Point::origin(), &Vector::zero()).is_none());
+    assert!(Transform::look_at(&eye, &Point::new(f32::NAN, 0f32, 0f32), &Vector::unit_y()).is_none());
+    assert!(Transform::look_at(&eye, &Point::origin(), &Vector::new(0f32, f32::INFINITY, 0f32)).is_none());
+}
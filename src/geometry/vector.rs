@@ -1,5 +1,5 @@
 use std::default::Default;
-use std::ops::{Add, Sub, Mul, Div, Neg};
+use std::ops::{Index, Add, Sub, Mul, Div, Neg};
 use std::fmt::{Display, Formatter, Result};
 
 use crate::geometry::{Matrix, Normal, HasTransform};
@@ -66,6 +66,34 @@ impl Vector {
         Normal::new(self.x, self.y, self.z).normalize()
     }
 
+    // Two unit vectors that, together with this (unit) vector, form an orthonormal basis.
+    pub fn coordinate_system(&self) -> (Vector, Vector) {
+        let v2 = if self.x.abs() > self.y.abs() {
+            Vector::new(-self.z, 0f32, self.x) / (self.x * self.x + self.z * self.z).sqrt()
+        } else {
+            Vector::new(0f32, self.z, -self.y) / (self.y * self.y + self.z * self.z).sqrt()
+        };
+        (v2, self.cross(&v2))
+    }
+
+    pub fn max_dimension(&self) -> usize {
+        if self.x > self.y {
+            if self.x > self.z { 0 } else { 2 }
+        } else if self.y > self.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn abs(&self) -> Vector {
+        Vector::new(self.x.abs(), self.y.abs(), self.z.abs())
+    }
+
+    pub fn permute(&self, x : usize, y : usize, z : usize) -> Vector {
+        Vector::new(self[x], self[y], self[z])
+    }
+
     pub fn mul_s(&self, s : f32) -> Vector {
         Vector::new(self.x * s, self.y * s, self.z * s)
     }
@@ -145,6 +173,17 @@ impl Vector {
     }
 }
 
+impl Index<usize> for Vector {
+    type Output = f32;
+    fn index(&self, index : usize) -> &f32 {
+        match index {
+            0 => &self.x,
+            1 => &self.y,
+            _ => &self.z,
+        }
+    }
+}
+
 impl Display for Vector {
     fn fmt(&self, f : &mut Formatter) -> Result {
         writeln!(f, "<{}, {}, {}>", self.x, self.y, self.z)
@@ -0,0 +1,83 @@
+use crate::color::Color;
+use crate::geometry::Ray;
+use crate::integrators::Integrator;
+use crate::sampler::{Sampler, to_hemisphere_cosine};
+use crate::scene::Scene;
+
+// Ambient occlusion, for looking over a scene's geometry: each visible
+// point is as bright as the fraction of the hemisphere about its normal that
+// is open, weighted by the cosine, out to max_distance.  Materials and
+// lights are ignored, and rays that hit nothing are black.
+pub struct AOIntegrator {
+    n_samples    : usize,
+    max_distance : f32,
+}
+
+impl AOIntegrator {
+    pub fn new(n_samples : usize, max_distance : f32) -> AOIntegrator {
+        AOIntegrator { n_samples: n_samples.max(1), max_distance: max_distance }
+    }
+}
+
+impl Integrator for AOIntegrator {
+    fn li(&self, r : &Ray, scene : &Scene, sampler : &mut dyn Sampler) -> Color {
+        let i = match scene.intersect(r) {
+            Some(i) => i,
+            None    => return Color::black(),
+        };
+
+        // the side of the surface the ray arrived on
+        let n = i.context.n.face_forward(&-r.direction).to_vector().normalize();
+        let (s, t) = n.coordinate_system();
+
+        // cosine-weighted directions make each open one count alike
+        let mut open = 0;
+        for _ in 0..self.n_samples {
+            let w = to_hemisphere_cosine(sampler.get_2d());
+            let mut ray = i.context.spawn_ray(&(s * w.x + t * w.y + n * w.z));
+            ray.t_max = self.max_distance;
+            if !scene.intersects(&ray) {
+                open += 1;
+            }
+        }
+        Color::gray(open as f32 / self.n_samples as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+    use std::sync::Arc;
+    use crate::geometry::{Point, Trans, Vector};
+    use crate::materials::MatteMaterial;
+    use crate::sampler::RandomSampler;
+    use crate::shapes::Plane;
+
+    #[test]
+    fn test_ao() {
+        let mut scene = Scene::new();
+        scene.add(Arc::new(Plane::new(100f32, 100f32)), Arc::new(MatteMaterial::default()));
+        scene.preprocess();
+
+        // an open plane is unoccluded, from either side
+        let mut sampler = RandomSampler::new(1);
+        let integrator = AOIntegrator::new(64, f32::INFINITY);
+        assert_eq!(integrator.li(&Ray::new(&Point::new(0f32, 0f32, 1f32), &-Vector::unit_z()), &scene, &mut sampler), Color::white());
+        assert_eq!(integrator.li(&Ray::new(&Point::new(0f32, 0f32, -1f32), &Vector::unit_z()), &scene, &mut sampler), Color::white());
+        assert!(integrator.li(&Ray::new(&Point::new(0f32, 0f32, 1f32), &Vector::unit_z()), &scene, &mut sampler).is_black());
+
+        // a wall standing on the plane along x = 0 closes off the half of
+        // the hemisphere beyond it, but only within reach
+        scene.add(Arc::new(Plane::new(100f32, 100f32).rotate(FRAC_PI_2, &Vector::unit_y())), Arc::new(MatteMaterial::default()));
+        scene.preprocess();
+        let r = Ray::new(&Point::new(0.01f32, 0f32, 1f32), &-Vector::unit_z());
+        let n = 100;
+        let mut sum = 0f32;
+        for _ in 0..n {
+            sum += integrator.li(&r, &scene, &mut sampler).luminance();
+        }
+        assert!((sum / n as f32 - 0.5f32).abs() < 0.03f32, "open fraction was {}", sum / n as f32);
+        assert_eq!(AOIntegrator::new(16, 0.001f32).li(&r, &scene, &mut sampler), Color::white());
+    }
+}
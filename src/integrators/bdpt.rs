@@ -0,0 +1,601 @@
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+use crate::cameras::Camera;
+use crate::color::Color;
+use crate::geometry::{Normal, Point, Ray, Vector};
+use crate::integrators::Integrator;
+use crate::lights::VisibilityTester;
+use crate::reflection::{BSDF, BSDF_ALL, TransportMode};
+use crate::sampler::Sampler;
+use crate::scene::{Scene, SceneIntersection};
+use crate::shapes::SurfaceContext;
+
+// Bidirectional path tracing, after pbrt-v3.  For each camera ray a subpath
+// is traced from the camera and another from a light, and every prefix of
+// one is joined to every prefix of the other, giving several strategies for
+// each path length which are combined by multiple importance sampling.
+// Joining a light subpath straight to the camera lands its light on an
+// arbitrary pixel, so it is returned as a splat, and is only possible for
+// cameras that implement Camera::sample_wi().
+pub struct BDPTIntegrator {
+    camera    : Arc<dyn Camera>,
+    max_depth : u32,
+}
+
+impl BDPTIntegrator {
+    pub fn new(camera : Arc<dyn Camera>, max_depth : u32) -> BDPTIntegrator {
+        BDPTIntegrator { camera: camera, max_depth: max_depth }
+    }
+
+    fn camera_subpath(&self, r : &Ray, scene : &Scene, sampler : &mut dyn Sampler) -> Vec<Vertex> {
+        let (_, pdf_dir) = self.camera.pdf_we(r);
+
+        // a camera that light subpaths cannot find is treated as a delta, so
+        // that no strategy counts on finding it
+        let mut camera = Vertex::endpoint(VertexKind::Camera, r.origin, Normal::zero(), None, Color::white(), 0f32);
+        camera.delta = pdf_dir == 0f32;
+
+        let mut path = vec![camera];
+        self.random_walk(scene, *r, sampler, Color::white(), pdf_dir, &mut path);
+        path
+    }
+
+    fn light_subpath(&self, scene : &Scene, sampler : &mut dyn Sampler) -> Vec<Vertex> {
+        let (ix, light_pdf) = match scene.sample_light(sampler.get_1d()) {
+            Some(l) if l.1 > 0f32 => l,
+            _                     => return Vec::new(),
+        };
+        let light = &scene.lights[ix];
+        let s = match light.sample_le(sampler.get_2d(), sampler.get_2d()) {
+            Some(s) if s.pdf_pos > 0f32 && s.pdf_dir > 0f32 && !s.le.is_black() => s,
+            _                                                                  => return Vec::new(),
+        };
+
+        let d = s.ray.direction.normalize();
+        let mut path = vec![Vertex::endpoint(VertexKind::Light, s.ray.origin, s.n, Some(ix), s.le, s.pdf_pos * light_pdf)];
+        let beta = s.le * (s.n.to_vector().dot(&d).abs() / (light_pdf * s.pdf_pos * s.pdf_dir));
+        self.random_walk(scene, s.ray, sampler, beta, s.pdf_dir, &mut path);
+
+        // a light at infinity picks the direction of its rays before their
+        // origin, so the first vertices' densities are the other way about
+        if light.is_infinite() {
+            if path.len() > 1 {
+                let cos = if path[1].on_surface() { path[1].context.n.to_vector().dot(&d).abs() } else { 1f32 };
+                path[1].pdf_fwd = s.pdf_pos * cos;
+            }
+            path[0].pdf_fwd = infinite_light_density(scene, &path[0].context, &d);
+        }
+        path
+    }
+
+    // Extends path, which ends at the origin of ray, with beta the throughput
+    // and pdf the density by solid angle with which ray was chosen.  Camera
+    // subpaths carry radiance and light subpaths importance, and camera
+    // subpaths may reach a vertex more, as a path needs a vertex at each end.
+    fn random_walk(&self, scene : &Scene, ray : Ray, sampler : &mut dyn Sampler, beta : Color, pdf : f32, path : &mut Vec<Vertex>) {
+        let mode = if path[0].kind == VertexKind::Camera { TransportMode::Radiance } else { TransportMode::Importance };
+        let max_depth = if mode == TransportMode::Radiance { self.max_depth + 1 } else { self.max_depth };
+        let (mut ray, mut beta, mut pdf_fwd) = (ray, beta, pdf);
+        let mut bounces = 0;
+
+        while bounces < max_depth && !beta.is_black() {
+            let i = match scene.intersect(&ray) {
+                None => {
+                    // a camera subpath that escapes ends at the environment
+                    if mode == TransportMode::Radiance {
+                        let d = ray.direction.normalize();
+                        path.push(Vertex::endpoint(VertexKind::Light, ray.origin + d, (-d).to_normal(), None, beta, pdf_fwd));
+                    }
+                    break;
+                },
+                Some(i) => i,
+            };
+
+            let mut bsdf = i.bsdf(true);
+            bsdf.mode = mode;
+            let wo = -ray.direction.normalize();
+            let prev = path.len() - 1;
+            let mut v = Vertex::surface(&i, bsdf, wo, beta);
+            v.pdf_fwd = path[prev].convert_density(pdf_fwd, &v, scene);
+            path.push(v);
+            bounces += 1;
+            if bounces >= max_depth {
+                break;
+            }
+
+            let v = &path[prev + 1];
+            let bsdf = v.bsdf.as_ref().unwrap();
+            let s = match bsdf.sample_f(&wo, sampler.get_2d(), BSDF_ALL) {
+                Some(s) => s,
+                None    => break,
+            };
+            beta = beta * s.f * (s.wi.dot(&bsdf.ns.to_vector()).abs() / s.pdf * correct_shading_normal(&v.context, &wo, &s.wi, mode));
+            ray = v.context.spawn_ray(&s.wi);
+
+            // a specular vertex cannot be found by any other strategy
+            let delta = s.is_specular();
+            let pdf_rev = if delta { 0f32 } else { bsdf.pdf(&s.wi, &wo, BSDF_ALL) };
+            pdf_fwd = if delta { 0f32 } else { s.pdf };
+
+            let rev = path[prev + 1].convert_density(pdf_rev, &path[prev], scene);
+            path[prev].pdf_rev = rev;
+            path[prev + 1].delta = delta;
+        }
+    }
+
+    // The light carried by the path made of the first s vertices of the
+    // light subpath and the first t of the camera subpath, weighted for the
+    // other strategies that could have found it, and the camera coordinates
+    // it lands at if it is not at the camera ray's.
+    fn connect(&self, scene : &Scene, light_path : &[Vertex], camera_path : &[Vertex], s : usize, t : usize, sampler : &mut dyn Sampler) -> (Color, Option<(f32, f32)>) {
+        let pt = &camera_path[t - 1];
+
+        // the environment ends a camera subpath, and cannot be joined to
+        if t > 1 && s != 0 && pt.kind == VertexKind::Light {
+            return (Color::black(), None);
+        }
+
+        let (l, sampled, film) = if s == 0 {
+            // the camera subpath found a light by itself
+            let l = if pt.is_light() { pt.le(scene, &camera_path[t - 2]) * pt.beta } else { Color::black() };
+            (l, None, None)
+        } else if t == 1 {
+            match self.join_camera(scene, &light_path[s - 1], sampler) {
+                Some((l, v, film)) => (l, Some(v), Some(film)),
+                None               => (Color::black(), None, None),
+            }
+        } else if s == 1 {
+            match join_light(scene, pt, sampler) {
+                Some((l, v)) => (l, Some(v), None),
+                None         => (Color::black(), None, None),
+            }
+        } else {
+            let qs = &light_path[s - 1];
+            let mut l = Color::black();
+            if qs.is_connectible(scene) && pt.is_connectible(scene) {
+                l = qs.beta * qs.f(pt, TransportMode::Importance) * pt.f(qs, TransportMode::Radiance) * pt.beta;
+                if !l.is_black() {
+                    l = l * g(scene, qs, pt);
+                }
+            }
+            (l, None, None)
+        };
+
+        if l.is_black() {
+            return (l, film);
+        }
+        (l * self.mis_weight(scene, light_path, camera_path, sampled.as_ref(), s, t), film)
+    }
+
+    // Joins the end of a light subpath to a point sampled on the lens.
+    fn join_camera(&self, scene : &Scene, qs : &Vertex, sampler : &mut dyn Sampler) -> Option<(Color, Vertex, (f32, f32))> {
+        if !qs.is_connectible(scene) {
+            return None;
+        }
+        let c = self.camera.sample_wi(&qs.context, sampler.get_2d())?;
+        if c.pdf == 0f32 || c.we.is_black() {
+            return None;
+        }
+
+        let v = Vertex::endpoint(VertexKind::Camera, c.p(), Normal::zero(), None, c.we / c.pdf, 0f32);
+        let mut l = qs.beta * qs.f(&v, TransportMode::Importance) * v.beta;
+        if qs.on_surface() {
+            l = l * c.wi.dot(&qs.context.shading.n.to_vector()).abs();
+        }
+        if !l.is_black() && !c.visibility.unoccluded(scene) {
+            l = Color::black();
+        }
+        Some((l, v, c.film))
+    }
+
+    // The weight of the strategy with s light and t camera vertices by the
+    // balance heuristic, found from the ratios of the densities with which
+    // each of the other strategies would have chosen the same path.  sampled
+    // is the vertex sampled to join the subpaths, if the strategy samples
+    // one, in place of the last vertex of its subpath.
+    fn mis_weight(&self, scene : &Scene, light_path : &[Vertex], camera_path : &[Vertex], sampled : Option<&Vertex>, s : usize, t : usize) -> f32 {
+        if s + t == 2 {
+            return 1f32;
+        }
+
+        let mut lv : Vec<&Vertex> = light_path[..s].iter().collect();
+        let mut cv : Vec<&Vertex> = camera_path[..t].iter().collect();
+        if s == 1 {
+            lv[0] = sampled.unwrap();
+        }
+        if t == 1 {
+            cv[0] = sampled.unwrap();
+        }
+
+        let qs = lv.last().copied();
+        let pt = cv[t - 1];
+        let qs_minus = if s > 1 { Some(lv[s - 2]) } else { None };
+        let pt_minus = if t > 1 { Some(cv[t - 2]) } else { None };
+
+        // the forward and reverse densities and delta flags of each vertex,
+        // with the reverse densities of those about the join as it makes them
+        let mut lp : Vec<(f32, f32, bool)> = lv.iter().map(|v| (v.pdf_fwd, v.pdf_rev, v.delta)).collect();
+        let mut cp : Vec<(f32, f32, bool)> = cv.iter().map(|v| (v.pdf_fwd, v.pdf_rev, v.delta)).collect();
+        let camera = self.camera.as_ref();
+
+        cp[t - 1].2 = false;
+        cp[t - 1].1 = match qs {
+            Some(qs) => qs.pdf(scene, camera, qs_minus, pt),
+            None     => pt.pdf_light_origin(scene, pt_minus.unwrap()),
+        };
+        if let Some(ptm) = pt_minus {
+            cp[t - 2].1 = match qs {
+                Some(qs) => pt.pdf(scene, camera, Some(qs), ptm),
+                None     => pt.pdf_light(scene, ptm),
+            };
+        }
+        if let Some(qs) = qs {
+            lp[s - 1].2 = false;
+            lp[s - 1].1 = pt.pdf(scene, camera, pt_minus, qs);
+        }
+        if let Some(qsm) = qs_minus {
+            lp[s - 2].1 = qs.unwrap().pdf(scene, camera, Some(pt), qsm);
+        }
+
+        // densities of zero belong to delta vertices, which are skipped
+        let remap = |f : f32| if f != 0f32 { f } else { 1f32 };
+        let mut sum_ri = 0f32;
+
+        let mut ri = 1f32;
+        for i in (1..t).rev() {
+            ri *= remap(cp[i].1) / remap(cp[i].0);
+            if !cp[i].2 && !cp[i - 1].2 {
+                sum_ri += ri;
+            }
+        }
+
+        ri = 1f32;
+        for i in (0..s).rev() {
+            ri *= remap(lp[i].1) / remap(lp[i].0);
+            let delta_light = if i > 0 { lp[i - 1].2 } else { lv[0].is_delta_light(scene) };
+            if !lp[i].2 && !delta_light {
+                sum_ri += ri;
+            }
+        }
+
+        1f32 / (1f32 + sum_ri)
+    }
+}
+
+impl Integrator for BDPTIntegrator {
+    // Light found by joining light subpaths to the camera is dropped, as
+    // there is nowhere to put it; the renderer uses li_splats().
+    fn li(&self, r : &Ray, scene : &Scene, sampler : &mut dyn Sampler) -> Color {
+        self.li_splats(r, scene, sampler, &mut Vec::new())
+    }
+
+    fn li_splats(&self, r : &Ray, scene : &Scene, sampler : &mut dyn Sampler, splats : &mut Vec<((f32, f32), Color)>) -> Color {
+        let camera_path = self.camera_subpath(r, scene, sampler);
+        let light_path = self.light_subpath(scene, sampler);
+
+        let mut l = Color::black();
+        for t in 1..=camera_path.len() {
+            for s in 0..=light_path.len() {
+                // joining the light's vertex straight to the camera's is left
+                // to the camera subpath, which finds the light as easily
+                if (s == 1 && t == 1) || s + t < 2 || s + t - 2 > self.max_depth as usize {
+                    continue;
+                }
+                match self.connect(scene, &light_path, &camera_path, s, t, sampler) {
+                    (c, Some(film)) => if !c.is_black() { splats.push((film, c)) },
+                    (c, None)       => l.add_self_c(&c),
+                }
+            }
+        }
+        l
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum VertexKind {
+    Camera,
+    Light,
+    Surface,
+}
+
+// A vertex of a subpath.  beta is the throughput of the subpath up to the
+// vertex, and pdf_fwd and pdf_rev the densities, by area, with which it is
+// chosen by its own subpath and by one traced the other way.  The normal of
+// the context is zero for points that are not on a surface, such as a
+// pinhole, and light vertices with no light are the environment.
+struct Vertex {
+    kind    : VertexKind,
+    context : SurfaceContext,
+    bsdf    : Option<BSDF>,
+    wo      : Vector,
+    light   : Option<usize>,
+    beta    : Color,
+    pdf_fwd : f32,
+    pdf_rev : f32,
+    delta   : bool,
+}
+
+impl Vertex {
+    fn endpoint(kind : VertexKind, p : Point, n : Normal, light : Option<usize>, beta : Color, pdf_fwd : f32) -> Vertex {
+        Vertex {
+            kind: kind,
+            context: SurfaceContext::new(p, n, (0f32, 0f32), (Vector::zero(), Vector::zero()), (Normal::zero(), Normal::zero())),
+            bsdf: None,
+            wo: Vector::zero(),
+            light: light,
+            beta: beta,
+            pdf_fwd: pdf_fwd,
+            pdf_rev: 0f32,
+            delta: false,
+        }
+    }
+
+    fn surface(i : &SceneIntersection, bsdf : BSDF, wo : Vector, beta : Color) -> Vertex {
+        Vertex {
+            kind: VertexKind::Surface,
+            context: i.context,
+            bsdf: Some(bsdf),
+            wo: wo,
+            light: i.light,
+            beta: beta,
+            pdf_fwd: 0f32,
+            pdf_rev: 0f32,
+            delta: false,
+        }
+    }
+
+    fn p(&self) -> Point {
+        self.context.p
+    }
+
+    fn on_surface(&self) -> bool {
+        self.context.n.magnitude_squared() > 0f32
+    }
+
+    fn is_light(&self) -> bool {
+        self.kind == VertexKind::Light || (self.kind == VertexKind::Surface && self.light.is_some())
+    }
+
+    fn is_infinite_light(&self, scene : &Scene) -> bool {
+        self.kind == VertexKind::Light && self.light.map_or(true, |ix| scene.lights[ix].is_infinite())
+    }
+
+    fn is_delta_light(&self, scene : &Scene) -> bool {
+        self.kind == VertexKind::Light && self.light.is_some_and(|ix| scene.lights[ix].is_delta())
+    }
+
+    // Whether a path can be joined at the vertex, which needs it to scatter
+    // light into directions other than a few special ones.
+    fn is_connectible(&self, scene : &Scene) -> bool {
+        match self.kind {
+            VertexKind::Camera  => true,
+            VertexKind::Light   => !(self.is_delta_light(scene) && self.is_infinite_light(scene)),
+            VertexKind::Surface => self.bsdf.as_ref().is_some_and(|b| b.has_non_specular()),
+        }
+    }
+
+    // The BSDF at the vertex for light leaving towards next.
+    fn f(&self, next : &Vertex, mode : TransportMode) -> Color {
+        match self.bsdf {
+            None           => Color::black(),
+            Some(ref bsdf) => {
+                let wi = (next.p() - self.p()).normalize();
+                bsdf.f(&self.wo, &wi, BSDF_ALL) * correct_shading_normal(&self.context, &self.wo, &wi, mode)
+            },
+        }
+    }
+
+    // Converts a density by solid angle at the vertex to one by area at
+    // next.
+    fn convert_density(&self, pdf : f32, next : &Vertex, scene : &Scene) -> f32 {
+        if next.is_infinite_light(scene) {
+            return pdf;
+        }
+        let w = next.p() - self.p();
+        let dist2 = w.magnitude_squared();
+        if dist2 == 0f32 {
+            return 0f32;
+        }
+        let mut pdf = pdf / dist2;
+        if next.on_surface() {
+            pdf *= next.context.n.to_vector().dot(&w).abs() / dist2.sqrt();
+        }
+        pdf
+    }
+
+    // The density, by area, with which a subpath reaching the vertex from
+    // prev would go on to next.
+    fn pdf(&self, scene : &Scene, camera : &dyn Camera, prev : Option<&Vertex>, next : &Vertex) -> f32 {
+        if self.kind == VertexKind::Light {
+            return self.pdf_light(scene, next);
+        }
+        let wn = next.p() - self.p();
+        if wn.magnitude_squared() == 0f32 {
+            return 0f32;
+        }
+        let wn = wn.normalize();
+        let pdf = match self.kind {
+            VertexKind::Camera => camera.pdf_we(&Ray::new(&self.p(), &wn)).1,
+            _                  => {
+                let wp = (prev.unwrap().p() - self.p()).normalize();
+                self.bsdf.as_ref().unwrap().pdf(&wp, &wn, BSDF_ALL)
+            },
+        };
+        self.convert_density(pdf, next, scene)
+    }
+
+    // The density, by area, with which the light at the vertex sends a ray
+    // to v.
+    fn pdf_light(&self, scene : &Scene, v : &Vertex) -> f32 {
+        let w = v.p() - self.p();
+        let dist2 = w.magnitude_squared();
+        if dist2 == 0f32 {
+            return 0f32;
+        }
+        let w = w / dist2.sqrt();
+
+        // rays from infinitely far away start on a disc as wide as the scene
+        if self.is_infinite_light(scene) {
+            let (_, radius) = scene.bounds.bounding_sphere();
+            return 1f32 / (PI * radius * radius);
+        }
+
+        let (_, pdf_dir) = scene.lights[self.light.unwrap()].pdf_le(&Ray::new(&self.p(), &w), &self.context.n);
+        let mut pdf = pdf_dir / dist2;
+        if v.on_surface() {
+            pdf *= v.context.n.to_vector().dot(&w).abs();
+        }
+        pdf
+    }
+
+    // The density, by area, with which a light subpath starts at the vertex,
+    // including the choice of light, when its ray goes towards v.
+    fn pdf_light_origin(&self, scene : &Scene, v : &Vertex) -> f32 {
+        let w = (v.p() - self.p()).normalize();
+        if self.is_infinite_light(scene) {
+            return infinite_light_density(scene, &v.context, &w);
+        }
+        let ix = self.light.unwrap();
+        let (pdf_pos, _) = scene.lights[ix].pdf_le(&Ray::new(&self.p(), &w), &self.context.n);
+        pdf_pos * scene.light_pdf(ix)
+    }
+
+    // The radiance the light at the vertex emits towards v.
+    fn le(&self, scene : &Scene, v : &Vertex) -> Color {
+        if !self.is_light() {
+            return Color::black();
+        }
+        let w = (v.p() - self.p()).normalize();
+        if self.is_infinite_light(scene) {
+            let r = Ray::new(&self.p(), &-w);
+            return scene.lights.iter().fold(Color::black(), |c, l| c + l.le(&r));
+        }
+        scene.lights[self.light.unwrap()].l(&self.context, &w)
+    }
+}
+
+// The density, by solid angle, with which the lights at infinity are chosen
+// and send light in direction w.
+fn infinite_light_density(scene : &Scene, context : &SurfaceContext, w : &Vector) -> f32 {
+    scene.lights.iter().enumerate()
+        .filter(|(_, l)| l.is_infinite())
+        .map(|(ix, l)| scene.light_pdf(ix) * l.pdf_li(context, &-*w))
+        .sum()
+}
+
+// Joins the end of a camera subpath to a point sampled on a light, returning
+// the light carried and the vertex sampled.
+fn join_light(scene : &Scene, pt : &Vertex, sampler : &mut dyn Sampler) -> Option<(Color, Vertex)> {
+    if !pt.is_connectible(scene) {
+        return None;
+    }
+    let (ix, light_pdf) = scene.sample_light(sampler.get_1d())?;
+    let ls = scene.lights[ix].sample_li(&pt.context, sampler.get_2d())?;
+    if ls.pdf == 0f32 || ls.li.is_black() {
+        return None;
+    }
+
+    let mut v = Vertex::endpoint(VertexKind::Light, ls.p(), ls.n, Some(ix), ls.li / (ls.pdf * light_pdf), 0f32);
+    v.pdf_fwd = v.pdf_light_origin(scene, pt);
+    let mut l = pt.beta * pt.f(&v, TransportMode::Radiance) * v.beta;
+    if pt.on_surface() {
+        l = l * ls.wi.dot(&pt.context.shading.n.to_vector()).abs();
+    }
+    if !l.is_black() && !ls.visibility.unoccluded(scene) {
+        l = Color::black();
+    }
+    Some((l, v))
+}
+
+// The geometric term joining two vertices, or zero if they cannot see each
+// other.
+fn g(scene : &Scene, v0 : &Vertex, v1 : &Vertex) -> f32 {
+    let d = v0.p() - v1.p();
+    let dist2 = d.magnitude_squared();
+    if dist2 == 0f32 {
+        return 0f32;
+    }
+    let d = d / dist2.sqrt();
+    let mut g = 1f32 / dist2;
+    if v0.on_surface() {
+        g *= v0.context.shading.n.to_vector().dot(&d).abs();
+    }
+    if v1.on_surface() {
+        g *= v1.context.shading.n.to_vector().dot(&d).abs();
+    }
+    if VisibilityTester::between(&v0.context, &v1.context).unoccluded(scene) { g } else { 0f32 }
+}
+
+// Shading normals make BSDFs asymmetric, which radiance sees but importance
+// does not, so importance is corrected for the difference.
+fn correct_shading_normal(context : &SurfaceContext, wo : &Vector, wi : &Vector, mode : TransportMode) -> f32 {
+    if mode == TransportMode::Radiance {
+        return 1f32;
+    }
+    let ns = context.shading.n.to_vector();
+    let ng = context.n.to_vector();
+    let denom = wo.dot(&ng).abs() * wi.dot(&ns).abs();
+    if denom == 0f32 {
+        return 0f32;
+    }
+    wo.dot(&ns).abs() * wi.dot(&ng).abs() / denom
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cameras::PerspectiveCamera;
+    use crate::geometry::{Trans, TransMut, Transform};
+    use crate::integrators::PathIntegrator;
+    use crate::lights::PointLight;
+    use crate::materials::MatteMaterial;
+    use crate::sampler::RandomSampler;
+    use crate::shapes::{Plane, Sphere};
+
+    // The average over the whole film of the light found by rays cast
+    // through it, including light splatted onto it.
+    fn film_average(integrator : &dyn Integrator, camera : &dyn Camera, scene : &Scene, n : usize) -> f32 {
+        let mut sampler = RandomSampler::new(1);
+        let mut splats = Vec::new();
+        let mut sum = 0f32;
+        for _ in 0..n {
+            let (x, y) = sampler.get_2d();
+            let r = camera.cast(2f32 * x - 1f32, 2f32 * y - 1f32);
+            sum += integrator.li_splats(&r, scene, &mut sampler, &mut splats).luminance();
+            sum += splats.drain(..).map(|(_, c)| c.luminance()).sum::<f32>();
+        }
+        sum / n as f32
+    }
+
+    // Inside a sphere that emits l and reflects a fraction a of the light
+    // falling on it, the radiance everywhere is l / (1 - a).
+    #[test]
+    fn test_furnace() {
+        let mut scene = Scene::new();
+        scene.add_area_light(Arc::new(Sphere::unit()), Arc::new(MatteMaterial::constant(Color::gray(0.5f32))), Color::gray(0.5f32), true);
+        scene.preprocess();
+
+        let camera : Arc<dyn Camera> = Arc::new(PerspectiveCamera::new(PI / 2f32, 1f32));
+        let mean = film_average(&BDPTIntegrator::new(camera.clone(), 20), camera.as_ref(), &scene, 2000);
+        assert!((mean - 1f32).abs() < 0.03f32, "mean radiance was {}", mean);
+    }
+
+    // Light traced from a point light onto a floor reaches the film through
+    // splats as well as through the camera rays, but in total agrees with
+    // path tracing.
+    #[test]
+    fn test_light_tracing() {
+        let mut scene = Scene::new();
+        scene.add(Arc::new(Plane::new(4f32, 4f32)), Arc::new(MatteMaterial::constant(Color::gray(0.5f32))));
+        scene.add_light(Box::new(PointLight::new(Color::gray(4f32)).translate(&Vector::new(0.5f32, 0f32, 1f32))));
+        scene.preprocess();
+
+        let mut camera = PerspectiveCamera::new(PI / 3f32, 1f32);
+        camera.transform_self(&Transform::look_at(&Point::new(0f32, -2f32, 2f32), &Point::origin(), &Vector::unit_z()).unwrap());
+        let camera : Arc<dyn Camera> = Arc::new(camera);
+        let bdpt = film_average(&BDPTIntegrator::new(camera.clone(), 1), camera.as_ref(), &scene, 20000);
+        let path = film_average(&PathIntegrator::new(1, 3), camera.as_ref(), &scene, 20000);
+        assert!((bdpt - path).abs() < 0.03f32 * path, "bdpt found {} where path tracing found {}", bdpt, path);
+    }
+}
@@ -0,0 +1,120 @@
+use crate::color::Color;
+use crate::geometry::{Ray, Vector};
+use crate::integrators::Integrator;
+use crate::sampler::Sampler;
+use crate::scene::Scene;
+
+// What DebugIntegrator shows of the surface context at each hit.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DebugMode {
+    // the shading normal
+    Normal,
+    // (u, v) in red and green
+    UV,
+    // the directions of the partial derivatives of the point
+    Dpdu,
+    Dpdv,
+    // the lengths of the partial derivatives of the normal
+    Dndu,
+    Dndv,
+    // the distance to the hit
+    Depth,
+    // a colour for each primitive
+    Primitive,
+}
+
+// Shows what the first surface each ray hits reports about itself, for
+// finding out why a shape misbehaves.  Directions are shown with each
+// component taken from [-1, 1] to [0, 1], lengths l as l / (1 + l) so that
+// large ones remain distinct, and distances as white nearby fading to black
+// at the far side of the scene.  Rays that hit nothing are black.
+pub struct DebugIntegrator {
+    mode : DebugMode,
+}
+
+impl DebugIntegrator {
+    pub fn new(mode : DebugMode) -> DebugIntegrator {
+        DebugIntegrator { mode: mode }
+    }
+}
+
+impl Integrator for DebugIntegrator {
+    fn li(&self, r : &Ray, scene : &Scene, _sampler : &mut dyn Sampler) -> Color {
+        let i = match scene.intersect(r) {
+            Some(i) => i,
+            None    => return Color::black(),
+        };
+
+        let direction = |v : Vector| {
+            let d = if v.magnitude_squared() > 0f32 { v.normalize() } else { v };
+            Color::new(d.x + 1f32, d.y + 1f32, d.z + 1f32) / 2f32
+        };
+        let length = |l : f32| Color::gray(l / (1f32 + l));
+
+        let c = &i.context;
+        match self.mode {
+            DebugMode::Normal    => direction(c.shading.n.to_vector()),
+            DebugMode::UV        => Color::new(c.u, c.v, 0f32),
+            DebugMode::Dpdu      => direction(c.dpdu),
+            DebugMode::Dpdv      => direction(c.dpdv),
+            DebugMode::Dndu      => length(c.dndu.magnitude()),
+            DebugMode::Dndv      => length(c.dndv.magnitude()),
+            DebugMode::Depth     => {
+                let (centre, radius) = scene.bounds.bounding_sphere();
+                let far = (r.origin - centre).magnitude() + radius;
+                Color::gray((1f32 - i.time * r.direction.magnitude() / far).max(0f32))
+            },
+            DebugMode::Primitive => primitive_color(i.primitive),
+        }
+    }
+}
+
+// A colour for primitive ix that stays the same from run to run, and seldom
+// resembles those of its neighbours.
+fn primitive_color(ix : usize) -> Color {
+    let h = (ix as u32).wrapping_add(1).wrapping_mul(0x9e37_79b9);
+    let channel = |shift : u32| 0.2f32 + 0.8f32 * ((h >> shift) & 0xff) as f32 / 255f32;
+    Color::new(channel(24), channel(16), channel(8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use crate::geometry::{Point, Trans};
+    use crate::materials::MatteMaterial;
+    use crate::sampler::RandomSampler;
+    use crate::shapes::Sphere;
+
+    #[test]
+    fn test_debug_modes() {
+        let mut scene = Scene::new();
+        scene.add(Arc::new(Sphere::new(1f32)), Arc::new(MatteMaterial::default()));
+        scene.add(Arc::new(Sphere::new(1f32).translate(&Vector::new(0f32, 0f32, 3f32))), Arc::new(MatteMaterial::default()));
+        scene.preprocess();
+
+        let mut sampler = RandomSampler::new(1);
+        let li = |mode : DebugMode, r : &Ray, sampler : &mut RandomSampler| DebugIntegrator::new(mode).li(r, &scene, sampler);
+        let close = |a : Color, b : Color| (0..3).all(|c| (a[c] - b[c]).abs() < 1e-4f32);
+
+        // the ray meets the first sphere on its equator, facing -y, where
+        // dpdv points up the sphere
+        let r = Ray::new(&Point::new(0f32, -5f32, 0f32), &Vector::unit_y());
+        assert!(close(li(DebugMode::Normal, &r, &mut sampler), Color::new(0.5f32, 0f32, 0.5f32)));
+        assert!(close(li(DebugMode::Dpdv, &r, &mut sampler), Color::new(0.5f32, 0.5f32, 1f32)));
+        assert!(close(li(DebugMode::UV, &r, &mut sampler), Color::new(0.75f32, 0.5f32, 0f32)));
+
+        // on a unit sphere the normal changes as fast as the point does
+        let dpdv = scene.intersect(&r).unwrap().context.dpdv.magnitude();
+        assert!((li(DebugMode::Dndv, &r, &mut sampler)[0] - dpdv / (1f32 + dpdv)).abs() < 1e-4f32);
+
+        let (centre, radius) = scene.bounds.bounding_sphere();
+        let far = (r.origin - centre).magnitude() + radius;
+        assert!((li(DebugMode::Depth, &r, &mut sampler)[0] - (1f32 - 4f32 / far)).abs() < 1e-4f32);
+
+        let other = Ray::new(&Point::new(0f32, -5f32, 3f32), &Vector::unit_y());
+        assert_eq!(li(DebugMode::Primitive, &r, &mut sampler), li(DebugMode::Primitive, &r, &mut sampler));
+        assert!(li(DebugMode::Primitive, &r, &mut sampler) != li(DebugMode::Primitive, &other, &mut sampler));
+        assert!(li(DebugMode::Normal, &Ray::new(&Point::new(5f32, 5f32, 0f32), &Vector::unit_x()), &mut sampler).is_black());
+    }
+}
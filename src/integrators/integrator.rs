@@ -0,0 +1,110 @@
+use crate::color::Color;
+use crate::geometry::{Ray, Vector};
+use crate::reflection::{BSDF, BSDF_ALL, BSDF_SPECULAR};
+use crate::sampler::Sampler;
+use crate::scene::{Scene, SceneIntersection};
+
+// Computes the light arriving along camera rays.  The renderer casts the rays
+// and accumulates the results on the film; an integrator decides how light
+// reaching each ray is found.
+pub trait Integrator : Send + Sync {
+    // The radiance arriving at the origin of r from the direction it points
+    // in, drawing any random numbers needed from sampler.
+    fn li(&self, r : &Ray, scene : &Scene, sampler : &mut dyn Sampler) -> Color;
+
+    // As li(), but also gathers light that paths traced from the lights carry
+    // to other points on the film, pushing each onto splats with the camera
+    // coordinates, as given to Camera::cast(), at which it lands.
+    fn li_splats(&self, r : &Ray, scene : &Scene, sampler : &mut dyn Sampler, _splats : &mut Vec<((f32, f32), Color)>) -> Color {
+        self.li(r, scene, sampler)
+    }
+}
+
+// Weighs a sample taken by a strategy that drew nf samples with density
+// f_pdf against one that drew ng with density g_pdf, favouring whichever
+// density is larger.
+pub fn power_heuristic(nf : u32, f_pdf : f32, ng : u32, g_pdf : f32) -> f32 {
+    let f = nf as f32 * f_pdf;
+    let g = ng as f32 * g_pdf;
+    if f.is_infinite() {
+        return 1f32;
+    }
+    if f == 0f32 && g == 0f32 {
+        return 0f32;
+    }
+    (f * f) / (f * f + g * g)
+}
+
+// The light arriving straight from one of the scene's lights, chosen at
+// random, and scattered towards wo.  Specular lobes are left to the caller,
+// since no light sample can find them.
+pub fn sample_one_light(scene : &Scene, i : &SceneIntersection, bsdf : &BSDF, wo : &Vector, sampler : &mut dyn Sampler) -> Color {
+    let (ix, light_pdf) = match scene.sample_light(sampler.get_1d()) {
+        Some(l) if l.1 > 0f32 => l,
+        _                     => return Color::black(),
+    };
+    let u_light = sampler.get_2d();
+    let u_scattering = sampler.get_2d();
+    estimate_direct(scene, i, bsdf, wo, ix, u_light, u_scattering) / light_pdf
+}
+
+// The light arriving from the light at index ix and scattered towards wo,
+// estimated both by sampling the light and by sampling the BSDF, and
+// combining the two with multiple importance sampling.
+pub fn estimate_direct(scene : &Scene, i : &SceneIntersection, bsdf : &BSDF, wo : &Vector, ix : usize, u_light : (f32, f32), u_scattering : (f32, f32)) -> Color {
+    let light = &scene.lights[ix];
+    let flags = BSDF_ALL & !BSDF_SPECULAR;
+    let ns = bsdf.ns.to_vector();
+    let mut ld = Color::black();
+
+    if let Some(s) = light.sample_li(&i.context, u_light) {
+        if s.pdf > 0f32 && !s.li.is_black() {
+            let f = bsdf.f(wo, &s.wi, flags) * s.wi.dot(&ns).abs();
+            if !f.is_black() && s.visibility.unoccluded(scene) {
+                let weight = if light.is_delta() { 1f32 } else { power_heuristic(1, s.pdf, 1, bsdf.pdf(wo, &s.wi, flags)) };
+                ld.add_self_c(&(f * s.li * (weight / s.pdf)));
+            }
+        }
+    }
+
+    // a delta light can only be found by sampling it
+    if light.is_delta() {
+        return ld;
+    }
+
+    if let Some(s) = bsdf.sample_f(wo, u_scattering, flags) {
+        let f = s.f * s.wi.dot(&ns).abs();
+        if f.is_black() {
+            return ld;
+        }
+        let light_pdf = light.pdf_li(&i.context, &s.wi);
+        if light_pdf == 0f32 {
+            return ld;
+        }
+        let weight = power_heuristic(1, s.pdf, 1, light_pdf);
+
+        let r = i.context.spawn_ray(&s.wi);
+        let li = match scene.intersect(&r) {
+            Some(hit) => if hit.light == Some(ix) { hit.le(scene, &-s.wi) } else { Color::black() },
+            None      => light.le(&r),
+        };
+        if !li.is_black() {
+            ld.add_self_c(&(f * li * (weight / s.pdf)));
+        }
+    }
+
+    ld
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_power_heuristic() {
+        assert_eq!(power_heuristic(1, 1f32, 1, 1f32), 0.5f32);
+        assert_eq!(power_heuristic(1, 3f32, 1, 1f32), 0.9f32);
+        assert_eq!(power_heuristic(1, 0f32, 1, 0f32), 0f32);
+        assert_eq!(power_heuristic(1, f32::INFINITY, 1, 1f32), 1f32);
+    }
+}
@@ -0,0 +1,17 @@
+pub mod ao;
+pub mod bdpt;
+pub mod debug;
+pub mod integrator;
+pub mod path;
+pub mod sppm;
+pub mod volpath;
+pub mod whitted;
+
+pub use ao::*;
+pub use bdpt::*;
+pub use debug::*;
+pub use integrator::*;
+pub use path::*;
+pub use sppm::*;
+pub use volpath::*;
+pub use whitted::*;
@@ -0,0 +1,151 @@
+use crate::color::Color;
+use crate::geometry::Ray;
+use crate::integrators::{Integrator, sample_one_light};
+use crate::reflection::{BSDF_ALL, BSDF_SPECULAR, BSDF_TRANSMISSION};
+use crate::sampler::Sampler;
+use crate::scene::Scene;
+
+// Unidirectional path tracing, after pbrt-v3.  Each path is extended from
+// the camera by sampling the BSDF at every vertex, and lit at each one by a
+// light sample, with the two strategies combined by multiple importance
+// sampling.  Paths end after max_depth bounces, or earlier at random once
+// they are rr_depth bounces long and carry little light.
+pub struct PathIntegrator {
+    max_depth : u32,
+    rr_depth  : u32,
+}
+
+impl PathIntegrator {
+    pub fn new(max_depth : u32, rr_depth : u32) -> PathIntegrator {
+        PathIntegrator { max_depth: max_depth, rr_depth: rr_depth }
+    }
+}
+
+impl Integrator for PathIntegrator {
+    fn li(&self, r : &Ray, scene : &Scene, sampler : &mut dyn Sampler) -> Color {
+        let mut l = Color::black();
+        let mut beta = Color::white();
+        let mut ray = *r;
+        let mut specular_bounce = false;
+        // the squared relative index of refraction crossed so far, which
+        // scales radiance but should not sway russian roulette
+        let mut eta_scale = 1f32;
+
+        let mut bounces = 0;
+        loop {
+            let hit = scene.intersect(&ray);
+
+            // emission found by sampling the BSDF was counted by the light
+            // sampling at the previous vertex, unless that vertex was
+            // specular and could not be lit that way
+            if bounces == 0 || specular_bounce {
+                match hit {
+                    Some(ref i) => l.add_self_c(&(beta * i.le(scene, &-ray.direction.normalize()))),
+                    None        => {
+                        for light in scene.lights.iter() {
+                            l.add_self_c(&(beta * light.le(&ray)));
+                        }
+                    },
+                }
+            }
+
+            let i = match hit {
+                Some(i) if bounces < self.max_depth => i,
+                _                                   => break,
+            };
+
+            let bsdf = i.bsdf(true);
+            let wo = -ray.direction.normalize();
+
+            if bsdf.has_non_specular() {
+                l.add_self_c(&(beta * sample_one_light(scene, &i, &bsdf, &wo, sampler)));
+            }
+
+            let s = match bsdf.sample_f(&wo, sampler.get_2d(), BSDF_ALL) {
+                Some(s) if !s.f.is_black() => s,
+                _                          => break,
+            };
+            beta.mul_self_c(&(s.f * (s.wi.dot(&bsdf.ns.to_vector()).abs() / s.pdf)));
+            specular_bounce = s.sampled_type & BSDF_SPECULAR != 0;
+            if specular_bounce && s.sampled_type & BSDF_TRANSMISSION != 0 {
+                let eta2 = bsdf.eta * bsdf.eta;
+                eta_scale *= if wo.dot(&i.context.n.to_vector()) > 0f32 { eta2 } else { 1f32 / eta2 };
+            }
+            ray = i.context.spawn_ray(&s.wi);
+
+            // end dim paths at random, weighting the survivors to make up
+            // for the light the others would have carried
+            let rr_beta = beta.max_component() * eta_scale;
+            if rr_beta < 1f32 && bounces > self.rr_depth {
+                let q = (1f32 - rr_beta).max(0.05f32);
+                if sampler.get_1d() < q {
+                    break;
+                }
+                beta.div_self_s(1f32 - q);
+            }
+
+            bounces += 1;
+        }
+
+        l
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+    use std::sync::Arc;
+    use crate::geometry::{Point, Trans, Vector};
+    use crate::lights::PointLight;
+    use crate::materials::{MatteMaterial, MirrorMaterial};
+    use crate::sampler::RandomSampler;
+    use crate::shapes::{Disc, Plane, Sphere};
+
+    #[test]
+    fn test_direct_lighting() {
+        let mut scene = Scene::new();
+        scene.add(Arc::new(Plane::unit()), Arc::new(MatteMaterial::constant(Color::gray(0.5f32))));
+        scene.add_light(Box::new(PointLight::new(Color::gray(4f32)).translate(&Vector::new(0f32, 0f32, 2f32))));
+        scene.preprocess();
+
+        // irradiance of 4 / 2², reflected diffusely, and nothing else to see
+        let c = PathIntegrator::new(5, 3).li(&Ray::new(&Point::new(0f32, 0f32, 1f32), &-Vector::unit_z()), &scene, &mut RandomSampler::new(1));
+        assert!((c.luminance() - 0.5f32 / PI).abs() < 1e-5f32);
+    }
+
+    #[test]
+    fn test_specular_bounce() {
+        let mut scene = Scene::new();
+        scene.add(Arc::new(Plane::new(5f32, 5f32)), Arc::new(MirrorMaterial::constant(Color::gray(0.5f32))));
+        scene.add_area_light(Arc::new(Disc::new(5f32).translate(&Vector::new(0f32, 0f32, 2f32))), Arc::new(MatteMaterial::constant(Color::black())), Color::gray(2f32), true);
+        scene.preprocess();
+
+        // a light seen in a mirror cannot be sampled, so is found by the
+        // reflected ray
+        let r = Ray::new(&Point::new(0.1f32, 0f32, 1f32), &-Vector::unit_z());
+        let mut sampler = RandomSampler::new(1);
+        assert!(PathIntegrator::new(0, 3).li(&r, &scene, &mut sampler).is_black());
+        assert_eq!(PathIntegrator::new(1, 3).li(&r, &scene, &mut sampler), Color::gray(1f32));
+    }
+
+    // Inside a sphere that emits l and reflects a fraction a of the light
+    // falling on it, the radiance everywhere is l / (1 - a).
+    #[test]
+    fn test_furnace() {
+        let mut scene = Scene::new();
+        scene.add_area_light(Arc::new(Sphere::unit()), Arc::new(MatteMaterial::constant(Color::gray(0.5f32))), Color::gray(0.5f32), true);
+        scene.preprocess();
+
+        let integrator = PathIntegrator::new(50, 3);
+        let mut sampler = RandomSampler::new(1);
+        let n = 4000;
+        let mut sum = 0f32;
+        for k in 0..n {
+            let d = Vector::new((k % 7) as f32 - 3f32, (k % 5) as f32 - 2f32, 1f32);
+            sum += integrator.li(&Ray::new(&Point::new(0.1f32, 0.2f32, 0f32), &d), &scene, &mut sampler).luminance();
+        }
+        let mean = sum / n as f32;
+        assert!((mean - 1f32).abs() < 0.03f32, "mean radiance was {}", mean);
+    }
+}
@@ -0,0 +1,405 @@
+use std::f32::consts::PI;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use rand::random;
+use threadpool::ThreadPool;
+
+use crate::cameras::Camera;
+use crate::color::Color;
+use crate::film::Film;
+use crate::geometry::{BoundingBox, Point, Ray, Vector};
+use crate::integrators::sample_one_light;
+use crate::reflection::{BSDF, BSDF_ALL, BSDF_DIFFUSE, BSDF_GLOSSY, BSDF_REFLECTION, BSDF_SPECULAR, BSDF_TRANSMISSION, TransportMode};
+use crate::renderer::RendererSetup;
+use crate::sampler::{Sampler, RandomSampler};
+use crate::scene::Scene;
+
+// Stochastic progressive photon mapping, after pbrt-v3.  Each iteration
+// follows a camera ray through every pixel to the first surface that is not
+// specular, its visible point, and then traces photons from the lights,
+// each one that lands within a pixel's search radius of its visible point
+// adding to that pixel's estimate of the light arriving there.  The radii
+// shrink as photons are gathered, so the estimate converges, and light
+// focused by specular surfaces, which paths from the camera cannot find
+// from small lights, is found by the photons.
+//
+// Unlike the other integrators it is not driven by the renderer, as every
+// pixel must be visited before the photons are traced, so it renders a
+// setup itself by render().
+pub struct SPPMIntegrator {
+    iterations            : u32,
+    photons_per_iteration : usize,
+    max_depth             : u32,
+    initial_radius        : f32,
+    write_frequency       : u32,
+}
+
+impl SPPMIntegrator {
+    // Images are written every write_frequency iterations, and after the
+    // last, so that progress can be watched.
+    pub fn new(iterations : u32, photons_per_iteration : usize, max_depth : u32, initial_radius : f32, write_frequency : u32) -> SPPMIntegrator {
+        SPPMIntegrator {
+            iterations: iterations,
+            photons_per_iteration: photons_per_iteration,
+            max_depth: max_depth,
+            initial_radius: initial_radius,
+            write_frequency: write_frequency.max(1),
+        }
+    }
+
+    // Renders scene onto the film and camera of setup, saving the image to
+    // its output file as it goes.  The setup's filter, sampler and
+    // integrator are not used, as each iteration takes one sample per pixel.
+    pub fn render(&self, setup : RendererSetup, scene : Scene) {
+        let (width, height) = (setup.film.width, setup.film.height);
+        let path = Path::new(&setup.output_filename);
+        self.run(width, height, setup.camera, scene, |image| {
+            let mut film = Film::new(width, height);
+            for y in 0..height {
+                for x in 0..width {
+                    film.splat(x, y, image[(y * width + x) as usize], 1f32);
+                }
+            }
+            if let Err(m) = film.save(path) {
+                println!("{}", m);
+            }
+        });
+    }
+
+    // Runs the iterations for a width by height image, handing the image so
+    // far to write, a row at a time from the bottom, whenever it is due.
+    fn run<F : FnMut(&[Color])>(&self, width : u32, height : u32, camera : Arc<dyn Camera>, scene : Scene, mut write : F) {
+        let mut scene = scene;
+        scene.preprocess();
+        let scene = Arc::new(scene);
+
+        let n_pixels = (width * height) as usize;
+        let pixels : Arc<Vec<Mutex<SPPMPixel>>> = Arc::new((0..n_pixels).map(|_| Mutex::new(SPPMPixel::new(self.initial_radius))).collect());
+        let pool = ThreadPool::new(8);
+
+        for iteration in 0..self.iterations {
+            // find each pixel's visible point, a few rows at a time
+            for ys in (0..height).step_by(16) {
+                let (camera, scene, pixels) = (camera.clone(), scene.clone(), pixels.clone());
+                let max_depth = self.max_depth;
+                pool.execute(move || {
+                    let mut sampler = RandomSampler::new(random());
+                    for y in ys..(ys + 16).min(height) {
+                        for x in 0..width {
+                            let (dx, dy) = sampler.get_2d();
+                            let cx = (x as f32 + dx) * 2f32 / width as f32 - 1f32;
+                            let cy = (y as f32 + dy) * 2f32 / height as f32 - 1f32;
+                            let (ld, vp) = visible_point(&camera.cast(cx, cy), &scene, &mut sampler, max_depth);
+
+                            let mut pixel = pixels[(y * width + x) as usize].lock().unwrap();
+                            pixel.ld.add_self_c(&ld);
+                            pixel.vp = vp;
+                        }
+                    }
+                });
+            }
+            pool.join();
+
+            // trace the photons, gathering them at the visible points
+            let grid = Arc::new(PhotonGrid::new(&pixels));
+            let jobs = 64;
+            for job in 0..jobs {
+                let (scene, pixels, grid) = (scene.clone(), pixels.clone(), grid.clone());
+                let count = (job + 1) * self.photons_per_iteration / jobs - job * self.photons_per_iteration / jobs;
+                let max_depth = self.max_depth;
+                pool.execute(move || {
+                    let mut sampler = RandomSampler::new(random());
+                    for _ in 0..count {
+                        trace_photon(&scene, &grid, &pixels, &mut sampler, max_depth);
+                    }
+                });
+            }
+            pool.join();
+
+            // fold this iteration's photons into each pixel, shrinking its
+            // radius by as much as the photons found allow
+            for pixel in pixels.iter() {
+                pixel.lock().unwrap().update(2f32 / 3f32);
+            }
+
+            if (iteration + 1) % self.write_frequency == 0 || iteration + 1 == self.iterations {
+                let n_photons = (iteration + 1) as f32 * self.photons_per_iteration as f32;
+                let image : Vec<Color> = pixels.iter().map(|p| p.lock().unwrap().radiance(iteration + 1, n_photons)).collect();
+                write(&image);
+            }
+        }
+    }
+}
+
+// A point seen through a pixel, where photons are gathered, with the BSDF
+// that scatters them towards the camera and the throughput of the path
+// from the camera.
+struct VisiblePoint {
+    p    : Point,
+    wo   : Vector,
+    bsdf : BSDF,
+    beta : Color,
+}
+
+struct SPPMPixel {
+    radius : f32,
+    // the light found along camera paths, summed over the iterations
+    ld     : Color,
+    vp     : Option<VisiblePoint>,
+    // the photons gathered this iteration, and their count
+    phi    : Color,
+    m      : u32,
+    // the count of photons the estimate rests on, and their flux
+    n      : f32,
+    tau    : Color,
+}
+
+impl SPPMPixel {
+    fn new(radius : f32) -> SPPMPixel {
+        SPPMPixel { radius: radius, ld: Color::black(), vp: None, phi: Color::black(), m: 0, n: 0f32, tau: Color::black() }
+    }
+
+    // Keeps a fraction alpha of the photons gathered this iteration, and
+    // shrinks the radius so that the density they were found at is kept.
+    fn update(&mut self, alpha : f32) {
+        if self.m > 0 {
+            let n = self.n + alpha * self.m as f32;
+            let radius = self.radius * (n / (self.n + self.m as f32)).sqrt();
+            let beta = self.vp.as_ref().map_or(Color::black(), |vp| vp.beta);
+            self.tau = (self.tau + beta * self.phi) * ((radius * radius) / (self.radius * self.radius));
+            self.n = n;
+            self.radius = radius;
+            self.m = 0;
+            self.phi = Color::black();
+        }
+        self.vp = None;
+    }
+
+    // The pixel's estimate after the given number of iterations, which
+    // traced n_photons photons between them.
+    fn radiance(&self, iterations : u32, n_photons : f32) -> Color {
+        self.ld / iterations as f32 + self.tau / (n_photons * PI * self.radius * self.radius)
+    }
+}
+
+// Follows r from the camera to its visible point, the first surface that is
+// diffuse, or glossy at the last bounce, returning the light found on the
+// way, by light sampling and by hitting lights, and the visible point.
+fn visible_point(r : &Ray, scene : &Scene, sampler : &mut dyn Sampler, max_depth : u32) -> (Color, Option<VisiblePoint>) {
+    let mut ld = Color::black();
+    let mut beta = Color::white();
+    let mut ray = *r;
+    let mut specular_bounce = false;
+
+    for depth in 0..max_depth {
+        let i = match scene.intersect(&ray) {
+            Some(i) => i,
+            None    => {
+                if depth == 0 || specular_bounce {
+                    for light in scene.lights.iter() {
+                        ld.add_self_c(&(beta * light.le(&ray)));
+                    }
+                }
+                break;
+            },
+        };
+
+        let wo = -ray.direction.normalize();
+        if depth == 0 || specular_bounce {
+            ld.add_self_c(&(beta * i.le(scene, &wo)));
+        }
+
+        let bsdf = i.bsdf(true);
+        if bsdf.has_non_specular() {
+            ld.add_self_c(&(beta * sample_one_light(scene, &i, &bsdf, &wo, sampler)));
+        }
+
+        let is_diffuse = bsdf.num_components(BSDF_DIFFUSE | BSDF_REFLECTION | BSDF_TRANSMISSION) > 0;
+        let is_glossy = bsdf.num_components(BSDF_GLOSSY | BSDF_REFLECTION | BSDF_TRANSMISSION) > 0;
+        if is_diffuse || (is_glossy && depth == max_depth - 1) {
+            return (ld, Some(VisiblePoint { p: i.context.p, wo: wo, bsdf: bsdf, beta: beta }));
+        }
+
+        let s = match bsdf.sample_f(&wo, sampler.get_2d(), BSDF_ALL) {
+            Some(s) if !s.f.is_black() => s,
+            _                          => break,
+        };
+        beta.mul_self_c(&(s.f * (s.wi.dot(&bsdf.ns.to_vector()).abs() / s.pdf)));
+        specular_bounce = s.sampled_type & BSDF_SPECULAR != 0;
+        ray = i.context.spawn_ray(&s.wi);
+
+        if beta.luminance() < 0.25f32 {
+            let q = (1f32 - beta.luminance()).max(0f32);
+            if sampler.get_1d() < q {
+                break;
+            }
+            beta.div_self_s(1f32 - q);
+        }
+    }
+
+    (ld, None)
+}
+
+// Traces a photon from a light chosen by power, adding it to the pixels
+// whose visible points it lands near.  Photons arriving straight from the
+// lights are left out, as the camera paths found that light by sampling.
+fn trace_photon(scene : &Scene, grid : &PhotonGrid, pixels : &[Mutex<SPPMPixel>], sampler : &mut dyn Sampler, max_depth : u32) {
+    let (ix, light_pdf) = match scene.sample_light(sampler.get_1d()) {
+        Some(l) if l.1 > 0f32 => l,
+        _                     => return,
+    };
+    let s = match scene.lights[ix].sample_le(sampler.get_2d(), sampler.get_2d()) {
+        Some(s) if s.pdf_pos > 0f32 && s.pdf_dir > 0f32 && !s.le.is_black() => s,
+        _                                                                  => return,
+    };
+
+    let mut ray = s.ray;
+    let mut beta = s.le * (s.n.to_vector().dot(&ray.direction.normalize()).abs() / (light_pdf * s.pdf_pos * s.pdf_dir));
+    for depth in 0..max_depth {
+        let i = match scene.intersect(&ray) {
+            Some(i) => i,
+            None    => break,
+        };
+
+        let wi = -ray.direction.normalize();
+        if depth > 0 {
+            let p = i.context.p;
+            for &ix in grid.candidates(&p) {
+                let mut pixel = pixels[ix].lock().unwrap();
+                let radius2 = pixel.radius * pixel.radius;
+                let phi = match pixel.vp {
+                    Some(ref vp) if (vp.p - p).magnitude_squared() <= radius2 => beta * vp.bsdf.f(&vp.wo, &wi, BSDF_ALL),
+                    _                                                         => continue,
+                };
+                pixel.phi.add_self_c(&phi);
+                pixel.m += 1;
+            }
+        }
+
+        let mut bsdf = i.bsdf(true);
+        bsdf.mode = TransportMode::Importance;
+        let s = match bsdf.sample_f(&wi, sampler.get_2d(), BSDF_ALL) {
+            Some(s) if !s.f.is_black() && s.pdf > 0f32 => s,
+            _                                          => break,
+        };
+        let beta_new = beta * s.f * (s.wi.dot(&bsdf.ns.to_vector()).abs() / s.pdf);
+
+        // photons are ended in proportion to the light they lose, so that
+        // the survivors carry about as much as before
+        let q = (1f32 - beta_new.luminance() / beta.luminance()).max(0f32);
+        if sampler.get_1d() < q {
+            break;
+        }
+        beta = beta_new / (1f32 - q);
+        ray = i.context.spawn_ray(&s.wi);
+    }
+}
+
+// The visible points of an iteration, hashed into a uniform grid by the
+// cells their search spheres overlap, so that a photon need only be checked
+// against the points listed for its own cell.  Cells share lists when their
+// hashes collide, which costs only some extra checks.
+struct PhotonGrid {
+    bounds : BoundingBox,
+    res    : [usize; 3],
+    cells  : Vec<Vec<usize>>,
+}
+
+impl PhotonGrid {
+    fn new(pixels : &[Mutex<SPPMPixel>]) -> PhotonGrid {
+        let mut bounds = BoundingBox::empty();
+        let mut max_radius = 0f32;
+        for pixel in pixels.iter() {
+            let pixel = pixel.lock().unwrap();
+            if let Some(ref vp) = pixel.vp {
+                let r = Vector::new(pixel.radius, pixel.radius, pixel.radius);
+                bounds.add_self_points(&[vp.p - r, vp.p + r]);
+                max_radius = max_radius.max(pixel.radius);
+            }
+        }
+
+        // cells about as wide as the largest search sphere
+        let mut grid = PhotonGrid { bounds: bounds, res: [1, 1, 1], cells: vec![Vec::new(); pixels.len().max(1)] };
+        if bounds.is_empty() {
+            return grid;
+        }
+        let diagonal = bounds.diagonal();
+        let max_diagonal = diagonal.x.max(diagonal.y).max(diagonal.z);
+        let base_res = max_diagonal / max_radius;
+        for (axis, d) in [diagonal.x, diagonal.y, diagonal.z].iter().enumerate() {
+            grid.res[axis] = ((base_res * d / max_diagonal) as usize).max(1);
+        }
+
+        for (ix, pixel) in pixels.iter().enumerate() {
+            let pixel = pixel.lock().unwrap();
+            if let Some(ref vp) = pixel.vp {
+                let r = Vector::new(pixel.radius, pixel.radius, pixel.radius);
+                let (lo, hi) = (grid.cell(&(vp.p - r)), grid.cell(&(vp.p + r)));
+                for z in lo[2]..=hi[2] {
+                    for y in lo[1]..=hi[1] {
+                        for x in lo[0]..=hi[0] {
+                            // cells whose hashes collide list a point once
+                            let h = grid.hash([x, y, z]);
+                            if grid.cells[h].last() != Some(&ix) {
+                                grid.cells[h].push(ix);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        grid
+    }
+
+    // The cell holding p, clamped to the grid.
+    fn cell(&self, p : &Point) -> [usize; 3] {
+        let o = self.bounds.offset(p);
+        let clamp = |t : f32, n : usize| ((t * n as f32).max(0f32) as usize).min(n - 1);
+        [clamp(o.x, self.res[0]), clamp(o.y, self.res[1]), clamp(o.z, self.res[2])]
+    }
+
+    fn hash(&self, [x, y, z] : [usize; 3]) -> usize {
+        let h = (x as u64).wrapping_mul(73856093) ^ (y as u64).wrapping_mul(19349663) ^ (z as u64).wrapping_mul(83492791);
+        (h % self.cells.len() as u64) as usize
+    }
+
+    // The pixels whose visible points may be near p.
+    fn candidates(&self, p : &Point) -> &[usize] {
+        if self.bounds.is_empty() || !self.bounds.contains(p) {
+            return &[];
+        }
+        &self.cells[self.hash(self.cell(p))]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cameras::PerspectiveCamera;
+    use crate::geometry::{TransMut, Trans, Transform};
+    use crate::lights::PointLight;
+    use crate::materials::{MatteMaterial, MirrorMaterial};
+    use crate::shapes::Plane;
+
+    // A point light between a mirror and a matte ceiling lights the
+    // ceiling directly, and through the mirror as if from its reflection,
+    // which only the photons can find.
+    #[test]
+    fn test_caustic() {
+        let mut scene = Scene::new();
+        scene.add(Arc::new(Plane::new(20f32, 20f32)), Arc::new(MirrorMaterial::constant(Color::white())));
+        scene.add(Arc::new(Plane::new(20f32, 20f32).translate(&Vector::new(0f32, 0f32, 2f32))), Arc::new(MatteMaterial::constant(Color::gray(0.5f32))));
+        scene.add_light(Box::new(PointLight::new(Color::white()).translate(&Vector::new(0f32, 0f32, 1f32))));
+
+        let mut camera = PerspectiveCamera::new(0.01f32, 1f32);
+        camera.transform_self(&Transform::translation(&Vector::new(0f32, 0f32, 1.5f32)));
+
+        let mut image = Vec::new();
+        SPPMIntegrator::new(32, 10000, 2, 0.3f32, 100).run(1, 1, Arc::new(camera), scene, |i| image = i.to_vec());
+
+        let direct = 0.5f32 / PI;
+        let expected = direct * (1f32 + 1f32 / 9f32);
+        assert!((image[0].luminance() - expected).abs() < 0.03f32 * expected, "{} != {}", image[0].luminance(), expected);
+    }
+}
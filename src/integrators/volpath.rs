@@ -0,0 +1,299 @@
+use std::sync::Arc;
+
+use crate::color::Color;
+use crate::geometry::{Ray, Vector};
+use crate::integrators::{Integrator, power_heuristic};
+use crate::media::{Medium, PhaseFunction};
+use crate::reflection::{BSDF, BSDF_ALL, BSDF_SPECULAR, BSDF_TRANSMISSION};
+use crate::sampler::Sampler;
+use crate::scene::{Scene, SceneIntersection};
+use crate::shapes::SurfaceContext;
+
+// Volumetric path tracing, after pbrt-v3.  Paths are traced as by the path
+// tracer, but are followed through the media the camera and the shapes'
+// interfaces put them in, where the light may be absorbed, or scattered by
+// the medium's phase function at points that are lit just as surfaces are.
+// Light reaching any point is dimmed by the media it passes through, and
+// shapes that only bound media are passed through without counting as a
+// bounce.
+pub struct VolPathIntegrator {
+    max_depth : u32,
+    rr_depth  : u32,
+}
+
+impl VolPathIntegrator {
+    pub fn new(max_depth : u32, rr_depth : u32) -> VolPathIntegrator {
+        VolPathIntegrator { max_depth: max_depth, rr_depth: rr_depth }
+    }
+}
+
+impl Integrator for VolPathIntegrator {
+    fn li(&self, r : &Ray, scene : &Scene, sampler : &mut dyn Sampler) -> Color {
+        let mut l = Color::black();
+        let mut beta = Color::white();
+        let mut ray = *r;
+        let mut medium = scene.camera_medium.clone();
+        let mut specular_bounce = false;
+        let mut eta_scale = 1f32;
+
+        let mut bounces = 0;
+        loop {
+            let hit = scene.intersect_media(&ray);
+
+            // the light may be scattered within the medium before the ray
+            // reaches the hit
+            let mut scattered = None;
+            if let Some(ref m) = medium {
+                let s = m.sample(&ray, hit.as_ref().map_or(ray.t_max, |i| i.time), sampler);
+                beta.mul_self_c(&s.beta);
+                scattered = s.p.map(|p| (p, m.clone()));
+            }
+            if beta.is_black() {
+                break;
+            }
+
+            if let Some((p, m)) = scattered {
+                if bounces >= self.max_depth {
+                    break;
+                }
+                let wo = -ray.direction.normalize();
+                let v = Scattering::Medium(SurfaceContext::for_point(p), m.phase());
+                l.add_self_c(&(beta * sample_one_light_tr(scene, &v, &wo, &medium, sampler)));
+
+                // the phase function is its own density, so beta is unchanged
+                let (wi, _) = m.phase().sample_p(&wo, sampler.get_2d());
+                ray = Ray::new(&p, &wi);
+                specular_bounce = false;
+            } else {
+                if bounces == 0 || specular_bounce {
+                    match hit {
+                        Some(ref i) => l.add_self_c(&(beta * i.le(scene, &-ray.direction.normalize()))),
+                        None        => {
+                            for light in scene.lights.iter() {
+                                l.add_self_c(&(beta * light.le(&ray)));
+                            }
+                        },
+                    }
+                }
+
+                let i = match hit {
+                    Some(i) if bounces < self.max_depth => i,
+                    _                                   => break,
+                };
+
+                // pass into the medium beyond a shape that only bounds it
+                if i.material.is_none() {
+                    medium = i.medium(&ray.direction, medium);
+                    ray = i.context.spawn_ray(&ray.direction);
+                    continue;
+                }
+
+                let bsdf = i.bsdf(true);
+                let wo = -ray.direction.normalize();
+                if bsdf.has_non_specular() {
+                    let v = Scattering::Surface(&i, &bsdf);
+                    l.add_self_c(&(beta * sample_one_light_tr(scene, &v, &wo, &medium, sampler)));
+                }
+
+                let s = match bsdf.sample_f(&wo, sampler.get_2d(), BSDF_ALL) {
+                    Some(s) if !s.f.is_black() => s,
+                    _                          => break,
+                };
+                beta.mul_self_c(&(s.f * (s.wi.dot(&bsdf.ns.to_vector()).abs() / s.pdf)));
+                specular_bounce = s.sampled_type & BSDF_SPECULAR != 0;
+                if specular_bounce && s.sampled_type & BSDF_TRANSMISSION != 0 {
+                    let eta2 = bsdf.eta * bsdf.eta;
+                    eta_scale *= if wo.dot(&i.context.n.to_vector()) > 0f32 { eta2 } else { 1f32 / eta2 };
+                }
+                medium = i.medium(&s.wi, medium);
+                ray = i.context.spawn_ray(&s.wi);
+            }
+
+            let rr_beta = beta.max_component() * eta_scale;
+            if rr_beta < 1f32 && bounces > self.rr_depth {
+                let q = (1f32 - rr_beta).max(0.05f32);
+                if sampler.get_1d() < q {
+                    break;
+                }
+                beta.div_self_s(1f32 - q);
+            }
+
+            bounces += 1;
+        }
+
+        l
+    }
+}
+
+// A point at which light is scattered: on a surface, by its BSDF, or within
+// a medium, by its phase function.
+enum Scattering<'a> {
+    Surface(&'a SceneIntersection, &'a BSDF),
+    Medium(SurfaceContext, &'a dyn PhaseFunction),
+}
+
+impl<'a> Scattering<'a> {
+    fn context(&self) -> &SurfaceContext {
+        match *self {
+            Scattering::Surface(i, _)           => &i.context,
+            Scattering::Medium(ref context, _) => context,
+        }
+    }
+
+    // The medium a ray leaving the point in direction w travels through,
+    // given the medium the point was reached in.
+    fn medium(&self, w : &Vector, medium : &Option<Arc<dyn Medium>>) -> Option<Arc<dyn Medium>> {
+        match *self {
+            Scattering::Surface(i, _) => i.medium(w, medium.clone()),
+            Scattering::Medium(..)    => medium.clone(),
+        }
+    }
+
+    // The light scattered from wi towards wo, with the cosine at surfaces,
+    // leaving out specular lobes, which no light sample can find.
+    fn f(&self, wo : &Vector, wi : &Vector) -> Color {
+        match *self {
+            Scattering::Surface(_, bsdf) => bsdf.f(wo, wi, BSDF_ALL & !BSDF_SPECULAR) * wi.dot(&bsdf.ns.to_vector()).abs(),
+            Scattering::Medium(_, phase) => Color::gray(phase.p(wo, wi)),
+        }
+    }
+
+    fn pdf(&self, wo : &Vector, wi : &Vector) -> f32 {
+        match *self {
+            Scattering::Surface(_, bsdf) => bsdf.pdf(wo, wi, BSDF_ALL & !BSDF_SPECULAR),
+            Scattering::Medium(_, phase) => phase.p(wo, wi),
+        }
+    }
+
+    // Samples wi, returning it, f() for it and its density.
+    fn sample(&self, wo : &Vector, u : (f32, f32)) -> Option<(Vector, Color, f32)> {
+        match *self {
+            Scattering::Surface(_, bsdf) => {
+                bsdf.sample_f(wo, u, BSDF_ALL & !BSDF_SPECULAR).map(|s| (s.wi, s.f * s.wi.dot(&bsdf.ns.to_vector()).abs(), s.pdf))
+            },
+            Scattering::Medium(_, phase) => {
+                let (wi, p) = phase.sample_p(wo, u);
+                Some((wi, Color::gray(p), p))
+            },
+        }
+    }
+}
+
+// As sample_one_light(), for a point v that may be within a medium, and
+// with the light dimmed by the media between it and the light.
+fn sample_one_light_tr(scene : &Scene, v : &Scattering, wo : &Vector, medium : &Option<Arc<dyn Medium>>, sampler : &mut dyn Sampler) -> Color {
+    let (ix, light_pdf) = match scene.sample_light(sampler.get_1d()) {
+        Some(l) if l.1 > 0f32 => l,
+        _                     => return Color::black(),
+    };
+    estimate_direct_tr(scene, v, wo, medium, ix, sampler) / light_pdf
+}
+
+// As estimate_direct(), for a point v that may be within a medium, and with
+// the light dimmed by the media between it and the light.
+fn estimate_direct_tr(scene : &Scene, v : &Scattering, wo : &Vector, medium : &Option<Arc<dyn Medium>>, ix : usize, sampler : &mut dyn Sampler) -> Color {
+    let light = &scene.lights[ix];
+    let u_light = sampler.get_2d();
+    let u_scattering = sampler.get_2d();
+    let mut ld = Color::black();
+
+    if let Some(s) = light.sample_li(v.context(), u_light) {
+        if s.pdf > 0f32 && !s.li.is_black() {
+            let f = v.f(wo, &s.wi);
+            if !f.is_black() {
+                let li = s.li * s.visibility.tr(scene, v.medium(&s.wi, medium), sampler);
+                if !li.is_black() {
+                    let weight = if light.is_delta() { 1f32 } else { power_heuristic(1, s.pdf, 1, v.pdf(wo, &s.wi)) };
+                    ld.add_self_c(&(f * li * (weight / s.pdf)));
+                }
+            }
+        }
+    }
+
+    // a delta light can only be found by sampling it
+    if light.is_delta() {
+        return ld;
+    }
+
+    if let Some((wi, f, pdf)) = v.sample(wo, u_scattering) {
+        if f.is_black() || pdf == 0f32 {
+            return ld;
+        }
+        let light_pdf = light.pdf_li(v.context(), &wi);
+        if light_pdf == 0f32 {
+            return ld;
+        }
+        let weight = power_heuristic(1, pdf, 1, light_pdf);
+
+        let r = v.context().spawn_ray(&wi);
+        let (hit, tr) = scene.intersect_tr(&r, v.medium(&wi, medium), sampler);
+        let li = match hit {
+            Some(hit) => if hit.light == Some(ix) { hit.le(scene, &-wi) } else { Color::black() },
+            None      => light.le(&r),
+        };
+        if !li.is_black() {
+            ld.add_self_c(&(f * li * tr * (weight / pdf)));
+        }
+    }
+
+    ld
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::{Point, Trans};
+    use crate::lights::InfiniteAreaLight;
+    use crate::materials::MatteMaterial;
+    use crate::media::{HomogeneousMedium, MediumInterface};
+    use crate::sampler::RandomSampler;
+    use crate::shapes::{Disc, Sphere};
+
+    // An emitter seen through an invisible ball of absorbing medium is dimmed
+    // by its transmittance across the ball.
+    #[test]
+    fn test_transmittance() {
+        let mut scene = Scene::new();
+        let fog : Arc<dyn Medium> = Arc::new(HomogeneousMedium::new(Color::gray(0.5f32), Color::black(), 0f32));
+        scene.add_medium_boundary(Arc::new(Sphere::new(1f32)), None, MediumInterface::new(Some(fog), None));
+        scene.add_area_light(Arc::new(Disc::new(5f32).translate(&Vector::new(0f32, 0f32, 3f32))), Arc::new(MatteMaterial::constant(Color::black())), Color::gray(2f32), true);
+        scene.preprocess();
+
+        let integrator = VolPathIntegrator::new(5, 3);
+        let r = Ray::new(&Point::new(0f32, 0f32, -3f32), &Vector::unit_z());
+        let mut sampler = RandomSampler::new(1);
+        let n = 10000;
+        let mut sum = 0f32;
+        for _ in 0..n {
+            sum += integrator.li(&r, &scene, &mut sampler).luminance();
+        }
+        let mean = sum / n as f32;
+        assert!((mean - 2f32 * (-1f32).exp()).abs() < 0.03f32, "mean radiance was {}", mean);
+
+        // missing the ball, the path tracer agrees
+        let r = Ray::new(&Point::new(2f32, 0f32, -3f32), &Vector::unit_z());
+        assert_eq!(integrator.li(&r, &scene, &mut sampler), Color::gray(2f32));
+    }
+
+    // A ball of medium that scatters all the light it stops, lit evenly from
+    // all around, looks as bright as its surroundings.
+    #[test]
+    fn test_furnace() {
+        let mut scene = Scene::new();
+        let fog : Arc<dyn Medium> = Arc::new(HomogeneousMedium::new(Color::black(), Color::gray(2f32), 0.3f32));
+        scene.add_medium_boundary(Arc::new(Sphere::new(1f32)), None, MediumInterface::new(Some(fog), None));
+        scene.add_light(Box::new(InfiniteAreaLight::constant(Color::white())));
+        scene.preprocess();
+
+        let integrator = VolPathIntegrator::new(100, 3);
+        let mut sampler = RandomSampler::new(1);
+        let n = 4000;
+        let mut sum = 0f32;
+        for k in 0..n {
+            let o = Point::new((k % 7) as f32 * 0.2f32 - 0.6f32, (k % 5) as f32 * 0.2f32 - 0.4f32, -3f32);
+            sum += integrator.li(&Ray::new(&o, &Vector::unit_z()), &scene, &mut sampler).luminance();
+        }
+        let mean = sum / n as f32;
+        assert!((mean - 1f32).abs() < 0.03f32, "mean radiance was {}", mean);
+    }
+}
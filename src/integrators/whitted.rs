@@ -0,0 +1,116 @@
+use std::f32::consts::PI;
+
+use crate::color::Color;
+use crate::geometry::{Ray, Vector};
+use crate::integrators::Integrator;
+use crate::reflection::{BSDF, BSDF_ALL, BSDF_REFLECTION, BSDF_TRANSMISSION, BSDF_SPECULAR};
+use crate::sampler::Sampler;
+use crate::scene::{Scene, SceneIntersection};
+
+// Classic recursive ray tracing: surfaces are lit directly by the scene's
+// lights, or by a light at the eye if it has none, and perfectly specular
+// surfaces reflect and refract whatever they see, to a depth of max_depth.
+// Area lights are seen directly, and rays that escape the scene see the
+// environment, but light reaching a surface by any other indirect path is
+// ignored.
+pub struct WhittedIntegrator {
+    max_depth : u32,
+}
+
+impl WhittedIntegrator {
+    pub fn new(max_depth : u32) -> WhittedIntegrator {
+        WhittedIntegrator { max_depth: max_depth }
+    }
+
+    fn li_depth(&self, r : &Ray, scene : &Scene, sampler : &mut dyn Sampler, depth : u32) -> Color {
+        let i = match scene.intersect(r) {
+            None    => return scene.lights.iter().fold(Color::black(), |c, l| c + l.le(r)),
+            Some(i) => i,
+        };
+
+        let bsdf = i.bsdf(false);
+        let wo = -r.direction.normalize();
+
+        let mut c = if scene.lights.is_empty() {
+            // scaled by pi so that a white diffuse surface facing the eye is white
+            bsdf.f(&wo, &wo, BSDF_ALL) * (PI * wo.dot(&bsdf.ns.to_vector()).abs())
+        } else {
+            i.le(scene, &wo) + direct_lighting(scene, &i, &bsdf, &wo, sampler)
+        };
+
+        if depth < self.max_depth {
+            for flags in [BSDF_REFLECTION | BSDF_SPECULAR, BSDF_TRANSMISSION | BSDF_SPECULAR].iter() {
+                if let Some(s) = bsdf.sample_f(&wo, sampler.get_2d(), *flags) {
+                    let weight = s.f * (s.wi.dot(&bsdf.ns.to_vector()).abs() / s.pdf);
+                    if !weight.is_black() {
+                        c.add_self_c(&(weight * self.li_depth(&i.context.spawn_ray(&s.wi), scene, sampler, depth + 1)));
+                    }
+                }
+            }
+        }
+
+        c
+    }
+}
+
+impl Integrator for WhittedIntegrator {
+    fn li(&self, r : &Ray, scene : &Scene, sampler : &mut dyn Sampler) -> Color {
+        self.li_depth(r, scene, sampler, 0)
+    }
+}
+
+// The light arriving straight from each of the lights and scattered towards
+// wo.  Lights that are not delta lights are sampled at one point each time,
+// so their soft shadows resolve as samples accumulate.
+fn direct_lighting(scene : &Scene, i : &SceneIntersection, bsdf : &BSDF, wo : &Vector, sampler : &mut dyn Sampler) -> Color {
+    let mut c = Color::black();
+    for light in scene.lights.iter() {
+        if let Some(s) = light.sample_li(&i.context, sampler.get_2d()) {
+            if s.pdf == 0f32 || s.li.is_black() {
+                continue;
+            }
+            let f = bsdf.f(wo, &s.wi, BSDF_ALL) * s.wi.dot(&bsdf.ns.to_vector()).abs();
+            if !f.is_black() && s.visibility.unoccluded(scene) {
+                c.add_self_c(&(f * s.li / s.pdf));
+            }
+        }
+    }
+    c
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use crate::geometry::{Point, Trans};
+    use crate::lights::PointLight;
+    use crate::materials::{MatteMaterial, MirrorMaterial};
+    use crate::sampler::RandomSampler;
+    use crate::shapes::{Disc, Plane};
+
+    #[test]
+    fn test_direct_lighting() {
+        let mut scene = Scene::new();
+        scene.add(Arc::new(Plane::unit()), Arc::new(MatteMaterial::constant(Color::gray(0.5f32))));
+        scene.add_light(Box::new(PointLight::new(Color::gray(4f32)).translate(&Vector::new(0f32, 0f32, 2f32))));
+        scene.preprocess();
+
+        // irradiance of 4 / 2², reflected diffusely
+        let c = WhittedIntegrator::new(5).li(&Ray::new(&Point::new(0f32, 0f32, 1f32), &-Vector::unit_z()), &scene, &mut RandomSampler::new(1));
+        assert!((c.luminance() - 0.5f32 / PI).abs() < 1e-5f32);
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let mut scene = Scene::new();
+        scene.add(Arc::new(Plane::new(5f32, 5f32)), Arc::new(MirrorMaterial::constant(Color::gray(0.5f32))));
+        scene.add_area_light(Arc::new(Disc::new(5f32).translate(&Vector::new(0f32, 0f32, 2f32))), Arc::new(MatteMaterial::constant(Color::black())), Color::gray(2f32), true);
+        scene.preprocess();
+
+        // the mirror only shows the light once reflections are followed
+        let r = Ray::new(&Point::new(0.1f32, 0f32, 1f32), &-Vector::unit_z());
+        let mut sampler = RandomSampler::new(1);
+        assert!(WhittedIntegrator::new(0).li(&r, &scene, &mut sampler).is_black());
+        assert_eq!(WhittedIntegrator::new(1).li(&r, &scene, &mut sampler), Color::gray(1f32));
+    }
+}
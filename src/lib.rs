@@ -1,12 +1,23 @@
+pub mod bvh;
 pub mod cameras;
+pub mod color;
+pub mod distribution;
+pub mod efloat;
 pub mod filters;
 pub mod film;
 pub mod geometry;
+pub mod integrators;
+pub mod lights;
+pub mod loaders;
+pub mod materials;
 pub mod math;
+pub mod media;
+pub mod reflection;
 pub mod renderer;
 pub mod sampler;
 pub mod scene;
 pub mod shapes;
+pub mod spectrum;
 pub mod textures;
 
 #[cfg(test)]
@@ -0,0 +1,102 @@
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+use crate::color::Color;
+use crate::geometry::{Normal, Ray, Vector};
+use crate::lights::{Light, LightSample, LightRaySample, VisibilityTester};
+use crate::sampler::to_hemisphere_cosine;
+use crate::shapes::{Shape, SurfaceContext};
+
+// A shape that emits radiance l equally in every direction from the side its
+// normal faces, or from both sides if it is two-sided.  The shape is sampled
+// for its light, so unlike the other lights this one casts soft shadows.
+#[derive(Clone)]
+pub struct DiffuseAreaLight {
+    l         : Color,
+    shape     : Arc<dyn Shape>,
+    two_sided : bool,
+}
+
+impl DiffuseAreaLight {
+    pub fn new(l : Color, shape : Arc<dyn Shape>, two_sided : bool) -> DiffuseAreaLight {
+        DiffuseAreaLight { l: l, shape: shape, two_sided: two_sided }
+    }
+
+    pub fn shape(&self) -> &Arc<dyn Shape> {
+        &self.shape
+    }
+
+    fn emitted(&self, n : &Normal, w : &Vector) -> Color {
+        if self.two_sided || n.to_vector().dot(w) > 0f32 { self.l } else { Color::black() }
+    }
+}
+
+impl Light for DiffuseAreaLight {
+    fn sample_li(&self, context : &SurfaceContext, u : (f32, f32)) -> Option<LightSample> {
+        let (p, n, pdf) = self.shape.sample_from(context, u)?;
+        let wi = (p - context.p).normalize();
+        let li = self.emitted(&n, &-wi);
+        if li.is_black() {
+            return None;
+        }
+        Some(LightSample { n: n, .. LightSample::new(wi, li, pdf, VisibilityTester::new(context, &p)) })
+    }
+
+    // Rays leave in a cosine-weighted distribution about the normal, or
+    // either side of it for a two-sided light, with u2.0 picking the side.
+    fn sample_le(&self, u1 : (f32, f32), u2 : (f32, f32)) -> Option<LightRaySample> {
+        let (p, n, p_error) = self.shape.sample(u1);
+        let (u, flip) = if !self.two_sided {
+            (u2, false)
+        } else if u2.0 < 0.5f32 {
+            (((2f32 * u2.0).min(1f32 - f32::EPSILON), u2.1), false)
+        } else {
+            (((2f32 * u2.0 - 1f32).min(1f32 - f32::EPSILON), u2.1), true)
+        };
+        let mut w = to_hemisphere_cosine(u);
+        if flip {
+            w.z = -w.z;
+        }
+
+        let nv = n.to_vector();
+        let (v1, v2) = nv.coordinate_system();
+        let w = v1 * w.x + v2 * w.y + nv * w.z;
+        let (_, pdf_dir) = self.pdf_le(&Ray::new(&p, &w), &n);
+        let le = self.emitted(&n, &w);
+        if pdf_dir == 0f32 || le.is_black() {
+            return None;
+        }
+
+        let mut context = SurfaceContext::new(p, n, (0f32, 0f32), (Vector::zero(), Vector::zero()), (Normal::zero(), Normal::zero()));
+        context.p_error = p_error;
+        Some(LightRaySample::new(context.spawn_ray(&w), n, le, self.shape.pdf(), pdf_dir))
+    }
+
+    fn pdf_le(&self, r : &Ray, n : &Normal) -> (f32, f32) {
+        let cos_theta = n.to_vector().dot(&r.direction.normalize());
+        let pdf_dir = if self.two_sided {
+            0.5f32 * cos_theta.abs() / PI
+        } else {
+            cos_theta.max(0f32) / PI
+        };
+        (self.shape.pdf(), pdf_dir)
+    }
+
+    fn pdf_li(&self, context : &SurfaceContext, wi : &Vector) -> f32 {
+        self.shape.pdf_from(context, wi)
+    }
+
+    fn l(&self, context : &SurfaceContext, w : &Vector) -> Color {
+        self.emitted(&context.n, w)
+    }
+
+    fn power(&self) -> Color {
+        // shapes are sampled uniformly by world area
+        let area = 1f32 / self.shape.pdf();
+        self.l * (area * PI * if self.two_sided { 2f32 } else { 1f32 })
+    }
+
+    fn is_delta(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,91 @@
+use std::f32::consts::PI;
+
+use crate::color::Color;
+use crate::geometry::{Transform, Trans, TransMut, HasTransform, BoundingBox, Normal, Point, Ray, Vector};
+use crate::lights::{Light, LightSample, LightRaySample, VisibilityTester};
+use crate::sampler::to_disc_concentric;
+use crate::shapes::SurfaceContext;
+
+// A directional light, such as the sun, whose light travels down the +z axis
+// of its transform and arrives everywhere with radiance l.
+#[derive(Copy, Clone, Debug)]
+pub struct DistantLight {
+    transform    : Transform,
+    l            : Color,
+    world_center : Point,
+    world_radius : f32,
+}
+
+impl DistantLight {
+    pub fn new(l : Color) -> DistantLight {
+        DistantLight { transform: Transform::identity(), l: l, world_center: Point::origin(), world_radius: 0f32 }
+    }
+
+    // The world direction towards the light.
+    pub fn direction(&self) -> Vector {
+        -Vector::unit_z().from(self).normalize()
+    }
+}
+
+impl HasTransform for DistantLight {
+    fn get_transform(&self) -> &Transform {
+        &self.transform
+    }
+}
+
+impl Light for DistantLight {
+    fn sample_li(&self, context : &SurfaceContext, _u : (f32, f32)) -> Option<LightSample> {
+        let wi = self.direction();
+        // a point outside the scene, from which nothing can be in the way
+        let outside = context.p + wi * (2f32 * self.world_radius + (context.p - self.world_center).magnitude());
+        Some(LightSample::new(wi, self.l, 1f32, VisibilityTester::new(context, &outside)))
+    }
+
+    // Rays start on a disc as wide as the scene, outside it, facing the
+    // light's direction.
+    fn sample_le(&self, u1 : (f32, f32), _u2 : (f32, f32)) -> Option<LightRaySample> {
+        let w = self.direction();
+        let (v1, v2) = w.coordinate_system();
+        let (x, y) = to_disc_concentric(u1);
+        let origin = self.world_center + (v1 * x + v2 * y + w) * self.world_radius;
+        let pdf_pos = 1f32 / (PI * self.world_radius * self.world_radius);
+        Some(LightRaySample::new(Ray::new(&origin, &-w), (-w).to_normal(), self.l, pdf_pos, 1f32))
+    }
+
+    fn pdf_le(&self, _r : &Ray, _n : &Normal) -> (f32, f32) {
+        (1f32 / (PI * self.world_radius * self.world_radius), 0f32)
+    }
+
+    // The power falling on a disc as wide as the scene.
+    fn power(&self) -> Color {
+        self.l * (PI * self.world_radius * self.world_radius)
+    }
+
+    fn is_delta(&self) -> bool {
+        true
+    }
+
+    fn is_infinite(&self) -> bool {
+        true
+    }
+
+    fn preprocess(&mut self, scene_bounds : &BoundingBox) {
+        let (center, radius) = scene_bounds.bounding_sphere();
+        self.world_center = center;
+        self.world_radius = radius;
+    }
+}
+
+impl Trans for DistantLight {
+    type Output=DistantLight;
+
+    fn transform(&self, t : &Transform) -> DistantLight {
+        DistantLight { transform: *t + self.transform, .. *self }
+    }
+}
+
+impl TransMut for DistantLight {
+    fn transform_self(&mut self, t : &Transform) {
+        self.transform = *t + self.transform;
+    }
+}
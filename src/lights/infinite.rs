@@ -0,0 +1,239 @@
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+use crate::cameras::{lat_long_direction, lat_long_coordinates};
+use crate::color::Color;
+use crate::distribution::Distribution2D;
+use crate::geometry::{Transform, Trans, TransMut, HasTransform, BoundingBox, Normal, Point, Ray, Vector};
+use crate::lights::{Light, LightSample, LightRaySample, VisibilityTester};
+use crate::sampler::to_disc_concentric;
+use crate::shapes::SurfaceContext;
+
+// An environment surrounding the scene at an infinite distance, whose radiance
+// is given by an equirectangular image scaled by l.  The image uses the
+// mapping of the sphere camera, so that its panoramas can light scenes, and
+// is sampled in proportion to its brightness.
+#[derive(Clone)]
+pub struct InfiniteAreaLight {
+    transform    : Transform,
+    l            : Color,
+    width        : usize,
+    height       : usize,
+    texels       : Arc<Vec<Color>>,
+    distribution : Arc<Distribution2D>,
+    world_center : Point,
+    world_radius : f32,
+}
+
+impl InfiniteAreaLight {
+    // texels are given a row at a time, from the top of the image.
+    pub fn new(l : Color, width : usize, height : usize, texels : Vec<Color>) -> InfiniteAreaLight {
+        assert_eq!(texels.len(), width * height, "environment map size must match its texels");
+
+        // rows nearer the poles cover less of the sphere, so are weighted by
+        // the sine of their angle from the pole
+        let mut func = Vec::with_capacity(width * height);
+        for row in texels.chunks(width) {
+            let t = (func.len() / width) as f32 + 0.5f32;
+            let sin_theta = (PI * t / height as f32).sin();
+            func.extend(row.iter().map(|c| c.luminance() * sin_theta));
+        }
+
+        InfiniteAreaLight {
+            transform: Transform::identity(),
+            l: l,
+            width: width,
+            height: height,
+            texels: Arc::new(texels),
+            distribution: Arc::new(Distribution2D::new(&func, width, height)),
+            world_center: Point::origin(),
+            world_radius: 0f32,
+        }
+    }
+
+    // An environment of constant radiance l.
+    pub fn constant(l : Color) -> InfiniteAreaLight {
+        InfiniteAreaLight::new(l, 1, 1, vec![Color::white()])
+    }
+
+    // The radiance at (s, t) in the image, both in [0, 1] from the top left.
+    fn lookup(&self, (s, t) : (f32, f32)) -> Color {
+        let x = ((s * self.width as f32) as usize).min(self.width - 1);
+        let y = ((t * self.height as f32) as usize).min(self.height - 1);
+        self.texels[y * self.width + x] * self.l
+    }
+
+    // The density with which sample_li() chooses world direction wi, by
+    // solid angle.
+    fn pdf_direction(&self, wi : &Vector) -> f32 {
+        let (x, y) = lat_long_coordinates(&wi.to(self));
+        let cos_v = (y * PI / 2f32).cos();
+        if cos_v <= 0f32 {
+            return 0f32;
+        }
+        self.distribution.pdf(((x + 1f32) / 2f32, (1f32 - y) / 2f32)) / (2f32 * PI * PI * cos_v)
+    }
+}
+
+impl HasTransform for InfiniteAreaLight {
+    fn get_transform(&self) -> &Transform {
+        &self.transform
+    }
+}
+
+impl Light for InfiniteAreaLight {
+    fn sample_li(&self, context : &SurfaceContext, u : (f32, f32)) -> Option<LightSample> {
+        let ((s, t), map_pdf) = self.distribution.sample_continuous(u);
+        if map_pdf == 0f32 {
+            return None;
+        }
+
+        // the image spans 2π by π radians, and rows shrink by cos(v) as v
+        // moves from the horizon towards the poles
+        let (x, y) = (2f32 * s - 1f32, 1f32 - 2f32 * t);
+        let cos_v = (y * PI / 2f32).cos();
+        if cos_v <= 0f32 {
+            return None;
+        }
+        let pdf = map_pdf / (2f32 * PI * PI * cos_v);
+
+        let wi = lat_long_direction(x, y).from(self).normalize();
+        let outside = context.p + wi * (2f32 * self.world_radius + (context.p - self.world_center).magnitude());
+        Some(LightSample::new(wi, self.lookup((s, t)), pdf, VisibilityTester::new(context, &outside)))
+    }
+
+    fn pdf_li(&self, _context : &SurfaceContext, wi : &Vector) -> f32 {
+        self.pdf_direction(wi)
+    }
+
+    // Rays start on a disc as wide as the scene, outside it, facing back
+    // along a direction chosen as sample_li() would.
+    fn sample_le(&self, u1 : (f32, f32), u2 : (f32, f32)) -> Option<LightRaySample> {
+        let ((s, t), map_pdf) = self.distribution.sample_continuous(u1);
+        if map_pdf == 0f32 {
+            return None;
+        }
+        let (x, y) = (2f32 * s - 1f32, 1f32 - 2f32 * t);
+        let cos_v = (y * PI / 2f32).cos();
+        if cos_v <= 0f32 {
+            return None;
+        }
+
+        let d = -lat_long_direction(x, y).from(self).normalize();
+        let (v1, v2) = d.coordinate_system();
+        let (cx, cy) = to_disc_concentric(u2);
+        let origin = self.world_center + (v1 * cx + v2 * cy - d) * self.world_radius;
+        let pdf_pos = 1f32 / (PI * self.world_radius * self.world_radius);
+        let pdf_dir = map_pdf / (2f32 * PI * PI * cos_v);
+        Some(LightRaySample::new(Ray::new(&origin, &d), d.to_normal(), self.lookup((s, t)), pdf_pos, pdf_dir))
+    }
+
+    fn pdf_le(&self, r : &Ray, _n : &Normal) -> (f32, f32) {
+        (1f32 / (PI * self.world_radius * self.world_radius), self.pdf_direction(&-r.direction))
+    }
+
+    fn le(&self, r : &Ray) -> Color {
+        let (x, y) = lat_long_coordinates(&r.direction.to(self));
+        self.lookup(((x + 1f32) / 2f32, (1f32 - y) / 2f32))
+    }
+
+    // The power falling on a disc as wide as the scene, from the average
+    // radiance of the environment.
+    fn power(&self) -> Color {
+        let sum = self.texels.iter().fold(Color::black(), |s, c| s + *c);
+        sum * self.l * (PI * self.world_radius * self.world_radius / self.texels.len() as f32)
+    }
+
+    fn is_delta(&self) -> bool {
+        false
+    }
+
+    fn is_infinite(&self) -> bool {
+        true
+    }
+
+    fn preprocess(&mut self, scene_bounds : &BoundingBox) {
+        let (center, radius) = scene_bounds.bounding_sphere();
+        self.world_center = center;
+        self.world_radius = radius;
+    }
+}
+
+impl Trans for InfiniteAreaLight {
+    type Output=InfiniteAreaLight;
+
+    fn transform(&self, t : &Transform) -> InfiniteAreaLight {
+        InfiniteAreaLight { transform: *t + self.transform, .. self.clone() }
+    }
+}
+
+impl TransMut for InfiniteAreaLight {
+    fn transform_self(&mut self, t : &Transform) {
+        self.transform = *t + self.transform;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Normal;
+
+    // A map that is dark but for a single bright texel.
+    fn spot_map(x : usize, y : usize) -> InfiniteAreaLight {
+        let mut texels = vec![Color::gray(0.01f32); 16 * 8];
+        texels[y * 16 + x] = Color::gray(100f32);
+        InfiniteAreaLight::new(Color::white(), 16, 8, texels)
+    }
+
+    #[test]
+    fn test_sample_li() {
+        let light = spot_map(12, 3);
+        let context = SurfaceContext::new(Point::origin(), Normal::unit_y(), (0f32, 0f32), (Vector::unit_x(), Vector::unit_z()), (Normal::zero(), Normal::zero()));
+
+        // nearly all samples find the bright texel, and see it along the ray
+        // that sampled it
+        let mut bright = 0;
+        for i in 0..16 {
+            for j in 0..16 {
+                let u = ((i as f32 + 0.5f32) / 16f32, (j as f32 + 0.5f32) / 16f32);
+                let s = light.sample_li(&context, u).unwrap();
+                assert_eq!(s.li, light.le(&Ray::new(&Point::origin(), &s.wi)));
+                if s.li == Color::gray(100f32) {
+                    bright += 1;
+                }
+            }
+        }
+        assert!(bright > 240, "{} samples were bright", bright);
+    }
+
+    // Averaging li / pdf over the samples estimates the irradiance-like
+    // integral of the radiance over the sphere, which for a constant
+    // environment is 4π l.
+    #[test]
+    fn test_pdf_integrates() {
+        let light = InfiniteAreaLight::new(Color::gray(2f32), 4, 4, vec![Color::white(); 16]);
+        let context = SurfaceContext::new(Point::origin(), Normal::unit_y(), (0f32, 0f32), (Vector::unit_x(), Vector::unit_z()), (Normal::zero(), Normal::zero()));
+        let n = 64;
+        let mut sum = 0f32;
+        for i in 0..n {
+            for j in 0..n {
+                let s = light.sample_li(&context, ((i as f32 + 0.5f32) / n as f32, (j as f32 + 0.5f32) / n as f32)).unwrap();
+                sum += s.li.luminance() / s.pdf;
+                assert!((light.pdf_li(&context, &s.wi) - s.pdf).abs() < 1e-3f32 * s.pdf);
+            }
+        }
+        let expected = 4f32 * PI * Color::gray(2f32).luminance();
+        assert!((sum / (n * n) as f32 - expected).abs() < 0.05f32 * expected);
+    }
+
+    #[test]
+    fn test_rotation() {
+        // the texel at the centre of the map is seen along +z, until the map
+        // is turned to face +x
+        let light = spot_map(8, 4);
+        let center = Color::gray(100f32);
+        assert_eq!(light.le(&Ray::new(&Point::origin(), &Vector::new(0.01f32, -0.01f32, 1f32))), center);
+        let light = light.rotate(PI / 2f32, &Vector::unit_y());
+        assert_eq!(light.le(&Ray::new(&Point::origin(), &Vector::new(1f32, -0.01f32, -0.01f32))), center);
+    }
+}
@@ -0,0 +1,130 @@
+use std::sync::Arc;
+
+use crate::color::Color;
+use crate::geometry::{BoundingBox, Normal, Point, Ray, Vector};
+use crate::media::Medium;
+use crate::sampler::Sampler;
+use crate::scene::Scene;
+use crate::shapes::SurfaceContext;
+
+pub trait Light : Send + Sync {
+    // Samples the radiance arriving at the point of context from the light,
+    // with wi pointing towards the light.  None if no light arrives.
+    fn sample_li(&self, context : &SurfaceContext, u : (f32, f32)) -> Option<LightSample>;
+
+    // The density, with respect to solid angle, with which sample_li()
+    // chooses direction wi from the point of context.  Zero for delta
+    // lights, which no other strategy can find.
+    fn pdf_li(&self, _context : &SurfaceContext, _wi : &Vector) -> f32 {
+        0f32
+    }
+
+    // Samples a ray leaving the light, for tracing paths from it, with u1
+    // choosing its origin and u2 its direction.  None if no light leaves.
+    fn sample_le(&self, u1 : (f32, f32), u2 : (f32, f32)) -> Option<LightRaySample>;
+
+    // The densities with which sample_le() chooses the origin of r, by area,
+    // and its direction, by solid angle, when the light's normal there is n.
+    fn pdf_le(&self, r : &Ray, n : &Normal) -> (f32, f32);
+
+    // The radiance an area light emits in direction w from the point of
+    // context on its surface.
+    fn l(&self, _context : &SurfaceContext, _w : &Vector) -> Color {
+        Color::black()
+    }
+
+    // The radiance arriving along a ray that leaves the scene without
+    // hitting anything, for lights that surround it.
+    fn le(&self, _r : &Ray) -> Color {
+        Color::black()
+    }
+
+    // The total power the light emits.
+    fn power(&self) -> Color;
+
+    // Whether the light emits from a single point or in a single direction,
+    // so that it can only be found by sampling it and never by a ray.
+    fn is_delta(&self) -> bool;
+
+    // Whether the light is infinitely far away, so that its light arrives
+    // from a direction rather than from a point.
+    fn is_infinite(&self) -> bool {
+        false
+    }
+
+    // Called with the bounds of the scene once it is complete, before
+    // rendering.
+    fn preprocess(&mut self, _scene_bounds : &BoundingBox) {
+    }
+}
+
+// The light arriving from a point sampled on a light.  n is the light's
+// normal there, or zero if the light is not a surface.
+#[derive(Copy, Clone, Debug)]
+pub struct LightSample {
+    pub wi         : Vector,
+    pub li         : Color,
+    pub pdf        : f32,
+    pub visibility : VisibilityTester,
+    pub n          : Normal,
+}
+
+impl LightSample {
+    pub fn new(wi : Vector, li : Color, pdf : f32, visibility : VisibilityTester) -> LightSample {
+        LightSample { wi: wi, li: li, pdf: pdf, visibility: visibility, n: Normal::zero() }
+    }
+
+    // The point sampled on the light.
+    pub fn p(&self) -> Point {
+        self.visibility.ray.at_time(1f32)
+    }
+}
+
+// A ray leaving a light with radiance le, and the densities with which its
+// origin and direction were chosen.  n is the light's normal at the origin,
+// or the direction of the ray if the light is not a surface.
+#[derive(Copy, Clone, Debug)]
+pub struct LightRaySample {
+    pub ray     : Ray,
+    pub n       : Normal,
+    pub le      : Color,
+    pub pdf_pos : f32,
+    pub pdf_dir : f32,
+}
+
+impl LightRaySample {
+    pub fn new(ray : Ray, n : Normal, le : Color, pdf_pos : f32, pdf_dir : f32) -> LightRaySample {
+        LightRaySample { ray: ray, n: n, le: le, pdf_pos: pdf_pos, pdf_dir: pdf_dir }
+    }
+}
+
+// A shadow ray between a surface and a sampled point on a light.
+#[derive(Copy, Clone, Debug)]
+pub struct VisibilityTester {
+    pub ray : Ray,
+}
+
+impl VisibilityTester {
+    pub fn new(context : &SurfaceContext, p : &Point) -> VisibilityTester {
+        VisibilityTester { ray: context.spawn_ray_to(p) }
+    }
+
+    // Between two points that may both be on surfaces, neither of which
+    // should occlude the ray.
+    pub fn between(c0 : &SurfaceContext, c1 : &SurfaceContext) -> VisibilityTester {
+        VisibilityTester { ray: c0.spawn_ray_to_context(c1) }
+    }
+
+    pub fn unoccluded(&self, scene : &Scene) -> bool {
+        !scene.intersects(&self.ray)
+    }
+
+    // The fraction of light passing along the ray, which starts in medium,
+    // through the media on the way, or none if a surface blocks it.
+    pub fn tr(&self, scene : &Scene, medium : Option<Arc<dyn Medium>>, sampler : &mut dyn Sampler) -> Color {
+        match scene.intersect_tr(&self.ray, medium, sampler) {
+            (None, tr) => tr,
+            _          => Color::black(),
+        }
+    }
+}
@@ -0,0 +1,13 @@
+pub mod diffuse;
+pub mod distant;
+pub mod infinite;
+pub mod light;
+pub mod point;
+pub mod spot;
+
+pub use diffuse::*;
+pub use distant::*;
+pub use infinite::*;
+pub use light::*;
+pub use point::*;
+pub use spot::*;
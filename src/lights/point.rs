@@ -0,0 +1,74 @@
+use std::f32::consts::PI;
+
+use crate::color::Color;
+use crate::geometry::{Transform, Trans, TransMut, HasTransform, Normal, Point, Ray};
+use crate::lights::{Light, LightSample, LightRaySample, VisibilityTester};
+use crate::sampler::to_sphere_uniform;
+use crate::shapes::SurfaceContext;
+
+// A light emitting equally in all directions from the origin of its
+// transform, with intensity i.
+#[derive(Copy, Clone, Debug)]
+pub struct PointLight {
+    transform : Transform,
+    i         : Color,
+}
+
+impl PointLight {
+    pub fn new(i : Color) -> PointLight {
+        PointLight { transform: Transform::identity(), i: i }
+    }
+
+    pub fn position(&self) -> Point {
+        Point::origin().from(self)
+    }
+}
+
+impl HasTransform for PointLight {
+    fn get_transform(&self) -> &Transform {
+        &self.transform
+    }
+}
+
+impl Light for PointLight {
+    fn sample_li(&self, context : &SurfaceContext, _u : (f32, f32)) -> Option<LightSample> {
+        let p = self.position();
+        let d = p - context.p;
+        let dist2 = d.magnitude_squared();
+        if dist2 == 0f32 {
+            return None;
+        }
+        Some(LightSample::new(d.normalize(), self.i / dist2, 1f32, VisibilityTester::new(context, &p)))
+    }
+
+    fn sample_le(&self, u1 : (f32, f32), _u2 : (f32, f32)) -> Option<LightRaySample> {
+        let r = Ray::new(&self.position(), &to_sphere_uniform(u1));
+        Some(LightRaySample::new(r, r.direction.to_normal(), self.i, 1f32, 1f32 / (4f32 * PI)))
+    }
+
+    fn pdf_le(&self, _r : &Ray, _n : &Normal) -> (f32, f32) {
+        (0f32, 1f32 / (4f32 * PI))
+    }
+
+    fn power(&self) -> Color {
+        self.i * (4f32 * PI)
+    }
+
+    fn is_delta(&self) -> bool {
+        true
+    }
+}
+
+impl Trans for PointLight {
+    type Output=PointLight;
+
+    fn transform(&self, t : &Transform) -> PointLight {
+        PointLight { transform: *t + self.transform, .. *self }
+    }
+}
+
+impl TransMut for PointLight {
+    fn transform_self(&mut self, t : &Transform) {
+        self.transform = *t + self.transform;
+    }
+}
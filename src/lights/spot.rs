@@ -0,0 +1,129 @@
+use std::f32::consts::PI;
+
+use crate::color::Color;
+use crate::geometry::{Transform, Trans, TransMut, HasTransform, Normal, Point, Ray, Vector};
+use crate::lights::{Light, LightSample, LightRaySample, VisibilityTester};
+use crate::sampler::to_cone_uniform;
+use crate::shapes::SurfaceContext;
+
+// A point light at the origin of its transform that shines down +z in a cone
+// total_width radians wide (from the axis), fading smoothly to nothing at the
+// edge from falloff_start.
+#[derive(Copy, Clone, Debug)]
+pub struct SpotLight {
+    transform         : Transform,
+    i                 : Color,
+    cos_total_width   : f32,
+    cos_falloff_start : f32,
+}
+
+impl SpotLight {
+    pub fn new(i : Color, total_width : f32, falloff_start : f32) -> SpotLight {
+        SpotLight {
+            transform: Transform::identity(),
+            i: i,
+            cos_total_width: total_width.cos(),
+            cos_falloff_start: falloff_start.min(total_width).cos(),
+        }
+    }
+
+    pub fn position(&self) -> Point {
+        Point::origin().from(self)
+    }
+
+    // The fraction of the intensity emitted in world direction w.
+    fn falloff(&self, w : &Vector) -> f32 {
+        let cos_theta = w.to(self).normalize().z;
+        if cos_theta < self.cos_total_width {
+            return 0f32;
+        }
+        if cos_theta >= self.cos_falloff_start {
+            return 1f32;
+        }
+        let delta = (cos_theta - self.cos_total_width) / (self.cos_falloff_start - self.cos_total_width);
+        (delta * delta) * (delta * delta)
+    }
+}
+
+impl HasTransform for SpotLight {
+    fn get_transform(&self) -> &Transform {
+        &self.transform
+    }
+}
+
+impl Light for SpotLight {
+    fn sample_li(&self, context : &SurfaceContext, _u : (f32, f32)) -> Option<LightSample> {
+        let p = self.position();
+        let d = p - context.p;
+        let dist2 = d.magnitude_squared();
+        if dist2 == 0f32 {
+            return None;
+        }
+        let wi = d.normalize();
+        let li = self.i * (self.falloff(&-wi) / dist2);
+        if li.is_black() {
+            return None;
+        }
+        Some(LightSample::new(wi, li, 1f32, VisibilityTester::new(context, &p)))
+    }
+
+    fn sample_le(&self, u1 : (f32, f32), _u2 : (f32, f32)) -> Option<LightRaySample> {
+        let w = to_cone_uniform(u1, self.cos_total_width).from(self).normalize();
+        let r = Ray::new(&self.position(), &w);
+        let pdf_dir = 1f32 / (2f32 * PI * (1f32 - self.cos_total_width));
+        Some(LightRaySample::new(r, w.to_normal(), self.i * self.falloff(&w), 1f32, pdf_dir))
+    }
+
+    fn pdf_le(&self, r : &Ray, _n : &Normal) -> (f32, f32) {
+        if r.direction.to(self).normalize().z >= self.cos_total_width {
+            (0f32, 1f32 / (2f32 * PI * (1f32 - self.cos_total_width)))
+        } else {
+            (0f32, 0f32)
+        }
+    }
+
+    fn power(&self) -> Color {
+        self.i * (2f32 * PI * (1f32 - 0.5f32 * (self.cos_falloff_start + self.cos_total_width)))
+    }
+
+    fn is_delta(&self) -> bool {
+        true
+    }
+}
+
+impl Trans for SpotLight {
+    type Output=SpotLight;
+
+    fn transform(&self, t : &Transform) -> SpotLight {
+        SpotLight { transform: *t + self.transform, .. *self }
+    }
+}
+
+impl TransMut for SpotLight {
+    fn transform_self(&mut self, t : &Transform) {
+        self.transform = *t + self.transform;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Normal;
+
+    #[test]
+    fn test_cone_falloff() {
+        let deg = |a : f32| a * PI / 180f32;
+        let light = SpotLight::new(Color::white(), deg(30f32), deg(20f32)).look_at(&Point::origin(), &Point::new(0f32, -1f32, 0f32), &Vector::unit_z()).unwrap();
+        let li = |angle : f32| {
+            let p = Point::new(deg(angle).sin(), -deg(angle).cos(), 0f32);
+            let context = SurfaceContext::new(p, Normal::new(0f32, 1f32, 0f32), (0f32, 0f32), (Vector::unit_x(), Vector::unit_z()), (Normal::zero(), Normal::zero()));
+            light.sample_li(&context, (0.5f32, 0.5f32)).map_or(0f32, |s| s.li.r)
+        };
+
+        assert_eq!(li(0f32), 1f32);
+        assert_eq!(li(19f32), 1f32);
+        assert!(li(25f32) > 0f32 && li(25f32) < 1f32);
+        assert!(li(28f32) < li(25f32));
+        assert_eq!(li(31f32), 0f32);
+    }
+}
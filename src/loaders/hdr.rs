@@ -0,0 +1,43 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+use image::hdr::HDRDecoder;
+
+use crate::color::Color;
+use crate::loaders::LoadError;
+
+// A Radiance .hdr image, as its width, height and linear texels a row at a
+// time from the top.
+pub fn load_hdr(path : &Path) -> Result<(usize, usize, Vec<Color>), LoadError> {
+    parse_hdr(BufReader::new(File::open(path)?))
+}
+
+pub fn parse_hdr<R : BufRead>(reader : R) -> Result<(usize, usize, Vec<Color>), LoadError> {
+    let invalid = |e : image::ImageError| io::Error::new(io::ErrorKind::InvalidData, e.to_string());
+    let decoder = HDRDecoder::new(reader).map_err(invalid)?;
+    let meta = decoder.metadata();
+    let pixels = decoder.read_image_hdr().map_err(invalid)?;
+    let texels = pixels.iter().map(|p| Color::new(p.data[0], p.data[1], p.data[2])).collect();
+    Ok((meta.width as usize, meta.height as usize, texels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+    use image::hdr::HDREncoder;
+
+    #[test]
+    fn test_parse_hdr() {
+        let pixels : Vec<Rgb<f32>> = (0..6).map(|i| Rgb { data: [i as f32, 0.5f32, 2f32] }).collect();
+        let mut bytes = Vec::new();
+        HDREncoder::new(&mut bytes).encode(&pixels, 3, 2).unwrap();
+
+        let (w, h, texels) = parse_hdr(&bytes[..]).unwrap();
+        assert_eq!((w, h), (3, 2));
+        assert_eq!(texels[4], Color::new(4f32, 0.5f32, 2f32));
+
+        assert!(parse_hdr(&b"not an image"[..]).is_err());
+    }
+}
@@ -0,0 +1,31 @@
+use std::fmt::{Display, Formatter, Result};
+use std::io;
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Parse { line : usize, message : String },
+}
+
+impl LoadError {
+    pub fn parse<S : Into<String>>(line : usize, message : S) -> LoadError {
+        LoadError::Parse { line: line, message: message.into() }
+    }
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f : &mut Formatter) -> Result {
+        match self {
+            LoadError::Io(e) => write!(f, "{}", e),
+            LoadError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for LoadError { }
+
+impl From<io::Error> for LoadError {
+    fn from(e : io::Error) -> LoadError {
+        LoadError::Io(e)
+    }
+}
@@ -0,0 +1,15 @@
+pub mod hdr;
+pub mod load_error;
+pub mod obj;
+pub mod pbrt;
+pub mod ply;
+pub mod scene_file;
+pub mod voxels;
+
+pub use hdr::*;
+pub use load_error::*;
+pub use obj::*;
+pub use pbrt::*;
+pub use ply::*;
+pub use scene_file::*;
+pub use voxels::*;
@@ -0,0 +1,230 @@
+use log::*;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::geometry::{Point, Normal};
+use crate::loaders::LoadError;
+use crate::shapes::TriangleMesh;
+
+// One object or group from an OBJ file, with its own compacted vertex buffers.
+pub struct ObjObject {
+    pub name : String,
+    pub mesh : Arc<TriangleMesh>,
+}
+
+pub fn load_obj(path : &Path) -> Result<Vec<ObjObject>, LoadError> {
+    parse_obj(BufReader::new(File::open(path)?))
+}
+
+// (position, texture coordinate, normal) indices into the file-wide arrays
+type VertexRef = (usize, Option<usize>, Option<usize>);
+
+struct ObjBuilder {
+    p          : Vec<Point>,
+    uv         : Vec<(f32, f32)>,
+    n          : Vec<Normal>,
+    objects    : Vec<ObjObject>,
+    object     : Option<String>,
+    group      : Option<String>,
+    faces      : Vec<[VertexRef; 3]>,
+}
+
+impl ObjBuilder {
+    fn new() -> ObjBuilder {
+        ObjBuilder {
+            p:       Vec::new(),
+            uv:      Vec::new(),
+            n:       Vec::new(),
+            objects: Vec::new(),
+            object:  None,
+            group:   None,
+            faces:   Vec::new(),
+        }
+    }
+
+    fn name(&self) -> String {
+        match (&self.object, &self.group) {
+            (Some(o), Some(g)) if o != g => format!("{}/{}", o, g),
+            (Some(o), _)                 => o.clone(),
+            (None, Some(g))              => g.clone(),
+            (None, None)                 => String::from("default"),
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.faces.is_empty() {
+            return;
+        }
+
+        let has_uv = self.faces.iter().all(|f| f.iter().all(|v| v.1.is_some()));
+        let has_n  = self.faces.iter().all(|f| f.iter().all(|v| v.2.is_some()));
+
+        let mut remap : HashMap<VertexRef, usize> = HashMap::new();
+        let mut indices = Vec::with_capacity(self.faces.len() * 3);
+        let mut p  = Vec::new();
+        let mut uv = Vec::new();
+        let mut n  = Vec::new();
+
+        for f in self.faces.iter() {
+            for v in f.iter() {
+                let key = (v.0, if has_uv { v.1 } else { None }, if has_n { v.2 } else { None });
+                let ix = *remap.entry(key).or_insert_with(|| {
+                    p.push(self.p[key.0]);
+                    if let Some(t) = key.1 { uv.push(self.uv[t]); }
+                    if let Some(m) = key.2 { n.push(self.n[m]); }
+                    p.len() - 1
+                });
+                indices.push(ix);
+            }
+        }
+
+        let mesh = TriangleMesh::new(indices, p, if has_n { Some(n) } else { None }, if has_uv { Some(uv) } else { None });
+        self.objects.push(ObjObject { name: self.name(), mesh: Arc::new(mesh) });
+        self.faces.clear();
+    }
+}
+
+pub fn parse_obj<R : BufRead>(reader : R) -> Result<Vec<ObjObject>, LoadError> {
+    let mut b = ObjBuilder::new();
+
+    for (ix, line) in reader.lines().enumerate() {
+        let line_no = ix + 1;
+        let line = line?;
+        let line = match line.find('#') {
+            Some(c) => &line[..c],
+            None    => &line[..],
+        };
+
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(k) => k,
+            None    => continue,
+        };
+        let args : Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => {
+                let c = parse_floats(&args, 3, 4, line_no)?;
+                b.p.push(Point::new(c[0], c[1], c[2]));
+            },
+            "vt" => {
+                let c = parse_floats(&args, 1, 3, line_no)?;
+                b.uv.push((c[0], if c.len() > 1 { c[1] } else { 0f32 }));
+            },
+            "vn" => {
+                let c = parse_floats(&args, 3, 3, line_no)?;
+                b.n.push(Normal::new(c[0], c[1], c[2]));
+            },
+            "f" => {
+                if args.len() < 3 {
+                    return Err(LoadError::parse(line_no, format!("face needs at least 3 vertices, found {}", args.len())));
+                }
+                let vs = args.iter().map(|a| parse_vertex(a, &b, line_no)).collect::<Result<Vec<VertexRef>, LoadError>>()?;
+                for i in 1..(vs.len() - 1) {
+                    b.faces.push([vs[0], vs[i], vs[i + 1]]);
+                }
+            },
+            "o" => {
+                b.flush();
+                b.object = Some(args.join(" "));
+                b.group = None;
+            },
+            "g" => {
+                b.flush();
+                b.group = if args.is_empty() { None } else { Some(args.join(" ")) };
+            },
+            "s" | "usemtl" | "mtllib" | "l" | "p" => { },
+            _ => warn!("obj line {}: ignoring unsupported statement '{}'", line_no, keyword),
+        }
+    }
+
+    b.flush();
+    Ok(b.objects)
+}
+
+fn parse_floats(args : &[&str], min : usize, max : usize, line_no : usize) -> Result<Vec<f32>, LoadError> {
+    if args.len() < min || args.len() > max {
+        return Err(LoadError::parse(line_no, format!("expected {} to {} values, found {}", min, max, args.len())));
+    }
+    args.iter().map(|a| {
+        a.parse::<f32>().map_err(|_| LoadError::parse(line_no, format!("invalid number '{}'", a)))
+    }).collect()
+}
+
+fn parse_vertex(s : &str, b : &ObjBuilder, line_no : usize) -> Result<VertexRef, LoadError> {
+    let mut parts = s.split('/');
+    let p = parse_index(parts.next(), b.p.len(), "position", line_no)?;
+    let uv = match parts.next() {
+        None | Some("") => None,
+        t               => Some(parse_index(t, b.uv.len(), "texture coordinate", line_no)?),
+    };
+    let n = match parts.next() {
+        None | Some("") => None,
+        t               => Some(parse_index(t, b.n.len(), "normal", line_no)?),
+    };
+    if parts.next().is_some() {
+        return Err(LoadError::parse(line_no, format!("invalid face vertex '{}'", s)));
+    }
+    Ok((p, uv, n))
+}
+
+// OBJ indices are 1-based, and negative indices count back from the most recently defined element.
+fn parse_index(s : Option<&str>, count : usize, what : &str, line_no : usize) -> Result<usize, LoadError> {
+    let s = s.unwrap_or("");
+    let i = s.parse::<i64>().map_err(|_| LoadError::parse(line_no, format!("invalid {} index '{}'", what, s)))?;
+    let ix = if i > 0 { i - 1 } else { count as i64 + i };
+    if i == 0 || ix < 0 || ix >= count as i64 {
+        Err(LoadError::parse(line_no, format!("{} index {} out of range ({} defined)", what, i, count)))
+    } else {
+        Ok(ix as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_groups_and_ngons() {
+        let src = "# two objects\n\
+                   v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+                   vt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\n\
+                   vn 0 0 1\n\
+                   o quad\n\
+                   f 1/1/1 2/2/1 3/3/1 4/4/1\n\
+                   g back\n\
+                   f -1 -2 -3\n";
+        let objects = parse_obj(src.as_bytes()).unwrap();
+
+        assert_eq!(objects.len(), 2);
+        assert_eq!(objects[0].name, "quad");
+        assert_eq!(objects[0].mesh.triangle_count(), 2);
+        assert_eq!(objects[0].mesh.p.len(), 4);
+        assert!(objects[0].mesh.uv.is_some());
+        assert!(objects[0].mesh.n.is_some());
+
+        assert_eq!(objects[1].name, "quad/back");
+        assert_eq!(objects[1].mesh.triangle_count(), 1);
+        assert_eq!(objects[1].mesh.p, vec![Point::new(0f32, 1f32, 0f32), Point::new(1f32, 1f32, 0f32), Point::new(1f32, 0f32, 0f32)]);
+        assert!(objects[1].mesh.uv.is_none());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        match parse_obj("v 0 0 0\nv 1 0 0\nv 1 1 0\n\nf 1 2 4\n".as_bytes()) {
+            Err(LoadError::Parse { line, .. }) => assert_eq!(line, 5),
+            _ => panic!("expected a parse error"),
+        }
+        match parse_obj("v 0 0 zero\n".as_bytes()) {
+            Err(LoadError::Parse { line, .. }) => assert_eq!(line, 1),
+            _ => panic!("expected a parse error"),
+        }
+        match parse_obj("v 0 0 0\nv 1 0 0\nf 1 2\n".as_bytes()) {
+            Err(LoadError::Parse { line, .. }) => assert_eq!(line, 3),
+            _ => panic!("expected a parse error"),
+        }
+    }
+}
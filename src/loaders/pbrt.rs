@@ -0,0 +1,1082 @@
+// Imports the subset of the pbrt-v3 scene format that maps onto this renderer:
+// cameras, film, pixel filters, samplers, the transform and attribute stacks,
+// quadric shapes and triangle meshes, point, spot, distant and infinite lights,
+// diffuse area lights, the materials with constant parameters that have equivalents
+// here, and Include.
+// Anything else is skipped with a warning.
+
+use log::*;
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::color::Color;
+use crate::filters::{Filter, BoxFilter, GaussianFilter, TriangleFilter, MitchellFilter, LanczosSincFilter, CachingFilter};
+use crate::geometry::{Matrix, Transform, Trans, Point, Vector, Normal};
+use crate::lights::{Light, PointLight, SpotLight, DistantLight, InfiniteAreaLight};
+use crate::loaders::{LoadError, SceneDescription, CameraDescription, CameraKind, load_hdr, load_ply};
+use crate::materials::{Material, MatteMaterial, MirrorMaterial, GlassMaterial, MetalMaterial, PlasticMaterial, DisneyMaterial, COPPER};
+use crate::sampler::Sampler2DKind;
+use crate::scene::Scene;
+use crate::shapes::{Shape, Sphere, Cylinder, Disc, Cone, Paraboloid, TriangleMesh};
+use crate::spectrum::blackbody_color;
+use crate::textures::{Texture, ConstantTexture};
+
+pub fn load_pbrt(path : &Path) -> Result<SceneDescription, LoadError> {
+    let base = path.parent().unwrap_or_else(|| Path::new(""));
+    let file = File::open(path)?;
+    let mut b = PbrtBuilder::new(base);
+    b.including.push(path.canonicalize()?);
+    b.parse(BufReader::new(file))?;
+    Ok(b.finish())
+}
+
+// Include and plymesh paths are resolved against base, the directory of the
+// top-level scene file, as pbrt does.
+pub fn parse_pbrt<R : BufRead>(reader : R, base : &Path) -> Result<SceneDescription, LoadError> {
+    let mut b = PbrtBuilder::new(base);
+    b.parse(reader)?;
+    Ok(b.finish())
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Num(f32),
+    Open,
+    Close,
+}
+
+fn tokenize_line(line : &str, line_no : usize, tokens : &mut Vec<(usize, Token)>) -> Result<(), LoadError> {
+    let mut chars = line.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        match c {
+            '#' => break,
+            '[' => tokens.push((line_no, Token::Open)),
+            ']' => tokens.push((line_no, Token::Close)),
+            '"' => {
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        None           => return Err(LoadError::parse(line_no, "unterminated string")),
+                        Some((_, '"')) => break,
+                        Some((_, c))   => s.push(c),
+                    }
+                }
+                tokens.push((line_no, Token::Str(s)));
+            },
+            c if c.is_whitespace() => { },
+            _ => {
+                let mut end = line.len();
+                while let Some(&(ix, c)) = chars.peek() {
+                    if c.is_whitespace() || c == '[' || c == ']' || c == '"' || c == '#' {
+                        end = ix;
+                        break;
+                    }
+                    chars.next();
+                }
+                let word = &line[start..end];
+                tokens.push((line_no, match word.parse::<f32>() {
+                    Ok(n)  => Token::Num(n),
+                    Err(_) => Token::Ident(String::from(word)),
+                }));
+            },
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Clone, Debug)]
+enum Value {
+    Num(f32),
+    Str(String),
+}
+
+struct Param {
+    ty     : String,
+    name   : String,
+    values : Vec<Value>,
+    used   : bool,
+}
+
+// The "type name" [ values ] list that follows most directives.
+struct ParamSet {
+    line_no : usize,
+    params  : Vec<Param>,
+}
+
+impl ParamSet {
+    fn find(&mut self, name : &str) -> Option<&mut Param> {
+        let p = self.params.iter_mut().find(|p| p.name == name)?;
+        p.used = true;
+        Some(p)
+    }
+
+    fn floats(&mut self, name : &str) -> Result<Option<Vec<f32>>, LoadError> {
+        let line_no = self.line_no;
+        match self.find(name) {
+            None    => Ok(None),
+            Some(p) => p.values.iter().map(|v| match v {
+                Value::Num(n) => Ok(*n),
+                Value::Str(s) => Err(LoadError::parse(line_no, format!("parameter '{}' expects numbers, found \"{}\"", name, s))),
+            }).collect::<Result<Vec<f32>, LoadError>>().map(Some),
+        }
+    }
+
+    // A single number.  Like colors, parameters bound to textures fall back to
+    // the default.
+    fn float(&mut self, name : &str, default : f32) -> Result<f32, LoadError> {
+        Ok(self.optional_float(name)?.unwrap_or(default))
+    }
+
+    // As float(), for parameters whose absence means something other than a
+    // fixed default.
+    fn optional_float(&mut self, name : &str) -> Result<Option<f32>, LoadError> {
+        if self.params.iter().any(|p| p.name == name && p.ty == "texture") {
+            self.find(name);
+            warn!("pbrt line {}: using the default for unsupported parameter 'texture {}'", self.line_no, name);
+            return Ok(None);
+        }
+        match self.floats(name)? {
+            None                        => Ok(None),
+            Some(ref v) if v.len() == 1 => Ok(Some(v[0])),
+            Some(v) => Err(LoadError::parse(self.line_no, format!("parameter '{}' expects one value, found {}", name, v.len()))),
+        }
+    }
+
+    fn int(&mut self, name : &str, default : i32) -> Result<i32, LoadError> {
+        let v = self.float(name, default as f32)?;
+        if v.fract() != 0f32 {
+            return Err(LoadError::parse(self.line_no, format!("parameter '{}' expects an integer, found {}", name, v)));
+        }
+        Ok(v as i32)
+    }
+
+    fn string(&mut self, name : &str) -> Result<Option<String>, LoadError> {
+        let line_no = self.line_no;
+        match self.find(name) {
+            None    => Ok(None),
+            Some(p) => match p.values.as_slice() {
+                [Value::Str(s)] => Ok(Some(s.clone())),
+                _ => Err(LoadError::parse(line_no, format!("parameter '{}' expects a single string", name))),
+            },
+        }
+    }
+
+    // An "rgb" or "color" parameter, or a "blackbody" one giving a
+    // temperature and a scale.  Other spectrum types and textures fall back to
+    // the default.
+    fn color(&mut self, name : &str, default : Color) -> Result<Color, LoadError> {
+        let ty = match self.params.iter().find(|p| p.name == name) {
+            None    => return Ok(default),
+            Some(p) => p.ty.clone(),
+        };
+        if ty == "blackbody" {
+            return match self.floats(name)?.as_deref() {
+                Some([t])        => Ok(blackbody_color(*t)),
+                Some([t, scale]) => Ok(blackbody_color(*t) * *scale),
+                _ => Err(LoadError::parse(self.line_no, format!("parameter '{}' expects a temperature and a scale", name))),
+            };
+        }
+        if ty != "rgb" && ty != "color" {
+            self.find(name);
+            warn!("pbrt line {}: using the default for unsupported parameter '{} {}'", self.line_no, ty, name);
+            return Ok(default);
+        }
+        match self.floats(name)? {
+            Some(ref v) if v.len() == 3 => Ok(Color::new(v[0], v[1], v[2])),
+            _ => Err(LoadError::parse(self.line_no, format!("parameter '{}' expects three values", name))),
+        }
+    }
+
+    fn point(&mut self, name : &str, default : Point) -> Result<Point, LoadError> {
+        match self.floats(name)? {
+            None                        => Ok(default),
+            Some(ref v) if v.len() == 3 => Ok(Point::new(v[0], v[1], v[2])),
+            Some(_) => Err(LoadError::parse(self.line_no, format!("parameter '{}' expects three values", name))),
+        }
+    }
+
+    fn bool(&mut self, name : &str, default : bool) -> Result<bool, LoadError> {
+        match self.string(name)?.as_ref().map(|s| &s[..]) {
+            None          => Ok(default),
+            Some("true")  => Ok(true),
+            Some("false") => Ok(false),
+            Some(s) => Err(LoadError::parse(self.line_no, format!("parameter '{}' expects true or false, found \"{}\"", name, s))),
+        }
+    }
+
+    fn warn_unused(&self, directive : &str) {
+        for p in self.params.iter().filter(|p| !p.used) {
+            warn!("pbrt line {}: ignoring unsupported {} parameter '{} {}'", self.line_no, directive, p.ty, p.name);
+        }
+    }
+}
+
+struct Directive {
+    name    : String,
+    line_no : usize,
+    args    : Vec<Token>,
+}
+
+impl Directive {
+    fn error<S : Into<String>>(&self, message : S) -> LoadError {
+        LoadError::parse(self.line_no, message)
+    }
+
+    // All arguments as one flat list of numbers, brackets optional.
+    fn numbers(&self, count : usize) -> Result<Vec<f32>, LoadError> {
+        let mut v = Vec::with_capacity(count);
+        for t in self.args.iter() {
+            match t {
+                Token::Num(n)              => v.push(*n),
+                Token::Open | Token::Close => { },
+                _ => return Err(self.error(format!("{} expects numbers", self.name))),
+            }
+        }
+        if v.len() != count {
+            return Err(self.error(format!("{} expects {} values, found {}", self.name, count, v.len())));
+        }
+        Ok(v)
+    }
+
+    fn string(&self) -> Result<String, LoadError> {
+        match self.args.as_slice() {
+            [Token::Str(s)] => Ok(s.clone()),
+            _ => Err(self.error(format!("{} expects a single string", self.name))),
+        }
+    }
+
+    // A type string followed by a parameter list, as in Shape "sphere" "float radius" 2.
+    fn typed_params(&self) -> Result<(String, ParamSet), LoadError> {
+        match self.args.first() {
+            Some(Token::Str(ty)) => Ok((ty.clone(), self.params(1)?)),
+            _ => Err(self.error(format!("{} expects a type string", self.name))),
+        }
+    }
+
+    fn params(&self, start : usize) -> Result<ParamSet, LoadError> {
+        let mut ps = ParamSet { line_no: self.line_no, params: Vec::new() };
+        let mut tokens = self.args[start..].iter();
+
+        while let Some(t) = tokens.next() {
+            let decl = match t {
+                Token::Str(s) => s,
+                _ => return Err(self.error(format!("expected a parameter declaration, found {:?}", t))),
+            };
+            let parts : Vec<&str> = decl.split_whitespace().collect();
+            if parts.len() != 2 {
+                return Err(self.error(format!("invalid parameter declaration \"{}\"", decl)));
+            }
+
+            let mut values = Vec::new();
+            match tokens.next() {
+                Some(Token::Num(n)) => values.push(Value::Num(*n)),
+                Some(Token::Str(s)) => values.push(Value::Str(s.clone())),
+                Some(Token::Open)   => loop {
+                    match tokens.next() {
+                        Some(Token::Num(n))   => values.push(Value::Num(*n)),
+                        Some(Token::Str(s))   => values.push(Value::Str(s.clone())),
+                        Some(Token::Ident(s)) => values.push(Value::Str(s.clone())),
+                        Some(Token::Close)    => break,
+                        _ => return Err(self.error(format!("unterminated value list for parameter \"{}\"", decl))),
+                    }
+                },
+                _ => return Err(self.error(format!("missing value for parameter \"{}\"", decl))),
+            }
+
+            ps.params.push(Param { ty: String::from(parts[0]), name: String::from(parts[1]), values: values, used: false });
+        }
+
+        Ok(ps)
+    }
+}
+
+const UNSUPPORTED : &[&str] = &[
+    "Accelerator", "ActiveTransform", "ColorSpace", "Integrator",
+    "MakeNamedMaterial", "MakeNamedMedium", "MediumInterface", "NamedMaterial",
+    "ObjectBegin", "ObjectEnd", "ObjectInstance", "Option", "ReverseOrientation", "Texture", "TransformTimes",
+];
+
+// The material and area light emission that AttributeBegin saves.
+type Attributes = (Arc<dyn Material>, Option<(Color, bool)>);
+
+struct PbrtBuilder<'a> {
+    base     : &'a Path,
+    scene    : Scene,
+    film     : (u32, u32),
+    filter   : Option<CachingFilter>,
+    sampler  : Option<Sampler2DKind>,
+    camera   : Option<CameraDescription>,
+    ctm      : Transform,
+    material : Arc<dyn Material>,
+    // the radiance, and two-sidedness, of the shapes that follow an AreaLightSource
+    emission : Option<(Color, bool)>,
+    // TransformBegin saves only the CTM, AttributeBegin the material and
+    // emission as well
+    stack    : Vec<(Transform, Option<Attributes>)>,
+    named    : HashMap<String, Transform>,
+    // the files being parsed, outermost first, so that a file which ends up
+    // including itself is reported rather than recursed into forever
+    including : Vec<PathBuf>,
+}
+
+impl<'a> PbrtBuilder<'a> {
+    fn new(base : &'a Path) -> PbrtBuilder<'a> {
+        PbrtBuilder {
+            base:    base,
+            scene:   Scene::new(),
+            film:    (640, 480),
+            filter:  None,
+            sampler: None,
+            camera:  None,
+            ctm:     Transform::identity(),
+            material: Arc::new(MatteMaterial::default()),
+            emission: None,
+            stack:   Vec::new(),
+            named:   HashMap::new(),
+            including: Vec::new(),
+        }
+    }
+
+    fn parse<R : BufRead>(&mut self, reader : R) -> Result<(), LoadError> {
+        let mut tokens = Vec::new();
+        for (ix, line) in reader.lines().enumerate() {
+            tokenize_line(&line?, ix + 1, &mut tokens)?;
+        }
+
+        let mut tokens = tokens.into_iter().peekable();
+        while let Some((line_no, t)) = tokens.next() {
+            let name = match t {
+                Token::Ident(name) => name,
+                t => return Err(LoadError::parse(line_no, format!("expected a directive, found {:?}", t))),
+            };
+
+            // arguments run up to the next bare word outside of brackets
+            let mut args = Vec::new();
+            let mut depth = 0;
+            while let Some((_, t)) = tokens.peek() {
+                match t {
+                    Token::Ident(_) if depth == 0 => break,
+                    Token::Open  => depth += 1,
+                    Token::Close => depth -= 1,
+                    _            => { },
+                }
+                args.push(tokens.next().unwrap().1);
+            }
+
+            self.directive(&Directive { name: name, line_no: line_no, args: args })?;
+        }
+
+        Ok(())
+    }
+
+    fn directive(&mut self, d : &Directive) -> Result<(), LoadError> {
+        match &d.name[..] {
+            "Identity" => {
+                d.numbers(0)?;
+                self.ctm = Transform::identity();
+            },
+            "Translate" => {
+                let v = d.numbers(3)?;
+                self.ctm = self.ctm + Transform::translation(&Vector::new(v[0], v[1], v[2]));
+            },
+            "Scale" => {
+                let v = d.numbers(3)?;
+                self.ctm = self.ctm + Transform::scaling(&Vector::new(v[0], v[1], v[2]));
+            },
+            "Rotate" => {
+                let v = d.numbers(4)?;
+                let axis = Vector::new(v[1], v[2], v[3]);
+                if axis.magnitude() == 0f32 {
+                    return Err(d.error("Rotate axis must be non-zero"));
+                }
+                self.ctm = self.ctm + Transform::rotation(v[0] * PI / 180f32, &axis.normalize());
+            },
+            "LookAt" => {
+                let v = d.numbers(9)?;
+                // pbrt's LookAt is the world-to-camera transform
+                let t = Transform::look_at(&Point::new(v[0], v[1], v[2]), &Point::new(v[3], v[4], v[5]), &Vector::new(v[6], v[7], v[8]))
+                    .ok_or_else(|| d.error("degenerate LookAt"))?;
+                self.ctm = self.ctm + t.inverse();
+            },
+            "Transform" | "ConcatTransform" => {
+                let t = transform_from_pbrt(&d.numbers(16)?).ok_or_else(|| d.error("transform matrix is singular or not finite"))?;
+                self.ctm = if d.name == "Transform" { t } else { self.ctm + t };
+            },
+            "CoordinateSystem" => {
+                self.named.insert(d.string()?, self.ctm);
+            },
+            "CoordSysTransform" => {
+                let name = d.string()?;
+                match self.named.get(&name) {
+                    Some(t) => self.ctm = *t,
+                    None    => warn!("pbrt line {}: unknown coordinate system \"{}\"", d.line_no, name),
+                }
+            },
+            "AttributeBegin" | "TransformBegin" => {
+                d.numbers(0)?;
+                let attributes = if d.name == "AttributeBegin" { Some((self.material.clone(), self.emission)) } else { None };
+                self.stack.push((self.ctm, attributes));
+            },
+            "AttributeEnd" | "TransformEnd" => {
+                d.numbers(0)?;
+                let (ctm, attributes) = self.stack.pop().ok_or_else(|| d.error(format!("{} without a matching begin", d.name)))?;
+                self.ctm = ctm;
+                if let Some((m, e)) = attributes {
+                    self.material = m;
+                    self.emission = e;
+                }
+            },
+            "WorldBegin" => {
+                d.numbers(0)?;
+                self.ctm = Transform::identity();
+                self.named.insert(String::from("world"), self.ctm);
+            },
+            "WorldEnd" => {
+                d.numbers(0)?;
+            },
+            "Camera" => {
+                let (ty, mut ps) = d.typed_params()?;
+                let kind = match &ty[..] {
+                    "perspective" => {
+                        let fov = ps.float("fov", 90f32)? * PI / 180f32;
+                        let lens_radius = ps.float("lensradius", 0f32)?;
+                        let focal_distance = ps.float("focaldistance", 1e6f32)?;
+                        if lens_radius > 0f32 {
+                            Some(CameraKind::PerspectiveLens { fov_y: fov, lens_radius: lens_radius, focal_distance: focal_distance })
+                        } else {
+                            Some(CameraKind::Perspective { fov_y: fov })
+                        }
+                    },
+                    "orthographic" => Some(CameraKind::Orthographic { scale: 1f32 }),
+                    "environment"  => Some(CameraKind::Sphere),
+                    _              => None,
+                };
+                match kind {
+                    None => warn!("pbrt line {}: ignoring unsupported {} camera", d.line_no, ty),
+                    Some(kind) => {
+                        ps.warn_unused("Camera");
+                        // the CTM maps world space to camera space; ours go the other way
+                        self.named.insert(String::from("camera"), self.ctm.inverse());
+                        self.camera = Some(CameraDescription { kind: kind, transform: self.ctm.inverse() });
+                    },
+                }
+            },
+            "Film" => {
+                let (ty, mut ps) = d.typed_params()?;
+                if ty != "image" {
+                    warn!("pbrt line {}: treating {} film as \"image\"", d.line_no, ty);
+                }
+                let (w, h) = (ps.int("xresolution", 640)?, ps.int("yresolution", 480)?);
+                if w <= 0 || h <= 0 {
+                    return Err(d.error("film resolution must be positive"));
+                }
+                self.film = (w as u32, h as u32);
+                ps.warn_unused("Film");
+            },
+            "PixelFilter" => {
+                let (ty, mut ps) = d.typed_params()?;
+                if let Some(f) = make_filter(&ty, &mut ps)? {
+                    self.filter = Some(CachingFilter::new(f.as_ref()));
+                } else {
+                    warn!("pbrt line {}: ignoring unsupported {} filter", d.line_no, ty);
+                }
+                ps.warn_unused("PixelFilter");
+            },
+            "Sampler" => {
+                let (ty, mut ps) = d.typed_params()?;
+                self.sampler = make_sampler(&ty, &mut ps, d.line_no)?;
+                ps.warn_unused("Sampler");
+            },
+            "Material" => {
+                let (ty, mut ps) = d.typed_params()?;
+                self.material = make_material(&ty, &mut ps, d.line_no)?;
+                ps.warn_unused("Material");
+            },
+            "LightSource" => {
+                let (ty, mut ps) = d.typed_params()?;
+                if let Some(light) = make_light(&ty, &mut ps, &self.ctm, self.base, d.line_no)? {
+                    self.scene.add_light(light);
+                }
+                ps.warn_unused("LightSource");
+            },
+            "AreaLightSource" => {
+                let (ty, mut ps) = d.typed_params()?;
+                self.emission = match &ty[..] {
+                    "diffuse" => {
+                        let l = ps.color("L", Color::white())? * ps.color("scale", Color::white())?;
+                        Some((l, ps.bool("twosided", false)?))
+                    },
+                    _ => {
+                        warn!("pbrt line {}: ignoring unsupported {} area light", d.line_no, ty);
+                        None
+                    },
+                };
+                ps.warn_unused("AreaLightSource");
+            },
+            "Shape" => {
+                let (ty, mut ps) = d.typed_params()?;
+                self.shape(&ty, &mut ps, d)?;
+                ps.warn_unused("Shape");
+            },
+            "Include" => {
+                let path = self.base.join(d.string()?);
+                let file = File::open(&path).map_err(|e| d.error(format!("{}: {}", path.display(), e)))?;
+                let canonical = path.canonicalize().map_err(|e| d.error(format!("{}: {}", path.display(), e)))?;
+                if self.including.contains(&canonical) {
+                    return Err(d.error(format!("recursive Include of {}", path.display())));
+                }
+                self.including.push(canonical);
+                let result = self.parse(BufReader::new(file));
+                self.including.pop();
+                result.map_err(|e| d.error(format!("{}: {}", path.display(), e)))?;
+            },
+            name if UNSUPPORTED.contains(&name) => {
+                warn!("pbrt line {}: ignoring unsupported directive {}", d.line_no, name);
+            },
+            name => return Err(d.error(format!("unknown directive {}", name))),
+        }
+
+        Ok(())
+    }
+
+    fn add<S : Shape + Trans<Output=S> + 'static>(&mut self, s : S, offset : f32) {
+        let t = self.ctm + Transform::translation(&Vector::new(0f32, 0f32, offset));
+        let shape = Arc::new(s.transform(&t));
+        match self.emission {
+            None                 => self.scene.add(shape, self.material.clone()),
+            Some((l, two_sided)) => self.scene.add_area_light(shape, self.material.clone(), l, two_sided),
+        }
+    }
+
+    fn add_mesh(&mut self, mesh : &TriangleMesh) {
+        let mesh = Arc::new(mesh.transform(&self.ctm));
+        match self.emission {
+            None                 => self.scene.add_mesh(&mesh, self.material.clone()),
+            Some((l, two_sided)) => self.scene.add_mesh_area_light(&mesh, self.material.clone(), l, two_sided),
+        };
+    }
+
+    fn shape(&mut self, ty : &str, ps : &mut ParamSet, d : &Directive) -> Result<(), LoadError> {
+        let deg = |a : f32| a.clamp(0f32, 360f32) * PI / 180f32;
+
+        match ty {
+            "sphere" => {
+                let radius = ps.float("radius", 1f32)?;
+                let (z_min, z_max) = (ps.float("zmin", -radius)?, ps.float("zmax", radius)?);
+                let phi_max = deg(ps.float("phimax", 360f32)?);
+                self.add(Sphere::new_partial(radius, (z_min.min(z_max), z_min.max(z_max)), phi_max), 0f32);
+            },
+            "cylinder" => {
+                let radius = ps.float("radius", 1f32)?;
+                let (z_min, z_max) = (ps.float("zmin", -1f32)?, ps.float("zmax", 1f32)?);
+                let phi_max = deg(ps.float("phimax", 360f32)?);
+                // ours are centred on the origin
+                self.add(Cylinder::new_partial(radius, (z_max - z_min).abs(), phi_max), (z_min + z_max) / 2f32);
+            },
+            "disk" => {
+                let height = ps.float("height", 0f32)?;
+                let radius = ps.float("radius", 1f32)?;
+                let inner_radius = ps.float("innerradius", 0f32)?;
+                let phi_max = deg(ps.float("phimax", 360f32)?);
+                self.add(Disc::new_partial_annulus(inner_radius, radius, phi_max), height);
+            },
+            "cone" => {
+                let radius = ps.float("radius", 1f32)?;
+                let height = ps.float("height", 1f32)?;
+                let phi_max = deg(ps.float("phimax", 360f32)?);
+                self.add(Cone::new_partial(radius, height, 0f32, height, phi_max), 0f32);
+            },
+            "paraboloid" => {
+                let radius = ps.float("radius", 1f32)?;
+                let (z_min, z_max) = (ps.float("zmin", 0f32)?, ps.float("zmax", 1f32)?);
+                let phi_max = deg(ps.float("phimax", 360f32)?);
+                let (z_min, z_max) = (z_min.min(z_max), z_min.max(z_max));
+                self.add(Paraboloid::new_partial(radius, z_max, z_min, z_max, phi_max), 0f32);
+            },
+            "trianglemesh" => {
+                let mesh = make_mesh(ps, d)?;
+                self.add_mesh(&mesh);
+            },
+            "plymesh" => {
+                let file = ps.string("filename")?.ok_or_else(|| d.error("plymesh needs a filename"))?;
+                let path = self.base.join(file);
+                let mesh = load_ply(&path).map_err(|e| d.error(format!("{}: {}", path.display(), e)))?;
+                self.add_mesh(&mesh);
+            },
+            _ => warn!("pbrt line {}: ignoring unsupported {} shape", d.line_no, ty),
+        }
+
+        Ok(())
+    }
+
+    fn finish(self) -> SceneDescription {
+        let aspect_ratio = self.film.0 as f32 / self.film.1 as f32;
+
+        // pbrt's fov and screen window span the shorter image axis, where ours
+        // always span the vertical one
+        let camera = self.camera.map(|mut c| {
+            if aspect_ratio < 1f32 {
+                let widen = |fov : f32| 2f32 * ((fov / 2f32).tan() / aspect_ratio).atan();
+                c.kind = match c.kind {
+                    CameraKind::Perspective { fov_y } => CameraKind::Perspective { fov_y: widen(fov_y) },
+                    CameraKind::PerspectiveLens { fov_y, lens_radius, focal_distance } => CameraKind::PerspectiveLens { fov_y: widen(fov_y), lens_radius: lens_radius, focal_distance: focal_distance },
+                    CameraKind::Orthographic { scale } => CameraKind::Orthographic { scale: scale / aspect_ratio },
+                    k => k,
+                };
+            }
+            c
+        });
+
+        SceneDescription {
+            scene:   self.scene,
+            film:    Some(self.film),
+            filter:  self.filter,
+            sampler: self.sampler,
+            camera:  camera,
+        }
+    }
+}
+
+// pbrt lists matrices column by column.
+fn transform_from_pbrt(v : &[f32]) -> Option<Transform> {
+    let mut m = [0f32; 16];
+    m.copy_from_slice(v);
+    Transform::from_matrix(&Matrix::new(&m).transpose())
+}
+
+fn make_filter(ty : &str, ps : &mut ParamSet) -> Result<Option<Box<dyn Filter>>, LoadError> {
+    let default_width = match ty {
+        "box"  => 0.5f32,
+        "sinc" => 4f32,
+        _      => 2f32,
+    };
+    let (w, h) = (ps.float("xwidth", default_width)?, ps.float("ywidth", default_width)?);
+
+    Ok(match ty {
+        "box"      => Some(Box::new(BoxFilter::new(w, h))),
+        "triangle" => Some(Box::new(TriangleFilter::new(w, h))),
+        "gaussian" => Some(Box::new(GaussianFilter::new(w, h, ps.float("alpha", 2f32)?))),
+        "mitchell" => Some(Box::new(MitchellFilter::new(w, h, ps.float("B", 1f32 / 3f32)?, ps.float("C", 1f32 / 3f32)?))),
+        "sinc"     => Some(Box::new(LanczosSincFilter::new(w, h, ps.float("tau", 3f32)?))),
+        _          => None,
+    })
+}
+
+fn make_sampler(ty : &str, ps : &mut ParamSet, line_no : usize) -> Result<Option<Sampler2DKind>, LoadError> {
+    let count = |ps : &mut ParamSet, name : &str, default : i32| -> Result<usize, LoadError> {
+        let n = ps.int(name, default)?;
+        if n <= 0 {
+            return Err(LoadError::parse(line_no, format!("parameter '{}' must be positive", name)));
+        }
+        Ok(n as usize)
+    };
+
+    Ok(match ty {
+        "random"                        => Some(Sampler2DKind::Uniform(count(ps, "pixelsamples", 4)?)),
+        "halton"                        => Some(Sampler2DKind::Halton(count(ps, "pixelsamples", 16)?)),
+        "02sequence" | "lowdiscrepancy" => Some(Sampler2DKind::S02(count(ps, "pixelsamples", 16)?)),
+        "stratified" => {
+            let (w, h) = (count(ps, "xsamples", 4)?, count(ps, "ysamples", 4)?);
+            if ps.bool("jitter", true)? {
+                Some(Sampler2DKind::Strata(w, h))
+            } else {
+                Some(Sampler2DKind::StrataCenters(w, h))
+            }
+        },
+        "sobol" | "maxmindist" => {
+            warn!("pbrt line {}: using a (0,2) sequence in place of the {} sampler", line_no, ty);
+            Some(Sampler2DKind::S02(count(ps, "pixelsamples", 16)?))
+        },
+        _ => {
+            warn!("pbrt line {}: ignoring unsupported {} sampler", line_no, ty);
+            None
+        },
+    })
+}
+
+fn make_material(ty : &str, ps : &mut ParamSet, line_no : usize) -> Result<Arc<dyn Material>, LoadError> {
+    Ok(match ty {
+        "matte" => Arc::new(MatteMaterial::constant(ps.color("Kd", Color::gray(0.5f32))?)),
+        "mirror" => Arc::new(MirrorMaterial::constant(ps.color("Kr", Color::gray(0.9f32))?)),
+        "glass" => {
+            let (kr, kt) = (ps.color("Kr", Color::white())?, ps.color("Kt", Color::white())?);
+            let eta = match ps.floats("eta")? {
+                Some(_) => ps.float("eta", 1.5f32)?,
+                None    => ps.float("index", 1.5f32)?,
+            };
+            let (u, v) = roughnesses(ps, 0f32)?;
+            let remap = ps.bool("remaproughness", true)?;
+            Arc::new(GlassMaterial::new_rough(Arc::new(ConstantTexture::new(kr)), Arc::new(ConstantTexture::new(kt)), eta,
+                                              Arc::new(ConstantTexture::new(u)), Arc::new(ConstantTexture::new(v)), remap))
+        },
+        "metal" => {
+            let (eta, k) = (ps.color("eta", COPPER.0)?, ps.color("k", COPPER.1)?);
+            let (u, v) = roughnesses(ps, 0.01f32)?;
+            let remap = ps.bool("remaproughness", true)?;
+            Arc::new(MetalMaterial::new(Arc::new(ConstantTexture::new(eta)), Arc::new(ConstantTexture::new(k)),
+                                        Arc::new(ConstantTexture::new(u)), Arc::new(ConstantTexture::new(v)), remap))
+        },
+        "plastic" => {
+            let (kd, ks) = (ps.color("Kd", Color::gray(0.25f32))?, ps.color("Ks", Color::gray(0.25f32))?);
+            let roughness = ps.float("roughness", 0.1f32)?;
+            let remap = ps.bool("remaproughness", true)?;
+            Arc::new(PlasticMaterial::new(Arc::new(ConstantTexture::new(kd)), Arc::new(ConstantTexture::new(ks)), Arc::new(ConstantTexture::new(roughness)), remap))
+        },
+        "disney" => {
+            let mut m = DisneyMaterial::constant(ps.color("color", Color::gray(0.5f32))?);
+            let mut param = |name : &str, default : f32| -> Result<Arc<dyn Texture<f32>>, LoadError> {
+                Ok(Arc::new(ConstantTexture::new(ps.float(name, default)?)))
+            };
+            m.metallic = param("metallic", 0f32)?;
+            m.eta = param("eta", 1.5f32)?;
+            m.roughness = param("roughness", 0.5f32)?;
+            m.specular_tint = param("speculartint", 0f32)?;
+            m.anisotropic = param("anisotropic", 0f32)?;
+            m.sheen = param("sheen", 0f32)?;
+            m.sheen_tint = param("sheentint", 0.5f32)?;
+            m.clearcoat = param("clearcoat", 0f32)?;
+            m.clearcoat_gloss = param("clearcoatgloss", 1f32)?;
+            m.spec_trans = param("spectrans", 0f32)?;
+            m.flatness = param("flatness", 0f32)?;
+            m.diff_trans = param("difftrans", 1f32)?;
+            m.thin = ps.bool("thin", false)?;
+            if let Some(specular) = ps.optional_float("specular")? {
+                m.specular = Some(Arc::new(ConstantTexture::new(specular)));
+            }
+            Arc::new(m)
+        },
+        _ => {
+            warn!("pbrt line {}: using a matte material in place of unsupported {} material", line_no, ty);
+            Arc::new(MatteMaterial::constant(ps.color("Kd", Color::gray(0.5f32))?))
+        },
+    })
+}
+
+fn make_light(ty : &str, ps : &mut ParamSet, ctm : &Transform, base : &Path, line_no : usize) -> Result<Option<Box<dyn Light>>, LoadError> {
+    let scale = ps.color("scale", Color::white())?;
+    let (from, to) = (ps.point("from", Point::origin())?, ps.point("to", Point::new(0f32, 0f32, 1f32))?);
+    Ok(Some(match ty {
+        "point" => {
+            let i = ps.color("I", Color::white())? * scale;
+            Box::new(PointLight::new(i).translate(&(from - Point::origin())).transform(ctm))
+        },
+        "spot" => {
+            let i = ps.color("I", Color::white())? * scale;
+            let cone_angle = ps.float("coneangle", 30f32)?;
+            let cone_delta = ps.float("conedeltaangle", 5f32)?;
+            let light = SpotLight::new(i, cone_angle * PI / 180f32, (cone_angle - cone_delta) * PI / 180f32);
+            let light = light.look_at(&from, &to, &Vector::unit_y()).ok_or_else(|| LoadError::parse(line_no, "light \"from\" and \"to\" must differ"))?;
+            Box::new(light.transform(ctm))
+        },
+        "distant" => {
+            let l = ps.color("L", Color::white())? * scale;
+            let light = DistantLight::new(l).look_at(&from, &to, &Vector::unit_y()).ok_or_else(|| LoadError::parse(line_no, "light \"from\" and \"to\" must differ"))?;
+            Box::new(light.transform(ctm))
+        },
+        "infinite" => {
+            let l = ps.color("L", Color::white())? * scale;
+            let light = match ps.string("mapname")? {
+                None    => InfiniteAreaLight::constant(l),
+                Some(f) => {
+                    let path = base.join(f);
+                    let (w, h, texels) = load_hdr(&path).map_err(|e| LoadError::parse(line_no, format!("{}: {}", path.display(), e)))?;
+                    InfiniteAreaLight::new(l, w, h, texels)
+                },
+            };
+            // pbrt's maps have +z at the top and +x at the left edge, where
+            // ours have +y at the top and -z at the left edge
+            let z_up = transform_from_pbrt(&[0f32, -1f32, 0f32, 0f32,  0f32, 0f32, 1f32, 0f32,  -1f32, 0f32, 0f32, 0f32,  0f32, 0f32, 0f32, 1f32]).unwrap();
+            Box::new(light.transform(&(*ctm + z_up)))
+        },
+        _ => {
+            warn!("pbrt line {}: ignoring unsupported {} light", line_no, ty);
+            return Ok(None);
+        },
+    }))
+}
+
+// The "uroughness" and "vroughness" parameters, each defaulting to "roughness".
+fn roughnesses(ps : &mut ParamSet, default : f32) -> Result<(f32, f32), LoadError> {
+    let roughness = ps.float("roughness", default)?;
+    Ok((ps.float("uroughness", roughness)?, ps.float("vroughness", roughness)?))
+}
+
+fn make_mesh(ps : &mut ParamSet, d : &Directive) -> Result<TriangleMesh, LoadError> {
+    let p : Vec<Point> = match ps.floats("P")? {
+        Some(ref v) if v.len() % 3 == 0 && !v.is_empty() => v.chunks(3).map(|c| Point::new(c[0], c[1], c[2])).collect(),
+        _ => return Err(d.error("trianglemesh needs a \"point P\" list of xyz triples")),
+    };
+
+    let indices : Vec<usize> = match ps.floats("indices")? {
+        None if p.len() == 3 => vec![0, 1, 2],
+        None => return Err(d.error("trianglemesh needs \"integer indices\"")),
+        Some(v) => {
+            if v.len() % 3 != 0 {
+                return Err(d.error(format!("trianglemesh has {} indices, not a multiple of 3", v.len())));
+            }
+            let mut indices = Vec::with_capacity(v.len());
+            for i in v.into_iter() {
+                if i < 0f32 || i.fract() != 0f32 || i as usize >= p.len() {
+                    return Err(d.error(format!("trianglemesh index {} out of range ({} points)", i, p.len())));
+                }
+                indices.push(i as usize);
+            }
+            indices
+        },
+    };
+
+    let n = match ps.floats("N")? {
+        None => None,
+        Some(ref v) if v.len() == 3 * p.len() => Some(v.chunks(3).map(|c| Normal::new(c[0], c[1], c[2])).collect()),
+        Some(_) => return Err(d.error("trianglemesh \"normal N\" needs one normal per point")),
+    };
+
+    let uv = match ps.floats("uv")? {
+        Some(v) => Some(v),
+        None    => ps.floats("st")?,
+    };
+    let uv = match uv {
+        None => None,
+        Some(ref v) if v.len() == 2 * p.len() => Some(v.chunks(2).map(|c| (c[0], c[1])).collect()),
+        Some(_) => return Err(d.error("trianglemesh \"uv\" needs one pair per point")),
+    };
+
+    Ok(TriangleMesh::new(indices, p, n, uv))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+    use image::hdr::HDREncoder;
+    use crate::geometry::Ray;
+    use crate::shapes::SurfaceContext;
+    use crate::reflection::{BSDF_REFLECTION, BSDF_SPECULAR, BSDF_DIFFUSE, BSDF_GLOSSY, BSDF_ALL, schlick_r0_from_eta};
+
+    fn parse(src : &str) -> Result<SceneDescription, LoadError> {
+        parse_pbrt(src.as_bytes(), Path::new(""))
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let mut tokens = Vec::new();
+        tokenize_line("Shape \"sphere\" \"float radius\" [2.5]# comment", 7, &mut tokens).unwrap();
+        let tokens : Vec<Token> = tokens.into_iter().map(|(line, t)| {
+            assert_eq!(line, 7);
+            t
+        }).collect();
+        assert_eq!(tokens, vec![Token::Ident(String::from("Shape")), Token::Str(String::from("sphere")), Token::Str(String::from("float radius")), Token::Open, Token::Num(2.5f32), Token::Close]);
+    }
+
+    #[test]
+    fn test_parse_pbrt() {
+        let desc = parse("LookAt 0 0 -5  0 0 0  0 1 0\n\
+                          Camera \"perspective\" \"float fov\" [ 45 ]\n\
+                          Film \"image\" \"integer xresolution\" [200] \"integer yresolution\" [100]\n\
+                          PixelFilter \"gaussian\"\n\
+                          Sampler \"stratified\" \"integer xsamples\" 2 \"integer ysamples\" 3\n\
+                          WorldBegin\n\
+                          LightSource \"point\" \"rgb I\" [1 1 1]\n\
+                          AttributeBegin\n\
+                          \x20 Translate 0 0 10\n\
+                          \x20 Shape \"sphere\" \"float radius\" 2\n\
+                          AttributeEnd\n\
+                          Shape \"trianglemesh\" \"integer indices\" [0 1 2 0 2 3]\n\
+                          \x20 \"point P\" [-1 -1 3  1 -1 3  1 1 3  -1 1 3]\n\
+                          WorldEnd\n").unwrap();
+
+        assert_eq!(desc.film, Some((200, 100)));
+        assert!(desc.filter.is_some());
+        assert_eq!(desc.sampler, Some(Sampler2DKind::Strata(2, 3)));
+        assert_eq!(desc.scene.primitives.len(), 3);
+        assert_eq!(desc.scene.lights.len(), 1);
+
+        let camera = desc.camera.unwrap();
+        assert_eq!(camera.kind, CameraKind::Perspective { fov_y: PI / 4f32 });
+
+        let r = camera.build(2f32).cast(0f32, 0f32);
+        assert!((r.origin - Point::new(0f32, 0f32, -5f32)).magnitude() < 1e-5);
+        assert!((r.direction - Vector::new(0f32, 0f32, 1f32)).magnitude() < 1e-5);
+
+        // the quad at z = 3 hides the sphere behind it
+        let i = desc.scene.intersect(&r).unwrap();
+        assert!((i.time - 8f32).abs() < 1e-4);
+
+        // the sphere was translated, but the quad after AttributeEnd was not
+        let i = desc.scene.intersect(&Ray::new(&Point::new(3f32, 0f32, 10f32), &Vector::new(-1f32, 0f32, 0f32))).unwrap();
+        assert!((i.time - 1f32).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_lights() {
+        let mut desc = parse("AttributeBegin\n\
+                              \x20 Translate 0 5 0\n\
+                              \x20 LightSource \"point\" \"rgb I\" [10 10 10]\n\
+                              AttributeEnd\n\
+                              LightSource \"spot\" \"blackbody I\" [6500 2] \"point from\" [0 5 0] \"point to\" [0 0 0]\n\
+                              LightSource \"distant\" \"point from\" [0 1 0] \"point to\" [0 0 0] \"rgb L\" [2 2 2]\n\
+                              LightSource \"goniometric\"\n").unwrap();
+        desc.scene.preprocess();
+        assert_eq!(desc.scene.lights.len(), 3);
+
+        // all three shine straight down on a point below them
+        let context = SurfaceContext::new(Point::new(0f32, -1f32, 0f32), Normal::new(0f32, 1f32, 0f32), (0f32, 0f32), (Vector::unit_x(), Vector::unit_z()), (Normal::zero(), Normal::zero()));
+        let expected = [Color::gray(10f32 / 36f32), blackbody_color(6500f32) * (2f32 / 36f32), Color::gray(2f32)];
+        for (light, li) in desc.scene.lights.iter().zip(expected.iter()) {
+            let s = light.sample_li(&context, (0.5f32, 0.5f32)).unwrap();
+            assert!((s.wi - Vector::unit_y()).magnitude() < 1e-5f32);
+            assert_eq!(s.li, *li);
+        }
+    }
+
+    #[test]
+    fn test_infinite_lights() {
+        let pixels : Vec<Rgb<f32>> = (0..8).map(|i| Rgb { data: [i as f32, i as f32, i as f32] }).collect();
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("pbrt_test_{}.hdr", std::process::id()));
+        HDREncoder::new(File::create(&path).unwrap()).encode(&pixels, 4, 2).unwrap();
+
+        let src = format!("LightSource \"infinite\" \"string mapname\" \"{}\" \"rgb scale\" [2 2 2]\n", path.file_name().unwrap().to_str().unwrap());
+        let desc = parse_pbrt(src.as_bytes(), &dir).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // as in pbrt, u is the angle around +z from +x and v the angle from +z
+        let le = |d : Vector| desc.scene.lights[0].le(&Ray::new(&Point::origin(), &d));
+        assert_eq!(le(Vector::new(1f32, 0.1f32, -0.1f32)), Color::gray(8f32));
+        assert_eq!(le(Vector::new(-0.1f32, 0.1f32, 1f32)), Color::gray(2f32));
+        assert_eq!(le(Vector::new(0.1f32, -1f32, -0.1f32)), Color::gray(14f32));
+
+        let desc = parse("LightSource \"infinite\" \"rgb L\" [0.5 0.5 0.5]\n").unwrap();
+        assert_eq!(desc.scene.lights[0].le(&Ray::new(&Point::origin(), &Vector::unit_x())), Color::gray(0.5f32));
+    }
+
+    #[test]
+    fn test_recursive_include() {
+        let dir = std::env::temp_dir();
+        let name = |n : &str| format!("pbrt_test_{}_{}.pbrt", std::process::id(), n);
+        std::fs::write(dir.join(name("self")), format!("Include \"{}\"\n", name("self"))).unwrap();
+        std::fs::write(dir.join(name("a")), format!("\nInclude \"{}\"\n", name("b"))).unwrap();
+        std::fs::write(dir.join(name("b")), format!("Include \"{}\"\n", name("a"))).unwrap();
+
+        let self_include = load_pbrt(&dir.join(name("self")));
+        let mutual_include = parse_pbrt(format!("Include \"{}\"\n", name("a")).as_bytes(), &dir);
+        for n in ["self", "a", "b"].iter() {
+            std::fs::remove_file(dir.join(name(n))).unwrap();
+        }
+
+        match self_include {
+            Err(LoadError::Parse { line, message }) => assert!(line == 1 && message.contains("recursive Include"), "{}", message),
+            _ => panic!("expected a parse error"),
+        }
+        match mutual_include {
+            Err(LoadError::Parse { line, message }) => assert!(line == 1 && message.contains("recursive Include"), "{}", message),
+            _ => panic!("expected a parse error"),
+        }
+    }
+
+    #[test]
+    fn test_area_lights() {
+        let desc = parse("AttributeBegin\n\
+                          \x20 AreaLightSource \"diffuse\" \"rgb L\" [2 2 2] \"rgb scale\" [3 3 3] \"bool twosided\" \"true\"\n\
+                          \x20 Shape \"sphere\"\n\
+                          \x20 Shape \"trianglemesh\" \"point P\" [5 0 0  6 0 0  5 1 0]\n\
+                          AttributeEnd\n\
+                          Shape \"disk\" \"float height\" -3\n").unwrap();
+        assert_eq!(desc.scene.lights.len(), 2);
+        let emitters : Vec<Option<usize>> = desc.scene.primitives.iter().map(|p| p.light).collect();
+        assert_eq!(emitters, vec![Some(0), Some(1), None]);
+
+        // two-sided, so the triangle is seen from behind too
+        let r = Ray::new(&Point::new(5.2f32, 0.2f32, 5f32), &-Vector::unit_z());
+        let i = desc.scene.intersect(&r).unwrap();
+        assert_eq!(i.le(&desc.scene, &-r.direction), Color::gray(6f32));
+        let r = Ray::new(&Point::new(5.2f32, 0.2f32, -5f32), &Vector::unit_z());
+        let i = desc.scene.intersect(&r).unwrap();
+        assert_eq!(i.le(&desc.scene, &-r.direction), Color::gray(6f32));
+    }
+
+    #[test]
+    fn test_transform_matches_translate() {
+        let a = parse("Translate 1 2 3\nShape \"sphere\"\n").unwrap();
+        let b = parse("Transform [1 0 0 0  0 1 0 0  0 0 1 0  1 2 3 1]\nShape \"sphere\"\n").unwrap();
+        let (ba, bb) = (a.scene.primitives[0].bound, b.scene.primitives[0].bound);
+        assert!((ba.centroid() - Point::new(1f32, 2f32, 3f32)).magnitude() < 1e-5);
+        assert!((bb.centroid() - ba.centroid()).magnitude() < 1e-5);
+
+        // rotations follow pbrt's sense, taking +x towards +y about +z
+        let c = parse("Rotate 90 0 0 1\nTranslate 2 0 0\nShape \"sphere\" \"float radius\" 0.5\n").unwrap();
+        assert!((c.scene.primitives[0].bound.centroid() - Point::new(0f32, 2f32, 0f32)).magnitude() < 1e-5);
+    }
+
+    #[test]
+    fn test_materials() {
+        let desc = parse("AttributeBegin\n\
+                          \x20 Material \"mirror\"\n\
+                          \x20 Shape \"sphere\"\n\
+                          \x20 TransformBegin\n\
+                          \x20   Material \"glass\" \"float index\" 1.33\n\
+                          \x20   Translate 0 0 3\n\
+                          \x20 TransformEnd\n\
+                          \x20 Translate 0 0 6\n\
+                          \x20 Shape \"sphere\"\n\
+                          AttributeEnd\n\
+                          Translate 0 0 -3\n\
+                          Material \"matte\" \"rgb Kd\" [0.1 0.2 0.3]\n\
+                          Shape \"sphere\"\n\
+                          Translate 0 0 -4\n\
+                          Material \"metal\" \"float uroughness\" 0.1 \"float vroughness\" 0.3\n\
+                          Shape \"sphere\"\n\
+                          Translate 0 0 -4\n\
+                          Material \"plastic\" \"rgb Kd\" [0.5 0.1 0.1] \"bool remaproughness\" \"false\"\n\
+                          Shape \"sphere\"\n\
+                          Translate 0 0 -4\n\
+                          Material \"disney\" \"rgb color\" [0.9 0.6 0.2] \"float metallic\" 1 \"texture roughness\" \"rough\"\n\
+                          Shape \"sphere\"\n\
+                          Translate 0 0 -4\n\
+                          Material \"disney\" \"float specular\" 1\n\
+                          Shape \"sphere\"\n\
+                          Translate 0 0 -4\n\
+                          Material \"disney\" \"texture specular\" \"spec\"\n\
+                          Shape \"sphere\"\n").unwrap();
+
+        let hit = |z : f32| desc.scene.intersect(&Ray::new(&Point::new(0f32, 0f32, z), &Vector::new(0f32, 0f32, 1f32))).unwrap().bsdf(true);
+        assert_eq!(hit(-1.5f32).num_components(BSDF_REFLECTION | BSDF_SPECULAR), 1);
+        // TransformEnd keeps the material, AttributeEnd restores the one before
+        assert_eq!(hit(4f32).eta, 1.33f32);
+        assert_eq!(hit(-5f32).num_components(BSDF_DIFFUSE | BSDF_REFLECTION), 1);
+        assert_eq!(hit(-9f32).num_components(BSDF_GLOSSY | BSDF_REFLECTION), 1);
+        assert_eq!(hit(-13f32).num_components(BSDF_DIFFUSE | BSDF_GLOSSY | BSDF_REFLECTION), 2);
+        // a pure metal has only its specular lobe, and textures fall back to defaults
+        assert_eq!(hit(-17f32).num_components(BSDF_ALL), 1);
+        assert!((schlick_r0_from_eta(hit(-21f32).eta) - 0.08f32).abs() < 1e-5);
+        // a textured specular warns and follows eta as if it were absent
+        assert_eq!(hit(-25f32).eta, 1.5f32);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let line_of = |src : &str| match parse(src) {
+            Err(LoadError::Parse { line, .. }) => line,
+            _ => panic!("expected a parse error"),
+        };
+
+        assert_eq!(line_of("WorldBegin\nTranslate 1 2\n"), 2);
+        assert_eq!(line_of("WorldBegin\n\nAttributeEnd\n"), 3);
+        assert_eq!(line_of("Shape \"sphere\" \"float radius\" \"big\"\n"), 1);
+        assert_eq!(line_of("Shape \"trianglemesh\" \"integer indices\" [0 1 5] \"point P\" [0 0 0 1 0 0 0 1 0]\n"), 1);
+        assert_eq!(line_of("Film \"image\"\n\"integer xresolution\" [10\n"), 1);
+        assert_eq!(line_of("Frobnicate 1\n"), 1);
+        assert_eq!(line_of("\nInclude \"missing.pbrt\"\n"), 2);
+        assert_eq!(line_of("LookAt 0 0 0  0 0 0  0 1 0\n"), 1);
+        assert_eq!(line_of("Translate 1 0 0\nConcatTransform [1 0 0 0  0 1 0 0  0 0 1 0  0 0 0 nan]\n"), 2);
+        assert_eq!(line_of("WorldBegin\nLightSource \"spot\" \"point from\" [0 1 0] \"point to\" [0 1 0]\n"), 2);
+    }
+}
@@ -0,0 +1,386 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::geometry::{Point, Normal};
+use crate::loaders::LoadError;
+use crate::shapes::TriangleMesh;
+
+pub fn load_ply(path : &Path) -> Result<Arc<TriangleMesh>, LoadError> {
+    parse_ply(BufReader::new(File::open(path)?))
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum ScalarType {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64,
+}
+
+impl ScalarType {
+    fn parse(s : &str, line_no : usize) -> Result<ScalarType, LoadError> {
+        match s {
+            "char"   | "int8"    => Ok(ScalarType::Int8),
+            "uchar"  | "uint8"   => Ok(ScalarType::UInt8),
+            "short"  | "int16"   => Ok(ScalarType::Int16),
+            "ushort" | "uint16"  => Ok(ScalarType::UInt16),
+            "int"    | "int32"   => Ok(ScalarType::Int32),
+            "uint"   | "uint32"  => Ok(ScalarType::UInt32),
+            "float"  | "float32" => Ok(ScalarType::Float32),
+            "double" | "float64" => Ok(ScalarType::Float64),
+            _ => Err(LoadError::parse(line_no, format!("unknown property type '{}'", s))),
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            ScalarType::Int8    | ScalarType::UInt8  => 1,
+            ScalarType::Int16   | ScalarType::UInt16 => 2,
+            ScalarType::Int32   | ScalarType::UInt32 | ScalarType::Float32 => 4,
+            ScalarType::Float64 => 8,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Property {
+    Scalar { name : String, ty : ScalarType },
+    List { name : String, count_ty : ScalarType, item_ty : ScalarType },
+}
+
+#[derive(Clone, Debug)]
+struct Element {
+    name       : String,
+    count      : usize,
+    properties : Vec<Property>,
+}
+
+struct Header {
+    format   : Format,
+    elements : Vec<Element>,
+    lines    : usize,
+}
+
+fn parse_header<R : BufRead>(reader : &mut R) -> Result<Header, LoadError> {
+    let mut format = None;
+    let mut elements : Vec<Element> = Vec::new();
+    let mut line_no = 0;
+
+    loop {
+        let mut line = String::new();
+        line_no += 1;
+        if reader.read_line(&mut line)? == 0 {
+            return Err(LoadError::parse(line_no, "unexpected end of file in header"));
+        }
+
+        let tokens : Vec<&str> = line.split_whitespace().collect();
+        if line_no == 1 {
+            if tokens != ["ply"] {
+                return Err(LoadError::parse(line_no, "missing 'ply' magic number"));
+            }
+            continue;
+        }
+
+        match tokens.first() {
+            None => { },
+            Some(&"comment") | Some(&"obj_info") => { },
+            Some(&"format") => {
+                if tokens.len() != 3 {
+                    return Err(LoadError::parse(line_no, "expected 'format <type> <version>'"));
+                }
+                format = Some(match tokens[1] {
+                    "ascii"                => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian"    => Format::BinaryBigEndian,
+                    f => return Err(LoadError::parse(line_no, format!("unknown format '{}'", f))),
+                });
+            },
+            Some(&"element") => {
+                if tokens.len() != 3 {
+                    return Err(LoadError::parse(line_no, "expected 'element <name> <count>'"));
+                }
+                let count = tokens[2].parse::<usize>().map_err(|_| LoadError::parse(line_no, format!("invalid element count '{}'", tokens[2])))?;
+                elements.push(Element { name: String::from(tokens[1]), count: count, properties: Vec::new() });
+            },
+            Some(&"property") => {
+                let element = match elements.last_mut() {
+                    Some(e) => e,
+                    None    => return Err(LoadError::parse(line_no, "property declared before any element")),
+                };
+                let property = if tokens.len() == 5 && tokens[1] == "list" {
+                    Property::List { name: String::from(tokens[4]), count_ty: ScalarType::parse(tokens[2], line_no)?, item_ty: ScalarType::parse(tokens[3], line_no)? }
+                } else if tokens.len() == 3 {
+                    Property::Scalar { name: String::from(tokens[2]), ty: ScalarType::parse(tokens[1], line_no)? }
+                } else {
+                    return Err(LoadError::parse(line_no, "malformed property declaration"));
+                };
+                element.properties.push(property);
+            },
+            Some(&"end_header") => break,
+            Some(t) => return Err(LoadError::parse(line_no, format!("unexpected header keyword '{}'", t))),
+        }
+    }
+
+    match format {
+        None         => Err(LoadError::parse(line_no, "missing format declaration")),
+        Some(format) => Ok(Header { format: format, elements: elements, lines: line_no }),
+    }
+}
+
+// Reads successive property values from the body of the file, whatever its
+// encoding, and says where in it an error was found.
+trait ValueReader {
+    fn read(&mut self, ty : ScalarType) -> Result<f64, LoadError>;
+    fn error(&self, message : String) -> LoadError;
+}
+
+struct AsciiReader<R : BufRead> {
+    reader  : R,
+    tokens  : Vec<String>,
+    line_no : usize,
+}
+
+impl<R : BufRead> ValueReader for AsciiReader<R> {
+    fn read(&mut self, _ty : ScalarType) -> Result<f64, LoadError> {
+        while self.tokens.is_empty() {
+            let mut line = String::new();
+            self.line_no += 1;
+            if self.reader.read_line(&mut line)? == 0 {
+                return Err(LoadError::parse(self.line_no, "unexpected end of file"));
+            }
+            self.tokens = line.split_whitespace().rev().map(String::from).collect();
+        }
+        let t = self.tokens.pop().unwrap();
+        t.parse::<f64>().map_err(|_| LoadError::parse(self.line_no, format!("invalid number '{}'", t)))
+    }
+
+    fn error(&self, message : String) -> LoadError {
+        LoadError::parse(self.line_no, message)
+    }
+}
+
+// Binary data has no lines, so its errors are only located by the element and
+// index in their message.
+struct BinaryReader<R : Read> {
+    reader     : R,
+    big_endian : bool,
+}
+
+impl<R : Read> ValueReader for BinaryReader<R> {
+    fn read(&mut self, ty : ScalarType) -> Result<f64, LoadError> {
+        let mut b = [0u8; 8];
+        let n = ty.size();
+        self.reader.read_exact(&mut b[..n])?;
+        if self.big_endian {
+            b[..n].reverse();
+        }
+        Ok(match ty {
+            ScalarType::Int8    => (b[0] as i8) as f64,
+            ScalarType::UInt8   => b[0] as f64,
+            ScalarType::Int16   => i16::from_le_bytes([b[0], b[1]]) as f64,
+            ScalarType::UInt16  => u16::from_le_bytes([b[0], b[1]]) as f64,
+            ScalarType::Int32   => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            ScalarType::UInt32  => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            ScalarType::Float32 => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            ScalarType::Float64 => f64::from_le_bytes(b),
+        })
+    }
+
+    fn error(&self, message : String) -> LoadError {
+        io::Error::new(io::ErrorKind::InvalidData, message).into()
+    }
+}
+
+pub fn parse_ply<R : BufRead>(mut reader : R) -> Result<Arc<TriangleMesh>, LoadError> {
+    let header = parse_header(&mut reader)?;
+    match header.format {
+        Format::Ascii => {
+            let mut values = AsciiReader { reader: reader, tokens: Vec::new(), line_no: header.lines };
+            read_body(&header, &mut values)
+        },
+        f => {
+            let mut values = BinaryReader { reader: reader, big_endian: f == Format::BinaryBigEndian };
+            read_body(&header, &mut values)
+        },
+    }
+}
+
+fn read_body(header : &Header, values : &mut dyn ValueReader) -> Result<Arc<TriangleMesh>, LoadError> {
+    let mut p  : Vec<Point> = Vec::new();
+    let mut n  : Vec<Normal> = Vec::new();
+    let mut uv : Vec<(f32, f32)> = Vec::new();
+    let mut indices : Vec<usize> = Vec::new();
+    let vertex_count : usize = header.elements.iter().filter(|e| e.name == "vertex").map(|e| e.count).sum();
+
+    for element in header.elements.iter() {
+        let slot = |name : &str| -> Option<usize> {
+            element.properties.iter().position(|prop| match prop {
+                Property::Scalar { name: n, .. } => n == name,
+                _ => false,
+            })
+        };
+        let slots = |names : &[&str]| names.iter().map(|n| slot(n)).collect::<Option<Vec<usize>>>();
+
+        let position = slots(&["x", "y", "z"]);
+        let normal   = slots(&["nx", "ny", "nz"]);
+        let texcoord = slots(&["u", "v"]).or_else(|| slots(&["s", "t"])).or_else(|| slots(&["texture_u", "texture_v"]));
+        let face     = element.properties.iter().position(|prop| match prop {
+            Property::List { name, .. } => name == "vertex_indices" || name == "vertex_index",
+            _ => false,
+        });
+
+        let mut scalars = vec![0f64; element.properties.len()];
+        for i in 0..element.count {
+            let mut polygon : Vec<usize> = Vec::new();
+
+            for (ix, prop) in element.properties.iter().enumerate() {
+                match prop {
+                    Property::Scalar { ty, .. } => scalars[ix] = values.read(*ty)?,
+                    Property::List { count_ty, item_ty, .. } => {
+                        let count = values.read(*count_ty)?;
+                        if count < 0f64 {
+                            return Err(values.error(format!("{} {}: negative list length", element.name, i)));
+                        }
+                        for _ in 0..(count as usize) {
+                            let v = values.read(*item_ty)?;
+                            if Some(ix) == face {
+                                if v < 0f64 || v.fract() != 0f64 {
+                                    return Err(values.error(format!("{} {}: invalid vertex index {}", element.name, i, v)));
+                                }
+                                polygon.push(v as usize);
+                            }
+                        }
+                    },
+                }
+            }
+
+            if element.name == "vertex" {
+                match position {
+                    Some(ref s) => p.push(Point::new(scalars[s[0]] as f32, scalars[s[1]] as f32, scalars[s[2]] as f32)),
+                    None        => return Err(values.error(format!("vertex {}: missing x, y or z", i))),
+                }
+                if let Some(ref s) = normal {
+                    n.push(Normal::new(scalars[s[0]] as f32, scalars[s[1]] as f32, scalars[s[2]] as f32));
+                }
+                if let Some(ref s) = texcoord {
+                    uv.push((scalars[s[0]] as f32, scalars[s[1]] as f32));
+                }
+            } else if element.name == "face" && face.is_some() {
+                if polygon.len() < 3 {
+                    return Err(values.error(format!("face {}: only {} vertices", i, polygon.len())));
+                }
+                if let Some(v) = polygon.iter().find(|&&v| v >= vertex_count) {
+                    return Err(values.error(format!("face {}: references vertex {} but only {} are defined", i, v, vertex_count)));
+                }
+                for i in 1..(polygon.len() - 1) {
+                    indices.push(polygon[0]);
+                    indices.push(polygon[i]);
+                    indices.push(polygon[i + 1]);
+                }
+            }
+        }
+    }
+
+    let n  = if n.is_empty()  { None } else { Some(n) };
+    let uv = if uv.is_empty() { None } else { Some(uv) };
+    Ok(Arc::new(TriangleMesh::new(indices, p, n, uv)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER : &str = "element vertex 4\n\
+                           property float x\nproperty float y\nproperty float z\n\
+                           property float s\nproperty float t\n\
+                           element face 1\n\
+                           property list uchar int vertex_indices\n\
+                           end_header\n";
+
+    const VERTICES : [[f32; 5]; 4] = [[0f32, 0f32, 0f32, 0f32, 0f32], [1f32, 0f32, 0f32, 1f32, 0f32], [1f32, 1f32, 0f32, 1f32, 1f32], [0f32, 1f32, 0f32, 0f32, 1f32]];
+
+    fn check(mesh : &TriangleMesh) {
+        assert_eq!(mesh.triangle_count(), 2);
+        assert_eq!(mesh.indices, vec![0, 1, 2, 0, 2, 3]);
+        assert_eq!(mesh.p[2], Point::new(1f32, 1f32, 0f32));
+        assert_eq!(mesh.uv.as_ref().unwrap()[1], (1f32, 0f32));
+        assert!(mesh.n.is_none());
+    }
+
+    fn binary(format : &str, big_endian : bool) -> Vec<u8> {
+        let mut data = format!("ply\nformat {} 1.0\ncomment test\n{}", format, HEADER).into_bytes();
+        for v in VERTICES.iter() {
+            for c in v.iter() {
+                data.extend_from_slice(&if big_endian { c.to_be_bytes() } else { c.to_le_bytes() });
+            }
+        }
+        data.push(4);
+        for i in 0..4i32 {
+            data.extend_from_slice(&if big_endian { i.to_be_bytes() } else { i.to_le_bytes() });
+        }
+        data
+    }
+
+    #[test]
+    fn test_parse_ascii() {
+        let src = format!("ply\nformat ascii 1.0\n{}0 0 0 0 0\n1 0 0 1 0\n1 1 0 1 1\n0 1 0 0 1\n4 0 1 2 3\n", HEADER);
+        check(&parse_ply(src.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn test_parse_binary() {
+        check(&parse_ply(&binary("binary_little_endian", false)[..]).unwrap());
+        check(&parse_ply(&binary("binary_big_endian", true)[..]).unwrap());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let src = format!("ply\nformat ascii 1.0\n{}0 0 0 0 0\n1 0 0 1 0\n1 1 0 1 one\n", HEADER);
+        match parse_ply(src.as_bytes()) {
+            Err(LoadError::Parse { line, .. }) => assert_eq!(line, 14),
+            _ => panic!("expected a parse error"),
+        }
+
+        let mut data = binary("binary_little_endian", false);
+        data.truncate(data.len() - 2);
+        assert!(parse_ply(&data[..]).is_err());
+
+        assert!(parse_ply("ply\nformat ascii 1.0\nelement vertex 1\nproperty float q x\nend_header\n".as_bytes()).is_err());
+
+        // negative, fractional and out of range indices don't wrap around to
+        // real vertices, and are reported on the face's own line
+        for face in ["4 0 1 2 -1", "4 0 1 2.5 3", "4 0 1 2 4"].iter() {
+            let src = format!("ply\nformat ascii 1.0\n{}0 0 0 0 0\n1 0 0 1 0\n1 1 0 1 1\n0 1 0 0 1\n{}\n", HEADER, face);
+            match parse_ply(src.as_bytes()) {
+                Err(LoadError::Parse { line, message }) => {
+                    assert!(message.contains("face 0"), "{}", message);
+                    assert_eq!(line, 16);
+                },
+                _ => panic!("expected a parse error for '{}'", face),
+            }
+        }
+        let mut data = binary("binary_little_endian", false);
+        let len = data.len();
+        data[len - 4..].copy_from_slice(&(-1i32).to_le_bytes());
+        match parse_ply(&data[..]) {
+            Err(LoadError::Io(e)) => {
+                assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+                assert!(e.to_string().contains("face 0"), "{}", e);
+            },
+            _ => panic!("expected invalid data"),
+        }
+    }
+}
@@ -0,0 +1,990 @@
+// A line-oriented scene description.  Each line is a statement, '#' starts a
+// comment, and all angles are in degrees.
+//
+//   film 640 480
+//   filter gaussian 1.4 1.4 0.25
+//   sampler lhc 16
+//   camera perspective 60
+//     look_at 0 1 -5  0 0 7  0 1 0
+//   material matte-checker 8  1 1 1  0.25 0.25 0.25
+//   shape sphere new_partial 0.5 -0.3 0.3 180
+//     rotate 90 1 0 0
+//     translate -5 -0.8 7
+//   mesh models/bunny.ply
+//     scale 10 10 10
+//   light spot 20 20 20  30 25
+//     look_at 0 5 0  0 0 7  0 1 0
+//
+// Transform statements (translate, rotate, rotate3, scale, look_at) apply to the most
+// recent camera, shape, mesh, light or medium, in the order they are written.  A material
+// statement applies to the shapes and meshes that follow it; before the first,
+// surfaces are matte grey.  The material types are
+//
+//   matte R G B
+//   matte-checker SCALE  R G B  R G B
+//   mirror R G B
+//   glass ETA
+//   rough-glass ETA U_ROUGHNESS V_ROUGHNESS
+//   metal gold|silver|copper|aluminium U_ROUGHNESS V_ROUGHNESS
+//   plastic KD_R KD_G KD_B  KS_R KS_G KS_B  ROUGHNESS
+//   disney R G B  [NAME VALUE]... [thin]
+//   none
+//
+// with roughnesses between 0 (smooth) and 1.  The disney parameters are
+// metallic, eta or specular, roughness, specular-tint, anisotropic, sheen,
+// sheen-tint, clearcoat, clearcoat-gloss, spec-trans, flatness and diff-trans.
+//
+// The light types are
+//
+//   point R G B
+//   spot R G B  TOTAL_WIDTH FALLOFF_START
+//   distant R G B
+//   infinite R G B [PATH]
+//
+// where point and spot lights sit at the origin, and spot and distant lights
+// shine along +z, until transformed.  An infinite light surrounds the scene
+// with the radiance of a lat-long .hdr image, mapped as the sphere camera
+// maps its panoramas, and scaled by R G B; without an image it is uniform.
+//
+// Like a material, an emit statement applies to the shapes and meshes that
+// follow it, making them area lights:
+//
+//   emit R G B [two-sided]
+//   emit off
+//
+// An area light emits from the side its normals face unless it is two-sided.
+// Wherever a light or emit statement takes R G B it also takes
+//
+//   blackbody KELVIN LUMINANCE
+//
+// for the colour of a blackbody at that temperature, such as 2700 for an
+// incandescent lamp, with the given luminance.
+//
+// The whitted integrator lights a scene without lights from the camera; the
+// others render it black.
+//
+// Media are named, so that later statements can refer to them:
+//
+//   medium NAME homogeneous SA_R SA_G SA_B  SS_R SS_G SS_B  G
+//   medium NAME grid SA_R SA_G SA_B  SS_R SS_G SS_B  G  PATH
+//
+// where light is absorbed at rate SA and scattered at rate SS per unit
+// distance, by a Henyey-Greenstein phase function with asymmetry G.  A grid
+// scales those rates by the densities of a voxel file, filling the unit cube
+// until transformed.  Like a material, an interface statement applies to the
+// shapes and meshes that follow it, giving the media inside and outside them,
+// either of which may be none:
+//
+//   interface INSIDE OUTSIDE
+//   interface off
+//
+// A shape whose material is none is invisible, and only bounds its media.
+// The camera sits in no medium unless given one by
+//
+//   camera_medium NAME
+//
+// Only the volpath integrator renders media.  Mesh, image and voxel paths are
+// relative to the directory containing the scene file.  The objects and groups
+// of an OBJ mesh become groups of the scene's primitives under their names.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::f32::consts::PI;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::cameras::{Camera, PerspectiveCamera, OrthographicCamera, HemisphereCamera, SphereCamera, PerspectiveLensCamera};
+use crate::color::Color;
+use crate::filters::{Filter, BoxFilter, GaussianFilter, TriangleFilter, MitchellFilter, LanczosSincFilter, CachingFilter};
+use crate::geometry::{Transform, Trans, TransMut, Point, Vector};
+use crate::lights::{Light, PointLight, SpotLight, DistantLight, InfiniteAreaLight};
+use crate::loaders::{LoadError, load_hdr, load_obj, load_ply, load_voxels};
+use crate::materials::{Material, MatteMaterial, MirrorMaterial, GlassMaterial, MetalMaterial, PlasticMaterial, DisneyMaterial, GOLD, SILVER, COPPER, ALUMINIUM};
+use crate::media::{Medium, MediumInterface, HomogeneousMedium, GridDensityMedium};
+use crate::sampler::Sampler2DKind;
+use crate::scene::Scene;
+use crate::shapes::{Shape, Sphere, Cylinder, Disc, Cone, Paraboloid, Plane, TriangleMesh};
+use crate::spectrum::blackbody_color;
+use crate::textures::{Texture, CheckerboardTexture, ConstantTexture, UVMapping2D};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CameraKind {
+    Perspective { fov_y : f32 },
+    PerspectiveLens { fov_y : f32, lens_radius : f32, focal_distance : f32 },
+    Orthographic { scale : f32 },
+    Hemisphere,
+    Sphere,
+}
+
+// A camera whose aspect ratio is not known until the film size is settled.
+#[derive(Copy, Clone, Debug)]
+pub struct CameraDescription {
+    pub kind      : CameraKind,
+    pub transform : Transform,
+}
+
+impl CameraDescription {
+    pub fn new(kind : CameraKind) -> CameraDescription {
+        CameraDescription { kind: kind, transform: Transform::identity() }
+    }
+
+    pub fn build(&self, aspect_ratio : f32) -> Arc<dyn Camera> {
+        let mut camera : Box<dyn Camera> = match self.kind {
+            CameraKind::Perspective { fov_y } => Box::new(PerspectiveCamera::new(fov_y, aspect_ratio)),
+            CameraKind::PerspectiveLens { fov_y, lens_radius, focal_distance } => Box::new(PerspectiveLensCamera::new(fov_y, aspect_ratio, lens_radius, focal_distance)),
+            CameraKind::Orthographic { scale } => Box::new(OrthographicCamera::new(scale, aspect_ratio)),
+            CameraKind::Hemisphere => Box::new(HemisphereCamera::new()),
+            CameraKind::Sphere     => Box::new(SphereCamera::new()),
+        };
+        camera.transform_self(&self.transform);
+        Arc::from(camera)
+    }
+}
+
+impl TransMut for CameraDescription {
+    fn transform_self(&mut self, t : &Transform) {
+        self.transform = *t + self.transform;
+    }
+}
+
+// Everything a scene file declares.  Settings the file leaves out are None, so
+// the caller can fall back to its own defaults.
+pub struct SceneDescription {
+    pub scene   : Scene,
+    pub film    : Option<(u32, u32)>,
+    pub filter  : Option<CachingFilter>,
+    pub sampler : Option<Sampler2DKind>,
+    pub camera  : Option<CameraDescription>,
+}
+
+pub fn load_scene(path : &Path) -> Result<SceneDescription, LoadError> {
+    let base = path.parent().unwrap_or_else(|| Path::new(""));
+    parse_scene(BufReader::new(File::open(path)?), base)
+}
+
+type PendingShape = Box<dyn FnOnce(&Transform) -> Arc<dyn Shape>>;
+type PendingLight = Box<dyn FnOnce(&Transform) -> Box<dyn Light>>;
+type PendingMedium = Box<dyn FnOnce(&Transform) -> Arc<dyn Medium>>;
+// a mesh with the name of its OBJ object or group, if it has one
+type NamedMesh = (Option<String>, Arc<TriangleMesh>);
+
+// The statement that transform statements currently apply to.
+enum Target {
+    None,
+    Camera,
+    Shape(PendingShape, Transform),
+    Meshes(Vec<NamedMesh>, Transform),
+    Light(PendingLight, Transform),
+    Medium(String, PendingMedium, Transform),
+}
+
+fn pending<S : Shape + Trans<Output=S> + 'static>(s : S) -> Target {
+    Target::Shape(Box::new(move |t : &Transform| Arc::new(s.transform(t)) as Arc<dyn Shape>), Transform::identity())
+}
+
+fn pending_light<L : Light + Trans<Output=L> + 'static>(l : L) -> Target {
+    Target::Light(Box::new(move |t : &Transform| Box::new(l.transform(t)) as Box<dyn Light>), Transform::identity())
+}
+
+struct SceneBuilder {
+    desc      : SceneDescription,
+    target    : Target,
+    material  : Option<Arc<dyn Material>>,
+    emission  : Option<(Color, bool)>,
+    interface : Option<MediumInterface>,
+    media     : HashMap<String, Arc<dyn Medium>>,
+}
+
+impl SceneBuilder {
+    fn new() -> SceneBuilder {
+        SceneBuilder {
+            desc: SceneDescription {
+                scene:   Scene::new(),
+                film:    None,
+                filter:  None,
+                sampler: None,
+                camera:  None,
+            },
+            target:    Target::None,
+            material:  Some(Arc::new(MatteMaterial::default())),
+            emission:  None,
+            interface: None,
+            media:     HashMap::new(),
+        }
+    }
+
+    fn flush(&mut self) {
+        match std::mem::replace(&mut self.target, Target::None) {
+            // check_surface() has ruled out the combinations missing here
+            Target::Shape(f, t) => match (&self.material, self.emission, &self.interface) {
+                (_, _, Some(i))                              => self.desc.scene.add_medium_boundary(f(&t), self.material.clone(), i.clone()),
+                (Some(material), None, None)                 => self.desc.scene.add(f(&t), material.clone()),
+                (Some(material), Some((l, two_sided)), None) => self.desc.scene.add_area_light(f(&t), material.clone(), l, two_sided),
+                (None, _, None)                              => { },
+            },
+            Target::Meshes(meshes, t) => {
+                for (name, m) in meshes.iter() {
+                    let mesh = Arc::new(m.transform(&t));
+                    let primitives = match (&self.material, self.emission, &self.interface) {
+                        (_, _, Some(i))                              => self.desc.scene.add_mesh_medium_boundary(&mesh, self.material.clone(), i.clone()),
+                        (Some(material), None, None)                 => self.desc.scene.add_mesh(&mesh, material.clone()),
+                        (Some(material), Some((l, two_sided)), None) => self.desc.scene.add_mesh_area_light(&mesh, material.clone(), l, two_sided),
+                        (None, _, None)                              => continue,
+                    };
+                    if let Some(name) = name {
+                        self.desc.scene.add_group(name, primitives);
+                    }
+                }
+            },
+            Target::Light(f, t) => self.desc.scene.add_light(f(&t)),
+            Target::Medium(name, f, t) => {
+                self.media.insert(name, f(&t));
+            },
+            Target::Camera | Target::None => { },
+        }
+    }
+
+    fn transform(&mut self, t : &Transform, line_no : usize) -> Result<(), LoadError> {
+        if !t.to_world.is_finite() || !t.to_object.is_finite() {
+            return Err(LoadError::parse(line_no, "transform is degenerate or not finite"));
+        }
+        match self.target {
+            Target::Shape(_, ref mut st) | Target::Meshes(_, ref mut st) | Target::Light(_, ref mut st) | Target::Medium(_, _, ref mut st) => *st = *t + *st,
+            Target::Camera => self.desc.camera.as_mut().unwrap().transform_self(t),
+            Target::None => return Err(LoadError::parse(line_no, "transform must follow a camera, shape, mesh, light or medium")),
+        }
+        Ok(())
+    }
+
+    // Checks that a shape or mesh can be made with the current material,
+    // emission and interface.
+    fn check_surface(&self, line_no : usize) -> Result<(), LoadError> {
+        if self.emission.is_some() && (self.material.is_none() || self.interface.is_some()) {
+            return Err(LoadError::parse(line_no, "an area light must have a material and no interface"));
+        }
+        if self.material.is_none() && self.interface.is_none() {
+            return Err(LoadError::parse(line_no, "a shape without a material must have an interface"));
+        }
+        Ok(())
+    }
+
+    // The medium called name, or None for "none".
+    fn medium(&self, args : &mut Args) -> Result<Option<Arc<dyn Medium>>, LoadError> {
+        match args.word("medium")? {
+            "none" => Ok(None),
+            name   => match self.media.get(name) {
+                Some(m) => Ok(Some(m.clone())),
+                None    => Err(args.error(format!("unknown medium '{}'", name))),
+            },
+        }
+    }
+}
+
+pub fn parse_scene<R : BufRead>(reader : R, base : &Path) -> Result<SceneDescription, LoadError> {
+    let mut b = SceneBuilder::new();
+
+    for (ix, line) in reader.lines().enumerate() {
+        let line_no = ix + 1;
+        let line = line?;
+        let line = match line.find('#') {
+            Some(c) => &line[..c],
+            None    => &line[..],
+        };
+
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(k) => k,
+            None    => continue,
+        };
+        let mut args = Args { line_no: line_no, tokens: tokens.collect(), pos: 0 };
+
+        match keyword {
+            "translate" => {
+                let v = args.vector()?;
+                args.finish()?;
+                b.transform(&Transform::translation(&v), line_no)?;
+            },
+            "scale" => {
+                let v = args.vector()?;
+                args.finish()?;
+                b.transform(&Transform::scaling(&v), line_no)?;
+            },
+            "rotate" => {
+                let angle = args.angle()?;
+                let axis = args.vector()?;
+                args.finish()?;
+                b.transform(&Transform::rotation(angle, &axis), line_no)?;
+            },
+            "rotate3" => {
+                let (pitch, yaw, roll) = (args.angle()?, args.angle()?, args.angle()?);
+                args.finish()?;
+                b.transform(&Transform::rotation3(pitch, yaw, roll), line_no)?;
+            },
+            "look_at" => {
+                let (eye, target, up) = (args.point()?, args.point()?, args.vector()?);
+                args.finish()?;
+                let t = Transform::look_at(&eye, &target, &up).ok_or_else(|| LoadError::parse(line_no, "degenerate look_at"))?;
+                b.transform(&t, line_no)?;
+            },
+            "film" => {
+                b.flush();
+                if b.desc.film.is_some() {
+                    return Err(LoadError::parse(line_no, "film declared more than once"));
+                }
+                let (w, h) = (args.usize()?, args.usize()?);
+                args.finish()?;
+                if w == 0 || h == 0 {
+                    return Err(LoadError::parse(line_no, "film size must be non-zero"));
+                }
+                match (u32::try_from(w), u32::try_from(h)) {
+                    (Ok(w), Ok(h)) => b.desc.film = Some((w, h)),
+                    _              => return Err(LoadError::parse(line_no, format!("film size {} x {} is too large", w, h))),
+                }
+            },
+            "filter" => {
+                b.flush();
+                if b.desc.filter.is_some() {
+                    return Err(LoadError::parse(line_no, "filter declared more than once"));
+                }
+                let filter = parse_filter(&mut args)?;
+                args.finish()?;
+                b.desc.filter = Some(CachingFilter::new(filter.as_ref()));
+            },
+            "sampler" => {
+                b.flush();
+                if b.desc.sampler.is_some() {
+                    return Err(LoadError::parse(line_no, "sampler declared more than once"));
+                }
+                let sampler = parse_sampler(&mut args)?;
+                args.finish()?;
+                b.desc.sampler = Some(sampler);
+            },
+            "camera" => {
+                b.flush();
+                if b.desc.camera.is_some() {
+                    return Err(LoadError::parse(line_no, "camera declared more than once"));
+                }
+                let kind = parse_camera(&mut args)?;
+                args.finish()?;
+                b.desc.camera = Some(CameraDescription::new(kind));
+                b.target = Target::Camera;
+            },
+            "shape" => {
+                b.flush();
+                let shape = parse_shape(&mut args)?;
+                args.finish()?;
+                b.check_surface(line_no)?;
+                b.target = shape;
+            },
+            "material" => {
+                b.flush();
+                let material = parse_material(&mut args)?;
+                args.finish()?;
+                b.material = material;
+            },
+            "light" => {
+                b.flush();
+                let light = parse_light(&mut args, base)?;
+                args.finish()?;
+                b.target = light;
+            },
+            "medium" => {
+                b.flush();
+                let name = args.word("medium name")?;
+                if name == "none" || b.media.contains_key(name) {
+                    return Err(args.error(format!("medium '{}' declared more than once", name)));
+                }
+                let medium = parse_medium(&mut args, base)?;
+                args.finish()?;
+                b.target = Target::Medium(name.to_string(), medium, Transform::identity());
+            },
+            "interface" => {
+                b.flush();
+                if args.more() && args.tokens[args.pos] == "off" {
+                    args.pos += 1;
+                    b.interface = None;
+                } else {
+                    let (inside, outside) = (b.medium(&mut args)?, b.medium(&mut args)?);
+                    b.interface = Some(MediumInterface::new(inside, outside));
+                }
+                args.finish()?;
+            },
+            "camera_medium" => {
+                b.flush();
+                let medium = b.medium(&mut args)?;
+                args.finish()?;
+                b.desc.scene.camera_medium = medium;
+            },
+            "emit" => {
+                b.flush();
+                let emission = parse_emission(&mut args)?;
+                args.finish()?;
+                b.emission = emission;
+            },
+            "mesh" => {
+                b.flush();
+                b.check_surface(line_no)?;
+                let path = base.join(args.rest()?);
+                let meshes = load_mesh(&path).map_err(|e| LoadError::parse(line_no, format!("{}: {}", path.display(), e)))?;
+                b.target = Target::Meshes(meshes, Transform::identity());
+            },
+            _ => return Err(LoadError::parse(line_no, format!("unknown statement '{}'", keyword))),
+        }
+    }
+
+    b.flush();
+    Ok(b.desc)
+}
+
+fn load_mesh(path : &Path) -> Result<Vec<NamedMesh>, LoadError> {
+    if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("ply")) {
+        Ok(vec![(None, load_ply(path)?)])
+    } else {
+        Ok(load_obj(path)?.into_iter().map(|o| (Some(o.name), o.mesh)).collect())
+    }
+}
+
+fn parse_filter(args : &mut Args) -> Result<Box<dyn Filter>, LoadError> {
+    Ok(match args.word("filter type")? {
+        "box"      => Box::new(BoxFilter::new(args.f32()?, args.f32()?)),
+        "triangle" => Box::new(TriangleFilter::new(args.f32()?, args.f32()?)),
+        "gaussian" => Box::new(GaussianFilter::new(args.f32()?, args.f32()?, args.f32()?)),
+        "mitchell" => Box::new(MitchellFilter::new(args.f32()?, args.f32()?, args.f32()?, args.f32()?)),
+        "lanczos"  => Box::new(LanczosSincFilter::new(args.f32()?, args.f32()?, args.f32()?)),
+        t          => return Err(args.error(format!("unknown filter type '{}'", t))),
+    })
+}
+
+fn parse_sampler(args : &mut Args) -> Result<Sampler2DKind, LoadError> {
+    Ok(match args.word("sampler type")? {
+        "centers"        => Sampler2DKind::Centers,
+        "uniform"        => Sampler2DKind::Uniform(args.usize()?),
+        "strata"         => Sampler2DKind::Strata(args.usize()?, args.usize()?),
+        "strata-centers" => Sampler2DKind::StrataCenters(args.usize()?, args.usize()?),
+        "halton"         => Sampler2DKind::Halton(args.usize()?),
+        "hammersley"     => Sampler2DKind::Hammersley(args.usize()?),
+        "lhc"            => Sampler2DKind::LHC(args.usize()?),
+        "02"             => Sampler2DKind::S02(args.usize()?),
+        t                => return Err(args.error(format!("unknown sampler type '{}'", t))),
+    })
+}
+
+fn parse_camera(args : &mut Args) -> Result<CameraKind, LoadError> {
+    Ok(match args.word("camera type")? {
+        "perspective"      => CameraKind::Perspective { fov_y: args.angle()? },
+        "perspective-lens" => CameraKind::PerspectiveLens { fov_y: args.angle()?, lens_radius: args.f32()?, focal_distance: args.f32()? },
+        "ortho"            => CameraKind::Orthographic { scale: args.f32()? },
+        "hemisphere"       => CameraKind::Hemisphere,
+        "sphere"           => CameraKind::Sphere,
+        t                  => return Err(args.error(format!("unknown camera type '{}'", t))),
+    })
+}
+
+fn parse_shape(args : &mut Args) -> Result<Target, LoadError> {
+    let shape = args.word("shape type")?;
+    let constructor = args.word("constructor")?;
+
+    Ok(match (shape, constructor) {
+        ("sphere", "unit")                   => pending(Sphere::unit()),
+        ("sphere", "new")                    => pending(Sphere::new(args.f32()?)),
+        ("sphere", "new_partial")            => pending(Sphere::new_partial(args.f32()?, (args.f32()?, args.f32()?), args.angle()?)),
+        ("cylinder", "unit")                 => pending(Cylinder::unit()),
+        ("cylinder", "new")                  => pending(Cylinder::new(args.f32()?, args.f32()?)),
+        ("cylinder", "new_partial")          => pending(Cylinder::new_partial(args.f32()?, args.f32()?, args.angle()?)),
+        ("disc", "unit")                     => pending(Disc::unit()),
+        ("disc", "new")                      => pending(Disc::new(args.f32()?)),
+        ("disc", "new_annulus")              => pending(Disc::new_annulus(args.f32()?, args.f32()?)),
+        ("disc", "new_partial")              => pending(Disc::new_partial(args.f32()?, args.angle()?)),
+        ("disc", "new_partial_annulus")      => pending(Disc::new_partial_annulus(args.f32()?, args.f32()?, args.angle()?)),
+        ("cone", "unit")                     => pending(Cone::unit()),
+        ("cone", "new")                      => pending(Cone::new(args.f32()?, args.f32()?)),
+        ("cone", "new_partial")              => pending(Cone::new_partial(args.f32()?, args.f32()?, args.f32()?, args.f32()?, args.angle()?)),
+        ("paraboloid", "unit")               => pending(Paraboloid::unit()),
+        ("paraboloid", "new")                => pending(Paraboloid::new(args.f32()?, args.f32()?)),
+        ("paraboloid", "new_partial")        => pending(Paraboloid::new_partial(args.f32()?, args.f32()?, args.f32()?, args.f32()?, args.angle()?)),
+        ("plane", "unit")                    => pending(Plane::unit()),
+        ("plane", "new")                     => pending(Plane::new(args.f32()?, args.f32()?)),
+        ("sphere", _) | ("cylinder", _) | ("disc", _) | ("cone", _) | ("paraboloid", _) | ("plane", _) => {
+            return Err(args.error(format!("unknown {} constructor '{}'", shape, constructor)));
+        },
+        _ => return Err(args.error(format!("unknown shape type '{}'", shape))),
+    })
+}
+
+fn parse_light(args : &mut Args, base : &Path) -> Result<Target, LoadError> {
+    Ok(match args.word("light type")? {
+        "point"   => pending_light(PointLight::new(args.light_color()?)),
+        "spot"    => pending_light(SpotLight::new(args.light_color()?, args.angle()?, args.angle()?)),
+        "distant" => pending_light(DistantLight::new(args.light_color()?)),
+        "infinite" => {
+            let l = args.light_color()?;
+            if !args.more() {
+                pending_light(InfiniteAreaLight::constant(l))
+            } else {
+                let path = base.join(args.rest()?);
+                let (w, h, texels) = load_hdr(&path).map_err(|e| args.error(format!("{}: {}", path.display(), e)))?;
+                pending_light(InfiniteAreaLight::new(l, w, h, texels))
+            }
+        },
+        t         => return Err(args.error(format!("unknown light type '{}'", t))),
+    })
+}
+
+fn parse_medium(args : &mut Args, base : &Path) -> Result<PendingMedium, LoadError> {
+    Ok(match args.word("medium type")? {
+        "homogeneous" => {
+            let m = HomogeneousMedium::new(args.color()?, args.color()?, args.f32()?);
+            Box::new(move |_ : &Transform| Arc::new(m) as Arc<dyn Medium>)
+        },
+        "grid" => {
+            let (sigma_a, sigma_s, g) = (args.color()?, args.color()?, args.f32()?);
+            let path = base.join(args.rest()?);
+            let (nx, ny, nz, density) = load_voxels(&path).map_err(|e| args.error(format!("{}: {}", path.display(), e)))?;
+            let m = GridDensityMedium::new(sigma_a, sigma_s, g, nx, ny, nz, density);
+            Box::new(move |t : &Transform| Arc::new(m.transform(t)) as Arc<dyn Medium>)
+        },
+        t => return Err(args.error(format!("unknown medium type '{}'", t))),
+    })
+}
+
+fn parse_emission(args : &mut Args) -> Result<Option<(Color, bool)>, LoadError> {
+    if args.more() && args.tokens[args.pos] == "off" {
+        args.pos += 1;
+        return Ok(None);
+    }
+    let l = args.light_color()?;
+    let two_sided = match args.more() {
+        false => false,
+        true  => match args.word("two-sided")? {
+            "two-sided" => true,
+            w           => return Err(args.error(format!("unexpected '{}'", w))),
+        },
+    };
+    Ok(Some((l, two_sided)))
+}
+
+fn parse_material(args : &mut Args) -> Result<Option<Arc<dyn Material>>, LoadError> {
+    Ok(Some(match args.word("material type")? {
+        "none"   => return Ok(None),
+        "matte"  => Arc::new(MatteMaterial::constant(args.color()?)),
+        "matte-checker" => {
+            let scale = args.f32()?;
+            let (a, b) = (args.color()?, args.color()?);
+            let checks = CheckerboardTexture::new(Box::new(UVMapping2D::new(scale, scale, 0f32, 0f32)), Arc::new(ConstantTexture::new(a)), Arc::new(ConstantTexture::new(b)));
+            Arc::new(MatteMaterial::new(Arc::new(checks)))
+        },
+        "mirror" => Arc::new(MirrorMaterial::constant(args.color()?)),
+        "glass"  => Arc::new(GlassMaterial::constant(Color::white(), Color::white(), args.f32()?)),
+        "rough-glass" => {
+            let (eta, u, v) = (args.f32()?, args.f32()?, args.f32()?);
+            let roughness = |r : f32| Arc::new(ConstantTexture::new(r));
+            Arc::new(GlassMaterial::new_rough(Arc::new(ConstantTexture::new(Color::white())), Arc::new(ConstantTexture::new(Color::white())), eta, roughness(u), roughness(v), true))
+        },
+        "metal" => {
+            let ior = match args.word("metal")? {
+                "gold"      => GOLD,
+                "silver"    => SILVER,
+                "copper"    => COPPER,
+                "aluminium" => ALUMINIUM,
+                m           => return Err(args.error(format!("unknown metal '{}'", m))),
+            };
+            Arc::new(MetalMaterial::constant(ior, args.f32()?, args.f32()?))
+        },
+        "plastic" => Arc::new(PlasticMaterial::constant(args.color()?, args.color()?, args.f32()?)),
+        "disney" => {
+            let mut m = DisneyMaterial::constant(args.color()?);
+            while args.more() {
+                let name = args.word("parameter")?;
+                if name == "thin" {
+                    m.thin = true;
+                    continue;
+                }
+                let v : Arc<dyn Texture<f32>> = Arc::new(ConstantTexture::new(args.f32()?));
+                match name {
+                    "metallic"        => m.metallic = v,
+                    "eta"             => m.eta = v,
+                    "specular"        => m.specular = Some(v),
+                    "roughness"       => m.roughness = v,
+                    "specular-tint"   => m.specular_tint = v,
+                    "anisotropic"     => m.anisotropic = v,
+                    "sheen"           => m.sheen = v,
+                    "sheen-tint"      => m.sheen_tint = v,
+                    "clearcoat"       => m.clearcoat = v,
+                    "clearcoat-gloss" => m.clearcoat_gloss = v,
+                    "spec-trans"      => m.spec_trans = v,
+                    "flatness"        => m.flatness = v,
+                    "diff-trans"      => m.diff_trans = v,
+                    n                 => return Err(args.error(format!("unknown disney parameter '{}'", n))),
+                }
+            }
+            Arc::new(m)
+        },
+        t        => return Err(args.error(format!("unknown material type '{}'", t))),
+    }))
+}
+
+struct Args<'a> {
+    line_no : usize,
+    tokens  : Vec<&'a str>,
+    pos     : usize,
+}
+
+impl<'a> Args<'a> {
+    fn error<S : Into<String>>(&self, message : S) -> LoadError {
+        LoadError::parse(self.line_no, message)
+    }
+
+    fn word(&mut self, what : &str) -> Result<&'a str, LoadError> {
+        match self.tokens.get(self.pos) {
+            Some(t) => {
+                self.pos += 1;
+                Ok(t)
+            },
+            None => Err(self.error(format!("missing {}", what))),
+        }
+    }
+
+    fn f32(&mut self) -> Result<f32, LoadError> {
+        let t = self.word("number")?;
+        t.parse::<f32>().map_err(|_| self.error(format!("invalid number '{}'", t)))
+    }
+
+    fn usize(&mut self) -> Result<usize, LoadError> {
+        let t = self.word("count")?;
+        t.parse::<usize>().map_err(|_| self.error(format!("invalid count '{}'", t)))
+    }
+
+    fn angle(&mut self) -> Result<f32, LoadError> {
+        Ok(self.f32()? * PI / 180f32)
+    }
+
+    fn vector(&mut self) -> Result<Vector, LoadError> {
+        Ok(Vector::new(self.f32()?, self.f32()?, self.f32()?))
+    }
+
+    fn point(&mut self) -> Result<Point, LoadError> {
+        Ok(Point::new(self.f32()?, self.f32()?, self.f32()?))
+    }
+
+    fn color(&mut self) -> Result<Color, LoadError> {
+        Ok(Color::new(self.f32()?, self.f32()?, self.f32()?))
+    }
+
+    // R G B, or blackbody KELVIN LUMINANCE.
+    fn light_color(&mut self) -> Result<Color, LoadError> {
+        if self.more() && self.tokens[self.pos] == "blackbody" {
+            self.pos += 1;
+            let (t, luminance) = (self.f32()?, self.f32()?);
+            if t <= 0f32 {
+                return Err(self.error(format!("invalid temperature '{}'", t)));
+            }
+            return Ok(blackbody_color(t) * luminance);
+        }
+        self.color()
+    }
+
+    fn more(&self) -> bool {
+        self.pos < self.tokens.len()
+    }
+
+    // The remainder of the line, for paths that may contain spaces.
+    fn rest(&mut self) -> Result<String, LoadError> {
+        if self.pos >= self.tokens.len() {
+            return Err(self.error("missing path"));
+        }
+        let s = self.tokens[self.pos..].join(" ");
+        self.pos = self.tokens.len();
+        Ok(s)
+    }
+
+    fn finish(&self) -> Result<(), LoadError> {
+        match self.tokens.get(self.pos) {
+            None    => Ok(()),
+            Some(t) => Err(self.error(format!("unexpected '{}'", t))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+    use image::hdr::HDREncoder;
+    use crate::geometry::{Ray, Normal};
+    use crate::shapes::SurfaceContext;
+    use crate::reflection::{BSDF_REFLECTION, BSDF_TRANSMISSION, BSDF_SPECULAR, BSDF_DIFFUSE, BSDF_GLOSSY, BSDF_ALL};
+
+    fn parse(src : &str) -> Result<SceneDescription, LoadError> {
+        parse_scene(src.as_bytes(), Path::new(""))
+    }
+
+    #[test]
+    fn test_parse_scene() {
+        let desc = parse("# a test scene\n\
+                          film 320 240\n\
+                          filter box 0.5 0.5\n\
+                          sampler strata 4 4\n\
+                          camera perspective 60\n\
+                          \x20 translate 0 0 -5\n\
+                          shape sphere new 1   # at the origin\n\
+                          shape disc new_partial_annulus 0.1 0.5 270\n\
+                          \x20 rotate 90 1 0 0\n\
+                          \x20 translate 0 0 5\n").unwrap();
+
+        assert_eq!(desc.film, Some((320, 240)));
+        assert!(desc.filter.is_some());
+        assert_eq!(desc.sampler, Some(Sampler2DKind::Strata(4, 4)));
+        assert_eq!(desc.scene.primitives.len(), 2);
+
+        let camera = desc.camera.unwrap();
+        assert_eq!(camera.kind, CameraKind::Perspective { fov_y: PI / 3f32 });
+        let r = camera.build(4f32 / 3f32).cast(0f32, 0f32);
+        assert!((r.origin - Point::new(0f32, 0f32, -5f32)).magnitude() < 1e-5);
+
+        let i = desc.scene.intersect(&r).unwrap();
+        assert!((i.time - 4f32).abs() < 1e-5);
+
+        let b = desc.scene.primitives[1].bound;
+        assert!(b.min().y.abs() < 1e-5 && b.max().y.abs() < 1e-5);
+        assert!((b.centroid().z - 5f32).abs() < 1e-5);
+        assert!(desc.scene.intersect(&Ray::new(&Point::new(0f32, 10f32, 5.3f32), &Vector::new(0f32, -1f32, 0f32))).is_some());
+    }
+
+    #[test]
+    fn test_materials() {
+        let desc = parse("shape sphere unit\n\
+                          material mirror 0.9 0.9 0.9\n\
+                          shape sphere unit\n\
+                          \x20 translate 0 0 2\n\
+                          material glass 1.5\n\
+                          shape sphere unit\n\
+                          \x20 translate 0 0 4\n\
+                          material metal gold 0.1 0.3\n\
+                          shape sphere unit\n\
+                          \x20 translate 0 0 6\n\
+                          material plastic 0.5 0.1 0.1  0.2 0.2 0.2  0.2\n\
+                          shape sphere unit\n\
+                          \x20 translate 0 0 8\n\
+                          material rough-glass 1.33 0.2 0.2\n\
+                          shape sphere unit\n\
+                          \x20 translate 0 0 10\n\
+                          material disney 0.8 0.2 0.2  roughness 0.3 clearcoat 1 spec-trans 0.5 thin\n\
+                          shape sphere unit\n\
+                          \x20 translate 0 0 12\n\
+                          material disney 0.8 0.2 0.2  eta 2 specular 0.5\n\
+                          shape sphere unit\n\
+                          \x20 translate 0 0 14\n").unwrap();
+
+        let hit = |z : f32| desc.scene.intersect(&Ray::new(&Point::new(0f32, 0f32, z), &Vector::unit_z())).unwrap().bsdf(true);
+        assert_eq!(hit(-1f32).num_components(BSDF_DIFFUSE | BSDF_REFLECTION), 1);
+        assert_eq!(hit(1f32).num_components(BSDF_SPECULAR | BSDF_REFLECTION), 1);
+        assert_eq!(hit(3f32).eta, 1.5f32);
+        assert_eq!(hit(5f32).num_components(BSDF_GLOSSY | BSDF_REFLECTION), 1);
+        assert_eq!(hit(7f32).num_components(BSDF_DIFFUSE | BSDF_GLOSSY | BSDF_REFLECTION), 2);
+        assert_eq!(hit(9f32).num_components(BSDF_GLOSSY | BSDF_TRANSMISSION), 1);
+        // diffuse, fake subsurface, retro-reflection, specular, clearcoat, rough
+        // and diffuse transmission
+        assert_eq!(hit(11f32).num_components(BSDF_ALL), 7);
+        // specular takes the place of eta
+        assert!((hit(13f32).eta - 1.5f32).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_lights() {
+        let mut desc = parse("light point 10 10 10\n\
+                              \x20 translate 0 5 0\n\
+                              light spot 1 1 1  30 20\n\
+                              \x20 look_at 0 5 0  0 0 0  0 0 1\n\
+                              light distant 2 2 2\n\
+                              \x20 rotate 90 1 0 0\n\
+                              shape sphere unit\n").unwrap();
+        desc.scene.preprocess();
+        assert_eq!(desc.scene.lights.len(), 3);
+
+        // all three shine straight down on a point below them
+        let context = SurfaceContext::new(Point::new(0f32, -1f32, 0f32), Normal::new(0f32, 1f32, 0f32), (0f32, 0f32), (Vector::unit_x(), Vector::unit_z()), (Normal::zero(), Normal::zero()));
+        let expected = [Color::gray(10f32 / 36f32), Color::gray(1f32 / 36f32), Color::gray(2f32)];
+        for (light, li) in desc.scene.lights.iter().zip(expected.iter()) {
+            let s = light.sample_li(&context, (0.5f32, 0.5f32)).unwrap();
+            assert!((s.wi - Vector::unit_y()).magnitude() < 1e-5f32);
+            assert_eq!(s.li, *li);
+            assert!(!s.visibility.unoccluded(&desc.scene));
+        }
+
+        // a warm lamp is redder than it is blue, at the luminance asked for
+        let desc = parse("light point blackbody 2700 3\nemit blackbody 6500 1 two-sided\nshape sphere unit\n").unwrap();
+        let lamp = desc.scene.lights[0].sample_li(&context, (0.5f32, 0.5f32)).unwrap().li;
+        assert!(lamp.r > lamp.b && (lamp.luminance() - 3f32).abs() < 1e-3f32, "{}", lamp);
+        assert_eq!(desc.scene.lights.len(), 2);
+        assert!(parse("light point blackbody 0 1\n").is_err());
+    }
+
+    #[test]
+    fn test_area_lights() {
+        let desc = parse("emit 4 4 4\n\
+                          shape disc new 1\n\
+                          \x20 rotate 90 1 0 0\n\
+                          \x20 translate 0 5 0\n\
+                          emit 1 1 1 two-sided\n\
+                          shape sphere unit\n\
+                          \x20 translate 5 0 0\n\
+                          emit off\n\
+                          shape plane unit\n").unwrap();
+        assert_eq!(desc.scene.lights.len(), 2);
+        let emitters : Vec<Option<usize>> = desc.scene.primitives.iter().map(|p| p.light).collect();
+        assert_eq!(emitters, vec![Some(0), Some(1), None]);
+
+        // the disc faces down, and is seen from below but not from above
+        let le = |o : Point| {
+            let r = Ray::new(&o, &(Point::new(0.2f32, 5f32, 0f32) - o).normalize());
+            let i = desc.scene.intersect(&r).unwrap();
+            i.le(&desc.scene, &-r.direction)
+        };
+        assert_eq!(le(Point::new(0f32, 2f32, 0f32)), Color::gray(4f32));
+        assert_eq!(le(Point::new(0f32, 8f32, 0f32)), Color::black());
+
+        let context = SurfaceContext::new(Point::origin(), Normal::new(0f32, 1f32, 0f32), (0f32, 0f32), (Vector::unit_x(), Vector::unit_z()), (Normal::zero(), Normal::zero()));
+        let s = desc.scene.lights[0].sample_li(&context, (0.3f32, 0.7f32)).unwrap();
+        assert_eq!(s.li, Color::gray(4f32));
+        assert!(s.wi.y > 0.98f32);
+    }
+
+    #[test]
+    fn test_infinite_lights() {
+        let pixels : Vec<Rgb<f32>> = (0..8).map(|i| Rgb { data: [i as f32, i as f32, i as f32] }).collect();
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("scene_file_test_{}.hdr", std::process::id()));
+        HDREncoder::new(File::create(&path).unwrap()).encode(&pixels, 4, 2).unwrap();
+
+        let src = format!("light infinite 2 2 2 {}\n\
+                           \x20 rotate 90 0 1 0\n\
+                           light infinite 0.5 0.5 0.5\n", path.file_name().unwrap().to_str().unwrap());
+        let desc = parse_scene(src.as_bytes(), &dir).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(desc.scene.lights.len(), 2);
+
+        // the map is turned so that its second column, which faces -x, is
+        // seen along +z
+        let r = Ray::new(&Point::origin(), &Vector::new(0.5f32, -0.5f32, 1f32));
+        assert_eq!(desc.scene.lights[0].le(&r), Color::gray(10f32));
+        assert_eq!(desc.scene.lights[1].le(&r), Color::gray(0.5f32));
+
+        assert!(parse("light infinite 1 1 1 does/not/exist.hdr\n").is_err());
+    }
+
+    #[test]
+    fn test_media() {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("scene_file_test_{}.vox", std::process::id()));
+        std::fs::write(&path, "2 2 2\n1 1 1 1  1 1 1 1\n").unwrap();
+
+        let src = format!("medium fog homogeneous 0.1 0.1 0.1  0.2 0.2 0.2  0\n\
+                           medium smoke grid 0 0 0  1 1 1  0.5  {}\n\
+                           \x20 scale 2 2 2\n\
+                           camera_medium fog\n\
+                           material none\n\
+                           interface smoke fog\n\
+                           shape sphere new 1\n\
+                           material glass 1.5\n\
+                           interface none fog\n\
+                           shape sphere new 1\n\
+                           \x20 translate 5 0 0\n\
+                           interface off\n\
+                           shape sphere new 1\n\
+                           \x20 translate -5 0 0\n", path.file_name().unwrap().to_str().unwrap());
+        let desc = parse_scene(src.as_bytes(), &dir).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(desc.scene.camera_medium.is_some());
+        assert_eq!(desc.scene.primitives.len(), 3);
+        let interfaces : Vec<bool> = desc.scene.primitives.iter().map(|p| p.medium_interface.is_some()).collect();
+        assert_eq!(interfaces, vec![true, true, false]);
+
+        // the invisible boundary is passed through, but bounds the media
+        let r = Ray::new(&Point::new(0f32, 0f32, -5f32), &Vector::unit_z());
+        assert!(desc.scene.intersect(&r).is_none());
+        let i = desc.scene.intersect_media(&r).unwrap();
+        assert!(i.material.is_none());
+        assert!(i.medium(&Vector::unit_z(), None).is_some());
+        assert!(desc.scene.intersect(&Ray::new(&Point::new(5f32, 0f32, -5f32), &Vector::unit_z())).unwrap().material.is_some());
+
+        assert!(parse("medium fog homogeneous 0.1 0.1 0.1  0.2 0.2 0.2  0\n  scale 2 2 2\n").is_ok());
+        assert!(parse("medium smoke grid 0 0 0  1 1 1  0.5  does/not/exist.vox\n").is_err());
+    }
+
+    #[test]
+    fn test_mesh_groups() {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("scene_file_test_{}.obj", std::process::id()));
+        std::fs::write(&path, "v -1 -1 0\nv 1 -1 0\nv 1 1 0\nv -1 1 0\nv 2 -1 0\nv 4 -1 0\nv 3 1 0\n\
+                               o lid\nf 1 2 3 4\n\
+                               o base\nf 5 6 7\n").unwrap();
+
+        let src = format!("mesh {}\n  translate 0 0 2\nmesh {}\n  translate 0 0 4\n", path.file_name().unwrap().to_str().unwrap(), path.file_name().unwrap().to_str().unwrap());
+        let mut desc = parse_scene(src.as_bytes(), &dir).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // both copies of each object are in its group
+        assert_eq!(desc.scene.primitives.len(), 6);
+        assert_eq!(desc.scene.groups["lid"], vec![0..2, 3..5]);
+        assert_eq!(desc.scene.groups["base"], vec![2..3, 5..6]);
+        assert_eq!(desc.scene.group("lid").unwrap().len(), 4);
+        assert!(desc.scene.group("handle").is_none());
+
+        assert!(desc.scene.set_group_material("lid", Arc::new(MirrorMaterial::constant(Color::white()))));
+        assert!(!desc.scene.set_group_material("handle", Arc::new(MirrorMaterial::constant(Color::white()))));
+        let hit = |x : f32, z : f32| desc.scene.intersect(&Ray::new(&Point::new(x, -0.5f32, z), &Vector::unit_z())).unwrap().bsdf(true);
+        assert_eq!(hit(0f32, 1f32).num_components(BSDF_SPECULAR | BSDF_REFLECTION), 1);
+        assert_eq!(hit(0f32, 3f32).num_components(BSDF_SPECULAR | BSDF_REFLECTION), 1);
+        assert_eq!(hit(3f32, 3f32).num_components(BSDF_DIFFUSE | BSDF_REFLECTION), 1);
+    }
+
+    #[test]
+    fn test_look_at() {
+        let desc = parse("camera ortho 2\n  look_at 0 10 0  0 0 0  0 0 1\nshape sphere unit\n").unwrap();
+        let r = desc.camera.unwrap().build(1f32).cast(0f32, 0f32);
+        assert!((r.origin - Point::new(0f32, 10f32, 0f32)).magnitude() < 1e-5);
+        assert!((desc.scene.intersect(&r).unwrap().time - 9.5f32).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let line_of = |src : &str| match parse(src) {
+            Err(LoadError::Parse { line, .. }) => line,
+            _ => panic!("expected a parse error"),
+        };
+
+        assert_eq!(line_of("film 320 240\n\ntranslate 1 2 3\n"), 3);
+        for film in ["film 0 240", "film -320 240", "film 320.5 240", "film 320 5000000000"].iter() {
+            assert_eq!(line_of(&format!("\n{}\n", film)), 2, "{}", film);
+        }
+        assert_eq!(line_of("shape sphere new\n"), 1);
+        assert_eq!(line_of("shape sphere new 1 2\n"), 1);
+        assert_eq!(line_of("shape sphere new_annulus 1 2\n"), 1);
+        assert_eq!(line_of("shape teapot unit\n"), 1);
+        assert_eq!(line_of("camera ortho 10\nshape plane unit\ncamera ortho 5\n"), 3);
+        assert_eq!(line_of("sampler lhc sixteen\n"), 1);
+        assert_eq!(line_of("\n\nmesh does/not/exist.obj\n"), 3);
+        assert_eq!(line_of("light point\n"), 1);
+        assert_eq!(line_of("material matte 1 1\n"), 1);
+        assert_eq!(line_of("material velvet 1 1 1\n"), 1);
+        assert_eq!(line_of("material metal brass 0.1 0.1\n"), 1);
+        assert_eq!(line_of("material disney 1 1 1 metallic\n"), 1);
+        assert_eq!(line_of("light sun 1 1 1\n"), 1);
+        assert_eq!(line_of("light spot 1 1 1 30\n"), 1);
+        assert_eq!(line_of("emit 1 1\n"), 1);
+        assert_eq!(line_of("emit 1 1 1 one-sided\n"), 1);
+        assert_eq!(line_of("material disney 1 1 1 shininess 1\n"), 1);
+        assert_eq!(line_of("material mirror 1 1 1\n  scale 2 2 2\n"), 2);
+        assert_eq!(line_of("camera sphere\nlook_at 0 0 0 1 1 1\n"), 2);
+        assert_eq!(line_of("camera sphere\nlook_at 1 1 1  1 1 1  0 1 0\n"), 2);
+        assert_eq!(line_of("shape sphere unit\n  translate nan 0 0\n"), 2);
+        assert_eq!(line_of("shape sphere unit\n  scale 1 0 1\n"), 2);
+        assert_eq!(line_of("medium fog homogeneous 1 1 1  1 1 1  0\nmedium fog homogeneous 1 1 1  1 1 1  0\n"), 2);
+        assert_eq!(line_of("medium fog cloud 1 1 1  1 1 1  0\n"), 1);
+        assert_eq!(line_of("interface fog none\n"), 1);
+        assert_eq!(line_of("material none\nshape sphere unit\n"), 2);
+        assert_eq!(line_of("medium fog homogeneous 1 1 1  1 1 1  0\ninterface fog none\nemit 1 1 1\nmesh models/box.obj\n"), 4);
+    }
+}
@@ -0,0 +1,123 @@
+// Voxel grids of densities, for media, in one of two simple formats.  An
+// ASCII file holds whitespace-separated numbers, with '#' starting a
+// comment: the counts NX NY NZ and then NX × NY × NZ densities, x varying
+// fastest, then y, then z.  A raw file, named *.raw, holds the same in
+// binary: three little-endian u32 counts and then little-endian f32
+// densities.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+
+use crate::loaders::LoadError;
+
+// A voxel grid's dimensions and densities.
+pub type Voxels = (usize, usize, usize, Vec<f32>);
+
+pub fn load_voxels(path : &Path) -> Result<Voxels, LoadError> {
+    let reader = BufReader::new(File::open(path)?);
+    if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("raw")) {
+        parse_voxels_raw(reader)
+    } else {
+        parse_voxels(reader)
+    }
+}
+
+pub fn parse_voxels<R : BufRead>(reader : R) -> Result<Voxels, LoadError> {
+    let mut numbers = Vec::new();
+    for (ix, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = match line.find('#') {
+            Some(c) => &line[..c],
+            None    => &line[..],
+        };
+        for t in line.split_whitespace() {
+            let n = t.parse::<f32>().map_err(|_| LoadError::parse(ix + 1, format!("invalid number '{}'", t)))?;
+            numbers.push((ix + 1, n));
+        }
+    }
+
+    if numbers.len() < 3 {
+        return Err(LoadError::parse(numbers.last().map_or(1, |n| n.0), "missing grid size"));
+    }
+    let mut size = [0usize; 3];
+    for (s, &(line_no, n)) in size.iter_mut().zip(numbers.iter()) {
+        if n < 1f32 || n.fract() != 0f32 {
+            return Err(LoadError::parse(line_no, format!("invalid count '{}'", n)));
+        }
+        *s = n as usize;
+    }
+
+    let [nx, ny, nz] = size;
+    let count = voxel_count(nx, ny, nz).ok_or_else(|| LoadError::parse(numbers[2].0, "grid size is too large"))?;
+    let densities : Vec<f32> = numbers[3..].iter().map(|n| n.1).collect();
+    if densities.len() != count {
+        let line_no = numbers.last().unwrap().0;
+        return Err(LoadError::parse(line_no, format!("expected {} densities but found {}", count, densities.len())));
+    }
+    Ok((nx, ny, nz, densities))
+}
+
+pub fn parse_voxels_raw<R : Read>(mut reader : R) -> Result<Voxels, LoadError> {
+    let mut word = [0u8; 4];
+    let mut size = [0usize; 3];
+    for s in size.iter_mut() {
+        reader.read_exact(&mut word)?;
+        *s = u32::from_le_bytes(word) as usize;
+    }
+    let [nx, ny, nz] = size;
+    if nx == 0 || ny == 0 || nz == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "grid size must be non-zero").into());
+    }
+
+    let count = voxel_count(nx, ny, nz).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "grid size is too large"))?;
+
+    // the header can't be trusted to say how much data follows, so reserve
+    // only a modest amount up front and let the rest grow as it is read
+    let mut densities = Vec::with_capacity(count.min(1 << 20));
+    for _ in 0..count {
+        reader.read_exact(&mut word)?;
+        densities.push(f32::from_le_bytes(word));
+    }
+    Ok((nx, ny, nz, densities))
+}
+
+fn voxel_count(nx : usize, ny : usize, nz : usize) -> Option<usize> {
+    nx.checked_mul(ny)?.checked_mul(nz)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_voxels() {
+        let (nx, ny, nz, d) = parse_voxels(&b"# a small grid\n2 1 2\n0 0.5\n1 2 # the top\n"[..]).unwrap();
+        assert_eq!((nx, ny, nz), (2, 1, 2));
+        assert_eq!(d, vec![0f32, 0.5f32, 1f32, 2f32]);
+
+        assert!(parse_voxels(&b"2 2 2\n1 2 3\n"[..]).is_err());
+        assert!(parse_voxels(&b"2 0.5 2\n"[..]).is_err());
+        assert!(parse_voxels(&b"1 1 1\nfog\n"[..]).is_err());
+
+        let mut bytes = Vec::new();
+        for n in [1u32, 2, 1].iter() {
+            bytes.extend_from_slice(&n.to_le_bytes());
+        }
+        for d in [0.25f32, 4f32].iter() {
+            bytes.extend_from_slice(&d.to_le_bytes());
+        }
+        assert_eq!(parse_voxels_raw(&bytes[..]).unwrap(), (1, 2, 1, vec![0.25f32, 4f32]));
+        assert!(parse_voxels_raw(&bytes[..bytes.len() - 1]).is_err());
+
+        // a corrupt header fails cleanly rather than overflowing or exhausting memory
+        assert!(parse_voxels(&b"1e30 1e30 1e30\n1\n"[..]).is_err());
+        let huge : Vec<u8> = [u32::MAX; 3].iter().flat_map(|n| n.to_le_bytes().to_vec()).collect();
+        match parse_voxels_raw(&huge[..]) {
+            Err(LoadError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::InvalidData),
+            _ => panic!("expected invalid data"),
+        }
+        let large : Vec<u8> = [1u32 << 16, 1 << 16, 1].iter().flat_map(|n| n.to_le_bytes().to_vec()).collect();
+        assert!(parse_voxels_raw(&large[..]).is_err());
+    }
+}
@@ -0,0 +1,140 @@
+use std::sync::Arc;
+
+use crate::color::Color;
+use crate::materials::Material;
+use crate::reflection::{BSDF, DisneyDiffuse, DisneyFakeSS, DisneyRetro, DisneySheen, DisneyClearcoat, DisneyFresnel, DisneyMicrofacetDistribution};
+use crate::reflection::{LambertianTransmission, MicrofacetDistribution, MicrofacetReflection, MicrofacetTransmission, TrowbridgeReitzDistribution, eta_from_specular, schlick_r0_from_eta};
+use crate::shapes::SurfaceContext;
+use crate::textures::{Texture, ConstantTexture};
+
+// The principled "base colour, metallic, roughness" model of Burley, with
+// transmission, as in pbrt-v3.  The weights are all in [0, 1].  A thin
+// surface, such as a leaf or a sheet of paper, has no inside: light passes
+// through it without refracting, diffusely in proportion to diff_trans, and
+// the diffuse lobe flattens towards fake subsurface scattering with flatness.
+// Materials exported from other tools usually give specular, a reflectance
+// in which 0.5 means an eta of 1.5; when it is set it takes the place of eta.
+pub struct DisneyMaterial {
+    pub color           : Arc<dyn Texture<Color>>,
+    pub metallic        : Arc<dyn Texture<f32>>,
+    pub eta             : Arc<dyn Texture<f32>>,
+    pub specular        : Option<Arc<dyn Texture<f32>>>,
+    pub roughness       : Arc<dyn Texture<f32>>,
+    pub specular_tint   : Arc<dyn Texture<f32>>,
+    pub anisotropic     : Arc<dyn Texture<f32>>,
+    pub sheen           : Arc<dyn Texture<f32>>,
+    pub sheen_tint      : Arc<dyn Texture<f32>>,
+    pub clearcoat       : Arc<dyn Texture<f32>>,
+    pub clearcoat_gloss : Arc<dyn Texture<f32>>,
+    pub spec_trans      : Arc<dyn Texture<f32>>,
+    pub flatness        : Arc<dyn Texture<f32>>,
+    pub diff_trans      : Arc<dyn Texture<f32>>,
+    pub thin            : bool,
+}
+
+fn constant(v : f32) -> Arc<dyn Texture<f32>> {
+    Arc::new(ConstantTexture::new(v))
+}
+
+impl DisneyMaterial {
+    // A non-metallic, half-rough dielectric of the given colour.  Other
+    // parameters are set through the fields.
+    pub fn new(color : Arc<dyn Texture<Color>>) -> DisneyMaterial {
+        DisneyMaterial {
+            color: color,
+            metallic: constant(0f32),
+            eta: constant(1.5f32),
+            specular: None,
+            roughness: constant(0.5f32),
+            specular_tint: constant(0f32),
+            anisotropic: constant(0f32),
+            sheen: constant(0f32),
+            sheen_tint: constant(0.5f32),
+            clearcoat: constant(0f32),
+            clearcoat_gloss: constant(1f32),
+            spec_trans: constant(0f32),
+            flatness: constant(0f32),
+            diff_trans: constant(1f32),
+            thin: false,
+        }
+    }
+
+    pub fn constant(color : Color) -> DisneyMaterial {
+        DisneyMaterial::new(Arc::new(ConstantTexture::new(color)))
+    }
+}
+
+fn mix(t : f32, a : Color, b : Color) -> Color {
+    a * (1f32 - t) + b * t
+}
+
+impl Material for DisneyMaterial {
+    fn bsdf(&self, context : &SurfaceContext, _allow_multiple_lobes : bool) -> BSDF {
+        let c = self.color.evaluate(context).clamp(0f32, f32::INFINITY);
+        let metallic = self.metallic.evaluate(context);
+        let eta = match self.specular {
+            Some(ref s) => eta_from_specular(s.evaluate(context)),
+            None        => self.eta.evaluate(context),
+        };
+        let roughness = self.roughness.evaluate(context);
+        let spec_trans = self.spec_trans.evaluate(context);
+        let diffuse_weight = (1f32 - metallic) * (1f32 - spec_trans);
+        let dt = self.diff_trans.evaluate(context) / 2f32;
+
+        let mut bsdf = BSDF::new(context, eta);
+
+        // the hue and saturation of the base colour, for tinting
+        let lum = c.luminance();
+        let tint = if lum > 0f32 { c / lum } else { Color::white() };
+
+        if diffuse_weight > 0f32 {
+            if self.thin {
+                let flat = self.flatness.evaluate(context);
+                bsdf.add(Box::new(DisneyDiffuse::new(c * (diffuse_weight * (1f32 - flat) * (1f32 - dt)))));
+                bsdf.add(Box::new(DisneyFakeSS::new(c * (diffuse_weight * flat * (1f32 - dt)), roughness)));
+            } else {
+                bsdf.add(Box::new(DisneyDiffuse::new(c * diffuse_weight)));
+            }
+            bsdf.add(Box::new(DisneyRetro::new(c * diffuse_weight, roughness)));
+
+            let sheen = self.sheen.evaluate(context);
+            if sheen > 0f32 {
+                let sheen_color = mix(self.sheen_tint.evaluate(context), Color::white(), tint);
+                bsdf.add(Box::new(DisneySheen::new(sheen_color * (diffuse_weight * sheen))));
+            }
+        }
+
+        let aspect = (1f32 - self.anisotropic.evaluate(context) * 0.9f32).sqrt();
+        let alphas = |r : f32| ((r * r / aspect).max(1e-3f32), (r * r * aspect).max(1e-3f32));
+        let (ax, ay) = alphas(roughness);
+
+        let r0 = mix(metallic, mix(self.specular_tint.evaluate(context), Color::white(), tint) * schlick_r0_from_eta(eta), c);
+        bsdf.add(Box::new(MicrofacetReflection::new(Color::white(), Box::new(DisneyMicrofacetDistribution::new(ax, ay)),
+                                                    Box::new(DisneyFresnel::new(r0, metallic, eta)))));
+
+        let clearcoat = self.clearcoat.evaluate(context);
+        if clearcoat > 0f32 {
+            let gloss = self.clearcoat_gloss.evaluate(context);
+            bsdf.add(Box::new(DisneyClearcoat::new(clearcoat, 0.1f32 * (1f32 - gloss) + 0.001f32 * gloss)));
+        }
+
+        if spec_trans > 0f32 {
+            let t = c.sqrt() * spec_trans;
+            // thin surfaces don't refract, so their roughness is scaled to
+            // give a similar spread of transmitted light
+            let distribution : Box<dyn MicrofacetDistribution> = if self.thin {
+                let (ax, ay) = alphas((0.65f32 * eta - 0.35f32) * roughness);
+                Box::new(TrowbridgeReitzDistribution::new(ax, ay))
+            } else {
+                Box::new(DisneyMicrofacetDistribution::new(ax, ay))
+            };
+            bsdf.add(Box::new(MicrofacetTransmission::new(t, distribution, 1f32, eta)));
+        }
+
+        if self.thin && dt > 0f32 {
+            bsdf.add(Box::new(LambertianTransmission::new(c * dt)));
+        }
+
+        bsdf
+    }
+}
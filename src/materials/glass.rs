@@ -0,0 +1,70 @@
+use std::sync::Arc;
+
+use crate::color::Color;
+use crate::materials::Material;
+use crate::reflection::{BSDF, FresnelSpecular, SpecularReflection, SpecularTransmission, FresnelDielectric};
+use crate::reflection::{MicrofacetReflection, MicrofacetTransmission, TrowbridgeReitzDistribution, roughness_to_alpha};
+use crate::shapes::SurfaceContext;
+use crate::textures::{Texture, ConstantTexture};
+
+// A dielectric such as glass or water, with index of refraction eta on the
+// inside (the side the surface normal points away from) and 1 outside.  It is
+// smooth unless given a roughness, which works as for MetalMaterial.
+pub struct GlassMaterial {
+    pub kr              : Arc<dyn Texture<Color>>,
+    pub kt              : Arc<dyn Texture<Color>>,
+    pub eta             : f32,
+    pub u_roughness     : Arc<dyn Texture<f32>>,
+    pub v_roughness     : Arc<dyn Texture<f32>>,
+    pub remap_roughness : bool,
+}
+
+impl GlassMaterial {
+    pub fn new(kr : Arc<dyn Texture<Color>>, kt : Arc<dyn Texture<Color>>, eta : f32) -> GlassMaterial {
+        GlassMaterial::new_rough(kr, kt, eta, Arc::new(ConstantTexture::new(0f32)), Arc::new(ConstantTexture::new(0f32)), false)
+    }
+
+    pub fn new_rough(kr : Arc<dyn Texture<Color>>, kt : Arc<dyn Texture<Color>>, eta : f32, u_roughness : Arc<dyn Texture<f32>>, v_roughness : Arc<dyn Texture<f32>>, remap_roughness : bool) -> GlassMaterial {
+        GlassMaterial { kr: kr, kt: kt, eta: eta, u_roughness: u_roughness, v_roughness: v_roughness, remap_roughness: remap_roughness }
+    }
+
+    pub fn constant(kr : Color, kt : Color, eta : f32) -> GlassMaterial {
+        GlassMaterial::new(Arc::new(ConstantTexture::new(kr)), Arc::new(ConstantTexture::new(kt)), eta)
+    }
+}
+
+impl Material for GlassMaterial {
+    fn bsdf(&self, context : &SurfaceContext, allow_multiple_lobes : bool) -> BSDF {
+        let mut bsdf = BSDF::new(context, self.eta);
+        let r = self.kr.evaluate(context).clamp(0f32, f32::INFINITY);
+        let t = self.kt.evaluate(context).clamp(0f32, f32::INFINITY);
+
+        let mut u = self.u_roughness.evaluate(context);
+        let mut v = self.v_roughness.evaluate(context);
+
+        if u == 0f32 && v == 0f32 {
+            if allow_multiple_lobes && !r.is_black() && !t.is_black() {
+                bsdf.add(Box::new(FresnelSpecular::new(r, t, 1f32, self.eta)));
+            } else {
+                if !r.is_black() {
+                    bsdf.add(Box::new(SpecularReflection::new(r, Box::new(FresnelDielectric::new(1f32, self.eta)))));
+                }
+                if !t.is_black() {
+                    bsdf.add(Box::new(SpecularTransmission::new(t, 1f32, self.eta)));
+                }
+            }
+        } else {
+            if self.remap_roughness {
+                u = roughness_to_alpha(u);
+                v = roughness_to_alpha(v);
+            }
+            if !r.is_black() {
+                bsdf.add(Box::new(MicrofacetReflection::new(r, Box::new(TrowbridgeReitzDistribution::new(u, v)), Box::new(FresnelDielectric::new(1f32, self.eta)))));
+            }
+            if !t.is_black() {
+                bsdf.add(Box::new(MicrofacetTransmission::new(t, Box::new(TrowbridgeReitzDistribution::new(u, v)), 1f32, self.eta)));
+            }
+        }
+        bsdf
+    }
+}
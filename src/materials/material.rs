@@ -0,0 +1,12 @@
+use crate::reflection::BSDF;
+use crate::shapes::SurfaceContext;
+
+// The appearance of a surface: which lobes scatter light where, given the
+// world-space surface context of a hit.
+pub trait Material : Send + Sync {
+    // When allow_multiple_lobes is set, a material may combine lobes into one
+    // that chooses between them (as glass does with reflection and
+    // transmission); otherwise each lobe is separate, for integrators that
+    // follow every specular lobe themselves.
+    fn bsdf(&self, context : &SurfaceContext, allow_multiple_lobes : bool) -> BSDF;
+}
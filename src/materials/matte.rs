@@ -0,0 +1,40 @@
+use std::sync::Arc;
+
+use crate::color::Color;
+use crate::materials::Material;
+use crate::reflection::{BSDF, LambertianReflection};
+use crate::shapes::SurfaceContext;
+use crate::textures::{Texture, ConstantTexture};
+
+// A purely diffuse surface.
+pub struct MatteMaterial {
+    pub kd : Arc<dyn Texture<Color>>,
+}
+
+impl MatteMaterial {
+    pub fn new(kd : Arc<dyn Texture<Color>>) -> MatteMaterial {
+        MatteMaterial { kd: kd }
+    }
+
+    pub fn constant(kd : Color) -> MatteMaterial {
+        MatteMaterial::new(Arc::new(ConstantTexture::new(kd)))
+    }
+}
+
+// A mid-grey, which is what surfaces without a material look like.
+impl Default for MatteMaterial {
+    fn default() -> MatteMaterial {
+        MatteMaterial::constant(Color::gray(0.5f32))
+    }
+}
+
+impl Material for MatteMaterial {
+    fn bsdf(&self, context : &SurfaceContext, _allow_multiple_lobes : bool) -> BSDF {
+        let mut bsdf = BSDF::new(context, 1f32);
+        let r = self.kd.evaluate(context).clamp(0f32, f32::INFINITY);
+        if !r.is_black() {
+            bsdf.add(Box::new(LambertianReflection::new(r)));
+        }
+        bsdf
+    }
+}
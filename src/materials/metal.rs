@@ -0,0 +1,53 @@
+use std::sync::Arc;
+
+use crate::color::Color;
+use crate::materials::Material;
+use crate::reflection::{BSDF, MicrofacetReflection, TrowbridgeReitzDistribution, FresnelConductor, roughness_to_alpha};
+use crate::shapes::SurfaceContext;
+use crate::textures::{Texture, ConstantTexture};
+
+// The complex indices of refraction (eta, k) of some common metals, at the
+// sRGB primaries.
+pub const GOLD      : (Color, Color) = (Color { r: 0.143119f32, g: 0.374957f32, b: 1.44248f32 }, Color { r: 3.98316f32, g: 2.38572f32, b: 1.60322f32 });
+pub const SILVER    : (Color, Color) = (Color { r: 0.155265f32, g: 0.116723f32, b: 0.138342f32 }, Color { r: 4.82835f32, g: 3.12225f32, b: 2.14696f32 });
+pub const COPPER    : (Color, Color) = (Color { r: 0.200438f32, g: 0.924033f32, b: 1.10221f32 }, Color { r: 3.91295f32, g: 2.45285f32, b: 2.14219f32 });
+pub const ALUMINIUM : (Color, Color) = (Color { r: 1.65746f32, g: 0.880369f32, b: 0.521229f32 }, Color { r: 9.22387f32, g: 6.26952f32, b: 4.837f32 });
+
+// A conductor with a rough surface, whose roughness may differ along dpdu
+// (u_roughness) and dpdv (v_roughness).  With remap_roughness, roughnesses
+// are perceptual values in [0, 1] rather than microfacet alphas.
+pub struct MetalMaterial {
+    pub eta             : Arc<dyn Texture<Color>>,
+    pub k               : Arc<dyn Texture<Color>>,
+    pub u_roughness     : Arc<dyn Texture<f32>>,
+    pub v_roughness     : Arc<dyn Texture<f32>>,
+    pub remap_roughness : bool,
+}
+
+impl MetalMaterial {
+    pub fn new(eta : Arc<dyn Texture<Color>>, k : Arc<dyn Texture<Color>>, u_roughness : Arc<dyn Texture<f32>>, v_roughness : Arc<dyn Texture<f32>>, remap_roughness : bool) -> MetalMaterial {
+        MetalMaterial { eta: eta, k: k, u_roughness: u_roughness, v_roughness: v_roughness, remap_roughness: remap_roughness }
+    }
+
+    pub fn constant((eta, k) : (Color, Color), u_roughness : f32, v_roughness : f32) -> MetalMaterial {
+        MetalMaterial::new(Arc::new(ConstantTexture::new(eta)), Arc::new(ConstantTexture::new(k)),
+                           Arc::new(ConstantTexture::new(u_roughness)), Arc::new(ConstantTexture::new(v_roughness)), true)
+    }
+}
+
+impl Material for MetalMaterial {
+    fn bsdf(&self, context : &SurfaceContext, _allow_multiple_lobes : bool) -> BSDF {
+        let mut bsdf = BSDF::new(context, 1f32);
+
+        let mut u = self.u_roughness.evaluate(context);
+        let mut v = self.v_roughness.evaluate(context);
+        if self.remap_roughness {
+            u = roughness_to_alpha(u);
+            v = roughness_to_alpha(v);
+        }
+
+        let fresnel = FresnelConductor::new(Color::white(), self.eta.evaluate(context), self.k.evaluate(context));
+        bsdf.add(Box::new(MicrofacetReflection::new(Color::white(), Box::new(TrowbridgeReitzDistribution::new(u, v)), Box::new(fresnel))));
+        bsdf
+    }
+}
@@ -0,0 +1,33 @@
+use std::sync::Arc;
+
+use crate::color::Color;
+use crate::materials::Material;
+use crate::reflection::{BSDF, SpecularReflection, FresnelNoOp};
+use crate::shapes::SurfaceContext;
+use crate::textures::{Texture, ConstantTexture};
+
+// A perfect mirror, tinted by kr.
+pub struct MirrorMaterial {
+    pub kr : Arc<dyn Texture<Color>>,
+}
+
+impl MirrorMaterial {
+    pub fn new(kr : Arc<dyn Texture<Color>>) -> MirrorMaterial {
+        MirrorMaterial { kr: kr }
+    }
+
+    pub fn constant(kr : Color) -> MirrorMaterial {
+        MirrorMaterial::new(Arc::new(ConstantTexture::new(kr)))
+    }
+}
+
+impl Material for MirrorMaterial {
+    fn bsdf(&self, context : &SurfaceContext, _allow_multiple_lobes : bool) -> BSDF {
+        let mut bsdf = BSDF::new(context, 1f32);
+        let r = self.kr.evaluate(context).clamp(0f32, f32::INFINITY);
+        if !r.is_black() {
+            bsdf.add(Box::new(SpecularReflection::new(r, Box::new(FresnelNoOp))));
+        }
+        bsdf
+    }
+}
@@ -0,0 +1,15 @@
+pub mod disney;
+pub mod glass;
+pub mod material;
+pub mod matte;
+pub mod metal;
+pub mod mirror;
+pub mod plastic;
+
+pub use disney::*;
+pub use glass::*;
+pub use material::*;
+pub use matte::*;
+pub use metal::*;
+pub use mirror::*;
+pub use plastic::*;
@@ -0,0 +1,49 @@
+use std::sync::Arc;
+
+use crate::color::Color;
+use crate::materials::Material;
+use crate::reflection::{BSDF, LambertianReflection, MicrofacetReflection, TrowbridgeReitzDistribution, FresnelDielectric, roughness_to_alpha};
+use crate::shapes::SurfaceContext;
+use crate::textures::{Texture, ConstantTexture};
+
+// A diffuse base (kd) under a glossy dielectric coat (ks) of the given
+// roughness, as with plastics and paints.
+pub struct PlasticMaterial {
+    pub kd              : Arc<dyn Texture<Color>>,
+    pub ks              : Arc<dyn Texture<Color>>,
+    pub roughness       : Arc<dyn Texture<f32>>,
+    pub remap_roughness : bool,
+}
+
+impl PlasticMaterial {
+    pub fn new(kd : Arc<dyn Texture<Color>>, ks : Arc<dyn Texture<Color>>, roughness : Arc<dyn Texture<f32>>, remap_roughness : bool) -> PlasticMaterial {
+        PlasticMaterial { kd: kd, ks: ks, roughness: roughness, remap_roughness: remap_roughness }
+    }
+
+    pub fn constant(kd : Color, ks : Color, roughness : f32) -> PlasticMaterial {
+        PlasticMaterial::new(Arc::new(ConstantTexture::new(kd)), Arc::new(ConstantTexture::new(ks)), Arc::new(ConstantTexture::new(roughness)), true)
+    }
+}
+
+impl Material for PlasticMaterial {
+    fn bsdf(&self, context : &SurfaceContext, _allow_multiple_lobes : bool) -> BSDF {
+        let mut bsdf = BSDF::new(context, 1f32);
+
+        let kd = self.kd.evaluate(context).clamp(0f32, f32::INFINITY);
+        if !kd.is_black() {
+            bsdf.add(Box::new(LambertianReflection::new(kd)));
+        }
+
+        let ks = self.ks.evaluate(context).clamp(0f32, f32::INFINITY);
+        if !ks.is_black() {
+            let mut alpha = self.roughness.evaluate(context);
+            if self.remap_roughness {
+                alpha = roughness_to_alpha(alpha);
+            }
+            let fresnel = FresnelDielectric::new(1.5f32, 1f32);
+            bsdf.add(Box::new(MicrofacetReflection::new(ks, Box::new(TrowbridgeReitzDistribution::new(alpha, alpha)), Box::new(fresnel))));
+        }
+
+        bsdf
+    }
+}
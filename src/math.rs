@@ -1,3 +1,5 @@
+use crate::efloat::EFloat;
+
 pub fn quadratic(a : f32, b : f32, c : f32) -> Option<(f32, f32)> {
     let d = b*b - 4f32*a*c;
     if d < 0f32 {
@@ -10,6 +12,37 @@ pub fn quadratic(a : f32, b : f32, c : f32) -> Option<(f32, f32)> {
     }
 }
 
+// As quadratic(), with bounds on the roots that take in the error in the
+// coefficients as well as that of solving.  The discriminant is found in
+// double precision, as that is where most of the rounding would come from.
+pub fn quadratic_efloat(a : EFloat, b : EFloat, c : EFloat) -> Option<(EFloat, EFloat)> {
+    let (av, bv, cv) = (a.v as f64, b.v as f64, c.v as f64);
+    let discrim = bv * bv - 4f64 * av * cv;
+    if discrim < 0f64 {
+        return None;
+    }
+
+    // how far the discriminant could be out given the coefficients' errors
+    let (ea, eb, ec) = (a.absolute_error() as f64, b.absolute_error() as f64, c.absolute_error() as f64);
+    let discrim_error = 2f64 * bv.abs() * eb + eb * eb + 4f64 * (av.abs() * ec + cv.abs() * ea + ea * ec);
+    let root = discrim.sqrt();
+    let root_error = (root - (discrim - discrim_error).max(0f64).sqrt()).max((discrim + discrim_error).sqrt() - root);
+    let root_discrim = EFloat::new(root as f32, root_error as f32 + gamma(1) * root as f32);
+
+    let half = EFloat::from(-0.5f32);
+    let q = if b.v < 0f32 { half * (b - root_discrim) } else { half * (b + root_discrim) };
+    let t0 = q / a;
+    let t1 = c / q;
+    if t0.v > t1.v { Some((t1, t0)) } else { Some((t0, t1)) }
+}
+
+// A bound on the relative error of n floating point operations in a row,
+// each of which rounds by at most half an ulp.
+pub fn gamma(n : i32) -> f32 {
+    let e = n as f32 * f32::EPSILON * 0.5f32;
+    e / (1f32 - e)
+}
+
 pub fn radical_inverse(n : u32, b : u32) -> f32 {
     helper(0f32, n, 1f32 / (b as f32), 1f32 / (b as f32), b)
 }
@@ -46,3 +79,52 @@ pub fn reverse_bits(n : u32) -> u32 {
     r = (r & 0x55555555) >> 1 | (r & 0xAAAAAAAA) << 1;
     r
 }
+
+// The error function, from Abramowitz and Stegun 7.1.26 (error below 1.5e-7).
+pub fn erf(x : f32) -> f32 {
+    let (a1, a2, a3, a4, a5, p) = (0.254829592f64, -0.284496736f64, 1.421413741f64, -1.453152027f64, 1.061405429f64, 0.3275911f64);
+
+    let sign = if x < 0f32 { -1f64 } else { 1f64 };
+    let x = (x as f64).abs();
+    let t = 1f64 / (1f64 + p * x);
+    let y = 1f64 - (((((a5 * t + a4) * t) + a3) * t + a2) * t + a1) * t * (-x * x).exp();
+    (sign * y) as f32
+}
+
+// The inverse of erf, from Giles' single precision approximation.
+pub fn erf_inv(x : f32) -> f32 {
+    let x = x.clamp(-0.99999f32, 0.99999f32) as f64;
+    let mut w = -((1f64 - x) * (1f64 + x)).ln();
+    let p = if w < 5f64 {
+        w -= 2.5f64;
+        [3.43273939e-07f64, -3.5233877e-06f64, -4.39150654e-06f64, 0.00021858087f64, -0.00125372503f64,
+         -0.00417768164f64, 0.246640727f64, 1.50140941f64].iter().fold(2.81022636e-08f64, |p, c| c + p * w)
+    } else {
+        w = w.sqrt() - 3f64;
+        [0.000100950558f64, 0.00134934322f64, -0.00367342844f64, 0.00573950773f64, -0.0076224613f64,
+         0.00943887047f64, 1.00167406f64, 2.83297682f64].iter().fold(-0.000200214257f64, |p, c| c + p * w)
+    };
+    (p * x) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contains(e : &EFloat, x : f64) -> bool {
+        e.lower_bound() as f64 <= x && x <= e.upper_bound() as f64
+    }
+
+    // The roots of (t - 1)(t - 1e4) are bounded closely, however different
+    // their sizes, and error in the coefficients widens the bounds to match.
+    #[test]
+    fn test_quadratic_efloat() {
+        let (t0, t1) = quadratic_efloat(EFloat::from(1f32), EFloat::from(-10001f32), EFloat::from(1e4f32)).unwrap();
+        assert!(contains(&t0, 1f64) && contains(&t1, 1e4f64));
+        assert!(t0.absolute_error() < 1e-5f32);
+
+        let (t0, t1) = quadratic_efloat(EFloat::new(1f32, 1e-3f32), EFloat::from(0f32), EFloat::from(-4f32)).unwrap();
+        assert!(contains(&t0, -2f64 / 1.001f64.sqrt()) && contains(&t1, 2f64 / 0.999f64.sqrt()));
+        assert!(quadratic_efloat(EFloat::from(1f32), EFloat::from(0f32), EFloat::from(1f32)).is_none());
+    }
+}
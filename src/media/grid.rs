@@ -0,0 +1,204 @@
+use std::sync::Arc;
+
+use crate::color::Color;
+use crate::geometry::{Transform, Trans, TransMut, HasTransform, BoundingBox, Point, Ray};
+use crate::media::{Medium, MediumSample, PhaseFunction, HenyeyGreenstein};
+use crate::sampler::Sampler;
+
+// A medium whose density varies over a grid of nx × ny × nz voxels filling
+// the unit cube, until transformed, with densities given x fastest, then y,
+// then z, and interpolated between voxel centres.  Light is absorbed at rate
+// sigma_a and scattered at rate sigma_s per unit distance, scaled by the
+// density.  As in pbrt-v3 the medium is sampled by delta tracking against
+// its greatest density, which needs its extinction to be the same in every
+// channel, so only the first channel's is used for that.
+#[derive(Clone)]
+pub struct GridDensityMedium {
+    transform       : Transform,
+    sigma_s         : Color,
+    sigma_t         : f32,
+    phase           : HenyeyGreenstein,
+    nx              : usize,
+    ny              : usize,
+    nz              : usize,
+    density         : Arc<Vec<f32>>,
+    inv_max_density : f32,
+}
+
+impl GridDensityMedium {
+    pub fn new(sigma_a : Color, sigma_s : Color, g : f32, nx : usize, ny : usize, nz : usize, density : Vec<f32>) -> GridDensityMedium {
+        assert_eq!(density.len(), nx * ny * nz, "grid size must match its densities");
+        let max_density = density.iter().fold(0f32, |m, d| m.max(*d));
+        GridDensityMedium {
+            transform: Transform::identity(),
+            sigma_s: sigma_s,
+            sigma_t: sigma_a[0] + sigma_s[0],
+            phase: HenyeyGreenstein::new(g),
+            nx: nx,
+            ny: ny,
+            nz: nz,
+            density: Arc::new(density),
+            inv_max_density: if max_density > 0f32 { 1f32 / max_density } else { 0f32 },
+        }
+    }
+
+    // The density of the voxel at (x, y, z), which is zero outside the grid.
+    fn d(&self, x : i64, y : i64, z : i64) -> f32 {
+        if x < 0 || y < 0 || z < 0 || x >= self.nx as i64 || y >= self.ny as i64 || z >= self.nz as i64 {
+            return 0f32;
+        }
+        self.density[(z as usize * self.ny + y as usize) * self.nx + x as usize]
+    }
+
+    // The density at p, in the medium's space.
+    pub fn density(&self, p : &Point) -> f32 {
+        let (x, y, z) = (p.x * self.nx as f32 - 0.5f32, p.y * self.ny as f32 - 0.5f32, p.z * self.nz as f32 - 0.5f32);
+        let (ix, iy, iz) = (x.floor(), y.floor(), z.floor());
+        let (dx, dy, dz) = (x - ix, y - iy, z - iz);
+        let (ix, iy, iz) = (ix as i64, iy as i64, iz as i64);
+
+        let lerp = |t : f32, a : f32, b : f32| a + (b - a) * t;
+        let d00 = lerp(dx, self.d(ix, iy, iz), self.d(ix + 1, iy, iz));
+        let d10 = lerp(dx, self.d(ix, iy + 1, iz), self.d(ix + 1, iy + 1, iz));
+        let d01 = lerp(dx, self.d(ix, iy, iz + 1), self.d(ix + 1, iy, iz + 1));
+        let d11 = lerp(dx, self.d(ix, iy + 1, iz + 1), self.d(ix + 1, iy + 1, iz + 1));
+        lerp(dz, lerp(dy, d00, d10), lerp(dy, d01, d11))
+    }
+
+    // r in the medium's space, with its parameter measuring distance in
+    // world space, and the range of that parameter over which it is within
+    // the grid before t_max.
+    fn clip(&self, r : &Ray, t_max : f32) -> Option<(Ray, f32, f32)> {
+        let length = r.direction.magnitude();
+        let ray = Ray::new_bounded(&r.origin, &(r.direction / length), 0f32, t_max * length).to(self);
+        let unit = BoundingBox::for_points(&[Point::origin(), Point::new(1f32, 1f32, 1f32)]);
+        unit.intersects(&ray).map(|(t0, t1)| (ray, t0, t1))
+    }
+}
+
+impl HasTransform for GridDensityMedium {
+    fn get_transform(&self) -> &Transform {
+        &self.transform
+    }
+}
+
+impl Medium for GridDensityMedium {
+    // Ratio tracking: each tentative collision, at the rate of the greatest
+    // density, lets through the fraction of light that a real one would not
+    // have stopped.
+    fn tr(&self, r : &Ray, t_max : f32, sampler : &mut dyn Sampler) -> Color {
+        let (ray, t_min, t_max) = match self.clip(r, t_max) {
+            Some(c) if self.inv_max_density > 0f32 => c,
+            _                                      => return Color::white(),
+        };
+
+        let mut tr = 1f32;
+        let mut t = t_min;
+        loop {
+            t -= (1f32 - sampler.get_1d()).ln() * self.inv_max_density / self.sigma_t;
+            if t >= t_max {
+                break;
+            }
+            tr *= 1f32 - (self.density(&ray.at_time(t)) * self.inv_max_density).max(0f32);
+            if tr <= 0f32 {
+                break;
+            }
+        }
+        Color::gray(tr)
+    }
+
+    // Delta tracking: tentative collisions are taken at the rate of the
+    // greatest density, and each is real with the ratio of the density there
+    // to the greatest.
+    fn sample(&self, r : &Ray, t_max : f32, sampler : &mut dyn Sampler) -> MediumSample {
+        let (ray, t_min, t_max) = match self.clip(r, t_max) {
+            Some(c) if self.inv_max_density > 0f32 => c,
+            _                                      => return MediumSample::new(Color::white(), None),
+        };
+
+        let mut t = t_min;
+        loop {
+            t -= (1f32 - sampler.get_1d()).ln() * self.inv_max_density / self.sigma_t;
+            if t >= t_max {
+                return MediumSample::new(Color::white(), None);
+            }
+            if self.density(&ray.at_time(t)) * self.inv_max_density > sampler.get_1d() {
+                return MediumSample::new(self.sigma_s / self.sigma_t, Some(ray.at_time(t).from(self)));
+            }
+        }
+    }
+
+    fn phase(&self) -> &dyn PhaseFunction {
+        &self.phase
+    }
+}
+
+impl Trans for GridDensityMedium {
+    type Output=GridDensityMedium;
+
+    fn transform(&self, t : &Transform) -> GridDensityMedium {
+        GridDensityMedium { transform: *t + self.transform, .. self.clone() }
+    }
+}
+
+impl TransMut for GridDensityMedium {
+    fn transform_self(&mut self, t : &Transform) {
+        self.transform = *t + self.transform;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Vector;
+    use crate::media::HomogeneousMedium;
+    use crate::sampler::RandomSampler;
+
+    #[test]
+    fn test_density() {
+        let medium = GridDensityMedium::new(Color::gray(1f32), Color::gray(1f32), 0f32, 2, 1, 1, vec![1f32, 3f32]);
+        assert_eq!(medium.density(&Point::new(0.25f32, 0.5f32, 0.5f32)), 1f32);
+        assert_eq!(medium.density(&Point::new(0.5f32, 0.5f32, 0.5f32)), 2f32);
+        assert_eq!(medium.density(&Point::new(0.75f32, 0.5f32, 0.5f32)), 3f32);
+        // the density falls away towards zero past the outermost centres
+        assert_eq!(medium.density(&Point::new(0.75f32, 0.5f32, 0.75f32)), 2.25f32);
+        assert_eq!(medium.density(&Point::new(2f32, 0.5f32, 0.5f32)), 0f32);
+    }
+
+    // A uniform grid, stretched over a box of side 2, lets through as much
+    // light as a homogeneous medium away from the box's faces, and none of
+    // it is outside the box.
+    #[test]
+    fn test_uniform_grid() {
+        let (sigma_a, sigma_s) = (Color::gray(0.2f32), Color::gray(0.3f32));
+        let grid = GridDensityMedium::new(sigma_a, sigma_s, 0f32, 4, 4, 4, vec![1f32; 64])
+            .transform(&(Transform::translation(&Vector::new(-1f32, -1f32, -1f32)) + Transform::scaling(&Vector::new(2f32, 2f32, 2f32))));
+        let homogeneous = HomogeneousMedium::new(sigma_a, sigma_s, 0f32);
+
+        // from the centre of the box most of the way to its far side
+        let r = Ray::new(&Point::new(0.1f32, 0.2f32, 0f32), &Vector::new(0f32, 0f32, 0.5f32));
+        let mut sampler = RandomSampler::new(1);
+        let expected = homogeneous.tr(&r, 1.2f32, &mut sampler)[0];
+        assert!((expected - (-0.3f32).exp()).abs() < 1e-6f32);
+
+        let n = 20000;
+        let (mut tr, mut passed) = (0f32, 0f32);
+        for _ in 0..n {
+            tr += grid.tr(&r, 1.2f32, &mut sampler)[0];
+            let s = grid.sample(&r, 1.2f32, &mut sampler);
+            match s.p {
+                Some(p) => {
+                    assert!(p.z > 0f32 && p.z < 0.6f32);
+                    assert_eq!(s.beta, Color::gray(0.6f32));
+                },
+                None    => passed += 1f32,
+            }
+        }
+        assert!((tr / n as f32 - expected).abs() < 0.01f32);
+        assert!((passed / n as f32 - expected).abs() < 0.01f32);
+
+        let outside = Ray::new(&Point::new(2f32, 0f32, 0f32), &Vector::unit_y());
+        assert_eq!(grid.tr(&outside, 10f32, &mut sampler), Color::white());
+        assert!(grid.sample(&outside, 10f32, &mut sampler).p.is_none());
+    }
+}
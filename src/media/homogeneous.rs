@@ -0,0 +1,94 @@
+use crate::color::Color;
+use crate::geometry::Ray;
+use crate::media::{Medium, MediumSample, PhaseFunction, HenyeyGreenstein};
+use crate::sampler::Sampler;
+
+// A medium of the same density throughout, absorbing light at rate sigma_a
+// and scattering it at rate sigma_s per unit distance.
+#[derive(Copy, Clone, Debug)]
+pub struct HomogeneousMedium {
+    sigma_s : Color,
+    sigma_t : Color,
+    phase   : HenyeyGreenstein,
+}
+
+impl HomogeneousMedium {
+    pub fn new(sigma_a : Color, sigma_s : Color, g : f32) -> HomogeneousMedium {
+        HomogeneousMedium { sigma_s: sigma_s, sigma_t: sigma_a + sigma_s, phase: HenyeyGreenstein::new(g) }
+    }
+
+    // The transmittance over distance d.
+    fn tr_distance(&self, d : f32) -> Color {
+        (self.sigma_t * -d.min(f32::MAX)).exp()
+    }
+}
+
+impl Medium for HomogeneousMedium {
+    fn tr(&self, r : &Ray, t_max : f32, _sampler : &mut dyn Sampler) -> Color {
+        self.tr_distance(t_max * r.direction.magnitude())
+    }
+
+    // A channel is chosen at random and the distance sampled by its
+    // extinction, with the density averaged over the channels, so that every
+    // channel is sampled well.
+    fn sample(&self, r : &Ray, t_max : f32, sampler : &mut dyn Sampler) -> MediumSample {
+        let channel = ((sampler.get_1d() * 3f32) as usize).min(2);
+        let length = r.direction.magnitude();
+        let distance = -(1f32 - sampler.get_1d()).ln() / self.sigma_t[channel];
+        let t = (distance / length).min(t_max);
+        let scattered = t < t_max;
+
+        let tr = self.tr_distance(t * length);
+        let density = if scattered { self.sigma_t * tr } else { tr };
+        let pdf = (density[0] + density[1] + density[2]) / 3f32;
+        if pdf == 0f32 {
+            return MediumSample::new(Color::black(), None);
+        }
+
+        if scattered {
+            MediumSample::new(tr * self.sigma_s / pdf, Some(r.at_time(t)))
+        } else {
+            MediumSample::new(tr / pdf, None)
+        }
+    }
+
+    fn phase(&self) -> &dyn PhaseFunction {
+        &self.phase
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::{Point, Vector};
+    use crate::sampler::RandomSampler;
+
+    #[test]
+    fn test_homogeneous() {
+        let medium = HomogeneousMedium::new(Color::new(0.1f32, 0.2f32, 0.3f32), Color::gray(0.4f32), 0f32);
+        let r = Ray::new(&Point::origin(), &Vector::new(0f32, 0f32, 2f32));
+        let mut sampler = RandomSampler::new(1);
+        assert_eq!(medium.tr(&r, 1f32, &mut sampler), Color::new((-1f32).exp(), (-1.2f32).exp(), (-1.4f32).exp()));
+
+        // the weights of the paths that pass through average the
+        // transmittance, and of those that scatter the scattered fraction
+        let n = 100000;
+        let (mut passed, mut scattered) = (Color::black(), Color::black());
+        for _ in 0..n {
+            let s = medium.sample(&r, 1f32, &mut sampler);
+            match s.p {
+                Some(p) => {
+                    assert!(p.z >= 0f32 && p.z < 2f32);
+                    scattered.add_self_c(&s.beta);
+                },
+                None    => passed.add_self_c(&s.beta),
+            }
+        }
+        let tr = medium.tr(&r, 1f32, &mut sampler);
+        for c in 0..3 {
+            assert!((passed[c] / n as f32 - tr[c]).abs() < 0.01f32);
+            let albedo = 0.4f32 / medium.sigma_t[c];
+            assert!((scattered[c] / n as f32 - albedo * (1f32 - tr[c])).abs() < 0.01f32);
+        }
+    }
+}
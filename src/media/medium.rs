@@ -0,0 +1,59 @@
+use std::sync::Arc;
+
+use crate::color::Color;
+use crate::geometry::{Normal, Point, Ray, Vector};
+use crate::media::PhaseFunction;
+use crate::sampler::Sampler;
+
+// A participating medium, such as fog or smoke, that absorbs and scatters
+// light passing through it.  Rays are followed through a medium from their
+// origin up to a parametric distance t_max, as with Ray::at_time(), so that
+// the distance to the next surface along a ray can be given directly.
+pub trait Medium : Send + Sync {
+    // The fraction of light that passes unabsorbed and unscattered along r
+    // from its origin to t_max, estimated with sampler if need be.
+    fn tr(&self, r : &Ray, t_max : f32, sampler : &mut dyn Sampler) -> Color;
+
+    // Samples the distance along r before t_max at which light is absorbed
+    // or scattered, by the medium's density.
+    fn sample(&self, r : &Ray, t_max : f32, sampler : &mut dyn Sampler) -> MediumSample;
+
+    // How the medium scatters the light that does not pass through it.
+    fn phase(&self) -> &dyn PhaseFunction;
+}
+
+// The outcome of Medium::sample(): the point at which light scattered, if it
+// did so before t_max, and the weight for the path so far, which accounts
+// for the transmittance up to that point, the scattering there and the
+// density of having chosen it.
+#[derive(Copy, Clone, Debug)]
+pub struct MediumSample {
+    pub beta : Color,
+    pub p    : Option<Point>,
+}
+
+impl MediumSample {
+    pub fn new(beta : Color, p : Option<Point>) -> MediumSample {
+        MediumSample { beta: beta, p: p }
+    }
+}
+
+// The media on either side of a surface, where the outside is the side its
+// normal faces.  Either may be None, for a vacuum.
+#[derive(Clone, Default)]
+pub struct MediumInterface {
+    pub inside  : Option<Arc<dyn Medium>>,
+    pub outside : Option<Arc<dyn Medium>>,
+}
+
+impl MediumInterface {
+    pub fn new(inside : Option<Arc<dyn Medium>>, outside : Option<Arc<dyn Medium>>) -> MediumInterface {
+        MediumInterface { inside: inside, outside: outside }
+    }
+
+    // The medium a ray leaving a surface with normal n in direction w
+    // travels through.
+    pub fn medium(&self, w : &Vector, n : &Normal) -> Option<Arc<dyn Medium>> {
+        if w.dot(&n.to_vector()) > 0f32 { self.outside.clone() } else { self.inside.clone() }
+    }
+}
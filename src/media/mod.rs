@@ -0,0 +1,9 @@
+pub mod grid;
+pub mod homogeneous;
+pub mod medium;
+pub mod phase;
+
+pub use grid::*;
+pub use homogeneous::*;
+pub use medium::*;
+pub use phase::*;
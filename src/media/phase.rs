@@ -0,0 +1,85 @@
+use std::f32::consts::PI;
+
+use crate::geometry::Vector;
+
+// The angular distribution of light scattered within a medium.  As with
+// BxDFs, wo points back along the light's path and wi towards where it came
+// from, both normalized.
+pub trait PhaseFunction : Send + Sync {
+    // The density, by solid angle, with which light arriving from wi is
+    // scattered towards wo.
+    fn p(&self, wo : &Vector, wi : &Vector) -> f32;
+
+    // Samples wi in proportion to p(), returning it and its density.
+    fn sample_p(&self, wo : &Vector, u : (f32, f32)) -> (Vector, f32);
+}
+
+// The Henyey-Greenstein phase function, which scatters light forwards for
+// g in (0, 1), backwards for g in (-1, 0) and evenly for g of 0, with g the
+// average cosine of the angle light is turned through.
+#[derive(Copy, Clone, Debug)]
+pub struct HenyeyGreenstein {
+    pub g : f32,
+}
+
+impl HenyeyGreenstein {
+    pub fn new(g : f32) -> HenyeyGreenstein {
+        HenyeyGreenstein { g: g }
+    }
+}
+
+// The density for an angle between wo and wi with the given cosine.
+fn phase_hg(cos_theta : f32, g : f32) -> f32 {
+    let denom = 1f32 + g * g + 2f32 * g * cos_theta;
+    (1f32 - g * g) / (4f32 * PI * denom * denom.max(0f32).sqrt())
+}
+
+impl PhaseFunction for HenyeyGreenstein {
+    fn p(&self, wo : &Vector, wi : &Vector) -> f32 {
+        phase_hg(wo.dot(wi), self.g)
+    }
+
+    fn sample_p(&self, wo : &Vector, (u1, u2) : (f32, f32)) -> (Vector, f32) {
+        let g = self.g;
+        let cos_theta = if g.abs() < 1e-3f32 {
+            1f32 - 2f32 * u1
+        } else {
+            let sqr = (1f32 - g * g) / (1f32 + g - 2f32 * g * u1);
+            -(1f32 + g * g - sqr * sqr) / (2f32 * g)
+        };
+        let sin_theta = (1f32 - cos_theta * cos_theta).max(0f32).sqrt();
+        let phi = 2f32 * PI * u2;
+
+        let (v1, v2) = wo.coordinate_system();
+        let wi = v1 * (sin_theta * phi.cos()) + v2 * (sin_theta * phi.sin()) + *wo * cos_theta;
+        (wi, phase_hg(cos_theta, g))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_henyey_greenstein() {
+        let wo = Vector::new(1f32, 2f32, -2f32).normalize();
+        for &g in [-0.7f32, 0f32, 0.3f32, 0.9f32].iter() {
+            let hg = HenyeyGreenstein::new(g);
+
+            // samples agree with p(), and turn light through an angle whose
+            // cosine averages g
+            let n = 64;
+            let mut mean_cos = 0f32;
+            for i in 0..n {
+                for j in 0..n {
+                    let (wi, pdf) = hg.sample_p(&wo, ((i as f32 + 0.5f32) / n as f32, (j as f32 + 0.5f32) / n as f32));
+                    assert!((wi.magnitude() - 1f32).abs() < 1e-4f32);
+                    assert!((hg.p(&wo, &wi) - pdf).abs() < 1e-3f32 * pdf.max(1f32));
+                    mean_cos += -wo.dot(&wi);
+                }
+            }
+            assert!((mean_cos / (n * n) as f32 - g).abs() < 0.01f32, "g = {}", g);
+        }
+        assert!((HenyeyGreenstein::new(0f32).p(&Vector::unit_x(), &Vector::unit_y()) - 1f32 / (4f32 * PI)).abs() < 1e-6f32);
+    }
+}
@@ -0,0 +1,239 @@
+use crate::color::Color;
+use crate::geometry::{Vector, Normal};
+use crate::reflection::{BxDF, BxDFSample, BxDFType, BSDF_REFLECTION, BSDF_TRANSMISSION, BSDF_SPECULAR};
+use crate::shapes::SurfaceContext;
+
+// Whether a BSDF carries radiance, from the lights towards the camera, or
+// importance, the other way, as when tracing paths from the lights.  The two
+// differ only where light is refracted, which compresses radiance by the
+// square of the ratio of the indices of refraction but leaves importance be.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TransportMode {
+    Radiance,
+    Importance,
+}
+
+// The lobes scattering light at one point on a surface.  Directions passed in
+// and out are in world space; the lobes see them in the local shading frame
+// (ss, ts, ns), built from the shading normal and dpdu.
+pub struct BSDF {
+    pub eta : f32,
+    pub ng  : Normal,
+    pub ns  : Normal,
+    pub ss  : Vector,
+    pub ts  : Vector,
+    pub mode : TransportMode,
+    bxdfs   : Vec<Box<dyn BxDF>>,
+}
+
+impl BSDF {
+    // eta is the relative index of refraction across the surface, for
+    // surfaces that transmit light, and 1 otherwise.
+    pub fn new(context : &SurfaceContext, eta : f32) -> BSDF {
+        let ns = context.shading.n.normalize();
+        let n = ns.to_vector();
+
+        // make dpdu perpendicular to the normal, or invent a tangent if it is degenerate
+        let s = context.shading.dpdu - n * n.dot(&context.shading.dpdu);
+        let ss = if s.magnitude_squared() > 1e-12f32 { s.normalize() } else { n.coordinate_system().0 };
+        let ts = n.cross(&ss);
+
+        BSDF {
+            eta: eta,
+            ng:  context.n.normalize(),
+            ns:  ns,
+            ss:  ss,
+            ts:  ts,
+            mode: TransportMode::Radiance,
+            bxdfs: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, bxdf : Box<dyn BxDF>) {
+        self.bxdfs.push(bxdf);
+    }
+
+    pub fn num_components(&self, flags : BxDFType) -> usize {
+        self.bxdfs.iter().filter(|b| b.matches(flags)).count()
+    }
+
+    pub fn has_non_specular(&self) -> bool {
+        self.bxdfs.iter().any(|b| b.bxdf_type() & BSDF_SPECULAR == 0)
+    }
+
+    pub fn world_to_local(&self, v : &Vector) -> Vector {
+        Vector::new(v.dot(&self.ss), v.dot(&self.ts), v.dot(&self.ns.to_vector()))
+    }
+
+    pub fn local_to_world(&self, v : &Vector) -> Vector {
+        self.ss * v.x + self.ts * v.y + self.ns.to_vector() * v.z
+    }
+
+    pub fn f(&self, wo_w : &Vector, wi_w : &Vector, flags : BxDFType) -> Color {
+        let wo = self.world_to_local(wo_w);
+        let wi = self.world_to_local(wi_w);
+        if wo.z == 0f32 {
+            return Color::black();
+        }
+
+        // the geometric normal decides between reflection and transmission,
+        // so that shading normals cannot leak light through the surface
+        let ng = self.ng.to_vector();
+        let reflect = wi_w.dot(&ng) * wo_w.dot(&ng) > 0f32;
+
+        let mut f = Color::black();
+        for b in self.bxdfs.iter().filter(|b| b.matches(flags)) {
+            let t = b.bxdf_type();
+            if (reflect && t & BSDF_REFLECTION != 0) || (!reflect && t & BSDF_TRANSMISSION != 0) {
+                f.add_self_c(&b.f(&wo, &wi));
+            }
+        }
+        if reflect { f } else { f * self.transmission_scale(&wo) }
+    }
+
+    // Samples one of the matching lobes, chosen with u.0, and returns the
+    // incident direction in world space along with the value and pdf of the
+    // whole BSDF for it.
+    pub fn sample_f(&self, wo_w : &Vector, u : (f32, f32), flags : BxDFType) -> Option<BxDFSample> {
+        let matching = self.num_components(flags);
+        if matching == 0 {
+            return None;
+        }
+
+        let comp = ((u.0 * matching as f32) as usize).min(matching - 1);
+        let (ix, bxdf) = self.bxdfs.iter().enumerate().filter(|(_, b)| b.matches(flags)).nth(comp).unwrap();
+
+        // reuse u.0, rescaled to [0, 1), for the chosen lobe
+        let u_remapped = ((u.0 * matching as f32 - comp as f32).min(1f32 - f32::EPSILON), u.1);
+
+        let wo = self.world_to_local(wo_w);
+        if wo.z == 0f32 {
+            return None;
+        }
+        let mut s = bxdf.sample_f(&wo, u_remapped)?;
+        if s.pdf == 0f32 {
+            return None;
+        }
+        let wi_w = self.local_to_world(&s.wi);
+
+        // specular lobes have a delta distribution, so the other lobes do not contribute
+        if s.sampled_type & BSDF_SPECULAR == 0 && matching > 1 {
+            for (jx, b) in self.bxdfs.iter().enumerate() {
+                if jx != ix && b.matches(flags) {
+                    s.pdf += b.pdf(&wo, &s.wi);
+                }
+            }
+        }
+        if matching > 1 {
+            s.pdf /= matching as f32;
+        }
+
+        if s.sampled_type & BSDF_SPECULAR == 0 {
+            s.f = self.f(wo_w, &wi_w, flags);
+        } else if s.sampled_type & BSDF_TRANSMISSION != 0 {
+            s.f = s.f * self.transmission_scale(&wo);
+        }
+
+        s.wi = wi_w;
+        Some(s)
+    }
+
+    // The lobes scale light they transmit for radiance, which importance
+    // should not see, so it is undone for importance leaving along wo.
+    fn transmission_scale(&self, wo : &Vector) -> f32 {
+        match self.mode {
+            TransportMode::Radiance   => 1f32,
+            TransportMode::Importance => if wo.z > 0f32 { self.eta * self.eta } else { 1f32 / (self.eta * self.eta) },
+        }
+    }
+
+    pub fn pdf(&self, wo_w : &Vector, wi_w : &Vector, flags : BxDFType) -> f32 {
+        let matching = self.num_components(flags);
+        if matching == 0 {
+            return 0f32;
+        }
+
+        let wo = self.world_to_local(wo_w);
+        let wi = self.world_to_local(wi_w);
+        if wo.z == 0f32 {
+            return 0f32;
+        }
+
+        let pdf : f32 = self.bxdfs.iter().filter(|b| b.matches(flags)).map(|b| b.pdf(&wo, &wi)).sum();
+        pdf / matching as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_1_PI;
+    use crate::geometry::Point;
+    use crate::reflection::{LambertianReflection, SpecularReflection, SpecularTransmission, FresnelNoOp, BSDF_ALL};
+
+    fn context() -> SurfaceContext {
+        // a surface tilted about x, with dpdu not quite perpendicular to n
+        let n = Normal::new(0f32, 1f32, 1f32).normalize();
+        SurfaceContext::new(Point::origin(), n, (0f32, 0f32), (Vector::new(1f32, 0.1f32, 0f32), Vector::new(0f32, 1f32, -1f32)), (Normal::zero(), Normal::zero()))
+    }
+
+    #[test]
+    fn test_frame() {
+        let bsdf = BSDF::new(&context(), 1f32);
+        let w = Vector::new(0.3f32, -0.2f32, 0.7f32);
+        assert_eq!(bsdf.local_to_world(&bsdf.world_to_local(&w)), w);
+        assert_eq!(bsdf.world_to_local(&bsdf.ns.to_vector()), Vector::unit_z());
+        assert!(bsdf.ss.dot(&bsdf.ns.to_vector()).abs() < 1e-6f32);
+    }
+
+    #[test]
+    fn test_lambertian() {
+        let mut bsdf = BSDF::new(&context(), 1f32);
+        bsdf.add(Box::new(LambertianReflection::new(Color::gray(0.5f32))));
+        let n = bsdf.ns.to_vector();
+        let wo = (n + bsdf.ss * 0.5f32).normalize();
+
+        assert_eq!(bsdf.f(&wo, &n, BSDF_ALL), Color::gray(0.5f32 * FRAC_1_PI));
+        assert!(bsdf.f(&wo, &-n, BSDF_ALL).is_black());
+
+        let s = bsdf.sample_f(&wo, (0.3f32, 0.8f32), BSDF_ALL).unwrap();
+        assert!(s.wi.dot(&n) > 0f32);
+        assert!((s.pdf - bsdf.pdf(&wo, &s.wi, BSDF_ALL)).abs() < 1e-5f32);
+        assert!((s.pdf - s.wi.dot(&n) * FRAC_1_PI).abs() < 1e-5f32);
+    }
+
+    #[test]
+    fn test_mixed_lobes() {
+        let mut bsdf = BSDF::new(&context(), 1f32);
+        bsdf.add(Box::new(LambertianReflection::new(Color::gray(0.5f32))));
+        bsdf.add(Box::new(SpecularReflection::new(Color::white(), Box::new(FresnelNoOp))));
+        let wo = bsdf.ns.to_vector();
+
+        assert_eq!(bsdf.num_components(BSDF_ALL), 2);
+        assert_eq!(bsdf.num_components(BSDF_REFLECTION | BSDF_SPECULAR), 1);
+        assert!(bsdf.has_non_specular());
+
+        let diffuse = bsdf.sample_f(&wo, (0.2f32, 0.4f32), BSDF_ALL).unwrap();
+        assert!(!diffuse.is_specular());
+        let specular = bsdf.sample_f(&wo, (0.7f32, 0.4f32), BSDF_ALL).unwrap();
+        assert!(specular.is_specular());
+        assert_eq!(specular.wi, wo);
+        assert!((specular.pdf - 0.5f32).abs() < 1e-6f32);
+    }
+
+    #[test]
+    fn test_transport_mode() {
+        let mut bsdf = BSDF::new(&context(), 1.5f32);
+        bsdf.add(Box::new(SpecularTransmission::new(Color::white(), 1f32, 1.5f32)));
+        let n = bsdf.ns.to_vector();
+
+        // radiance entering the glass is compressed, importance is not
+        let s = bsdf.sample_f(&n, (0.5f32, 0.5f32), BSDF_ALL).unwrap();
+        let radiance = s.f * (s.wi.dot(&n).abs() / s.pdf);
+        bsdf.mode = TransportMode::Importance;
+        let s = bsdf.sample_f(&n, (0.5f32, 0.5f32), BSDF_ALL).unwrap();
+        let importance = s.f * (s.wi.dot(&n).abs() / s.pdf);
+        assert!((importance.g - radiance.g * 1.5f32 * 1.5f32).abs() < 1e-5f32);
+        assert!((importance.g - 0.96f32).abs() < 1e-5f32);
+    }
+}
@@ -0,0 +1,155 @@
+use crate::color::Color;
+use crate::geometry::Vector;
+use crate::sampler::to_hemisphere_cosine;
+use std::f32::consts::FRAC_1_PI;
+
+// Flags describing a lobe: whether it reflects or transmits, and how sharp it is.
+pub type BxDFType = u8;
+
+pub const BSDF_REFLECTION   : BxDFType = 1;
+pub const BSDF_TRANSMISSION : BxDFType = 2;
+pub const BSDF_DIFFUSE      : BxDFType = 4;
+pub const BSDF_GLOSSY       : BxDFType = 8;
+pub const BSDF_SPECULAR     : BxDFType = 16;
+pub const BSDF_ALL          : BxDFType = 31;
+
+// A single scattering lobe.  All directions are in the local shading frame,
+// where the shading normal is +z, and point away from the surface.
+pub trait BxDF : Send + Sync {
+    fn bxdf_type(&self) -> BxDFType;
+
+    fn matches(&self, flags : BxDFType) -> bool {
+        self.bxdf_type() & flags == self.bxdf_type()
+    }
+
+    fn f(&self, wo : &Vector, wi : &Vector) -> Color;
+
+    // Chooses an incident direction for wo.  The default samples the cosine-
+    // weighted hemisphere on wo's side, which suits any non-specular reflection.
+    fn sample_f(&self, wo : &Vector, u : (f32, f32)) -> Option<BxDFSample> {
+        let mut wi = to_hemisphere_cosine(u);
+        if wo.z < 0f32 {
+            wi.z = -wi.z;
+        }
+        let pdf = self.pdf(wo, &wi);
+        if pdf == 0f32 {
+            return None;
+        }
+        Some(BxDFSample::new(wi, self.f(wo, &wi), pdf, self.bxdf_type()))
+    }
+
+    fn pdf(&self, wo : &Vector, wi : &Vector) -> f32 {
+        if same_hemisphere(wo, wi) { abs_cos_theta(wi) * FRAC_1_PI } else { 0f32 }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct BxDFSample {
+    pub wi           : Vector,
+    pub f            : Color,
+    pub pdf          : f32,
+    pub sampled_type : BxDFType,
+}
+
+impl BxDFSample {
+    pub fn new(wi : Vector, f : Color, pdf : f32, sampled_type : BxDFType) -> BxDFSample {
+        BxDFSample { wi: wi, f: f, pdf: pdf, sampled_type: sampled_type }
+    }
+
+    pub fn is_specular(&self) -> bool {
+        self.sampled_type & BSDF_SPECULAR != 0
+    }
+}
+
+// Spherical angles of a direction in the shading frame.
+
+pub fn cos_theta(w : &Vector) -> f32 {
+    w.z
+}
+
+pub fn cos2_theta(w : &Vector) -> f32 {
+    w.z * w.z
+}
+
+pub fn abs_cos_theta(w : &Vector) -> f32 {
+    w.z.abs()
+}
+
+pub fn sin2_theta(w : &Vector) -> f32 {
+    (1f32 - cos2_theta(w)).max(0f32)
+}
+
+pub fn sin_theta(w : &Vector) -> f32 {
+    sin2_theta(w).sqrt()
+}
+
+pub fn tan_theta(w : &Vector) -> f32 {
+    sin_theta(w) / cos_theta(w)
+}
+
+pub fn tan2_theta(w : &Vector) -> f32 {
+    sin2_theta(w) / cos2_theta(w)
+}
+
+pub fn cos_phi(w : &Vector) -> f32 {
+    let s = sin_theta(w);
+    if s == 0f32 { 1f32 } else { (w.x / s).clamp(-1f32, 1f32) }
+}
+
+pub fn sin_phi(w : &Vector) -> f32 {
+    let s = sin_theta(w);
+    if s == 0f32 { 0f32 } else { (w.y / s).clamp(-1f32, 1f32) }
+}
+
+pub fn cos2_phi(w : &Vector) -> f32 {
+    cos_phi(w) * cos_phi(w)
+}
+
+pub fn sin2_phi(w : &Vector) -> f32 {
+    sin_phi(w) * sin_phi(w)
+}
+
+pub fn same_hemisphere(w : &Vector, wp : &Vector) -> bool {
+    w.z * wp.z > 0f32
+}
+
+// The mirror image of wo about n.
+pub fn reflect(wo : &Vector, n : &Vector) -> Vector {
+    -*wo + *n * (2f32 * wo.dot(n))
+}
+
+// The direction wi refracts into on passing through a surface with normal n
+// (on wi's side), where eta is the ratio of the incident index of refraction
+// to the transmitted one.  None on total internal reflection.
+pub fn refract(wi : &Vector, n : &Vector, eta : f32) -> Option<Vector> {
+    let cos_theta_i = n.dot(wi);
+    let sin2_theta_i = (1f32 - cos_theta_i * cos_theta_i).max(0f32);
+    let sin2_theta_t = eta * eta * sin2_theta_i;
+    if sin2_theta_t >= 1f32 {
+        return None;
+    }
+    let cos_theta_t = (1f32 - sin2_theta_t).sqrt();
+    Some(-*wi * eta + *n * (eta * cos_theta_i - cos_theta_t))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reflect_refract() {
+        let n = Vector::unit_z();
+        let wo = Vector::new(0.6f32, 0f32, 0.8f32);
+        assert_eq!(reflect(&wo, &n), Vector::new(-0.6f32, 0f32, 0.8f32));
+
+        // Snell's law: 1.0 * sin(i) = 1.5 * sin(t)
+        let wt = refract(&wo, &n, 1f32 / 1.5f32).unwrap();
+        assert!((wt.magnitude() - 1f32).abs() < 1e-5f32);
+        assert!((sin_theta(&wt) - 0.6f32 / 1.5f32).abs() < 1e-5f32);
+        assert!(wt.z < 0f32 && wt.x < 0f32);
+
+        // leaving glass at a grazing angle reflects totally
+        let grazing = Vector::new(0.8f32, 0f32, 0.6f32);
+        assert!(refract(&grazing, &n, 1.5f32).is_none());
+    }
+}
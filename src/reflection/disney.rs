@@ -0,0 +1,341 @@
+use std::f32::consts::{PI, FRAC_1_PI};
+
+use crate::color::Color;
+use crate::geometry::Vector;
+use crate::reflection::{BxDF, BxDFSample, BxDFType, BSDF_REFLECTION, BSDF_DIFFUSE, BSDF_GLOSSY};
+use crate::reflection::{Fresnel, MicrofacetDistribution, TrowbridgeReitzDistribution, fr_dielectric, abs_cos_theta, same_hemisphere, reflect};
+
+// The lobes of Burley's principled BRDF, as extended to a BSDF for pbrt-v3.
+
+fn lerp(t : f32, a : Color, b : Color) -> Color {
+    a * (1f32 - t) + b * t
+}
+
+fn schlick_weight(cos_theta : f32) -> f32 {
+    let m = (1f32 - cos_theta).clamp(0f32, 1f32);
+    (m * m) * (m * m) * m
+}
+
+fn fr_schlick(r0 : Color, cos_theta : f32) -> Color {
+    lerp(schlick_weight(cos_theta), r0, Color::white())
+}
+
+// The normal-incidence reflectance of a dielectric with index eta.
+pub fn schlick_r0_from_eta(eta : f32) -> f32 {
+    ((eta - 1f32) * (eta - 1f32)) / ((eta + 1f32) * (eta + 1f32))
+}
+
+// The index of a dielectric with normal-incidence reflectance 0.08 ×
+// specular, the principled model's own parameter, so that the usual
+// specular of 0.5 is an eta of 1.5.  The reflectance is kept below one.
+pub fn eta_from_specular(specular : f32) -> f32 {
+    let r = (0.08f32 * specular).clamp(0f32, 0.99f32).sqrt();
+    (1f32 + r) / (1f32 - r)
+}
+
+// The normalized half vector of wo and wi, unless they are opposite.
+fn half_vector(wo : &Vector, wi : &Vector) -> Option<Vector> {
+    let wh = *wo + *wi;
+    if wh.magnitude_squared() == 0f32 { None } else { Some(wh.normalize()) }
+}
+
+// Lambertian reflection darkened at grazing angles.
+#[derive(Copy, Clone, Debug)]
+pub struct DisneyDiffuse {
+    pub r : Color,
+}
+
+impl DisneyDiffuse {
+    pub fn new(r : Color) -> DisneyDiffuse {
+        DisneyDiffuse { r: r }
+    }
+}
+
+impl BxDF for DisneyDiffuse {
+    fn bxdf_type(&self) -> BxDFType {
+        BSDF_REFLECTION | BSDF_DIFFUSE
+    }
+
+    fn f(&self, wo : &Vector, wi : &Vector) -> Color {
+        let fo = schlick_weight(abs_cos_theta(wo));
+        let fi = schlick_weight(abs_cos_theta(wi));
+        self.r * (FRAC_1_PI * (1f32 - fo / 2f32) * (1f32 - fi / 2f32))
+    }
+}
+
+// Hanrahan-Krueger-like flattening of the diffuse lobe, standing in for
+// subsurface scattering in thin surfaces.
+#[derive(Copy, Clone, Debug)]
+pub struct DisneyFakeSS {
+    pub r         : Color,
+    pub roughness : f32,
+}
+
+impl DisneyFakeSS {
+    pub fn new(r : Color, roughness : f32) -> DisneyFakeSS {
+        DisneyFakeSS { r: r, roughness: roughness }
+    }
+}
+
+impl BxDF for DisneyFakeSS {
+    fn bxdf_type(&self) -> BxDFType {
+        BSDF_REFLECTION | BSDF_DIFFUSE
+    }
+
+    fn f(&self, wo : &Vector, wi : &Vector) -> Color {
+        let wh = match half_vector(wo, wi) {
+            None     => return Color::black(),
+            Some(wh) => wh,
+        };
+        let cos_theta_d = wi.dot(&wh);
+
+        let fss90 = cos_theta_d * cos_theta_d * self.roughness;
+        let fo = schlick_weight(abs_cos_theta(wo));
+        let fi = schlick_weight(abs_cos_theta(wi));
+        let fss = (1f32 + (fss90 - 1f32) * fo) * (1f32 + (fss90 - 1f32) * fi);
+        let ss = 1.25f32 * (fss * (1f32 / (abs_cos_theta(wo) + abs_cos_theta(wi)) - 0.5f32) + 0.5f32);
+        self.r * (FRAC_1_PI * ss)
+    }
+}
+
+// Retro-reflection at grazing angles from rough surfaces.
+#[derive(Copy, Clone, Debug)]
+pub struct DisneyRetro {
+    pub r         : Color,
+    pub roughness : f32,
+}
+
+impl DisneyRetro {
+    pub fn new(r : Color, roughness : f32) -> DisneyRetro {
+        DisneyRetro { r: r, roughness: roughness }
+    }
+}
+
+impl BxDF for DisneyRetro {
+    fn bxdf_type(&self) -> BxDFType {
+        BSDF_REFLECTION | BSDF_DIFFUSE
+    }
+
+    fn f(&self, wo : &Vector, wi : &Vector) -> Color {
+        let wh = match half_vector(wo, wi) {
+            None     => return Color::black(),
+            Some(wh) => wh,
+        };
+        let cos_theta_d = wi.dot(&wh);
+
+        let fo = schlick_weight(abs_cos_theta(wo));
+        let fi = schlick_weight(abs_cos_theta(wi));
+        let rr = 2f32 * self.roughness * cos_theta_d * cos_theta_d;
+        self.r * (FRAC_1_PI * rr * (fo + fi + fo * fi * (rr - 1f32)))
+    }
+}
+
+// The extra grazing reflection of cloth.
+#[derive(Copy, Clone, Debug)]
+pub struct DisneySheen {
+    pub r : Color,
+}
+
+impl DisneySheen {
+    pub fn new(r : Color) -> DisneySheen {
+        DisneySheen { r: r }
+    }
+}
+
+impl BxDF for DisneySheen {
+    fn bxdf_type(&self) -> BxDFType {
+        BSDF_REFLECTION | BSDF_DIFFUSE
+    }
+
+    fn f(&self, wo : &Vector, wi : &Vector) -> Color {
+        match half_vector(wo, wi) {
+            None     => Color::black(),
+            Some(wh) => self.r * schlick_weight(wi.dot(&wh)),
+        }
+    }
+}
+
+// The generalized Trowbridge-Reitz distribution with gamma = 1.
+fn gtr1(cos_theta : f32, alpha : f32) -> f32 {
+    let alpha2 = alpha * alpha;
+    (alpha2 - 1f32) / (PI * alpha2.ln() * (1f32 + (alpha2 - 1f32) * cos_theta * cos_theta))
+}
+
+fn smith_g_ggx(cos_theta : f32, alpha : f32) -> f32 {
+    let alpha2 = alpha * alpha;
+    let cos2_theta = cos_theta * cos_theta;
+    1f32 / (cos_theta + (alpha2 + cos2_theta - alpha2 * cos2_theta).sqrt())
+}
+
+// A second, white specular layer with a fixed index of 1.5, such as varnish.
+#[derive(Copy, Clone, Debug)]
+pub struct DisneyClearcoat {
+    pub weight : f32,
+    pub gloss  : f32,
+}
+
+impl DisneyClearcoat {
+    pub fn new(weight : f32, gloss : f32) -> DisneyClearcoat {
+        DisneyClearcoat { weight: weight, gloss: gloss }
+    }
+}
+
+impl BxDF for DisneyClearcoat {
+    fn bxdf_type(&self) -> BxDFType {
+        BSDF_REFLECTION | BSDF_GLOSSY
+    }
+
+    fn f(&self, wo : &Vector, wi : &Vector) -> Color {
+        let wh = match half_vector(wo, wi) {
+            None     => return Color::black(),
+            Some(wh) => wh,
+        };
+
+        let dr = gtr1(abs_cos_theta(&wh), self.gloss);
+        let fr = 0.04f32 + 0.96f32 * schlick_weight(wo.dot(&wh));
+        let gr = smith_g_ggx(abs_cos_theta(wo), 0.25f32) * smith_g_ggx(abs_cos_theta(wi), 0.25f32);
+        Color::gray(self.weight * gr * fr * dr / 4f32)
+    }
+
+    fn sample_f(&self, wo : &Vector, u : (f32, f32)) -> Option<BxDFSample> {
+        if wo.z == 0f32 {
+            return None;
+        }
+
+        let alpha2 = self.gloss * self.gloss;
+        let cos_theta = ((1f32 - alpha2.powf(1f32 - u.0)) / (1f32 - alpha2)).max(0f32).sqrt();
+        let sin_theta = (1f32 - cos_theta * cos_theta).max(0f32).sqrt();
+        let phi = 2f32 * PI * u.1;
+        let mut wh = Vector::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+        if !same_hemisphere(wo, &wh) {
+            wh = -wh;
+        }
+
+        let wi = reflect(wo, &wh);
+        if !same_hemisphere(wo, &wi) {
+            return None;
+        }
+        let pdf = self.pdf(wo, &wi);
+        if pdf == 0f32 {
+            return None;
+        }
+        Some(BxDFSample::new(wi, self.f(wo, &wi), pdf, self.bxdf_type()))
+    }
+
+    fn pdf(&self, wo : &Vector, wi : &Vector) -> f32 {
+        if !same_hemisphere(wo, wi) {
+            return 0f32;
+        }
+        match half_vector(wo, wi) {
+            None     => 0f32,
+            Some(wh) => gtr1(abs_cos_theta(&wh), self.gloss) * abs_cos_theta(&wh) / (4f32 * wo.dot(&wh)),
+        }
+    }
+}
+
+// Blends dielectric Fresnel reflection with Schlick's approximation to a
+// metal of normal-incidence reflectance r0.
+#[derive(Copy, Clone, Debug)]
+pub struct DisneyFresnel {
+    pub r0       : Color,
+    pub metallic : f32,
+    pub eta      : f32,
+}
+
+impl DisneyFresnel {
+    pub fn new(r0 : Color, metallic : f32, eta : f32) -> DisneyFresnel {
+        DisneyFresnel { r0: r0, metallic: metallic, eta: eta }
+    }
+}
+
+impl Fresnel for DisneyFresnel {
+    fn evaluate(&self, cos_theta_i : f32) -> Color {
+        lerp(self.metallic, Color::gray(fr_dielectric(cos_theta_i, 1f32, self.eta)), fr_schlick(self.r0, cos_theta_i))
+    }
+}
+
+// Trowbridge-Reitz with the separable masking-shadowing the principled model
+// was fitted with.
+pub struct DisneyMicrofacetDistribution {
+    pub inner : TrowbridgeReitzDistribution,
+}
+
+impl DisneyMicrofacetDistribution {
+    pub fn new(alpha_x : f32, alpha_y : f32) -> DisneyMicrofacetDistribution {
+        DisneyMicrofacetDistribution { inner: TrowbridgeReitzDistribution::new(alpha_x, alpha_y) }
+    }
+}
+
+impl MicrofacetDistribution for DisneyMicrofacetDistribution {
+    fn d(&self, wh : &Vector) -> f32 {
+        self.inner.d(wh)
+    }
+
+    fn lambda(&self, w : &Vector) -> f32 {
+        self.inner.lambda(w)
+    }
+
+    fn sample_wh(&self, wo : &Vector, u : (f32, f32)) -> Vector {
+        self.inner.sample_wh(wo, u)
+    }
+
+    fn g(&self, wo : &Vector, wi : &Vector) -> f32 {
+        self.g1(wo) * self.g1(wi)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::to_hemisphere_cosine;
+
+    // The directional albedo of a lobe at wo, by cosine-weighted quadrature.
+    fn albedo(bxdf : &dyn BxDF, wo : &Vector) -> f32 {
+        let n = 64;
+        let mut sum = 0f32;
+        for i in 0..n {
+            for j in 0..n {
+                let wi = to_hemisphere_cosine(((i as f32 + 0.5f32) / n as f32, (j as f32 + 0.5f32) / n as f32));
+                sum += bxdf.f(wo, &wi).luminance() * PI;
+            }
+        }
+        sum / (n * n) as f32
+    }
+
+    #[test]
+    fn test_diffuse_darkens_at_grazing_angles() {
+        let d = DisneyDiffuse::new(Color::white());
+        let normal = albedo(&d, &Vector::new(0f32, 0f32, 1f32));
+        let grazing = albedo(&d, &Vector::new(0.995f32, 0f32, 0.0998f32));
+        assert!(normal <= 1f32 && normal > 0.7f32);
+        assert!(grazing < normal);
+    }
+
+    #[test]
+    fn test_clearcoat_sampling_matches_pdf() {
+        let c = DisneyClearcoat::new(1f32, 0.1f32);
+        let wo = Vector::new(0.3f32, 0.2f32, 0.9f32).normalize();
+        let s = c.sample_f(&wo, (0.3f32, 0.7f32)).unwrap();
+        assert!(same_hemisphere(&wo, &s.wi));
+        assert!((s.pdf - c.pdf(&wo, &s.wi)).abs() < 1e-4f32 * s.pdf);
+        assert_eq!(s.f, c.f(&wo, &s.wi));
+    }
+
+    #[test]
+    fn test_fresnel_blends_to_metal() {
+        let r0 = Color::new(0.9f32, 0.6f32, 0.2f32);
+        assert_eq!(DisneyFresnel::new(r0, 1f32, 1.5f32).evaluate(1f32), r0);
+        let dielectric = DisneyFresnel::new(r0, 0f32, 1.5f32).evaluate(1f32);
+        assert!((dielectric.r - schlick_r0_from_eta(1.5f32)).abs() < 1e-5f32);
+    }
+
+    #[test]
+    fn test_eta_from_specular() {
+        assert!((eta_from_specular(0.5f32) - 1.5f32).abs() < 1e-5f32);
+        assert_eq!(eta_from_specular(0f32), 1f32);
+        for s in [0.1f32, 0.8f32, 1f32].iter() {
+            assert!((schlick_r0_from_eta(eta_from_specular(*s)) - 0.08f32 * s).abs() < 1e-5f32);
+        }
+        assert!(eta_from_specular(20f32).is_finite());
+    }
+}
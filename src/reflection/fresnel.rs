@@ -0,0 +1,129 @@
+use crate::color::Color;
+
+// The fraction of light reflected from a dielectric interface, given the
+// cosine of the incident angle (negative when arriving from inside, i.e. on
+// the eta_t side) and the indices of refraction outside and inside.
+pub fn fr_dielectric(cos_theta_i : f32, eta_i : f32, eta_t : f32) -> f32 {
+    let mut cos_theta_i = cos_theta_i.clamp(-1f32, 1f32);
+    let (eta_i, eta_t) = if cos_theta_i > 0f32 { (eta_i, eta_t) } else { (eta_t, eta_i) };
+    cos_theta_i = cos_theta_i.abs();
+
+    let sin_theta_i = (1f32 - cos_theta_i * cos_theta_i).max(0f32).sqrt();
+    let sin_theta_t = eta_i / eta_t * sin_theta_i;
+    if sin_theta_t >= 1f32 {
+        return 1f32;
+    }
+    let cos_theta_t = (1f32 - sin_theta_t * sin_theta_t).max(0f32).sqrt();
+
+    let r_parl = ((eta_t * cos_theta_i) - (eta_i * cos_theta_t)) / ((eta_t * cos_theta_i) + (eta_i * cos_theta_t));
+    let r_perp = ((eta_i * cos_theta_i) - (eta_t * cos_theta_t)) / ((eta_i * cos_theta_i) + (eta_t * cos_theta_t));
+    (r_parl * r_parl + r_perp * r_perp) / 2f32
+}
+
+// The fraction of light reflected from a conductor with complex index of
+// refraction eta + ik, per colour channel, seen from a medium of index eta_i.
+pub fn fr_conductor(cos_theta_i : f32, eta_i : &Color, eta_t : &Color, k : &Color) -> Color {
+    let cos_theta_i = cos_theta_i.clamp(-1f32, 1f32);
+    let channel = |eta_i : f32, eta_t : f32, k : f32| {
+        let eta = eta_t / eta_i;
+        let eta_k = k / eta_i;
+
+        let cos2 = cos_theta_i * cos_theta_i;
+        let sin2 = 1f32 - cos2;
+        let eta2 = eta * eta;
+        let eta_k2 = eta_k * eta_k;
+
+        let t0 = eta2 - eta_k2 - sin2;
+        let a2_plus_b2 = (t0 * t0 + 4f32 * eta2 * eta_k2).sqrt();
+        let t1 = a2_plus_b2 + cos2;
+        let a = (0.5f32 * (a2_plus_b2 + t0)).max(0f32).sqrt();
+        let t2 = 2f32 * cos_theta_i * a;
+        let rs = (t1 - t2) / (t1 + t2);
+
+        let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+
+        0.5f32 * (rp + rs)
+    };
+    Color::new(channel(eta_i.r, eta_t.r, k.r), channel(eta_i.g, eta_t.g, k.g), channel(eta_i.b, eta_t.b, k.b))
+}
+
+// How much of the light arriving at an angle is reflected (rather than
+// transmitted or absorbed).
+pub trait Fresnel : Send + Sync {
+    fn evaluate(&self, cos_theta_i : f32) -> Color;
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct FresnelDielectric {
+    pub eta_i : f32,
+    pub eta_t : f32,
+}
+
+impl FresnelDielectric {
+    pub fn new(eta_i : f32, eta_t : f32) -> FresnelDielectric {
+        FresnelDielectric { eta_i: eta_i, eta_t: eta_t }
+    }
+}
+
+impl Fresnel for FresnelDielectric {
+    fn evaluate(&self, cos_theta_i : f32) -> Color {
+        Color::gray(fr_dielectric(cos_theta_i, self.eta_i, self.eta_t))
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct FresnelConductor {
+    pub eta_i : Color,
+    pub eta_t : Color,
+    pub k     : Color,
+}
+
+impl FresnelConductor {
+    pub fn new(eta_i : Color, eta_t : Color, k : Color) -> FresnelConductor {
+        FresnelConductor { eta_i: eta_i, eta_t: eta_t, k: k }
+    }
+}
+
+impl Fresnel for FresnelConductor {
+    fn evaluate(&self, cos_theta_i : f32) -> Color {
+        fr_conductor(cos_theta_i.abs(), &self.eta_i, &self.eta_t, &self.k)
+    }
+}
+
+// Reflects everything, for perfect mirrors.
+#[derive(Copy, Clone, Debug)]
+pub struct FresnelNoOp;
+
+impl Fresnel for FresnelNoOp {
+    fn evaluate(&self, _cos_theta_i : f32) -> Color {
+        Color::white()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dielectric() {
+        // 4% at normal incidence for glass, from either side
+        assert!((fr_dielectric(1f32, 1f32, 1.5f32) - 0.04f32).abs() < 1e-4f32);
+        assert!((fr_dielectric(-1f32, 1f32, 1.5f32) - 0.04f32).abs() < 1e-4f32);
+        // everything at grazing incidence, and past the critical angle inside
+        assert!(fr_dielectric(1e-4f32, 1f32, 1.5f32) > 0.99f32);
+        assert_eq!(fr_dielectric(-0.5f32, 1f32, 1.5f32), 1f32);
+    }
+
+    #[test]
+    fn test_conductor() {
+        // a conductor with no absorption is a dielectric
+        let c = fr_conductor(0.7f32, &Color::white(), &Color::gray(1.5f32), &Color::black());
+        assert!((c.r - fr_dielectric(0.7f32, 1f32, 1.5f32)).abs() < 1e-4f32);
+
+        // gold reflects far more red than blue at normal incidence
+        let gold = fr_conductor(1f32, &Color::white(), &Color::new(0.143f32, 0.374f32, 1.442f32), &Color::new(3.983f32, 2.385f32, 1.603f32));
+        assert!(gold.r > 0.9f32 && gold.b < 0.5f32);
+    }
+}
@@ -0,0 +1,66 @@
+use std::f32::consts::FRAC_1_PI;
+
+use crate::color::Color;
+use crate::geometry::Vector;
+use crate::reflection::{BxDF, BxDFSample, BxDFType, BSDF_REFLECTION, BSDF_TRANSMISSION, BSDF_DIFFUSE, abs_cos_theta, same_hemisphere};
+use crate::sampler::to_hemisphere_cosine;
+
+// Perfectly diffuse reflection, scattering equally in all directions.
+#[derive(Copy, Clone, Debug)]
+pub struct LambertianReflection {
+    pub r : Color,
+}
+
+impl LambertianReflection {
+    pub fn new(r : Color) -> LambertianReflection {
+        LambertianReflection { r: r }
+    }
+}
+
+impl BxDF for LambertianReflection {
+    fn bxdf_type(&self) -> BxDFType {
+        BSDF_REFLECTION | BSDF_DIFFUSE
+    }
+
+    fn f(&self, _wo : &Vector, _wi : &Vector) -> Color {
+        self.r * FRAC_1_PI
+    }
+}
+
+// Perfectly diffuse transmission, as through paper or a thin leaf.
+#[derive(Copy, Clone, Debug)]
+pub struct LambertianTransmission {
+    pub t : Color,
+}
+
+impl LambertianTransmission {
+    pub fn new(t : Color) -> LambertianTransmission {
+        LambertianTransmission { t: t }
+    }
+}
+
+impl BxDF for LambertianTransmission {
+    fn bxdf_type(&self) -> BxDFType {
+        BSDF_TRANSMISSION | BSDF_DIFFUSE
+    }
+
+    fn f(&self, _wo : &Vector, _wi : &Vector) -> Color {
+        self.t * FRAC_1_PI
+    }
+
+    fn sample_f(&self, wo : &Vector, u : (f32, f32)) -> Option<BxDFSample> {
+        let mut wi = to_hemisphere_cosine(u);
+        if wo.z > 0f32 {
+            wi.z = -wi.z;
+        }
+        let pdf = self.pdf(wo, &wi);
+        if pdf == 0f32 {
+            return None;
+        }
+        Some(BxDFSample::new(wi, self.f(wo, &wi), pdf, self.bxdf_type()))
+    }
+
+    fn pdf(&self, wo : &Vector, wi : &Vector) -> f32 {
+        if same_hemisphere(wo, wi) { 0f32 } else { abs_cos_theta(wi) * FRAC_1_PI }
+    }
+}
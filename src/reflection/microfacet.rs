@@ -0,0 +1,287 @@
+use std::f32::consts::PI;
+
+use crate::geometry::Vector;
+use crate::math::{erf, erf_inv};
+use crate::reflection::{cos_theta, cos2_theta, abs_cos_theta, tan_theta, tan2_theta, cos_phi, sin_phi, cos2_phi, sin2_phi};
+
+// A distribution of microfacet normals, in the local shading frame.  Roughness
+// may differ along the two tangents: alpha_x along dpdu and alpha_y along dpdv.
+pub trait MicrofacetDistribution : Send + Sync {
+    // The density of microfacets with normal wh, per unit of macrosurface area.
+    fn d(&self, wh : &Vector) -> f32;
+
+    // The ratio of masked to visible microfacet area seen from w (Smith's
+    // auxiliary function).
+    fn lambda(&self, w : &Vector) -> f32;
+
+    // Samples a normal from the microfacets visible from wo, on wo's side.
+    fn sample_wh(&self, wo : &Vector, u : (f32, f32)) -> Vector;
+
+    // The fraction of microfacets visible from w.
+    fn g1(&self, w : &Vector) -> f32 {
+        1f32 / (1f32 + self.lambda(w))
+    }
+
+    // The fraction visible from both wo and wi, with Smith's height-correlated
+    // masking-shadowing.
+    fn g(&self, wo : &Vector, wi : &Vector) -> f32 {
+        1f32 / (1f32 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // The density sample_wh chooses wh with, by solid angle.
+    fn pdf(&self, wo : &Vector, wh : &Vector) -> f32 {
+        self.d(wh) * self.g1(wo) * wo.dot(wh).abs() / abs_cos_theta(wo)
+    }
+}
+
+// Maps a perceptually uniform roughness in [0, 1] to the alpha parameter of
+// the distributions, as pbrt does.
+pub fn roughness_to_alpha(roughness : f32) -> f32 {
+    let x = roughness.max(1e-3f32).ln();
+    1.62142f32 + 0.819955f32 * x + 0.1734f32 * x * x + 0.0171201f32 * x * x * x + 0.000640711f32 * x * x * x * x
+}
+
+// Samples wh by stretching wi to the configuration where alpha is 1, sampling
+// the slopes of visible microfacets there with sample11, then unstretching.
+fn sample_visible<F : Fn(f32, f32, f32) -> (f32, f32)>(wi : &Vector, alpha_x : f32, alpha_y : f32, u : (f32, f32), sample11 : F) -> Vector {
+    let flip = wi.z < 0f32;
+    let wi = if flip { -*wi } else { *wi };
+
+    let stretched = Vector::new(alpha_x * wi.x, alpha_y * wi.y, wi.z).normalize();
+    let (slope_x, slope_y) = sample11(cos_theta(&stretched), u.0, u.1);
+
+    // rotate to wi's azimuth, then unstretch
+    let (cos, sin) = (cos_phi(&stretched), sin_phi(&stretched));
+    let (slope_x, slope_y) = (cos * slope_x - sin * slope_y, sin * slope_x + cos * slope_y);
+    let wh = Vector::new(-slope_x * alpha_x, -slope_y * alpha_y, 1f32).normalize();
+
+    if flip { -wh } else { wh }
+}
+
+// Beckmann-Spizzichino: a Gaussian distribution of microfacet slopes.
+#[derive(Copy, Clone, Debug)]
+pub struct BeckmannDistribution {
+    pub alpha_x : f32,
+    pub alpha_y : f32,
+}
+
+impl BeckmannDistribution {
+    pub fn new(alpha_x : f32, alpha_y : f32) -> BeckmannDistribution {
+        BeckmannDistribution { alpha_x: alpha_x.max(1e-4f32), alpha_y: alpha_y.max(1e-4f32) }
+    }
+}
+
+impl MicrofacetDistribution for BeckmannDistribution {
+    fn d(&self, wh : &Vector) -> f32 {
+        let tan2 = tan2_theta(wh);
+        if tan2.is_infinite() || tan2.is_nan() {
+            return 0f32;
+        }
+        let cos4 = cos2_theta(wh) * cos2_theta(wh);
+        let e = tan2 * (cos2_phi(wh) / (self.alpha_x * self.alpha_x) + sin2_phi(wh) / (self.alpha_y * self.alpha_y));
+        (-e).exp() / (PI * self.alpha_x * self.alpha_y * cos4)
+    }
+
+    fn lambda(&self, w : &Vector) -> f32 {
+        let abs_tan = tan_theta(w).abs();
+        if abs_tan.is_infinite() || abs_tan.is_nan() {
+            return 0f32;
+        }
+        let alpha = (cos2_phi(w) * self.alpha_x * self.alpha_x + sin2_phi(w) * self.alpha_y * self.alpha_y).sqrt();
+        let a = 1f32 / (alpha * abs_tan);
+        if a >= 1.6f32 {
+            return 0f32;
+        }
+        (1f32 - 1.259f32 * a + 0.396f32 * a * a) / (3.535f32 * a + 2.181f32 * a * a)
+    }
+
+    fn sample_wh(&self, wo : &Vector, u : (f32, f32)) -> Vector {
+        sample_visible(wo, self.alpha_x, self.alpha_y, u, beckmann_sample11)
+    }
+}
+
+// Slopes of the visible normals of the alpha = 1 Beckmann distribution, seen
+// from a direction at cos_theta_i (Jakob's inversion of the slope CDF).
+fn beckmann_sample11(cos_theta_i : f32, u1 : f32, u2 : f32) -> (f32, f32) {
+    if cos_theta_i > 0.9999f32 {
+        let r = (-(1f32 - u1).ln()).sqrt();
+        let phi = 2f32 * PI * u2;
+        return (r * phi.cos(), r * phi.sin());
+    }
+
+    let sin_theta_i = (1f32 - cos_theta_i * cos_theta_i).max(0f32).sqrt();
+    let tan_theta_i = sin_theta_i / cos_theta_i;
+    let cot_theta_i = 1f32 / tan_theta_i;
+
+    // search for the x slope with Newton-bisection, bracketed by [a, c]
+    let mut a = -1f32;
+    let mut c = erf(cot_theta_i);
+    let sample_x = u1.max(1e-6f32);
+
+    let theta_i = cos_theta_i.acos();
+    let fit = 1f32 + theta_i * (-0.876f32 + theta_i * (0.4265f32 - 0.0594f32 * theta_i));
+    let mut b = c - (1f32 + c) * (1f32 - sample_x).powf(fit);
+
+    let sqrt_pi_inv = 1f32 / PI.sqrt();
+    let normalization = 1f32 / (1f32 + c + sqrt_pi_inv * tan_theta_i * (-cot_theta_i * cot_theta_i).exp());
+
+    for _ in 0..9 {
+        if b < a || b > c {
+            b = 0.5f32 * (a + c);
+        }
+
+        let inv_erf = erf_inv(b);
+        let value = normalization * (1f32 + b + sqrt_pi_inv * tan_theta_i * (-inv_erf * inv_erf).exp()) - sample_x;
+        let derivative = normalization * (1f32 - inv_erf * tan_theta_i);
+        if value.abs() < 1e-5f32 {
+            break;
+        }
+
+        if value > 0f32 { c = b; } else { a = b; }
+        b -= value / derivative;
+    }
+
+    (erf_inv(b), erf_inv(2f32 * u2.max(1e-6f32) - 1f32))
+}
+
+// Trowbridge-Reitz (GGX): longer tails than Beckmann, and so the broader
+// highlights of real surfaces.
+#[derive(Copy, Clone, Debug)]
+pub struct TrowbridgeReitzDistribution {
+    pub alpha_x : f32,
+    pub alpha_y : f32,
+}
+
+impl TrowbridgeReitzDistribution {
+    pub fn new(alpha_x : f32, alpha_y : f32) -> TrowbridgeReitzDistribution {
+        TrowbridgeReitzDistribution { alpha_x: alpha_x.max(1e-4f32), alpha_y: alpha_y.max(1e-4f32) }
+    }
+}
+
+impl MicrofacetDistribution for TrowbridgeReitzDistribution {
+    fn d(&self, wh : &Vector) -> f32 {
+        let tan2 = tan2_theta(wh);
+        if tan2.is_infinite() || tan2.is_nan() {
+            return 0f32;
+        }
+        let cos4 = cos2_theta(wh) * cos2_theta(wh);
+        let e = tan2 * (cos2_phi(wh) / (self.alpha_x * self.alpha_x) + sin2_phi(wh) / (self.alpha_y * self.alpha_y));
+        1f32 / (PI * self.alpha_x * self.alpha_y * cos4 * (1f32 + e) * (1f32 + e))
+    }
+
+    fn lambda(&self, w : &Vector) -> f32 {
+        let abs_tan = tan_theta(w).abs();
+        if abs_tan.is_infinite() || abs_tan.is_nan() {
+            return 0f32;
+        }
+        let alpha = (cos2_phi(w) * self.alpha_x * self.alpha_x + sin2_phi(w) * self.alpha_y * self.alpha_y).sqrt();
+        let alpha2_tan2 = (alpha * abs_tan) * (alpha * abs_tan);
+        (-1f32 + (1f32 + alpha2_tan2).sqrt()) / 2f32
+    }
+
+    fn sample_wh(&self, wo : &Vector, u : (f32, f32)) -> Vector {
+        sample_visible(wo, self.alpha_x, self.alpha_y, u, trowbridge_reitz_sample11)
+    }
+}
+
+// Slopes of the visible normals of the alpha = 1 Trowbridge-Reitz
+// distribution, seen from a direction at cos_theta_i (Heitz and d'Eon).
+fn trowbridge_reitz_sample11(cos_theta_i : f32, u1 : f32, u2 : f32) -> (f32, f32) {
+    if cos_theta_i > 0.9999f32 {
+        let r = (u1 / (1f32 - u1)).sqrt();
+        let phi = 2f32 * PI * u2;
+        return (r * phi.cos(), r * phi.sin());
+    }
+
+    let sin_theta_i = (1f32 - cos_theta_i * cos_theta_i).max(0f32).sqrt();
+    let tan_theta_i = sin_theta_i / cos_theta_i;
+    let a = 1f32 / tan_theta_i;
+    let g1 = 2f32 / (1f32 + (1f32 + 1f32 / (a * a)).sqrt());
+
+    let a = 2f32 * u1 / g1 - 1f32;
+    let tmp = (1f32 / (a * a - 1f32)).min(1e10f32);
+    let b = tan_theta_i;
+    let d = (b * b * tmp * tmp - (a * a - b * b) * tmp).max(0f32).sqrt();
+    let slope_x_1 = b * tmp - d;
+    let slope_x_2 = b * tmp + d;
+    let slope_x = if a < 0f32 || slope_x_2 > 1f32 / tan_theta_i { slope_x_1 } else { slope_x_2 };
+
+    let (s, u2) = if u2 > 0.5f32 { (1f32, 2f32 * (u2 - 0.5f32)) } else { (-1f32, 2f32 * (0.5f32 - u2)) };
+    let z = (u2 * (u2 * (u2 * 0.27385f32 - 0.73369f32) + 0.46341f32)) / (u2 * (u2 * (u2 * 0.093073f32 + 0.30942f32) - 1f32) + 0.597999f32);
+    let slope_y = s * z * (1f32 + slope_x * slope_x).sqrt();
+
+    (slope_x, slope_y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::{Sampler2D, StrataSampler2D};
+    use crate::reflection::sin_theta;
+
+    fn distributions() -> Vec<Box<dyn MicrofacetDistribution>> {
+        vec![Box::new(BeckmannDistribution::new(0.3f32, 0.3f32)),
+             Box::new(BeckmannDistribution::new(0.2f32, 0.6f32)),
+             Box::new(TrowbridgeReitzDistribution::new(0.3f32, 0.3f32)),
+             Box::new(TrowbridgeReitzDistribution::new(0.6f32, 0.2f32))]
+    }
+
+    // Integrates over the hemisphere in theta and phi with the midpoint rule.
+    fn integrate<F : Fn(&Vector) -> f32>(f : F) -> f32 {
+        let (nt, np) = (400, 200);
+        let mut sum = 0f32;
+        for i in 0..nt {
+            let theta = (i as f32 + 0.5f32) / (nt as f32) * PI / 2f32;
+            for j in 0..np {
+                let phi = (j as f32 + 0.5f32) / (np as f32) * 2f32 * PI;
+                let w = Vector::new(theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos());
+                sum += f(&w) * theta.sin();
+            }
+        }
+        sum * (PI / 2f32 / nt as f32) * (2f32 * PI / np as f32)
+    }
+
+    #[test]
+    fn test_projected_area_is_one() {
+        for d in distributions().iter() {
+            let area = integrate(|wh| d.d(wh) * cos_theta(wh));
+            assert!((area - 1f32).abs() < 0.01f32, "projected area {}", area);
+        }
+    }
+
+    #[test]
+    fn test_visible_normals_pdf_integrates_to_one() {
+        let wo = Vector::new(0.5f32, 0.3f32, 0.8f32).normalize();
+        for d in distributions().iter() {
+            // over the normals facing wo, which are the only ones sampled
+            let total = integrate(|wh| if wo.dot(wh) > 0f32 { d.pdf(&wo, wh) } else { 0f32 });
+            assert!((total - 1f32).abs() < 0.01f32, "pdf integrates to {}", total);
+        }
+    }
+
+    #[test]
+    fn test_sampled_normals_match_pdf() {
+        // compare the sampled fraction of normals within 30 degrees of +z to the pdf's
+        let wo = Vector::new(-0.4f32, 0.2f32, 0.9f32).normalize();
+        for d in distributions().iter() {
+            let mut sampler = StrataSampler2D::new(64, 64);
+            let samples = sampler.get_samples();
+            let inside = samples.iter().filter(|&&u| {
+                let wh = d.sample_wh(&wo, u);
+                assert!(wh.z > 0f32 && (wh.magnitude() - 1f32).abs() < 1e-4f32);
+                sin_theta(&wh) < 0.5f32
+            }).count() as f32 / samples.len() as f32;
+            let expected = integrate(|wh| if sin_theta(wh) < 0.5f32 { d.pdf(&wo, wh) } else { 0f32 });
+            assert!((inside - expected).abs() < 0.02f32, "sampled {} expected {}", inside, expected);
+        }
+    }
+
+    #[test]
+    fn test_masking() {
+        let d = TrowbridgeReitzDistribution::new(0.5f32, 0.5f32);
+        assert!((d.g1(&Vector::unit_z()) - 1f32).abs() < 1e-6f32);
+        let grazing = Vector::new(0.999f32, 0f32, 0.0447f32).normalize();
+        assert!(d.g1(&grazing) < 0.2f32);
+        assert!(d.g(&grazing, &Vector::unit_z()) <= d.g1(&grazing));
+    }
+}
@@ -0,0 +1,17 @@
+pub mod bsdf;
+pub mod bxdf;
+pub mod disney;
+pub mod fresnel;
+pub mod lambertian;
+pub mod microfacet;
+pub mod specular;
+pub mod torrance_sparrow;
+
+pub use bsdf::*;
+pub use bxdf::*;
+pub use disney::*;
+pub use fresnel::*;
+pub use lambertian::*;
+pub use microfacet::*;
+pub use specular::*;
+pub use torrance_sparrow::*;
@@ -0,0 +1,174 @@
+use crate::color::Color;
+use crate::geometry::Vector;
+use crate::reflection::{BxDF, BxDFSample, BxDFType, BSDF_REFLECTION, BSDF_TRANSMISSION, BSDF_SPECULAR};
+use crate::reflection::{Fresnel, FresnelDielectric, fr_dielectric, cos_theta, abs_cos_theta, refract};
+
+// The specular lobes scatter into a single direction, so f and pdf are zero
+// for every pair of directions and all of the work is done in sample_f, which
+// returns a pdf of 1 and an f already divided by the cosine term.
+
+// Mirror reflection, scaled by a Fresnel term.
+pub struct SpecularReflection {
+    pub r       : Color,
+    pub fresnel : Box<dyn Fresnel>,
+}
+
+impl SpecularReflection {
+    pub fn new(r : Color, fresnel : Box<dyn Fresnel>) -> SpecularReflection {
+        SpecularReflection { r: r, fresnel: fresnel }
+    }
+}
+
+impl BxDF for SpecularReflection {
+    fn bxdf_type(&self) -> BxDFType {
+        BSDF_REFLECTION | BSDF_SPECULAR
+    }
+
+    fn f(&self, _wo : &Vector, _wi : &Vector) -> Color {
+        Color::black()
+    }
+
+    fn sample_f(&self, wo : &Vector, _u : (f32, f32)) -> Option<BxDFSample> {
+        let wi = Vector::new(-wo.x, -wo.y, wo.z);
+        let f = self.fresnel.evaluate(cos_theta(&wi)) * self.r / abs_cos_theta(&wi);
+        Some(BxDFSample::new(wi, f, 1f32, self.bxdf_type()))
+    }
+
+    fn pdf(&self, _wo : &Vector, _wi : &Vector) -> f32 {
+        0f32
+    }
+}
+
+// Refraction through a dielectric boundary between indices eta_a (on the
+// +z side) and eta_b (on the -z side).
+#[derive(Copy, Clone, Debug)]
+pub struct SpecularTransmission {
+    pub t       : Color,
+    pub eta_a   : f32,
+    pub eta_b   : f32,
+    pub fresnel : FresnelDielectric,
+}
+
+impl SpecularTransmission {
+    pub fn new(t : Color, eta_a : f32, eta_b : f32) -> SpecularTransmission {
+        SpecularTransmission { t: t, eta_a: eta_a, eta_b: eta_b, fresnel: FresnelDielectric::new(eta_a, eta_b) }
+    }
+}
+
+impl BxDF for SpecularTransmission {
+    fn bxdf_type(&self) -> BxDFType {
+        BSDF_TRANSMISSION | BSDF_SPECULAR
+    }
+
+    fn f(&self, _wo : &Vector, _wi : &Vector) -> Color {
+        Color::black()
+    }
+
+    fn sample_f(&self, wo : &Vector, _u : (f32, f32)) -> Option<BxDFSample> {
+        let entering = cos_theta(wo) > 0f32;
+        let (eta_i, eta_t) = if entering { (self.eta_a, self.eta_b) } else { (self.eta_b, self.eta_a) };
+        let n = if entering { Vector::unit_z() } else { -Vector::unit_z() };
+
+        let wi = refract(wo, &n, eta_i / eta_t)?;
+        let ft = self.t * (Color::white() - self.fresnel.evaluate(cos_theta(&wi)));
+        // radiance is compressed into a smaller solid angle on entering a denser medium
+        let ft = ft * ((eta_i * eta_i) / (eta_t * eta_t));
+        Some(BxDFSample::new(wi, ft / abs_cos_theta(&wi), 1f32, self.bxdf_type()))
+    }
+
+    fn pdf(&self, _wo : &Vector, _wi : &Vector) -> f32 {
+        0f32
+    }
+}
+
+// Specular reflection and transmission together, choosing between them in
+// proportion to the Fresnel reflectance.  This is what glass uses.
+#[derive(Copy, Clone, Debug)]
+pub struct FresnelSpecular {
+    pub r     : Color,
+    pub t     : Color,
+    pub eta_a : f32,
+    pub eta_b : f32,
+}
+
+impl FresnelSpecular {
+    pub fn new(r : Color, t : Color, eta_a : f32, eta_b : f32) -> FresnelSpecular {
+        FresnelSpecular { r: r, t: t, eta_a: eta_a, eta_b: eta_b }
+    }
+}
+
+impl BxDF for FresnelSpecular {
+    fn bxdf_type(&self) -> BxDFType {
+        BSDF_REFLECTION | BSDF_TRANSMISSION | BSDF_SPECULAR
+    }
+
+    fn f(&self, _wo : &Vector, _wi : &Vector) -> Color {
+        Color::black()
+    }
+
+    fn sample_f(&self, wo : &Vector, u : (f32, f32)) -> Option<BxDFSample> {
+        let fr = fr_dielectric(cos_theta(wo), self.eta_a, self.eta_b);
+        if u.0 < fr {
+            let wi = Vector::new(-wo.x, -wo.y, wo.z);
+            let f = self.r * fr / abs_cos_theta(&wi);
+            Some(BxDFSample::new(wi, f, fr, BSDF_REFLECTION | BSDF_SPECULAR))
+        } else {
+            let entering = cos_theta(wo) > 0f32;
+            let (eta_i, eta_t) = if entering { (self.eta_a, self.eta_b) } else { (self.eta_b, self.eta_a) };
+            let n = if entering { Vector::unit_z() } else { -Vector::unit_z() };
+
+            let wi = refract(wo, &n, eta_i / eta_t)?;
+            let ft = self.t * (1f32 - fr) * ((eta_i * eta_i) / (eta_t * eta_t));
+            Some(BxDFSample::new(wi, ft / abs_cos_theta(&wi), 1f32 - fr, BSDF_TRANSMISSION | BSDF_SPECULAR))
+        }
+    }
+
+    fn pdf(&self, _wo : &Vector, _wi : &Vector) -> f32 {
+        0f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reflection::FresnelNoOp;
+
+    #[test]
+    fn test_specular_reflection() {
+        let mirror = SpecularReflection::new(Color::white(), Box::new(FresnelNoOp));
+        let wo = Vector::new(0.6f32, 0f32, 0.8f32);
+        let s = mirror.sample_f(&wo, (0.5f32, 0.5f32)).unwrap();
+        assert_eq!(s.wi, Vector::new(-0.6f32, 0f32, 0.8f32));
+        assert_eq!(s.f * abs_cos_theta(&s.wi) / s.pdf, Color::white());
+        assert!(s.is_specular());
+        assert!(mirror.f(&wo, &s.wi).is_black());
+    }
+
+    #[test]
+    fn test_specular_transmission_conserves_energy() {
+        let glass = SpecularTransmission::new(Color::white(), 1f32, 1.5f32);
+        let fr = FresnelDielectric::new(1f32, 1.5f32);
+        let wo = Vector::new(0.6f32, 0f32, 0.8f32);
+
+        let s = glass.sample_f(&wo, (0.5f32, 0.5f32)).unwrap();
+        assert!(s.wi.z < 0f32);
+        // undoing the solid angle compression, reflected plus transmitted is everything
+        let t = s.f * abs_cos_theta(&s.wi) * (1.5f32 * 1.5f32);
+        assert_eq!(t + fr.evaluate(cos_theta(&wo)), Color::white());
+
+        // the reverse path comes back out along wo
+        let back = glass.sample_f(&s.wi, (0.5f32, 0.5f32)).unwrap();
+        assert_eq!(back.wi, wo);
+    }
+
+    #[test]
+    fn test_fresnel_specular_chooses_by_reflectance() {
+        let glass = FresnelSpecular::new(Color::white(), Color::white(), 1f32, 1.5f32);
+        let wo = Vector::unit_z();
+        let r = glass.sample_f(&wo, (0.01f32, 0f32)).unwrap();
+        let t = glass.sample_f(&wo, (0.5f32, 0f32)).unwrap();
+        assert_eq!(r.sampled_type, BSDF_REFLECTION | BSDF_SPECULAR);
+        assert_eq!(t.sampled_type, BSDF_TRANSMISSION | BSDF_SPECULAR);
+        assert!((r.pdf - 0.04f32).abs() < 1e-4f32 && (t.pdf - 0.96f32).abs() < 1e-4f32);
+    }
+}
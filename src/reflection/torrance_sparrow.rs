@@ -0,0 +1,204 @@
+use crate::color::Color;
+use crate::geometry::Vector;
+use crate::reflection::{BxDF, BxDFSample, BxDFType, BSDF_REFLECTION, BSDF_TRANSMISSION, BSDF_GLOSSY};
+use crate::reflection::{Fresnel, FresnelDielectric, MicrofacetDistribution, cos_theta, abs_cos_theta, same_hemisphere, reflect, refract};
+
+// Torrance-Sparrow reflection from a surface of perfectly specular
+// microfacets, scaled by a Fresnel term.
+pub struct MicrofacetReflection {
+    pub r            : Color,
+    pub distribution : Box<dyn MicrofacetDistribution>,
+    pub fresnel      : Box<dyn Fresnel>,
+}
+
+impl MicrofacetReflection {
+    pub fn new(r : Color, distribution : Box<dyn MicrofacetDistribution>, fresnel : Box<dyn Fresnel>) -> MicrofacetReflection {
+        MicrofacetReflection { r: r, distribution: distribution, fresnel: fresnel }
+    }
+}
+
+impl BxDF for MicrofacetReflection {
+    fn bxdf_type(&self) -> BxDFType {
+        BSDF_REFLECTION | BSDF_GLOSSY
+    }
+
+    fn f(&self, wo : &Vector, wi : &Vector) -> Color {
+        let cos_o = abs_cos_theta(wo);
+        let cos_i = abs_cos_theta(wi);
+        let wh = *wi + *wo;
+        if cos_i == 0f32 || cos_o == 0f32 || wh.magnitude_squared() == 0f32 {
+            return Color::black();
+        }
+        let wh = wh.normalize();
+        let wh_up = if wh.z < 0f32 { -wh } else { wh };
+
+        let f = self.fresnel.evaluate(wi.dot(&wh_up));
+        self.r * f * (self.distribution.d(&wh) * self.distribution.g(wo, wi) / (4f32 * cos_i * cos_o))
+    }
+
+    fn sample_f(&self, wo : &Vector, u : (f32, f32)) -> Option<BxDFSample> {
+        if wo.z == 0f32 {
+            return None;
+        }
+        let wh = self.distribution.sample_wh(wo, u);
+        if wo.dot(&wh) < 0f32 {
+            return None;
+        }
+        let wi = reflect(wo, &wh);
+        if !same_hemisphere(wo, &wi) {
+            return None;
+        }
+
+        let pdf = self.distribution.pdf(wo, &wh) / (4f32 * wo.dot(&wh));
+        Some(BxDFSample::new(wi, self.f(wo, &wi), pdf, self.bxdf_type()))
+    }
+
+    fn pdf(&self, wo : &Vector, wi : &Vector) -> f32 {
+        if !same_hemisphere(wo, wi) {
+            return 0f32;
+        }
+        let wh = (*wo + *wi).normalize();
+        self.distribution.pdf(wo, &wh) / (4f32 * wo.dot(&wh))
+    }
+}
+
+// Torrance-Sparrow transmission through a rough dielectric boundary between
+// indices eta_a (on the +z side) and eta_b (on the -z side).
+pub struct MicrofacetTransmission {
+    pub t            : Color,
+    pub distribution : Box<dyn MicrofacetDistribution>,
+    pub eta_a        : f32,
+    pub eta_b        : f32,
+    pub fresnel      : FresnelDielectric,
+}
+
+impl MicrofacetTransmission {
+    pub fn new(t : Color, distribution : Box<dyn MicrofacetDistribution>, eta_a : f32, eta_b : f32) -> MicrofacetTransmission {
+        MicrofacetTransmission { t: t, distribution: distribution, eta_a: eta_a, eta_b: eta_b, fresnel: FresnelDielectric::new(eta_a, eta_b) }
+    }
+
+    // The ratio of the indices of refraction on wi's side to wo's.
+    fn eta(&self, wo : &Vector) -> f32 {
+        if cos_theta(wo) > 0f32 { self.eta_b / self.eta_a } else { self.eta_a / self.eta_b }
+    }
+
+    // The generalized half vector for a refraction, facing +z.
+    fn half_vector(&self, wo : &Vector, wi : &Vector) -> Vector {
+        let wh = (*wo + *wi * self.eta(wo)).normalize();
+        if wh.z < 0f32 { -wh } else { wh }
+    }
+}
+
+impl BxDF for MicrofacetTransmission {
+    fn bxdf_type(&self) -> BxDFType {
+        BSDF_TRANSMISSION | BSDF_GLOSSY
+    }
+
+    fn f(&self, wo : &Vector, wi : &Vector) -> Color {
+        if same_hemisphere(wo, wi) {
+            return Color::black();
+        }
+        let cos_o = cos_theta(wo);
+        let cos_i = cos_theta(wi);
+        if cos_i == 0f32 || cos_o == 0f32 {
+            return Color::black();
+        }
+
+        let eta = self.eta(wo);
+        let wh = self.half_vector(wo, wi);
+        // both directions must be on the same side of the microfacet
+        if wo.dot(&wh) * wi.dot(&wh) > 0f32 {
+            return Color::black();
+        }
+
+        let f = self.fresnel.evaluate(wo.dot(&wh));
+        let sqrt_denom = wo.dot(&wh) + eta * wi.dot(&wh);
+        // radiance is compressed into a smaller solid angle on entering a denser medium
+        let factor = 1f32 / eta;
+
+        let v = self.distribution.d(&wh) * self.distribution.g(wo, wi) * eta * eta * wi.dot(&wh).abs() * wo.dot(&wh).abs() * factor * factor
+              / (cos_i * cos_o * sqrt_denom * sqrt_denom);
+        (Color::white() - f) * self.t * v.abs()
+    }
+
+    fn sample_f(&self, wo : &Vector, u : (f32, f32)) -> Option<BxDFSample> {
+        if wo.z == 0f32 {
+            return None;
+        }
+        let wh = self.distribution.sample_wh(wo, u);
+        if wo.dot(&wh) < 0f32 {
+            return None;
+        }
+        let wi = refract(wo, &wh, 1f32 / self.eta(wo))?;
+
+        let pdf = self.pdf(wo, &wi);
+        Some(BxDFSample::new(wi, self.f(wo, &wi), pdf, self.bxdf_type()))
+    }
+
+    fn pdf(&self, wo : &Vector, wi : &Vector) -> f32 {
+        if same_hemisphere(wo, wi) {
+            return 0f32;
+        }
+        let eta = self.eta(wo);
+        let wh = self.half_vector(wo, wi);
+        if wo.dot(&wh) * wi.dot(&wh) > 0f32 {
+            return 0f32;
+        }
+
+        let sqrt_denom = wo.dot(&wh) + eta * wi.dot(&wh);
+        let dwh_dwi = (eta * eta * wi.dot(&wh)).abs() / (sqrt_denom * sqrt_denom);
+        self.distribution.pdf(wo, &wh) * dwh_dwi
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reflection::{TrowbridgeReitzDistribution, BeckmannDistribution, FresnelNoOp};
+    use crate::sampler::{Sampler2D, StrataSampler2D};
+
+    // Estimates the directional albedo of a lobe for wo by importance sampling.
+    fn albedo(bxdf : &dyn BxDF, wo : &Vector) -> Color {
+        let samples = StrataSampler2D::new(128, 128).get_samples();
+        let mut sum = Color::black();
+        for u in samples.iter() {
+            if let Some(s) = bxdf.sample_f(wo, *u) {
+                if s.pdf > 0f32 {
+                    // sampling and evaluation must agree
+                    assert!((s.pdf - bxdf.pdf(wo, &s.wi)).abs() <= 1e-3f32 * s.pdf.max(1f32));
+                    sum.add_self_c(&(s.f * (abs_cos_theta(&s.wi) / s.pdf)));
+                }
+            }
+        }
+        sum / samples.len() as f32
+    }
+
+    #[test]
+    fn test_reflection_conserves_energy() {
+        let wo = Vector::new(0.3f32, -0.2f32, 0.9f32).normalize();
+        for alpha in [0.05f32, 0.3f32, 0.8f32].iter() {
+            let tr = MicrofacetReflection::new(Color::white(), Box::new(TrowbridgeReitzDistribution::new(*alpha, *alpha * 0.5f32)), Box::new(FresnelNoOp));
+            let b = MicrofacetReflection::new(Color::white(), Box::new(BeckmannDistribution::new(*alpha, *alpha)), Box::new(FresnelNoOp));
+            for a in [albedo(&tr, &wo), albedo(&b, &wo)].iter() {
+                // single scattering loses energy as roughness grows, but never gains any
+                let min = if *alpha < 0.1f32 { 0.99f32 } else { 0.5f32 };
+                assert!(a.g <= 1.01f32 && a.g > min, "albedo {} at alpha {}", a, alpha);
+            }
+        }
+    }
+
+    #[test]
+    fn test_transmission() {
+        let t = MicrofacetTransmission::new(Color::white(), Box::new(TrowbridgeReitzDistribution::new(0.2f32, 0.2f32)), 1f32, 1.5f32);
+        let wo = Vector::new(0.2f32, 0f32, 0.95f32).normalize();
+        let s = t.sample_f(&wo, (0.4f32, 0.6f32)).unwrap();
+        assert!(s.wi.z < 0f32);
+        assert!(t.f(&wo, &wo).is_black());
+
+        // a rough boundary transmits about as much as a smooth one, once the
+        // 1 / eta^2 radiance compression is undone
+        let a = albedo(&t, &wo) * (1.5f32 * 1.5f32);
+        let smooth = 1f32 - FresnelDielectric::new(1f32, 1.5f32).evaluate(cos_theta(&wo)).g;
+        assert!((a.g - smooth).abs() < 0.05f32, "transmitted {} smooth {}", a.g, smooth);
+    }
+}
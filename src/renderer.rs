@@ -3,11 +3,12 @@ use threadpool::ThreadPool;
 use std::sync::{Arc, Mutex};
 
 use crate::scene::Scene;
-use crate::sampler::{SamplerFactory2D, Sampler2D};
+use crate::sampler::{SamplerFactory2D, Sampler2D, RandomSampler};
 use crate::film::Film;
 use crate::filters::{Filter, CachingFilter};
 use crate::cameras::Camera;
-use crate::geometry::Point;
+use crate::color::Color;
+use crate::integrators::Integrator;
 
 type Patch = (u32, u32, u32, u32);
 
@@ -16,16 +17,18 @@ pub struct RendererSetup {
     pub filter          : CachingFilter,
     pub camera          : Arc<dyn Camera>,
     pub sampler_factory : Arc<dyn SamplerFactory2D>,
+    pub integrator      : Arc<dyn Integrator>,
     pub output_filename : String,
 }
 
 impl RendererSetup {
-    pub fn new(film : Film, filter : CachingFilter, camera : Arc<dyn Camera>, sampler_factory : Arc<SamplerFactory2D>, output_filename : String) -> RendererSetup {
+    pub fn new(film : Film, filter : CachingFilter, camera : Arc<dyn Camera>, sampler_factory : Arc<dyn SamplerFactory2D>, integrator : Arc<dyn Integrator>, output_filename : String) -> RendererSetup {
         RendererSetup {
             film:            film,
             filter:          filter,
             camera:          camera,
             sampler_factory: sampler_factory,
+            integrator:      integrator,
             output_filename: output_filename,
         }
     }
@@ -34,6 +37,8 @@ impl RendererSetup {
 pub fn render(setup : RendererSetup, scene : Scene) {
     let patches = get_patches(&setup.film, 16);
 
+    let mut scene = scene;
+    scene.preprocess();
     let scene = Arc::new(scene);
     let filter = Arc::new(setup.filter);
     let film = Arc::new(Mutex::new(setup.film));
@@ -45,8 +50,9 @@ pub fn render(setup : RendererSetup, scene : Scene) {
         let filter = filter.clone();
         let scene = scene.clone();
         let film = film.clone();
+        let integrator = setup.integrator.clone();
         let sampler = setup.sampler_factory.get_sampler();
-        pool.execute(move || { render_patch(patch, film, camera, filter, scene, sampler); });
+        pool.execute(move || { render_patch(patch, film, camera, filter, scene, integrator, sampler); });
     }
 
     pool.join();
@@ -57,44 +63,45 @@ pub fn render(setup : RendererSetup, scene : Scene) {
     };
 }
 
-pub fn render_patch(patch : Patch, film : Arc<Mutex<Film>>, camera : Arc<dyn Camera>, filter : Arc<CachingFilter>, scene : Arc<Scene>, mut sampler : Box<dyn Sampler2D>) {
+pub fn render_patch(patch : Patch, film : Arc<Mutex<Film>>, camera : Arc<dyn Camera>, filter : Arc<CachingFilter>, scene : Arc<Scene>, integrator : Arc<dyn Integrator>, mut sampler : Box<dyn Sampler2D>) {
     let (xs, ys, xe, ye) = patch;
+    // seeded by where the patch is, so that a scene renders the same way
+    // each time, whichever thread gets to each patch first
+    let mut path_sampler = RandomSampler::new(((xs as u64) << 32) | ys as u64);
 
     let the_film = film.lock().unwrap();
     let x_scale = 2f32 / (the_film.width as f32);
     let y_scale = 2f32 / (the_film.height as f32);
     drop(the_film);
 
+    let mut splats = Vec::new();
+
     for x in xs..xe {
         for y in ys..ye {
-            let mut sum = 0f32;
+            let mut sum = Color::black();
             let mut weight_sum = 0f32;
 
-            for (dx, dy) in sampler.get_samples().into_iter() {
+            let samples = sampler.get_samples();
+            let n = samples.len() as f32;
+            for (dx, dy) in samples.into_iter() {
                 let fx = (x as f32) + dx;
                 let fy = (y as f32) + dy;
                 let cx = fx * x_scale - 1f32;
                 let cy = fy * y_scale - 1f32;
                 let r = camera.cast(cx, cy);
 
-                let v = match scene.intersect(&r) {
-                    None => 0f32,
-                    Some(i) => {
-                        let fudge = ((Point::origin() - i.context.p.from(&i.shape)).to_normal().dot(&i.context.n.from(&i.shape).normalize()) / 2f32) + 0.5f32;
-                        if ((i.context.u * 8f32).floor() as u32 % 2 == 0) ^ ((i.context.v * 8f32).floor() as u32 % 2 == 0) {
-                            255f32 * (1f32 - fudge)
-                        } else {
-                            64f32 * (1f32 - fudge)
-                        }
-                    }
-                };
+                let c = integrator.li_splats(&r, &scene, &mut path_sampler, &mut splats);
 
                 let w = filter.weight(dx - 0.5f32, dy - 0.5f32);
-                sum += v * w;
+                sum.add_self_c(&(c * w));
                 weight_sum += w;
             }
 
-            film.lock().unwrap().splat(x, y, sum, weight_sum);
+            let mut the_film = film.lock().unwrap();
+            the_film.splat(x, y, sum, weight_sum);
+            for ((cx, cy), c) in splats.drain(..) {
+                the_film.add_splat((cx + 1f32) / x_scale, (cy + 1f32) / y_scale, c / n);
+            }
         }
     }
 }
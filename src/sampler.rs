@@ -2,7 +2,9 @@ use std::f32::consts::*;
 
 use rand::prelude::*;
 use rand::distributions::uniform::Uniform;
+use rand::rngs::StdRng;
 
+use crate::geometry::Vector;
 use crate::math::{radical_inverse, sobol, van_der_corput};
 
 pub trait SamplerFactory1D {
@@ -14,6 +16,51 @@ pub trait SamplerFactory2D {
 }
 
 
+// The 2D sampler types by name, for choosing one from configuration.  Each
+// kind is also a factory for samplers of that kind.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Sampler2DKind {
+    Centers,
+    Uniform(usize),
+    Strata(usize, usize),
+    StrataCenters(usize, usize),
+    Halton(usize),
+    Hammersley(usize),
+    LHC(usize),
+    S02(usize),
+}
+
+impl Sampler2DKind {
+    pub fn sample_count(&self) -> usize {
+        match *self {
+            Sampler2DKind::Centers             => 1,
+            Sampler2DKind::Strata(w, h)        => w * h,
+            Sampler2DKind::StrataCenters(w, h) => w * h,
+            Sampler2DKind::Uniform(n)          => n,
+            Sampler2DKind::Halton(n)           => n,
+            Sampler2DKind::Hammersley(n)       => n,
+            Sampler2DKind::LHC(n)              => n,
+            Sampler2DKind::S02(n)              => n,
+        }
+    }
+}
+
+impl SamplerFactory2D for Sampler2DKind {
+    fn get_sampler(&self) -> Box<dyn Sampler2D> {
+        match *self {
+            Sampler2DKind::Centers             => Box::new(CentersSampler2D::new()),
+            Sampler2DKind::Uniform(n)          => Box::new(UniformSampler2D::new(n)),
+            Sampler2DKind::Strata(w, h)        => Box::new(StrataSampler2D::new(w, h)),
+            Sampler2DKind::StrataCenters(w, h) => Box::new(StrataCentersSampler2D::new(w, h)),
+            Sampler2DKind::Halton(n)           => Box::new(HaltonSampler2D::new(n)),
+            Sampler2DKind::Hammersley(n)       => Box::new(HammersleySampler2D::new(n)),
+            Sampler2DKind::LHC(n)              => Box::new(LHCSampler2D::new(n)),
+            Sampler2DKind::S02(n)              => Box::new(S02Sampler2D::new(random(), random(), n)),
+        }
+    }
+}
+
+
 pub trait Sampler1D : Sync + Send  {
     fn get_samples(&mut self) -> Vec<f32>;
 }
@@ -337,6 +384,35 @@ impl Sampler2D for S02Sampler2D {
 }
 
 
+// A stream of sample values for an integrator to draw on as it follows a
+// path, as many as the path needs.
+pub trait Sampler : Send {
+    fn get_1d(&mut self) -> f32;
+    fn get_2d(&mut self) -> (f32, f32);
+}
+
+
+pub struct RandomSampler {
+    rng : StdRng,
+}
+
+impl RandomSampler {
+    pub fn new(seed : u64) -> RandomSampler {
+        RandomSampler { rng: StdRng::seed_from_u64(seed) }
+    }
+}
+
+impl Sampler for RandomSampler {
+    fn get_1d(&mut self) -> f32 {
+        self.rng.gen()
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        (self.rng.gen(), self.rng.gen())
+    }
+}
+
+
 pub fn to_disc_uniform((u1, u2) : (f32, f32)) -> (f32, f32) {
     let r = u1.sqrt();
     let theta = u2 * 2f32 * PI;
@@ -360,3 +436,27 @@ pub fn to_disc_concentric((u1, u2) : (f32, f32)) -> (f32, f32) {
         (radius * theta.cos(), radius * theta.sin())
     }
 }
+
+// A direction on the +z hemisphere, with density cos(theta) / pi.
+pub fn to_hemisphere_cosine(u : (f32, f32)) -> Vector {
+    let (x, y) = to_disc_concentric(u);
+    let z = (1f32 - x * x - y * y).max(0f32).sqrt();
+    Vector::new(x, y, z)
+}
+
+// A direction on the unit sphere, with density 1 / (4 pi).
+pub fn to_sphere_uniform((u1, u2) : (f32, f32)) -> Vector {
+    let z = 1f32 - 2f32 * u1;
+    let r = (1f32 - z * z).max(0f32).sqrt();
+    let phi = 2f32 * PI * u2;
+    Vector::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+// A direction within the cone of directions about +z whose angle from it has
+// cosine at least cos_max, with density 1 / (2 pi (1 - cos_max)).
+pub fn to_cone_uniform((u1, u2) : (f32, f32), cos_max : f32) -> Vector {
+    let z = (1f32 - u1) + u1 * cos_max;
+    let r = (1f32 - z * z).max(0f32).sqrt();
+    let phi = 2f32 * PI * u2;
+    Vector::new(r * phi.cos(), r * phi.sin(), z)
+}
@@ -1,40 +1,266 @@
+use std::collections::HashMap;
+use std::ops::Range;
 use std::sync::Arc;
 
-use crate::geometry::{Ray, BoundingBox, Transform, HasTransform};
-use crate::shapes::{Shape, ShapeIntersection, SurfaceContext};
+use crate::bvh::BVH;
+use crate::color::Color;
+use crate::distribution::Distribution1D;
+use crate::geometry::{Ray, BoundingBox, Transform, HasTransform, Vector};
+use crate::lights::{Light, DiffuseAreaLight};
+use crate::materials::Material;
+use crate::media::{Medium, MediumInterface};
+use crate::reflection::BSDF;
+use crate::sampler::Sampler;
+use crate::shapes::{Shape, ShapeIntersection, SurfaceContext, TriangleMesh};
+
+// A shape in the scene, with its world bound and appearance, and the index
+// of its light in the scene's lights if it emits.  A shape without a
+// material is invisible, and only bounds the media of its interface; one
+// without an interface leaves rays in the medium they were in.
+#[derive(Clone)]
+pub struct Primitive {
+    pub bound            : BoundingBox,
+    pub shape            : Arc<dyn Shape>,
+    pub material         : Option<Arc<dyn Material>>,
+    pub light            : Option<usize>,
+    pub medium_interface : Option<MediumInterface>,
+}
 
 pub struct Scene {
-    pub primitives : Vec<(BoundingBox, Arc<dyn Shape>)>,
-    pub bounds : BoundingBox
+    pub primitives : Vec<Primitive>,
+    pub lights : Vec<Box<dyn Light>>,
+    pub bounds : BoundingBox,
+    // the medium the camera sits in, if any
+    pub camera_medium : Option<Arc<dyn Medium>>,
+    // named sets of primitives, such as the objects and groups of an OBJ
+    // file, as ranges of indices into primitives
+    pub groups : HashMap<String, Vec<Range<usize>>>,
+    bvh : Option<BVH>,
+    light_distribution : Option<Distribution1D>,
 }
 
 impl Scene {
     pub fn new() -> Scene {
         Scene {
             primitives: Vec::new(),
-            bounds: BoundingBox::empty()
+            lights: Vec::new(),
+            bounds: BoundingBox::empty(),
+            camera_medium: None,
+            groups: HashMap::new(),
+            bvh: None,
+            light_distribution: None,
+        }
+    }
+
+    pub fn add(&mut self, shape : Arc<dyn Shape>, material : Arc<dyn Material>) {
+        self.add_primitive(shape, Some(material), None, None);
+    }
+
+    // The mesh_* methods add a primitive per triangle, and return the range
+    // of primitives they added.
+    pub fn add_mesh(&mut self, mesh : &Arc<TriangleMesh>, material : Arc<dyn Material>) -> Range<usize> {
+        let start = self.primitives.len();
+        for t in TriangleMesh::triangles(mesh) {
+            self.add(Arc::new(t), material.clone());
+        }
+        start..self.primitives.len()
+    }
+
+    // Adds a shape that emits radiance l, as well as reflecting light as its
+    // material does.
+    pub fn add_area_light(&mut self, shape : Arc<dyn Shape>, material : Arc<dyn Material>, l : Color, two_sided : bool) {
+        self.add_light(Box::new(DiffuseAreaLight::new(l, shape.clone(), two_sided)));
+        let ix = self.lights.len() - 1;
+        self.add_primitive(shape, Some(material), Some(ix), None);
+    }
+
+    // Adds a mesh whose triangles each emit radiance l.
+    pub fn add_mesh_area_light(&mut self, mesh : &Arc<TriangleMesh>, material : Arc<dyn Material>, l : Color, two_sided : bool) -> Range<usize> {
+        let start = self.primitives.len();
+        for t in TriangleMesh::triangles(mesh) {
+            self.add_area_light(Arc::new(t), material.clone(), l, two_sided);
+        }
+        start..self.primitives.len()
+    }
+
+    // Adds a shape with media on either side, such as a glass of water, or,
+    // without a material, an invisible one that only bounds a medium, such as
+    // the extent of a cloud of smoke.
+    pub fn add_medium_boundary(&mut self, shape : Arc<dyn Shape>, material : Option<Arc<dyn Material>>, interface : MediumInterface) {
+        self.add_primitive(shape, material, None, Some(interface));
+    }
+
+    pub fn add_mesh_medium_boundary(&mut self, mesh : &Arc<TriangleMesh>, material : Option<Arc<dyn Material>>, interface : MediumInterface) -> Range<usize> {
+        let start = self.primitives.len();
+        for t in TriangleMesh::triangles(mesh) {
+            self.add_medium_boundary(Arc::new(t), material.clone(), interface.clone());
         }
+        start..self.primitives.len()
     }
 
-    pub fn add(&mut self, p : Arc<dyn Shape>) {
-        let b = p.world_bound();
-        self.primitives.push((b, p));
+    // Adds the primitives in range to the group called name.
+    pub fn add_group(&mut self, name : &str, primitives : Range<usize>) {
+        self.groups.entry(String::from(name)).or_default().push(primitives);
+    }
+
+    // The primitives in the group called name, if there is one.
+    pub fn group(&self, name : &str) -> Option<Vec<&Primitive>> {
+        self.groups.get(name).map(|ranges| ranges.iter().flat_map(|r| self.primitives[r.clone()].iter()).collect())
+    }
+
+    // Gives every primitive in the group called name the material, returning
+    // false if there is no such group.
+    pub fn set_group_material(&mut self, name : &str, material : Arc<dyn Material>) -> bool {
+        match self.groups.get(name) {
+            Some(ranges) => {
+                for r in ranges.iter() {
+                    for p in self.primitives[r.clone()].iter_mut() {
+                        p.material = Some(material.clone());
+                    }
+                }
+                true
+            },
+            None => false,
+        }
+    }
+
+    fn add_primitive(&mut self, shape : Arc<dyn Shape>, material : Option<Arc<dyn Material>>, light : Option<usize>, interface : Option<MediumInterface>) {
+        let b = shape.world_bound();
+        self.primitives.push(Primitive { bound: b, shape: shape, material: material, light: light, medium_interface: interface });
         self.bounds.add_self_bounding_box(&b);
+        self.bvh = None;
     }
 
+    pub fn add_light(&mut self, light : Box<dyn Light>) {
+        self.lights.push(light);
+        self.light_distribution = None;
+    }
+
+    // Readies a complete scene for rendering: builds the BVH, lets the
+    // lights see the scene's bounds and weighs them by their power.
+    pub fn preprocess(&mut self) {
+        self.build_bvh();
+        for l in self.lights.iter_mut() {
+            l.preprocess(&self.bounds);
+        }
+        if !self.lights.is_empty() {
+            let power : Vec<f32> = self.lights.iter().map(|l| l.power().luminance()).collect();
+            self.light_distribution = Some(Distribution1D::new(&power));
+        }
+    }
+
+    // Picks one of the lights with u, returning its index and the probability
+    // of choosing it.  Brighter lights are chosen more often once the scene
+    // has been preprocessed, and all alike before.
+    pub fn sample_light(&self, u : f32) -> Option<(usize, f32)> {
+        if self.lights.is_empty() {
+            return None;
+        }
+        match self.light_distribution {
+            Some(ref d) => Some(d.sample_discrete(u)),
+            None        => {
+                let n = self.lights.len();
+                Some((((u * n as f32) as usize).min(n - 1), 1f32 / n as f32))
+            },
+        }
+    }
+
+    // The probability that sample_light() picks the light at index ix.
+    pub fn light_pdf(&self, ix : usize) -> f32 {
+        match self.light_distribution {
+            Some(ref d) => d.discrete_pdf(ix),
+            None        => 1f32 / self.lights.len() as f32,
+        }
+    }
+
+    // Builds the acceleration structure used by intersect().  Adding another
+    // primitive discards it, and intersect() falls back to a linear scan until
+    // it is rebuilt.
+    pub fn build_bvh(&mut self) {
+        if self.bvh.is_none() {
+            let bounds : Vec<BoundingBox> = self.primitives.iter().map(|p| p.bound).collect();
+            self.bvh = Some(BVH::new(&bounds));
+        }
+    }
+
+    // The first visible surface along r, passing through the shapes that
+    // only bound media.
     pub fn intersect(&self, r : &Ray) -> Option<SceneIntersection> {
-        let mut first_intersection = None;
-
-        if self.bounds.intersects(&r) {
-            for (a, p) in self.primitives.iter() {
-                if a.intersects(r) {
-                    match p.intersect(r) {
-                        None => { }
-                        Some(i) => {
-                            match first_intersection {
-                                None => first_intersection = Some(SceneIntersection::new(p.clone(), i)),
-                                Some(ref i0) => if i.time < i0.time { first_intersection = Some(SceneIntersection::new(p.clone(), i)) },
-                            }
+        self.intersect_primitives(r, false)
+    }
+
+    // Whether any visible surface lies along r.  Cheaper than intersect(), as
+    // it stops at the first surface found, which suits shadow rays.
+    pub fn intersects(&self, r : &Ray) -> bool {
+        match self.bvh {
+            Some(ref bvh) => bvh.intersects(r, |ix, ray| {
+                let p = &self.primitives[ix];
+                p.material.is_some() && p.shape.intersects(ray)
+            }),
+            None => {
+                self.bounds.intersects(r).is_some() && self.primitives.iter().any(|p| {
+                    p.material.is_some() && p.bound.intersects(r).is_some() && p.shape.intersects(r)
+                })
+            },
+        }
+    }
+
+    // As intersect(), but also stopping at the shapes that only bound media,
+    // for integrators that follow rays through them.
+    pub fn intersect_media(&self, r : &Ray) -> Option<SceneIntersection> {
+        self.intersect_primitives(r, true)
+    }
+
+    // The first visible surface along r, which starts in medium, and the
+    // transmittance of the media along the way to it, or to the end of r if
+    // nothing is hit.
+    pub fn intersect_tr(&self, r : &Ray, medium : Option<Arc<dyn Medium>>, sampler : &mut dyn Sampler) -> (Option<SceneIntersection>, Color) {
+        let mut ray = *r;
+        let mut medium = medium;
+        let mut tr = Color::white();
+        loop {
+            let hit = self.intersect_media(&ray);
+            if let Some(ref m) = medium {
+                tr.mul_self_c(&m.tr(&ray, hit.as_ref().map_or(ray.t_max, |i| i.time), sampler));
+            }
+
+            match hit {
+                Some(ref i) if i.material.is_none() => {
+                    medium = i.medium(&ray.direction, medium);
+                    ray = if ray.t_max.is_finite() { i.context.spawn_ray_to(&ray.at_time(ray.t_max)) } else { i.context.spawn_ray(&ray.direction) };
+                },
+                _ => return (hit, tr),
+            }
+        }
+    }
+
+    fn intersect_primitives(&self, r : &Ray, boundaries : bool) -> Option<SceneIntersection> {
+        match self.bvh {
+            Some(ref bvh) => {
+                bvh.intersect(r, |ix, ray| {
+                    let p = &self.primitives[ix];
+                    if boundaries || p.material.is_some() { p.shape.intersect(ray) } else { None }
+                }).map(|(ix, i)| SceneIntersection::new(ix, &self.primitives[ix], i))
+            },
+            None => self.intersect_linear(r, boundaries),
+        }
+    }
+
+    fn intersect_linear(&self, r : &Ray, boundaries : bool) -> Option<SceneIntersection> {
+        let mut ray = *r;
+        let mut first_intersection : Option<SceneIntersection> = None;
+
+        if self.bounds.intersects(&ray).is_some() {
+            for (ix, p) in self.primitives.iter().enumerate().filter(|(_, p)| boundaries || p.material.is_some()) {
+                if p.bound.intersects(&ray).is_some() {
+                    if let Some(i) = p.shape.intersect(&ray) {
+                        let closer = match first_intersection {
+                            None         => true,
+                            Some(ref i0) => i.time < i0.time,
+                        };
+                        if closer {
+                            ray.t_max = i.time;
+                            first_intersection = Some(SceneIntersection::new(ix, p, i));
                         }
                     }
                 }
@@ -51,21 +277,56 @@ impl HasTransform for Arc<dyn Shape> {
     }
 }
 
+// A hit on a primitive.  Unlike a ShapeIntersection, the context is in world space.
 #[derive(Clone)]
 pub struct SceneIntersection {
     pub ray : Ray,
     pub time : f32,
+    // the index of the primitive hit in the scene's primitives
+    pub primitive : usize,
     pub shape : Arc<dyn Shape>,
+    pub material : Option<Arc<dyn Material>>,
+    pub light : Option<usize>,
+    pub medium_interface : Option<MediumInterface>,
     pub context : SurfaceContext,
 }
 
 impl SceneIntersection {
-    pub fn new(shape : Arc<dyn Shape>, i : ShapeIntersection) -> SceneIntersection {
+    pub fn new(ix : usize, p : &Primitive, i : ShapeIntersection) -> SceneIntersection {
         SceneIntersection {
             ray: i.ray,
             time: i.time,
-            shape: shape,
-            context: i.context
+            primitive: ix,
+            shape: p.shape.clone(),
+            material: p.material.clone(),
+            light: p.light,
+            medium_interface: p.medium_interface.clone(),
+            context: i.context.from(&p.shape)
+        }
+    }
+
+    // The surface's BSDF, which scatters nothing if it only bounds media.
+    pub fn bsdf(&self, allow_multiple_lobes : bool) -> BSDF {
+        match self.material {
+            Some(ref m) => m.bsdf(&self.context, allow_multiple_lobes),
+            None        => BSDF::new(&self.context, 1f32),
+        }
+    }
+
+    // The medium a ray leaving the hit in direction w travels through, given
+    // that the ray which found it was in medium.
+    pub fn medium(&self, w : &Vector, medium : Option<Arc<dyn Medium>>) -> Option<Arc<dyn Medium>> {
+        match self.medium_interface {
+            Some(ref interface) => interface.medium(w, &self.context.n),
+            None                => medium,
+        }
+    }
+
+    // The radiance emitted in direction w from the hit, if it is on a light.
+    pub fn le(&self, scene : &Scene, w : &Vector) -> Color {
+        match self.light {
+            None     => Color::black(),
+            Some(ix) => scene.lights[ix].l(&self.context, w),
         }
     }
 }
@@ -1,8 +1,9 @@
 use std::default::Default;
 use std::f32::consts::*;
 
-use crate::geometry::{Transform, Trans, TransMut, HasTransform, BoundingBox, Ray, Point, Vector};
-use crate::math::quadratic;
+use crate::geometry::{Transform, Trans, TransMut, HasTransform, BoundingBox, Ray, Point, Vector, Normal};
+use crate::efloat::EFloat;
+use crate::math::{quadratic_efloat, gamma};
 use crate::shapes::{Shape, ShapeIntersection, SurfaceContext};
 
 #[derive(Copy, Clone)]
@@ -27,6 +28,71 @@ impl Cone {
     pub fn unit() -> Cone {
         Cone::new(0.5f32, 1f32)
     }
+
+    // The nearest hit along r that lies within the surface's bounds, in
+    // object space, with the point hit, a bound on its error and its azimuth.
+    // Roots that might, given their error, lie before the start of the ray
+    // are passed over, so that a ray leaving the surface cannot hit it again.
+    fn hit(&self, r : &Ray) -> Option<(f32, Point, Vector, f32)> {
+        let (ray, o_err, d_err) = r.to_with_error(self);
+        let (ox, oy, oz) = (EFloat::new(ray.origin.x, o_err.x), EFloat::new(ray.origin.y, o_err.y), EFloat::new(ray.origin.z, o_err.z));
+        let (dx, dy, dz) = (EFloat::new(ray.direction.x, d_err.x), EFloat::new(ray.direction.y, d_err.y), EFloat::new(ray.direction.z, d_err.z));
+        let m = EFloat::from((self.height * self.height) / (self.radius * self.radius));
+        let h = EFloat::from(self.height);
+
+        let a = m * (dx * dx + dy * dy) - dz * dz;
+        let b = EFloat::from(2f32) * (m * (ox * dx + oy * dy) - dz * (oz - h));
+        let c = m * (ox * ox + oy * oy) - (oz - h) * (oz - h);
+
+        let (t0, t1) = quadratic_efloat(a, b, c)?;
+        if t0.v > ray.t_max || t1.lower_bound() <= ray.t_min {
+            return None
+        }
+
+        let mut thit = t0;
+        if thit.lower_bound() <= ray.t_min {
+            thit = t1;
+            if thit.v > ray.t_max {
+                return None
+            }
+        }
+
+        let mut phit = ray.at_time(thit.v);
+        if phit.x == 0f32 && phit.y == 0f32 {
+            phit.x = 1e-5f32 * self.radius;
+        }
+
+        let mut phi = phit.y.atan2(phit.x);
+        if phi < 0f32 {
+            phi += 2f32 * PI;
+        }
+
+        if phit.z < self.z_min || phit.z > self.z_max || phi > self.phi_max {
+            if thit.v == t1.v || t1.v > ray.t_max {
+                return None
+            }
+
+            thit = t1;
+
+            phit = ray.at_time(thit.v);
+            if phit.x == 0f32 && phit.y == 0f32 {
+                phit.x = 1e-5f32 * self.radius;
+            }
+
+            phi = phit.y.atan2(phit.x);
+            if phi < 0f32 {
+                phi += 2f32 * PI;
+            }
+
+            if phit.z < self.z_min || phit.z > self.z_max || phi > self.phi_max {
+                return None
+            }
+        }
+
+        // the true hit is somewhere along the ray within thit's error
+        let p_error = Vector::new((ox + thit * dx).absolute_error(), (oy + thit * dy).absolute_error(), (oz + thit * dz).absolute_error());
+        Some((thit.v, phit, p_error, phi))
+    }
 }
 
 impl Default for Cone {
@@ -51,86 +117,65 @@ impl Shape for Cone {
     }
 
     fn surface_area(&self) -> f32 {
-        ((self.phi_max * self.radius) / (2f32 * self.height)) * (self.z_max - self.z_min) * (self.radius + (self.height * self.height + self.radius * self.radius).sqrt())
+        // the radii at either end, and the slant length between them
+        let r0 = self.radius * (1f32 - self.z_min / self.height);
+        let r1 = self.radius * (1f32 - self.z_max / self.height);
+        let slant = (self.z_max - self.z_min) * (1f32 + (self.radius * self.radius) / (self.height * self.height)).sqrt();
+        (self.phi_max / 2f32) * (r0 + r1) * slant
     }
 
     fn intersect(&self, r : &Ray) -> Option<ShapeIntersection> {
-        let ray = r.to(self);
-
-        let m = (self.height * self.height) / (self.radius * self.radius);
-
-        let a = m * (ray.direction.x * ray.direction.x + ray.direction.y * ray.direction.y) - (ray.direction.z * ray.direction.z);
-        let b = 2f32 * (m * (ray.origin.x * ray.direction.x + ray.origin.y * ray.direction.y) + (-ray.origin.z * ray.direction.z + ray.direction.z * self.height));
-        let c = m * (ray.origin.x * ray.origin.x + ray.origin.y * ray.origin.y) + (-ray.origin.z * ray.origin.z + 2f32 * ray.origin.z * self.height - self.height * self.height);
-
-        match quadratic(a, b, c) {
-            None => { None }
-            Some((t0, t1)) => {
-                let mut thit = if t0 >= 0f32 { t0 } else { t1 };
-                if thit < 0f32 {
-                    return None
-                }
-
-                let mut phit = ray.at_time(thit);
-                if phit.x == 0f32 && phit.y == 0f32 {
-                    phit.x = 1e-5f32 * self.radius;
-                }
+        let (thit, phit, p_error, phi) = self.hit(r)?;
 
-                let mut phi = phit.y.atan2(phit.x);
-                if phi < 0f32 {
-                    phi += 2f32 * PI;
-                }
+        let u = phi / self.phi_max;
+        let v = (phit.z - self.z_min) / (self.z_max - self.z_min);
 
-                if phit.z < self.z_min || phit.z > self.z_max || phi > self.phi_max {
-                    if thit == t1 {
-                        return None
-                    }
+        let dpdu = Vector::new(-self.phi_max * phit.y, self.phi_max * phit.x, 0f32);
+        let dpdv = (self.z_max - self.z_min) * Vector::new(-phit.x / (self.height - phit.z), -phit.y / (self.height - phit.z), 1f32);
 
-                    thit = t1;
+        let normal = dpdu.cross(&dpdv).normalize().to_normal();
 
-                    phit = ray.at_time(thit);
-                    if phit.x == 0f32 && phit.y == 0f32 {
-                        phit.x = 1e-5f32 * self.radius;
-                    }
+        let d2pduu = -self.phi_max * self.phi_max * Vector::new(phit.x, phit.y, 0f32);
+        let d2pduv = (self.phi_max * (self.z_max - self.z_min) / (self.height - phit.z)) * Vector::new(phit.y, -phit.x, 0f32);
+        let d2pdvv = Vector::zero();
 
-                    phi = phit.y.atan2(phit.x);
-                    if phi < 0f32 {
-                        phi += 2f32 * PI;
-                    }
+        let c_e = dpdu.dot(&dpdu);
+        let c_f = dpdu.dot(&dpdv);
+        let c_g = dpdv.dot(&dpdv);
+        let n = dpdu.cross(&dpdv).normalize();
+        let e = n.dot(&d2pduu);
+        let f = n.dot(&d2pduv);
+        let g = n.dot(&d2pdvv);
 
-                    if phit.z < self.z_min || phit.z > self.z_max || phi > self.phi_max {
-                        return None
-                    }
-                }
+        let egf2 = 1f32 / (c_e*c_g - c_f*c_f);
 
-                let u = phi / self.phi_max;
-                let v = (phit.z - self.z_min) / (self.z_max - self.z_min);
+        // the Weingarten equations; unlike the normal, these keep their length
+        let dndu = (f*c_f - e*c_g) * egf2 * dpdu + (e*c_f - f*c_e) * egf2 * dpdv;
+        let dndv = (g*c_f - f*c_g) * egf2 * dpdu + (f*c_f - g*c_e) * egf2 * dpdv;
 
-                let dpdu = Vector::new(-self.phi_max * phit.y, self.phi_max * phit.x, 0f32);
-                let dpdv = Vector::new(-phit.x / (1f32 - v), phit.y / (1f32 - v), self.z_max - self.z_min);
-
-                let normal = dpdu.cross(&dpdv).normalize().to_normal().face_forward(&ray.direction);
-
-                let d2pduu = -self.phi_max * self.phi_max * Vector::new(phit.x, phit.y, 0f32);
-                let d2pduv = (self.phi_max / (1f32 - v)) * Vector::new(phit.y, -phit.x, 0f32);
-                let d2pdvv = Vector::zero();
-
-                let c_e = dpdu.dot(&dpdu);
-                let c_f = dpdu.dot(&dpdv);
-                let c_g = dpdv.dot(&dpdv);
-                let n = dpdu.cross(&dpdv).normalize();
-                let e = n.dot(&d2pduu);
-                let f = n.dot(&d2pduv);
-                let g = n.dot(&d2pdvv);
-
-                let egf2 = 1f32 / (c_e*c_g - c_f*c_f);
+        let mut context = SurfaceContext::new(phit, normal, (u, v), (dpdu, dpdv), (Normal::new(dndu.x, dndu.y, dndu.z), Normal::new(dndv.x, dndv.y, dndv.z)));
+        context.p_error = p_error;
+        Some(ShapeIntersection::new(*r, thit, context))
+    }
 
-                let dndu = ((f*c_f - e*c_e) * egf2 * dpdu + (e*c_f - f*c_e) * egf2 * dpdv).to_normal();
-                let dndv = ((g*c_f - f*c_e) * egf2 * dpdu + (f*c_f - g*c_e) * egf2 * dpdv).to_normal();
+    fn intersects(&self, r : &Ray) -> bool {
+        self.hit(r).is_some()
+    }
 
-                return Some(ShapeIntersection::new(*r, thit, SurfaceContext::new(phit, normal, (u, v), (dpdu, dpdv), (dndu, dndv))));
-            }
-        }
+    fn sample(&self, (u0, u1) : (f32, f32)) -> (Point, Normal, Vector) {
+        // area grows with the radius, which is proportional to s = 1 - z/h,
+        // so s² is uniform
+        let s0 = 1f32 - self.z_min / self.height;
+        let s1 = 1f32 - self.z_max / self.height;
+        let s = (s1 * s1 + u0 * (s0 * s0 - s1 * s1)).sqrt();
+        let phi = u1 * self.phi_max;
+        let rho = self.radius * s;
+        let p = Point::new(rho * phi.cos(), rho * phi.sin(), self.height * (1f32 - s));
+        // the radius of the cone at p.z may be a few ulps of the full radius
+        // from rho, as the two are found from s by different routes
+        let (pw, p_error) = p.from_with_error(self, &(Vector::new(p.x.abs() + self.radius, p.y.abs() + self.radius, 0f32) * gamma(5)));
+        let n = Normal::new(self.height * phi.cos(), self.height * phi.sin(), self.radius);
+        (pw, n.from(self).normalize(), p_error)
     }
 }
 
@@ -1,8 +1,9 @@
 use std::default::Default;
 use std::f32::consts::*;
 
-use crate::geometry::{Transform, Trans, TransMut, HasTransform, BoundingBox, Ray, Point, Vector};
-use crate::math::quadratic;
+use crate::geometry::{Transform, Trans, TransMut, HasTransform, BoundingBox, Ray, Point, Vector, Normal};
+use crate::efloat::EFloat;
+use crate::math::{quadratic_efloat, gamma};
 use crate::shapes::{Shape, ShapeIntersection, SurfaceContext};
 
 #[derive(Copy, Clone)]
@@ -38,6 +39,76 @@ impl Cylinder {
     pub fn unit() -> Cylinder {
         Cylinder::new(0.5f32, 1f32)
     }
+
+    // The nearest hit along r that lies within the surface's bounds, in
+    // object space, with the point hit, a bound on its error and its azimuth.
+    // Roots that might, given their error, lie before the start of the ray
+    // are passed over, so that a ray leaving the surface cannot hit it again.
+    fn hit(&self, r : &Ray) -> Option<(f32, Point, Vector, f32)> {
+        let (ray, o_err, d_err) = r.to_with_error(self);
+        let (ox, oy) = (EFloat::new(ray.origin.x, o_err.x), EFloat::new(ray.origin.y, o_err.y));
+        let (dx, dy) = (EFloat::new(ray.direction.x, d_err.x), EFloat::new(ray.direction.y, d_err.y));
+        let radius = EFloat::from(self.radius);
+
+        let a = dx * dx + dy * dy;
+        let b = EFloat::from(2f32) * (dx * ox + dy * oy);
+        let c = ox * ox + oy * oy - radius * radius;
+
+        let (t0, t1) = quadratic_efloat(a, b, c)?;
+        if t0.v > ray.t_max || t1.lower_bound() <= ray.t_min {
+            return None
+        }
+
+        let mut thit = t0;
+        if thit.lower_bound() <= ray.t_min {
+            thit = t1;
+            if thit.v > ray.t_max {
+                return None
+            }
+        }
+
+        // the point is moved back onto the cylinder, which leaves less error
+        // than finding it along the ray
+        let point_at = |t : f32| {
+            let mut p = ray.at_time(t);
+            let s = self.radius / (p.x * p.x + p.y * p.y).sqrt();
+            p.x *= s;
+            p.y *= s;
+            if p.x == 0f32 && p.y == 0f32 {
+                p.x = 1e-5f32 * self.radius;
+            }
+            p
+        };
+
+        let mut phit = point_at(thit.v);
+
+        let mut phi = phit.y.atan2(phit.x);
+        if phi < 0f32 {
+            phi += 2f32 * PI;
+        }
+
+        if phit.z < self.z_min || phit.z > self.z_max || phi > self.phi_max {
+            if thit.v == t1.v || t1.v > ray.t_max {
+                return None
+            }
+
+            thit = t1;
+
+            phit = point_at(thit.v);
+
+            phi = phit.y.atan2(phit.x);
+            if phi < 0f32 {
+                phi += 2f32 * PI;
+            }
+
+            if phit.z < self.z_min || phit.z > self.z_max || phi > self.phi_max {
+                return None
+            }
+        }
+
+        let p_error = Vector::new(phit.x, phit.y, 0f32).abs() * gamma(3);
+        Some((thit.v, phit, p_error, phi))
+    }
 }
 
 impl Default for Cylinder {
@@ -66,79 +137,50 @@ impl Shape for Cylinder {
     }
 
     fn intersect(&self, r : &Ray) -> Option<ShapeIntersection> {
-        let ray = r.to(self);
-
-        let a = (ray.direction.x * ray.direction.x) + (ray.direction.y * ray.direction.y);
-        let b = 2f32 * ((ray.direction.x * ray.origin.x) + (ray.direction.y * ray.origin.y));
-        let c = (ray.origin.x * ray.origin.x) + (ray.origin.y * ray.origin.y) - (self.radius * self.radius);
-        match quadratic(a, b, c) {
-            None => { None },
-            Some((t0, t1)) => {
-                let mut thit = if t0 >= 0f32 { t0 } else { t1 };
-                if thit < 0f32 {
-                    return None
-                }
-
-                let mut phit = ray.at_time(thit);
-                if phit.x == 0f32 && phit.y == 0f32 {
-                    phit.x = 1e-5f32 * self.radius;
-                }
-
-                let mut phi = phit.y.atan2(phit.x);
-                if phi < 0f32 {
-                    phi += 2f32 * PI;
-                }
-
-                if phit.z < self.z_min || phit.z > self.z_max || phi > self.phi_max {
-                    if thit == t1 {
-                        return None
-                    }
-
-                    thit = t1;
-
-                    phit = ray.at_time(thit);
-                    if phit.x == 0f32 && phit.y == 0f32 {
-                        phit.x = 1e-5f32 * self.radius;
-                    }
-
-                    phi = phit.y.atan2(phit.x);
-                    if phi < 0f32 {
-                        phi += 2f32 * PI;
-                    }
-
-                    if phit.z < self.z_min || phit.z > self.z_max || phi > self.phi_max {
-                        return None
-                    }
-                }
-
-                let u = phi / self.phi_max;
-                let v = (phit.z - self.z_min) / (self.z_max - self.z_min);
-
-                let dpdu = Vector::new(-self.phi_max * phit.y, self.phi_max * phit.x, 0f32);
-                let dpdv = Vector::new(0f32, 0f32, self.z_max - self.z_min);
-
-                let normal = dpdu.cross(&dpdv).normalize().to_normal().face_forward(&ray.direction);
-
-                let d2pduu = -self.phi_max * self.phi_max * Vector::new(phit.x, phit.y, 0f32);
-                let d2pduv = Vector::zero();
-                let d2pdvv = Vector::zero();
-
-                let c_e = dpdu.dot(&dpdu);
-                let c_f = dpdu.dot(&dpdv);
-                let c_g = dpdv.dot(&dpdv);
-                let n = dpdu.cross(&dpdv).normalize();
-                let e = n.dot(&d2pduu);
-                let f = n.dot(&d2pduv);
-                let g = n.dot(&d2pdvv);
-
-                let egf2 = 1f32 / (c_e*c_g - c_f*c_f);
-
-                let dndu = ((f*c_f - e*c_e) * egf2 * dpdu + (e*c_f - f*c_e) * egf2 * dpdv).to_normal();
-                let dndv = ((g*c_f - f*c_e) * egf2 * dpdu + (f*c_f - g*c_e) * egf2 * dpdv).to_normal();
-
-                return Some(ShapeIntersection::new(*r, thit, SurfaceContext::new(phit, normal, (u, v), (dpdu, dpdv), (dndu, dndv))));
-            },
-        }
+        let (thit, phit, p_error, phi) = self.hit(r)?;
+
+        let u = phi / self.phi_max;
+        let v = (phit.z - self.z_min) / (self.z_max - self.z_min);
+
+        let dpdu = Vector::new(-self.phi_max * phit.y, self.phi_max * phit.x, 0f32);
+        let dpdv = Vector::new(0f32, 0f32, self.z_max - self.z_min);
+
+        let normal = dpdu.cross(&dpdv).normalize().to_normal();
+
+        let d2pduu = -self.phi_max * self.phi_max * Vector::new(phit.x, phit.y, 0f32);
+        let d2pduv = Vector::zero();
+        let d2pdvv = Vector::zero();
+
+        let c_e = dpdu.dot(&dpdu);
+        let c_f = dpdu.dot(&dpdv);
+        let c_g = dpdv.dot(&dpdv);
+        let n = dpdu.cross(&dpdv).normalize();
+        let e = n.dot(&d2pduu);
+        let f = n.dot(&d2pduv);
+        let g = n.dot(&d2pdvv);
+
+        let egf2 = 1f32 / (c_e*c_g - c_f*c_f);
+
+        // the Weingarten equations; unlike the normal, these keep their length
+        let dndu = (f*c_f - e*c_g) * egf2 * dpdu + (e*c_f - f*c_e) * egf2 * dpdv;
+        let dndv = (g*c_f - f*c_g) * egf2 * dpdu + (f*c_f - g*c_e) * egf2 * dpdv;
+
+        let mut context = SurfaceContext::new(phit, normal, (u, v), (dpdu, dpdv), (Normal::new(dndu.x, dndu.y, dndu.z), Normal::new(dndv.x, dndv.y, dndv.z)));
+        context.p_error = p_error;
+        Some(ShapeIntersection::new(*r, thit, context))
+    }
+
+    fn intersects(&self, r : &Ray) -> bool {
+        self.hit(r).is_some()
+    }
+
+    fn sample(&self, (u0, u1) : (f32, f32)) -> (Point, Normal, Vector) {
+        let z = self.z_min + u0 * (self.z_max - self.z_min);
+        let phi = u1 * self.phi_max;
+        let p = Point::new(self.radius * phi.cos(), self.radius * phi.sin(), z);
+        let (pw, p_error) = p.from_with_error(self, &(Vector::new(p.x, p.y, 0f32).abs() * gamma(3)));
+        let n = Normal::new(p.x, p.y, 0f32);
+        (pw, n.from(self).normalize(), p_error)
     }
 }
 
@@ -162,7 +204,19 @@ fn test_cylinder() {
     let r = Ray::z_axis();
 
     match c.intersect(&r) {
-        None => assert!(false),
+        None    => panic!("expected a hit"),
         Some(t) => assert!((t.time - 9.5f32).abs() < 1e-4f32),
     }
 }
+
+#[test]
+fn test_cylinder_extent() {
+    let c = Cylinder::unit().rotate3(FRAC_PI_2, 0f32, 0f32).translate(&Vector::new(0f32, 0f32, 10f32));
+
+    assert!(c.intersect(&Ray::new_bounded(&Point::origin(), &Vector::unit_z(), 0f32, 9f32)).is_none());
+
+    match c.intersect(&Ray::new_bounded(&Point::origin(), &Vector::unit_z(), 9.75f32, 20f32)) {
+        None    => panic!("expected a hit"),
+        Some(t) => assert!((t.time - 10.5f32).abs() < 1e-4f32),
+    }
+}
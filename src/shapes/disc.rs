@@ -52,6 +52,38 @@ impl Disc {
     pub fn unit() -> Disc {
         Disc::new(1f32)
     }
+
+    // The hit along ray, which is in object space, if it lies within the
+    // disc, with the point hit and its azimuth.
+    fn hit(&self, ray : &Ray) -> Option<(f32, Point, f32)> {
+        if ray.direction.z.abs() < 1e-7f32 { return None; }
+
+        // a ray leaving the surface, which may start on it, must not hit it
+        // again at thit = 0
+        let thit = -ray.origin.z / ray.direction.z;
+        if thit <= ray.t_min || thit > ray.t_max {
+            return None;
+        }
+
+        // the point is put exactly on the surface, so has no error
+        let mut phit = ray.at_time(thit);
+        phit.z = 0f32;
+
+        let dist2 = phit.x * phit.x + phit.y * phit.y;
+        if dist2 > (self.outer_radius * self.outer_radius) || dist2 < (self.inner_radius * self.inner_radius) {
+            return None;
+        }
+
+        let mut phi = phit.y.atan2(phit.x);
+        if phi < 0f32 {
+            phi += 2f32 * PI;
+        }
+        if phi > self.phi_max {
+            return None;
+        }
+
+        Some((thit, phit, phi))
+    }
 }
 
 impl Default for Disc {
@@ -80,42 +112,34 @@ impl Shape for Disc {
     }
 
     fn intersect(&self, r : &Ray) -> Option<ShapeIntersection> {
-        let ray = r.to(self);
-
-        if ray.direction.z.abs() < 1e-7f32 { return None; }
-
-        let thit = -ray.origin.z / ray.direction.z;
-        if thit < 0f32 {
-            return None;
-        }
-
-        let phit = ray.at_time(thit);
+        let (thit, phit, phi) = self.hit(&r.to(self))?;
 
         let dist2 = phit.x * phit.x + phit.y * phit.y;
-        if dist2 > (self.outer_radius * self.outer_radius) || dist2 < (self.inner_radius * self.inner_radius) {
-            return None;
-        }
-
-        let mut phi = phit.y.atan2(phit.x);
-        if phi < 0f32 {
-            phi += 2f32 * PI;
-        }
-        if phi > self.phi_max {
-            return None;
-        }
-
         let u = phi / self.phi_max;
         let v = 1f32 - ((dist2.sqrt() - self.inner_radius) / (self.outer_radius - self.inner_radius));
 
         let dpdu = (self.phi_max / FRAC_PI_2) * Vector::new(-self.phi_max * phit.y, self.phi_max * phit.x, 0f32);
         let dpdv = ((self.outer_radius - self.inner_radius) / self.outer_radius) * Vector::new(-phit.x / (1f32-v), -phit.y / (1f32-v), 0f32);
 
-        let normal = dpdu.cross(&dpdv).normalize().to_normal().face_forward(&ray.direction);
+        let normal = dpdu.cross(&dpdv).normalize().to_normal();
 
         let dndu = Normal::new(0f32, 0f32, 0f32);
         let dndv = Normal::new(0f32, 0f32, 0f32);
 
-        return Some(ShapeIntersection::new(*r, thit, SurfaceContext::new(phit, normal, (u, v), (dpdu, dpdv), (dndu, dndv))));
+        Some(ShapeIntersection::new(*r, thit, SurfaceContext::new(phit, normal, (u, v), (dpdu, dpdv), (dndu, dndv))))
+    }
+
+    fn intersects(&self, r : &Ray) -> bool {
+        self.hit(&r.to(self)).is_some()
+    }
+
+    fn sample(&self, (u0, u1) : (f32, f32)) -> (Point, Normal, Vector) {
+        // area grows with the square of the radius
+        let ri2 = self.inner_radius * self.inner_radius;
+        let r = (ri2 + u0 * (self.outer_radius * self.outer_radius - ri2)).sqrt();
+        let phi = u1 * self.phi_max;
+        let (p, p_error) = Point::new(r * phi.cos(), r * phi.sin(), 0f32).from_with_error(self, &Vector::zero());
+        (p, Normal::unit_z().from(self).normalize(), p_error)
     }
 }
 
@@ -6,6 +6,7 @@ pub mod plane;
 pub mod shape;
 pub mod sphere;
 pub mod surface_context;
+pub mod triangle;
 
 pub use cone::*;
 pub use cylinder::*;
@@ -15,3 +16,4 @@ pub use plane::*;
 pub use shape::*;
 pub use sphere::*;
 pub use surface_context::*;
+pub use triangle::*;
@@ -18,6 +18,29 @@ impl Plane {
     pub fn unit() -> Plane {
         Plane::new(0.5f32, 0.5f32)
     }
+
+    // The hit along ray, which is in object space, if it lies within the
+    // plane's extent, with the point hit.
+    fn hit(&self, ray : &Ray) -> Option<(f32, Point)> {
+        if ray.direction.z.abs() < 1e-7f32 { return None; }
+
+        // a ray leaving the surface, which may start on it, must not hit it
+        // again at thit = 0
+        let thit = -ray.origin.z / ray.direction.z;
+        if thit <= ray.t_min || thit > ray.t_max {
+            return None;
+        }
+
+        // the point is put exactly on the surface, so has no error
+        let mut phit = ray.at_time(thit);
+        phit.z = 0f32;
+
+        if phit.x.abs() > self.dx || phit.y.abs() > self.dy {
+            return None;
+        }
+
+        Some((thit, phit))
+    }
 }
 
 impl Default for Plane {
@@ -46,20 +69,7 @@ impl Shape for Plane {
     }
 
     fn intersect(&self, r : &Ray) -> Option<ShapeIntersection> {
-        let ray = r.to(self);
-
-        if ray.direction.z.abs() < 1e-7f32 { return None; }
-
-        let thit = -ray.origin.z / ray.direction.z;
-        if thit < 0f32 {
-            return None;
-        }
-
-        let phit = ray.at_time(thit);
-
-        if phit.x.abs() > self.dx || phit.y.abs() > self.dy {
-            return None;
-        }
+        let (thit, phit) = self.hit(&r.to(self))?;
 
         let u = (phit.x + self.dx) / (self.dx * 2f32);
         let v = (phit.y + self.dy) / (self.dy * 2f32);
@@ -67,12 +77,22 @@ impl Shape for Plane {
         let dpdu = Vector::unit_x();
         let dpdv = Vector::unit_y();
 
-        let normal = Normal::new(0f32, 0f32, 1f32).face_forward(&ray.direction);
+        let normal = Normal::new(0f32, 0f32, 1f32);
 
         let dndu = Normal::new(0f32, 0f32, 0f32);
         let dndv = Normal::new(0f32, 0f32, 0f32);
 
-        return Some(ShapeIntersection::new(*r, thit, SurfaceContext::new(phit, normal, (u, v), (dpdu, dpdv), (dndu, dndv))));
+        Some(ShapeIntersection::new(*r, thit, SurfaceContext::new(phit, normal, (u, v), (dpdu, dpdv), (dndu, dndv))))
+    }
+
+    fn intersects(&self, r : &Ray) -> bool {
+        self.hit(&r.to(self)).is_some()
+    }
+
+    fn sample(&self, (u0, u1) : (f32, f32)) -> (Point, Normal, Vector) {
+        let p = Point::new((2f32 * u0 - 1f32) * self.dx, (2f32 * u1 - 1f32) * self.dy, 0f32);
+        let (p, p_error) = p.from_with_error(self, &Vector::zero());
+        (p, Normal::unit_z().from(self).normalize(), p_error)
     }
 }
 
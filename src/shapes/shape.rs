@@ -1,4 +1,4 @@
-use crate::geometry::{BoundingBox, Ray, HasTransform};
+use crate::geometry::{BoundingBox, Ray, Point, Vector, Normal, Transform, HasTransform};
 use crate::shapes::SurfaceContext;
 
 pub trait Shape : HasTransform + Send + Sync {
@@ -9,14 +9,75 @@ pub trait Shape : HasTransform + Send + Sync {
 
     fn intersect(&self, r : &Ray) -> Option<ShapeIntersection>;
 
+    // Whether r hits the shape at all, for shadow rays.  Shapes override this
+    // to skip working out the surface around the hit.
     fn intersects(&self, r : &Ray) -> bool {
-        match self.intersect(r) {
-            None => false,
-            Some(_) => true,
-        }
+        self.intersect(r).is_some()
+    }
+
+    // Chooses a point on the surface uniformly by area, returning it, the
+    // surface normal there and a bound on the point's error, in world space.
+    fn sample(&self, u : (f32, f32)) -> (Point, Normal, Vector);
+
+    // The density, by world area, of the points sample() chooses.  Areas are
+    // exact for transforms that scale uniformly.
+    fn pdf(&self) -> f32 {
+        1f32 / (self.surface_area() * area_scale(self.get_transform()))
+    }
+
+    // Chooses a point on the surface to light the point of context, returning
+    // it, the surface normal there, and its density by solid angle as seen
+    // from context.  The default samples by area and converts.
+    fn sample_from(&self, context : &SurfaceContext, u : (f32, f32)) -> Option<(Point, Normal, f32)> {
+        sample_by_area(self, context, u)
+    }
+
+    // The density with which sample_from() chooses the point seen from
+    // context in direction wi, by solid angle.
+    fn pdf_from(&self, context : &SurfaceContext, wi : &Vector) -> f32 {
+        pdf_by_area(self, context, wi)
     }
 }
 
+// The default sample_from(), for shapes that override it only in some cases.
+pub fn sample_by_area<S : Shape + ?Sized>(shape : &S, context : &SurfaceContext, u : (f32, f32)) -> Option<(Point, Normal, f32)> {
+    let (p, n, _) = shape.sample(u);
+    let pdf = solid_angle_pdf(shape.pdf(), &context.p, &p, &n);
+    if pdf.is_finite() && pdf > 0f32 { Some((p, n, pdf)) } else { None }
+}
+
+// The default pdf_from(), to match sample_by_area().
+pub fn pdf_by_area<S : Shape + ?Sized>(shape : &S, context : &SurfaceContext, wi : &Vector) -> f32 {
+    match shape.intersect(&context.spawn_ray(wi)) {
+        None    => 0f32,
+        Some(i) => {
+            let hit = i.context.from(shape.get_transform());
+            let pdf = solid_angle_pdf(shape.pdf(), &context.p, &hit.p, &hit.n);
+            if pdf.is_finite() { pdf } else { 0f32 }
+        },
+    }
+}
+
+// How much t scales areas by, assuming that it scales equally in every
+// direction.
+pub fn area_scale(t : &Transform) -> f32 {
+    let m = &t.to_world;
+    let det = m[0] * (m[5] * m[10] - m[6] * m[9]) - m[1] * (m[4] * m[10] - m[6] * m[8]) + m[2] * (m[4] * m[9] - m[5] * m[8]);
+    det.abs().powf(2f32 / 3f32)
+}
+
+// Converts a density by area at p, with normal n, to one by solid angle as
+// seen from the point from.
+fn solid_angle_pdf(pdf : f32, from : &Point, p : &Point, n : &Normal) -> f32 {
+    let d = *p - *from;
+    let dist2 = d.magnitude_squared();
+    if dist2 == 0f32 {
+        return 0f32;
+    }
+    let cos = n.to_vector().dot(&d).abs() / dist2.sqrt();
+    pdf * dist2 / cos
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct ShapeIntersection {
     pub ray : Ray,
@@ -30,3 +91,201 @@ impl ShapeIntersection {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+    use std::sync::Arc;
+    use crate::geometry::{Point, Vector, Normal, Trans};
+    use crate::shapes::{Sphere, Cylinder, Disc, Cone, Paraboloid, Plane, Triangle, TriangleMesh};
+
+    // Normals follow the parameterization rather than the ray, and for all of
+    // the quadrics that means pointing out of the surface.
+    #[test]
+    fn test_normals_face_outward() {
+        let cases : Vec<(Box<dyn Shape>, Point, Vector)> = vec![
+            (Box::new(Sphere::unit()),     Point::new(0f32, 0f32, -5f32),  Vector::unit_z()),
+            (Box::new(Sphere::unit()),     Point::new(0.1f32, 5f32, 0f32), -Vector::unit_y()),
+            (Box::new(Cylinder::unit()),   Point::new(5f32, 0f32, 0.1f32), -Vector::unit_x()),
+            (Box::new(Cone::unit()),       Point::new(5f32, 0f32, 0.25f32), -Vector::unit_x()),
+            (Box::new(Paraboloid::unit()), Point::new(5f32, 0f32, 0.5f32), -Vector::unit_x()),
+            (Box::new(Disc::unit()),       Point::new(0.2f32, 0f32, 5f32), -Vector::unit_z()),
+            (Box::new(Plane::unit()),      Point::new(0.2f32, 0f32, 5f32), -Vector::unit_z()),
+        ];
+
+        for (ix, (s, o, d)) in cases.iter().enumerate() {
+            let i = s.intersect(&Ray::new(o, d)).unwrap_or_else(|| panic!("case {} missed", ix));
+            assert!(i.context.n.to_vector().dot(d) < 0f32, "case {} faces inward", ix);
+            assert!((i.context.n.magnitude() - 1f32).abs() < 1e-5f32);
+        }
+    }
+
+    // The normal of a sphere is its point over its radius, and of a cylinder
+    // the same without z, so the normal changes with u and v as those do.
+    #[test]
+    fn test_normal_derivatives() {
+        let cases : Vec<(Box<dyn Shape>, Vector)> = vec![
+            (Box::new(Sphere::new(2f32)),                                 Vector::new(1f32, 1f32, 1f32)),
+            (Box::new(Sphere::new_partial(2f32, (-1f32, 1.5f32), 4f32)), Vector::new(1f32, 1f32, 1f32)),
+            (Box::new(Cylinder::new_partial(2f32, 3f32, 4f32)),          Vector::new(1f32, 1f32, 0f32)),
+        ];
+        for (ix, (s, keep)) in cases.iter().enumerate() {
+            let c = s.intersect(&Ray::new(&Point::new(0.3f32, -5f32, 0.4f32), &Vector::unit_y())).unwrap().context;
+            let expected = |dp : Vector| Vector::new(dp.x * keep.x, dp.y * keep.y, dp.z * keep.z) / 2f32;
+            assert!((c.dndu.to_vector() - expected(c.dpdu)).magnitude() < 1e-4f32, "case {} dndu {:?}", ix, c.dndu);
+            assert!((c.dndv.to_vector() - expected(c.dpdv)).magnitude() < 1e-4f32, "case {} dndv {:?}", ix, c.dndv);
+        }
+    }
+
+    // Each of the shapes, turned and placed about offset.
+    fn transformed_about(offset : Vector) -> Vec<Box<dyn Shape>> {
+        let t = |v : Vector| Transform::translation(&offset) + Transform::rotation(0.5f32, &Vector::new(1f32, 1f32, 0f32).normalize()) + Transform::translation(&v);
+        let mesh = Arc::new(TriangleMesh::new(vec![0, 1, 2], vec![Point::new(0f32, 0f32, 0f32), Point::new(1f32, 0f32, 0f32), Point::new(0f32, 2f32, 0f32)], None, None));
+        vec![
+            Box::new(Sphere::new(2f32).transform(&t(Vector::new(1f32, 0f32, 0f32)))),
+            Box::new(Sphere::new_partial(1f32, (-0.5f32, 0.8f32), 4f32).transform(&t(Vector::zero()))),
+            Box::new(Cylinder::new_partial(1f32, 2f32, 5f32).transform(&t(Vector::unit_y()))),
+            Box::new(Disc::new_partial_annulus(0.5f32, 2f32, 5f32).transform(&t(Vector::unit_z()))),
+            Box::new(Cone::new_partial(1f32, 2f32, 0.5f32, 1.5f32, 5f32).transform(&t(Vector::zero()))),
+            Box::new(Paraboloid::new_partial(1f32, 2f32, 0.5f32, 2f32, 5f32).transform(&t(Vector::zero()))),
+            Box::new(Plane::new(1f32, 2f32).transform(&t(Vector::zero()))),
+            Box::new(Triangle::new(Arc::new(mesh.transform(&t(Vector::unit_x()))), 0)),
+        ]
+    }
+
+    fn transformed() -> Vec<Box<dyn Shape>> {
+        transformed_about(Vector::zero())
+    }
+
+    fn grid() -> Vec<(f32, f32)> {
+        (0..10).flat_map(|i| (0..10).map(move |j| ((i as f32 + 0.5f32) / 10f32, (j as f32 + 0.5f32) / 10f32))).collect()
+    }
+
+    // intersects() agrees with intersect(), including at the edges of the
+    // partial shapes and for rays that stop short.
+    #[test]
+    fn test_intersects() {
+        for (ix, s) in transformed().iter().enumerate() {
+            let c = s.world_bound().centroid();
+            let mut hits = 0;
+            for (u, v) in grid() {
+                let o = Point::new(c.x + 6f32 * u - 3f32, c.y + 6f32 * v - 3f32, c.z - 5f32);
+                for t_max in [0.6f32, 0.9f32, 1.5f32].iter() {
+                    let r = Ray::new_bounded(&o, &Vector::new(0.1f32, 0.2f32, 5f32), 0f32, *t_max);
+                    assert_eq!(s.intersects(&r), s.intersect(&r).is_some(), "case {} ray {:?}", ix, r);
+                    if s.intersects(&r) {
+                        hits += 1;
+                    }
+                }
+            }
+            assert!(hits > 0, "case {} never hit", ix);
+        }
+    }
+
+    // Rays spawned from a hit far from the origin, where a fixed offset
+    // would be lost in rounding, never find the surface they left again,
+    // whichever way they go.  On a sphere, the hit is as close to it as its
+    // error says, and the rays start on the side they leave by.
+    #[test]
+    fn test_spawned_rays_leave_surface() {
+        let far = Vector::new(20000f32, -40000f32, 10000f32);
+        for (ix, s) in transformed_about(far).iter().enumerate() {
+            let c = s.world_bound().centroid();
+            let mut hits = 0;
+            for (u, v) in grid() {
+                let o = Point::new(c.x + 6f32 * u - 3f32, c.y + 6f32 * v - 3f32, c.z - 5f32);
+                let d = Vector::new(0.1f32, 0.2f32, 5f32);
+                let hit = match s.intersect(&Ray::new(&o, &d)) {
+                    Some(i) => i.context.from(s.get_transform()),
+                    None    => continue,
+                };
+                hits += 1;
+                let n = hit.n.to_vector();
+                for w in [d, -d, n, -n].iter() {
+                    if let Some(j) = s.intersect(&hit.spawn_ray(w)) {
+                        assert!(j.time * w.magnitude() > 1e-3f32, "case {} hit itself at {} going {:?}", ix, j.time, w);
+                    }
+                }
+            }
+            assert!(hits > 0, "case {} never hit", ix);
+        }
+
+        let sphere = Sphere::new(2f32).translate(&far);
+        for (u, v) in grid() {
+            let o = Point::new(far.x + 2f32 * u - 1f32, far.y + 2f32 * v - 1f32, far.z - 5f32);
+            let hit = sphere.intersect(&Ray::new(&o, &Vector::unit_z())).unwrap().context.from(&sphere);
+            let distance = |p : &Point| ((p.x as f64 - far.x as f64).powi(2) + (p.y as f64 - far.y as f64).powi(2) + (p.z as f64 - far.z as f64).powi(2)).sqrt();
+            assert!((distance(&hit.p) - 2f64).abs() <= hit.p_error.magnitude() as f64, "{} is not within {:?} of the sphere", hit.p, hit.p_error);
+
+            let n = hit.n.to_vector();
+            assert!(distance(&hit.spawn_ray(&n).origin) > 2f64, "ray leaving from {} starts inside", hit.p);
+            assert!(distance(&hit.spawn_ray(&-n).origin) < 2f64, "ray entering from {} starts outside", hit.p);
+
+            // a segment through the inside to where the ray leaves again
+            // is clear of the surface at both ends
+            let exit = sphere.intersect(&hit.spawn_ray(&Vector::unit_z())).unwrap().context.from(&sphere);
+            assert!(sphere.intersect(&hit.spawn_ray_to_context(&exit)).is_none(), "segment from {} to {} hit the sphere", hit.p, exit.p);
+        }
+    }
+
+    // Sampled points are on the surface, where intersect() would find them,
+    // with the same normals.
+    #[test]
+    fn test_samples_on_surface() {
+        for (ix, s) in transformed().iter().enumerate() {
+            for u in grid() {
+                let (p, n, _) = s.sample(u);
+                assert!((n.magnitude() - 1f32).abs() < 1e-4f32, "case {} normal not unit length", ix);
+                let o = p + n.to_vector() * 0.1f32;
+                let i = s.intersect(&Ray::new(&o, &-n.to_vector())).unwrap_or_else(|| panic!("case {} sample {:?} missed", ix, u));
+                let hit = i.context.from(s.get_transform());
+                assert!(hit.p.distance(&p) < 1e-3f32, "case {} sample {:?} at {} not {}", ix, u, p, hit.p);
+                assert!(hit.n.to_vector().dot(&n.to_vector()) > 0.999f32, "case {} sample {:?} normal differs", ix, u);
+            }
+        }
+    }
+
+    #[test]
+    fn test_area() {
+        let area = |s : &dyn Shape| 1f32 / s.pdf();
+        assert!((area(&Sphere::new(1f32).scale(&Vector::new(2f32, 2f32, 2f32))) - 16f32 * PI).abs() < 1e-3f32);
+        assert!((area(&Cylinder::new(1f32, 2f32)) - 4f32 * PI).abs() < 1e-4f32);
+        assert!((area(&Disc::new_annulus(1f32, 2f32)) - 3f32 * PI).abs() < 1e-4f32);
+        assert!((area(&Cone::new(1f32, 1f32)) - PI * 2f32.sqrt()).abs() < 1e-4f32);
+        assert!((area(&Paraboloid::new(1f32, 1f32)) - PI / 6f32 * (5f32.powf(1.5f32) - 1f32)).abs() < 1e-4f32);
+        assert!((area(&Plane::new(1f32, 2f32).scale(&Vector::new(3f32, 3f32, 3f32))) - 72f32).abs() < 1e-3f32);
+    }
+
+    // Averaging 1 / pdf over the sampled directions estimates the solid angle
+    // the shape fills, which for a sphere is known exactly.
+    #[test]
+    fn test_sphere_solid_angle() {
+        let context = SurfaceContext::new(Point::new(0f32, 0f32, -4f32), Normal::unit_z(), (0f32, 0f32), (Vector::unit_x(), Vector::unit_y()), (Normal::zero(), Normal::zero()));
+        let sphere = Sphere::new(1f32).translate(&Vector::new(0f32, 0f32, 1f32));
+        let expected = 2f32 * PI * (1f32 - (1f32 - 1f32 / 25f32).sqrt());
+
+        let samples = grid();
+        let mut sum = 0f32;
+        for u in samples.iter() {
+            let (p, n, pdf) = sphere.sample_from(&context, *u).unwrap();
+            let wi = (p - context.p).normalize();
+            assert!(n.to_vector().dot(&wi) < 0f32, "sample {:?} is on the far side", u);
+            assert!((pdf - sphere.pdf_from(&context, &wi)).abs() < 1e-3f32);
+            sum += 1f32 / pdf;
+        }
+        assert!((sum / samples.len() as f32 - expected).abs() < 1e-4f32);
+        assert_eq!(sphere.pdf_from(&context, &Vector::unit_x()), 0f32);
+
+        // sampling by area instead, the estimate for a disc facing the point
+        // is its solid angle, 2 pi (1 - d / sqrt(d^2 + r^2)) from distance d
+        let disc = Disc::new(1f32).translate(&Vector::new(0f32, 0f32, 1f32));
+        let expected = 2f32 * PI * (1f32 - 5f32 / 26f32.sqrt());
+        let mut sum = 0f32;
+        for u in samples.iter() {
+            let (_, _, pdf) = disc.sample_from(&context, *u).unwrap();
+            sum += 1f32 / pdf;
+        }
+        assert!((sum / samples.len() as f32 - expected).abs() < 1e-5f32);
+    }
+}
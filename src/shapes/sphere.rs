@@ -2,9 +2,10 @@ use log::*;
 use std::default::Default;
 use std::f32::consts::PI;
 
-use crate::geometry::{Transform, Trans, TransMut, HasTransform, BoundingBox, Ray, Point, Vector};
-use crate::math::quadratic;
-use crate::shapes::{Shape, ShapeIntersection, SurfaceContext};
+use crate::geometry::{Transform, Trans, TransMut, HasTransform, BoundingBox, Ray, Point, Vector, Normal};
+use crate::efloat::EFloat;
+use crate::math::{quadratic_efloat, gamma};
+use crate::shapes::{Shape, ShapeIntersection, SurfaceContext, area_scale, sample_by_area, pdf_by_area};
 
 #[derive(Copy, Clone, Debug)]
 pub struct Sphere {
@@ -24,8 +25,8 @@ impl Sphere {
 		    radius:    radius,
 		    z_min:    -radius,
 		    z_max:     radius,
-		    theta_min: 0f32,
-		    theta_max: PI,
+		    theta_min: PI,
+		    theta_max: 0f32,
 		    phi_max:   2f32 * PI
         }
     }
@@ -33,7 +34,7 @@ impl Sphere {
     pub fn new_partial(radius : f32, (z_min, z_max) : (f32, f32), phi_max : f32) -> Sphere {
         let z_min = z_min.max(-radius).min(radius);
         let z_max = z_max.max(-radius).min(radius);
-        let phi_max = phi_max.max(0f32).min(2f32 * PI);
+        let phi_max = phi_max.clamp(0f32, 2f32 * PI);
 
         Sphere {
             transform: Transform::identity(),
@@ -49,6 +50,100 @@ impl Sphere {
     pub fn unit() -> Sphere {
         Sphere::new(0.5f32)
     }
+
+    fn is_complete(&self) -> bool {
+        self.z_min <= -self.radius && self.z_max >= self.radius && self.phi_max >= 2f32 * PI
+    }
+
+    // The centre, radius and the cosine of the half-angle of the cone of
+    // directions that the sphere fills as seen from the point of context, in
+    // world space, if it is outside a complete sphere.
+    fn visible_cone(&self, context : &SurfaceContext) -> Option<(Point, f32, f32)> {
+        if !self.is_complete() {
+            return None
+        }
+        let center = Point::origin().from(self);
+        // a point on the sphere itself is moved off it towards the centre,
+        // as a ray leaving it would be, so that it is never seen as outside
+        let p = &context.spawn_ray(&(center - context.p)).origin;
+        let radius = self.radius * area_scale(&self.transform).sqrt();
+        let sin2_theta_max = radius * radius / p.distance_squared(&center);
+        if sin2_theta_max >= 1f32 {
+            return None
+        }
+        Some((center, radius, (1f32 - sin2_theta_max).sqrt()))
+    }
+
+    // The nearest hit along r that lies within the surface's bounds, in
+    // object space, with the point hit, a bound on its error and its azimuth.
+    // Roots that might, given their error, lie before the start of the ray
+    // are passed over, so that a ray leaving the sphere cannot hit it again.
+    fn hit(&self, r : &Ray) -> Option<(f32, Point, Vector, f32)> {
+        let (ray, o_err, d_err) = r.to_with_error(self);
+        let (ox, oy, oz) = (EFloat::new(ray.origin.x, o_err.x), EFloat::new(ray.origin.y, o_err.y), EFloat::new(ray.origin.z, o_err.z));
+        let (dx, dy, dz) = (EFloat::new(ray.direction.x, d_err.x), EFloat::new(ray.direction.y, d_err.y), EFloat::new(ray.direction.z, d_err.z));
+        let radius = EFloat::from(self.radius);
+
+        let a = dx * dx + dy * dy + dz * dz;
+        let b = EFloat::from(2f32) * (dx * ox + dy * oy + dz * oz);
+        let c = ox * ox + oy * oy + oz * oz - radius * radius;
+
+        let (t0, t1) = quadratic_efloat(a, b, c)?;
+        if t0.v > ray.t_max || t1.lower_bound() <= ray.t_min {
+            return None
+        }
+
+        let mut thit = t0;
+        if thit.lower_bound() <= ray.t_min {
+            thit = t1;
+            if thit.v > ray.t_max {
+                return None
+            }
+        }
+
+        // the point is moved back onto the sphere, which leaves less error
+        // than finding it along the ray
+        let point_at = |t : f32| {
+            let mut p = ray.at_time(t);
+            let s = self.radius / p.distance(&Point::origin());
+            p = Point::new(p.x * s, p.y * s, p.z * s);
+            if p.x == 0f32 && p.y == 0f32 {
+                p.x = 1e-5f32 * self.radius;
+            }
+            p
+        };
+
+        let mut phit = point_at(thit.v);
+
+        let mut phi = phit.y.atan2(phit.x);
+        if phi < 0f32 {
+            phi += 2f32 * PI;
+        }
+
+        debug!("sphere.phi  = {:?}", phi);
+
+        if (self.z_min > -self.radius && phit.z < self.z_min) || (self.z_max < self.radius && phit.z > self.z_max) || phi > self.phi_max {
+            if thit.v == t1.v || t1.v > ray.t_max {
+                return None
+            }
+
+            thit = t1;
+
+            phit = point_at(thit.v);
+
+            phi = phit.y.atan2(phit.x);
+            if phi < 0f32 {
+                phi += 2f32 * PI;
+            }
+
+            if (self.z_min > -self.radius && phit.z < self.z_min) || (self.z_max < self.radius && phit.z > self.z_max) || phi > self.phi_max {
+                return None
+            }
+        }
+
+        let p_error = (phit - Point::origin()).abs() * gamma(5);
+        Some((thit.v, phit, p_error, phi))
+    }
 }
 
 impl Default for Sphere {
@@ -77,85 +172,97 @@ impl Shape for Sphere {
     }
 
     fn intersect(&self, r : &Ray) -> Option<ShapeIntersection> {
-        let ray = r.to(self);
-
-        let a = ray.direction.magnitude_squared();
-        let b = 2f32 * ray.direction.dot(&ray.origin.sub_p(&Point::origin()));
-        let c = ray.origin.distance_squared(&Point::origin()) - (self.radius * self.radius);
-
-        match quadratic(a, b, c) {
-            None => { None },
-            Some((t0, t1)) => {
-                let mut thit = if t0 >= 0f32 { t0 } else { t1 };
-                if thit < 0f32 {
-                    return None
-                }
+        let (thit, phit, p_error, phi) = self.hit(r)?;
 
-                let mut phit = ray.at_time(thit);
-                if phit.x == 0f32 && phit.y == 0f32 {
-                    phit.x = 1e-5f32 * self.radius;
-                }
-
-                let mut phi = phit.y.atan2(phit.x);
-                if phi < 0f32 {
-                    phi += 2f32 * PI;
-                }
+        let u = phi / self.phi_max;
+        let theta = (phit.z / self.radius).clamp(-1f32, 1f32).acos();
+        let v = (theta - self.theta_min) / (self.theta_max - self.theta_min);
 
-                debug!("sphere.phi  = {:?}", phi);
+        let zr = (phit.x*phit.x + phit.y*phit.y).sqrt();
+        let cosphi = phit.x / zr;
+        let sinphi = phit.y / zr;
 
-                if (self.z_min > -self.radius && phit.z < self.z_min) || (self.z_max < self.radius && phit.z > self.z_max) || phi > self.phi_max {
-                    if thit == t1 {
-                        return None
-                    }
+        let dpdu = Vector::new(-self.phi_max * phit.y, self.phi_max * phit.x, 0f32);
+        let dpdv = (self.theta_max - self.theta_min) * Vector::new(phit.z * cosphi, phit.z * sinphi, -self.radius * theta.sin());
 
-                    thit = t1;
+        let normal = dpdu.cross(&dpdv).normalize().to_normal();
 
-                    phit = ray.at_time(thit);
-                    if phit.x == 0f32 && phit.y == 0f32 {
-                        phit.x = 1e-5f32 * self.radius;
-                    }
+        let d2pduu = -self.phi_max * self.phi_max * Vector::new(phit.x, phit.y, 0f32);
+        let d2pduv = (self.theta_max - self.theta_min) * phit.z * self.phi_max * Vector::new(-sinphi, cosphi, 0f32);
+        let d2pdvv = -(self.theta_max - self.theta_min) * (self.theta_max - self.theta_min) * Vector::new(phit.x, phit.y, phit.z);
 
-                    phi = phit.y.atan2(phit.x);
-                    if phi < 0f32 {
-                        phi += 2f32 * PI;
-                    }
+        let c_e = dpdu.dot(&dpdu);
+        let c_f = dpdu.dot(&dpdv);
+        let c_g = dpdv.dot(&dpdv);
+        let n = dpdu.cross(&dpdv).normalize();
+        let e = n.dot(&d2pduu);
+        let f = n.dot(&d2pduv);
+        let g = n.dot(&d2pdvv);
 
-                    if (self.z_min > -self.radius && phit.z < self.z_min) || (self.z_max < self.radius && phit.z > self.z_max) || phi > self.phi_max {
-                        return None
-                    }
-                }
+        let egf2 = 1f32 / (c_e*c_g - c_f*c_f);
 
-                let u = phi / self.phi_max;
-                let theta = (phit.z / self.radius).min(1f32).max(-1f32).acos();
-                let v = (theta - self.theta_min) / (self.theta_max - self.theta_min);
+        // the Weingarten equations; unlike the normal, these keep their length
+        let dndu = (f*c_f - e*c_g) * egf2 * dpdu + (e*c_f - f*c_e) * egf2 * dpdv;
+        let dndv = (g*c_f - f*c_g) * egf2 * dpdu + (f*c_f - g*c_e) * egf2 * dpdv;
 
-                let zr = (phit.x*phit.x + phit.y*phit.y).sqrt();
-                let cosphi = phit.x / zr;
-                let sinphi = phit.y / zr;
-
-                let dpdu = Vector::new(-self.phi_max * phit.y, self.phi_max * phit.x, 0f32);
-                let dpdv = (self.theta_max - self.theta_min) * Vector::new(phit.z * cosphi, phit.z * sinphi, -self.radius * theta.sin());
-
-                let normal = dpdu.cross(&dpdv).normalize().to_normal().face_forward(&ray.direction);
-
-                let d2pduu = -self.phi_max * self.phi_max * Vector::new(phit.x, phit.y, 0f32);
-                let d2pduv = (self.theta_max - self.theta_min) * phit.z * self.phi_max * Vector::new(-sinphi, cosphi, 0f32);
-                let d2pdvv = -(self.theta_max - self.theta_min) * (self.theta_max * self.theta_min) * Vector::new(phit.x, phit.y, phit.z);
+        let mut context = SurfaceContext::new(phit, normal, (u, v), (dpdu, dpdv), (Normal::new(dndu.x, dndu.y, dndu.z), Normal::new(dndv.x, dndv.y, dndv.z)));
+        context.p_error = p_error;
+        Some(ShapeIntersection::new(*r, thit, context))
+    }
 
-                let c_e = dpdu.dot(&dpdu);
-                let c_f = dpdu.dot(&dpdv);
-                let c_g = dpdv.dot(&dpdv);
-                let n = dpdu.cross(&dpdv).normalize();
-                let e = n.dot(&d2pduu);
-                let f = n.dot(&d2pduv);
-                let g = n.dot(&d2pdvv);
+    fn intersects(&self, r : &Ray) -> bool {
+        self.hit(r).is_some()
+    }
 
-                let egf2 = 1f32 / (c_e*c_g - c_f*c_f);
+    fn sample(&self, (u0, u1) : (f32, f32)) -> (Point, Normal, Vector) {
+        // by Archimedes, area is uniform in z
+        let z = self.z_min + u0 * (self.z_max - self.z_min);
+        let phi = u1 * self.phi_max;
+        let rho = (self.radius * self.radius - z * z).max(0f32).sqrt();
+        let mut p = Point::new(rho * phi.cos(), rho * phi.sin(), z);
+        let s = self.radius / p.distance(&Point::origin());
+        p = Point::new(p.x * s, p.y * s, p.z * s);
+        let (pw, p_error) = p.from_with_error(self, &((p - Point::origin()).abs() * gamma(5)));
+        let n = Normal::new(p.x, p.y, p.z) / self.radius;
+        (pw, n.from(self).normalize(), p_error)
+    }
 
-                let dndu = ((f*c_f - e*c_e) * egf2 * dpdu + (e*c_f - f*c_e) * egf2 * dpdv).to_normal();
-                let dndv = ((g*c_f - f*c_e) * egf2 * dpdu + (f*c_f - g*c_e) * egf2 * dpdv).to_normal();
+    // Seen from outside, a complete sphere is sampled uniformly over the cone
+    // of directions it fills, which wastes no samples on its far side.
+    fn sample_from(&self, context : &SurfaceContext, (u0, u1) : (f32, f32)) -> Option<(Point, Normal, f32)> {
+        let (center, radius, cos_theta_max) = match self.visible_cone(context) {
+            None    => return sample_by_area(self, context, (u0, u1)),
+            Some(c) => c,
+        };
+
+        let dc = context.p.distance(&center);
+        let cos_theta = (1f32 - u0) + u0 * cos_theta_max;
+        let sin2_theta = (1f32 - cos_theta * cos_theta).max(0f32);
+        let phi = u1 * 2f32 * PI;
+
+        // the angle, at the centre, from the direction of p to the sampled point
+        let ds = dc * cos_theta - (radius * radius - dc * dc * sin2_theta).max(0f32).sqrt();
+        let cos_alpha = ((dc * dc + radius * radius - ds * ds) / (2f32 * dc * radius)).clamp(-1f32, 1f32);
+        let sin_alpha = (1f32 - cos_alpha * cos_alpha).max(0f32).sqrt();
+
+        let wc = (center - context.p).normalize();
+        let (wc_x, wc_y) = wc.coordinate_system();
+        let n = -(sin_alpha * phi.cos() * wc_x + sin_alpha * phi.sin() * wc_y + cos_alpha * wc);
+        let p = center + n * radius;
+
+        Some((p, n.to_normal(), 1f32 / (2f32 * PI * (1f32 - cos_theta_max))))
+    }
 
-                return Some(ShapeIntersection::new(*r, thit, SurfaceContext::new(phit, normal, (u, v), (dpdu, dpdv), (dndu, dndv))));
+    fn pdf_from(&self, context : &SurfaceContext, wi : &Vector) -> f32 {
+        match self.visible_cone(context) {
+            None => pdf_by_area(self, context, wi),
+            Some((center, _, cos_theta_max)) => {
+                let wc = (center - context.p).normalize();
+                if wi.normalize().dot(&wc) < cos_theta_max {
+                    0f32
+                } else {
+                    1f32 / (2f32 * PI * (1f32 - cos_theta_max))
+                }
             },
         }
     }
@@ -1,8 +1,11 @@
-use crate::geometry::{Normal, Point, Vector};
+use crate::geometry::{Normal, Point, Vector, Ray, HasTransform};
 
 #[derive(Copy, Clone, Debug)]
 pub struct SurfaceContext {
     pub p : Point,
+    // a bound on how far p may be from the true surface in each dimension,
+    // from rounding in finding it
+    pub p_error : Vector,
     pub n : Normal,
     pub u : f32,
     pub v : f32,
@@ -10,19 +13,113 @@ pub struct SurfaceContext {
     pub dpdv : Vector,
     pub dndu : Normal,
     pub dndv : Normal,
+    pub shading : ShadingGeometry,
+}
+
+// Geometry used for shading, which may differ from the true surface geometry
+// (e.g. interpolated vertex normals on a triangle mesh).
+#[derive(Copy, Clone, Debug)]
+pub struct ShadingGeometry {
+    pub n : Normal,
+    pub dpdu : Vector,
+    pub dpdv : Vector,
+    pub dndu : Normal,
+    pub dndv : Normal,
 }
 
 impl SurfaceContext {
     pub fn new(p : Point, n : Normal, (u, v) : (f32, f32), (dpdu, dpdv) : (Vector, Vector), (dndu, dndv) : (Normal, Normal)) -> SurfaceContext {
         SurfaceContext {
             p: p,
+            p_error: Vector::zero(),
             n: n,
             u: u,
             v: v,
             dpdu: dpdu,
             dpdv: dpdv,
             dndu: dndu,
-            dndv: dndv
+            dndv: dndv,
+            shading: ShadingGeometry {
+                n: n,
+                dpdu: dpdu,
+                dpdv: dpdv,
+                dndu: dndu,
+                dndv: dndv,
+            }
+        }
+    }
+
+    // A context for a point that is not on a surface, such as one within a
+    // medium, which has no normal.
+    pub fn for_point(p : Point) -> SurfaceContext {
+        SurfaceContext::new(p, Normal::zero(), (0f32, 0f32), (Vector::zero(), Vector::zero()), (Normal::zero(), Normal::zero()))
+    }
+
+    pub fn set_shading_geometry(&mut self, n : Normal, (dpdu, dpdv) : (Vector, Vector), (dndu, dndv) : (Normal, Normal)) {
+        self.shading = ShadingGeometry {
+            n: n,
+            dpdu: dpdu,
+            dpdv: dpdv,
+            dndu: dndu,
+            dndv: dndv,
+        };
+    }
+
+    // The same context in the world space of t, for shading.
+    pub fn from<T : HasTransform>(&self, t : &T) -> SurfaceContext {
+        let (p, p_error) = self.p.from_with_error(t, &self.p_error);
+        SurfaceContext {
+            p: p,
+            p_error: p_error,
+            n: self.n.from(t).normalize(),
+            u: self.u,
+            v: self.v,
+            dpdu: self.dpdu.from(t),
+            dpdv: self.dpdv.from(t),
+            dndu: self.dndu.from(t),
+            dndv: self.dndv.from(t),
+            shading: ShadingGeometry {
+                n: self.shading.n.from(t).normalize(),
+                dpdu: self.shading.dpdu.from(t),
+                dpdv: self.shading.dpdv.from(t),
+                dndu: self.shading.dndu.from(t),
+                dndv: self.shading.dndv.from(t),
+            }
         }
     }
+
+    // Where a ray leaving the surface in direction w should start: far enough
+    // along the normal, on w's side, that p's error cannot leave it on the
+    // other side, and rounded away from the surface so that it is surely
+    // clear of it.  Points that are not on a surface need no offset.
+    fn offset_origin(&self, w : &Vector) -> Point {
+        let n = self.n.to_vector();
+        let mut offset = n * n.abs().dot(&self.p_error);
+        if w.dot(&n) < 0f32 {
+            offset = -offset;
+        }
+        let away = |p : f32, o : f32| if o > 0f32 { p.next_up() } else if o < 0f32 { p.next_down() } else { p };
+        let p = self.p + offset;
+        Point::new(away(p.x, offset.x), away(p.y, offset.y), away(p.z, offset.z))
+    }
+
+    // A ray leaving the surface in direction d, started just off the surface
+    // on d's side so that it does not hit the surface it left.
+    pub fn spawn_ray(&self, d : &Vector) -> Ray {
+        Ray::new(&self.offset_origin(d), d)
+    }
+
+    // A ray from the surface that stops just short of p, offset as for spawn_ray.
+    pub fn spawn_ray_to(&self, p : &Point) -> Ray {
+        let origin = self.offset_origin(&(*p - self.p));
+        Ray::new_segment(&origin, p)
+    }
+
+    // As spawn_ray_to(), towards another context whose end of the segment is
+    // offset by its own error in the same way.
+    pub fn spawn_ray_to_context(&self, c : &SurfaceContext) -> Ray {
+        let origin = self.offset_origin(&(c.p - self.p));
+        let target = c.offset_origin(&(origin - c.p));
+        Ray::new_segment(&origin, &target)
+    }
 }
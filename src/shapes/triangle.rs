@@ -0,0 +1,366 @@
+use std::sync::Arc;
+
+use crate::geometry::{Transform, Trans, TransMut, HasTransform, BoundingBox, Ray, Point, Vector, Normal};
+use crate::math::gamma;
+use crate::shapes::{Shape, ShapeIntersection, SurfaceContext};
+
+// Vertex data shared by all of the triangles of a mesh.  Indices are stored
+// three per triangle, and the optional normal and uv buffers, when present,
+// are indexed the same way as the positions.
+#[derive(Clone, Debug)]
+pub struct TriangleMesh {
+    transform   : Transform,
+    pub indices : Vec<usize>,
+    pub p       : Vec<Point>,
+    pub n       : Option<Vec<Normal>>,
+    pub uv      : Option<Vec<(f32, f32)>>,
+}
+
+impl TriangleMesh {
+    pub fn new(indices : Vec<usize>, p : Vec<Point>, n : Option<Vec<Normal>>, uv : Option<Vec<(f32, f32)>>) -> TriangleMesh {
+        assert!(indices.len() % 3 == 0, "triangle mesh index count must be a multiple of 3");
+        assert!(indices.iter().all(|&i| i < p.len()), "triangle mesh index out of range");
+        assert!(n.as_ref().map_or(true, |n| n.len() == p.len()), "triangle mesh normal count must match vertex count");
+        assert!(uv.as_ref().map_or(true, |uv| uv.len() == p.len()), "triangle mesh uv count must match vertex count");
+
+        TriangleMesh {
+            transform: Transform::identity(),
+            indices:   indices,
+            p:         p,
+            n:         n,
+            uv:        uv,
+        }
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    pub fn triangles(mesh : &Arc<TriangleMesh>) -> Vec<Triangle> {
+        (0..mesh.triangle_count()).map(|ix| Triangle::new(mesh.clone(), ix)).collect()
+    }
+}
+
+impl HasTransform for TriangleMesh {
+    fn get_transform(&self) -> &Transform {
+        &self.transform
+    }
+}
+
+impl Trans for TriangleMesh {
+    type Output=TriangleMesh;
+
+    fn transform(&self, t : &Transform) -> TriangleMesh {
+        TriangleMesh { transform: *t + self.transform, .. self.clone() }
+    }
+}
+
+impl TransMut for TriangleMesh {
+    fn transform_self(&mut self, t : &Transform) {
+        self.transform = *t + self.transform;
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Triangle {
+    mesh  : Arc<TriangleMesh>,
+    index : usize,
+}
+
+impl Triangle {
+    pub fn new(mesh : Arc<TriangleMesh>, index : usize) -> Triangle {
+        Triangle { mesh: mesh, index: index }
+    }
+
+    pub fn mesh(&self) -> &Arc<TriangleMesh> {
+        &self.mesh
+    }
+
+    fn vertex_indices(&self) -> (usize, usize, usize) {
+        let i = &self.mesh.indices[(3 * self.index)..(3 * self.index + 3)];
+        (i[0], i[1], i[2])
+    }
+
+    fn positions(&self) -> (Point, Point, Point) {
+        let (i0, i1, i2) = self.vertex_indices();
+        (self.mesh.p[i0], self.mesh.p[i1], self.mesh.p[i2])
+    }
+
+    // Triangles without per-vertex uvs are parameterized by their barycentric coordinates.
+    fn uvs(&self) -> ((f32, f32), (f32, f32), (f32, f32)) {
+        match self.mesh.uv {
+            None => ((0f32, 0f32), (1f32, 0f32), (0f32, 1f32)),
+            Some(ref uv) => {
+                let (i0, i1, i2) = self.vertex_indices();
+                (uv[i0], uv[i1], uv[i2])
+            }
+        }
+    }
+
+    // Watertight ray/triangle intersection (Woop et al. 2013): the vertices are
+    // translated, permuted and sheared into a space where the ray runs down +z
+    // from the origin, and the edge functions are evaluated there in 2D.  Edges
+    // shared by two triangles produce the same edge function values for both,
+    // so a ray can never pass between them.  Returns the hit along ray, which
+    // is in object space, with its barycentric coordinates.
+    fn hit(&self, ray : &Ray) -> Option<(f32, (f32, f32, f32))> {
+        let (p0, p1, p2) = self.positions();
+
+        let o = ray.origin;
+        let mut p0t = Vector::new(p0.x - o.x, p0.y - o.y, p0.z - o.z);
+        let mut p1t = Vector::new(p1.x - o.x, p1.y - o.y, p1.z - o.z);
+        let mut p2t = Vector::new(p2.x - o.x, p2.y - o.y, p2.z - o.z);
+
+        let kz = ray.direction.abs().max_dimension();
+        let kx = if kz == 2 { 0 } else { kz + 1 };
+        let ky = if kx == 2 { 0 } else { kx + 1 };
+        let d = ray.direction.permute(kx, ky, kz);
+        p0t = p0t.permute(kx, ky, kz);
+        p1t = p1t.permute(kx, ky, kz);
+        p2t = p2t.permute(kx, ky, kz);
+
+        let sx = -d.x / d.z;
+        let sy = -d.y / d.z;
+        let sz = 1f32 / d.z;
+        p0t.x += sx * p0t.z;
+        p0t.y += sy * p0t.z;
+        p1t.x += sx * p1t.z;
+        p1t.y += sy * p1t.z;
+        p2t.x += sx * p2t.z;
+        p2t.y += sy * p2t.z;
+
+        let mut e0 = p1t.x * p2t.y - p1t.y * p2t.x;
+        let mut e1 = p2t.x * p0t.y - p2t.y * p0t.x;
+        let mut e2 = p0t.x * p1t.y - p0t.y * p1t.x;
+
+        // fall back to double precision when a ray passes exactly through an edge
+        if e0 == 0f32 || e1 == 0f32 || e2 == 0f32 {
+            e0 = ((p1t.x as f64) * (p2t.y as f64) - (p1t.y as f64) * (p2t.x as f64)) as f32;
+            e1 = ((p2t.x as f64) * (p0t.y as f64) - (p2t.y as f64) * (p0t.x as f64)) as f32;
+            e2 = ((p0t.x as f64) * (p1t.y as f64) - (p0t.y as f64) * (p1t.x as f64)) as f32;
+        }
+
+        if (e0 < 0f32 || e1 < 0f32 || e2 < 0f32) && (e0 > 0f32 || e1 > 0f32 || e2 > 0f32) {
+            return None;
+        }
+
+        let det = e0 + e1 + e2;
+        if det == 0f32 {
+            return None;
+        }
+
+        p0t.z *= sz;
+        p1t.z *= sz;
+        p2t.z *= sz;
+        let t_scaled = e0 * p0t.z + e1 * p1t.z + e2 * p2t.z;
+        if det < 0f32 && (t_scaled > ray.t_min * det || t_scaled < ray.t_max * det) {
+            return None;
+        }
+        if det > 0f32 && (t_scaled < ray.t_min * det || t_scaled > ray.t_max * det) {
+            return None;
+        }
+
+        let inv_det = 1f32 / det;
+        let t = t_scaled * inv_det;
+
+        // t must be beyond the start of the ray by more than its error can
+        // be, which is bounded by following the rounding through the
+        // transformed vertices and the edge functions
+        let max_abs = |a : f32, b : f32, c : f32| a.abs().max(b.abs()).max(c.abs());
+        let max_xt = max_abs(p0t.x, p1t.x, p2t.x);
+        let max_yt = max_abs(p0t.y, p1t.y, p2t.y);
+        let max_zt = max_abs(p0t.z, p1t.z, p2t.z);
+        let delta_x = gamma(5) * (max_xt + max_zt);
+        let delta_y = gamma(5) * (max_yt + max_zt);
+        let delta_z = gamma(3) * max_zt;
+        let delta_e = 2f32 * (gamma(2) * max_xt * max_yt + delta_y * max_xt + delta_x * max_yt);
+        let max_e = max_abs(e0, e1, e2);
+        let delta_t = 3f32 * (gamma(3) * max_e * max_zt + delta_e * max_zt + delta_z * max_e) * inv_det.abs();
+        if t <= ray.t_min + delta_t {
+            return None;
+        }
+
+        Some((t, (e0 * inv_det, e1 * inv_det, e2 * inv_det)))
+    }
+}
+
+impl HasTransform for Triangle {
+    fn get_transform(&self) -> &Transform {
+        self.mesh.get_transform()
+    }
+}
+
+impl Shape for Triangle {
+    fn bound(&self) -> BoundingBox {
+        let (p0, p1, p2) = self.positions();
+        BoundingBox::for_points(&[p0, p1, p2])
+    }
+
+    fn world_bound(&self) -> BoundingBox {
+        let (p0, p1, p2) = self.positions();
+        BoundingBox::for_points(&[p0.from(self), p1.from(self), p2.from(self)])
+    }
+
+    fn surface_area(&self) -> f32 {
+        let (p0, p1, p2) = self.positions();
+        0.5f32 * (p1 - p0).cross(&(p2 - p0)).magnitude()
+    }
+
+    fn intersect(&self, r : &Ray) -> Option<ShapeIntersection> {
+        let (thit, (b0, b1, b2)) = self.hit(&r.to(self))?;
+        let (p0, p1, p2) = self.positions();
+
+        let (uv0, uv1, uv2) = self.uvs();
+        let duv02 = (uv0.0 - uv2.0, uv0.1 - uv2.1);
+        let duv12 = (uv1.0 - uv2.0, uv1.1 - uv2.1);
+        let dp02 = p0 - p2;
+        let dp12 = p1 - p2;
+
+        let uv_det = duv02.0 * duv12.1 - duv02.1 * duv12.0;
+        let degenerate_uv = uv_det.abs() < 1e-8f32;
+        let (dpdu, dpdv) = if degenerate_uv {
+            (p2 - p0).cross(&(p1 - p0)).normalize().coordinate_system()
+        } else {
+            let inv_uv_det = 1f32 / uv_det;
+            ((dp02 * duv12.1 - dp12 * duv02.1) * inv_uv_det,
+             (dp12 * duv02.0 - dp02 * duv12.0) * inv_uv_det)
+        };
+
+        let phit = Point::new(b0 * p0.x + b1 * p1.x + b2 * p2.x,
+                              b0 * p0.y + b1 * p1.y + b2 * p2.y,
+                              b0 * p0.z + b1 * p1.z + b2 * p2.z);
+        let abs_sum = |a : f32, b : f32, c : f32| (b0 * a).abs() + (b1 * b).abs() + (b2 * c).abs();
+        let p_error = Vector::new(abs_sum(p0.x, p1.x, p2.x), abs_sum(p0.y, p1.y, p2.y), abs_sum(p0.z, p1.z, p2.z)) * gamma(7);
+        let u = b0 * uv0.0 + b1 * uv1.0 + b2 * uv2.0;
+        let v = b0 * uv0.1 + b1 * uv1.1 + b2 * uv2.1;
+
+        let ng = dp02.cross(&dp12).normalize().to_normal();
+
+        match self.mesh.n {
+            None => {
+                let mut context = SurfaceContext::new(phit, ng, (u, v), (dpdu, dpdv), (Normal::zero(), Normal::zero()));
+                context.p_error = p_error;
+                Some(ShapeIntersection::new(*r, thit, context))
+            },
+            Some(ref n) => {
+                let (i0, i1, i2) = self.vertex_indices();
+                let (n0, n1, n2) = (n[i0], n[i1], n[i2]);
+
+                let ns = (n0 * b0 + n1 * b1 + n2 * b2).normalize();
+                let ns = if ns.magnitude_squared() > 0f32 { ns } else { ng };
+
+                // shading tangents follow dpdu, made orthogonal to the shading normal
+                let mut ss = dpdu.normalize();
+                let mut ts = ns.to_vector().cross(&ss);
+                if ts.magnitude_squared() > 0f32 {
+                    ts.normalize_self();
+                    ss = ts.cross(&ns.to_vector());
+                } else {
+                    let (s, t) = ns.to_vector().coordinate_system();
+                    ss = s;
+                    ts = t;
+                }
+
+                let (dndu, dndv) = if degenerate_uv {
+                    (Normal::zero(), Normal::zero())
+                } else {
+                    let dn1 = n0 - n2;
+                    let dn2 = n1 - n2;
+                    let inv_uv_det = 1f32 / uv_det;
+                    ((dn1 * duv12.1 - dn2 * duv02.1) * inv_uv_det,
+                     (dn2 * duv02.0 - dn1 * duv12.0) * inv_uv_det)
+                };
+
+                let mut context = SurfaceContext::new(phit, ng.face_forward(&ns.to_vector()), (u, v), (dpdu, dpdv), (Normal::zero(), Normal::zero()));
+                context.set_shading_geometry(ns, (ss, ts), (dndu, dndv));
+                context.p_error = p_error;
+                Some(ShapeIntersection::new(*r, thit, context))
+            },
+        }
+    }
+
+    fn intersects(&self, r : &Ray) -> bool {
+        self.hit(&r.to(self)).is_some()
+    }
+
+    fn sample(&self, (u0, u1) : (f32, f32)) -> (Point, Normal, Vector) {
+        let (p0, p1, p2) = self.positions();
+        let su0 = u0.sqrt();
+        let b0 = 1f32 - su0;
+        let b1 = u1 * su0;
+        let b2 = 1f32 - b0 - b1;
+        let p = Point::new(b0 * p0.x + b1 * p1.x + b2 * p2.x,
+                           b0 * p0.y + b1 * p1.y + b2 * p2.y,
+                           b0 * p0.z + b1 * p1.z + b2 * p2.z);
+        let abs_sum = |a : f32, b : f32, c : f32| (b0 * a).abs() + (b1 * b).abs() + (b2 * c).abs();
+        let p_error = Vector::new(abs_sum(p0.x, p1.x, p2.x), abs_sum(p0.y, p1.y, p2.y), abs_sum(p0.z, p1.z, p2.z)) * gamma(6);
+
+        // the same geometric normal as intersect() gives
+        let mut n = (p0 - p2).cross(&(p1 - p2)).normalize().to_normal();
+        if let Some(ref ns) = self.mesh.n {
+            let (i0, i1, i2) = self.vertex_indices();
+            n.face_forward_self(&(ns[i0] * b0 + ns[i1] * b1 + ns[i2] * b2).to_vector());
+        }
+        let (p, p_error) = p.from_with_error(self, &p_error);
+        (p, n.from(self).normalize(), p_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quad(n : Option<Vec<Normal>>) -> Arc<TriangleMesh> {
+        let p = vec![Point::new(-1f32, -1f32, 5f32), Point::new(1f32, -1f32, 5f32), Point::new(1f32, 1f32, 5f32), Point::new(-1f32, 1f32, 5f32)];
+        Arc::new(TriangleMesh::new(vec![0, 1, 2, 0, 2, 3], p, n, None))
+    }
+
+    #[test]
+    fn test_triangle_intersect() {
+        let ts = TriangleMesh::triangles(&quad(None));
+        assert_eq!(ts.len(), 2);
+
+        let r = Ray::new(&Point::new(0.5f32, -0.5f32, 0f32), &Vector::unit_z());
+        let i = ts[0].intersect(&r).unwrap();
+        assert!((i.time - 5f32).abs() < 1e-5f32);
+        assert!((i.context.u - 0.5f32).abs() < 1e-5f32);
+        assert!((i.context.v - 0.25f32).abs() < 1e-5f32);
+        assert!(ts[1].intersect(&r).is_none());
+
+        assert!(ts[0].intersect(&Ray::new_bounded(&Point::new(0.5f32, -0.5f32, 0f32), &Vector::unit_z(), 0f32, 4f32)).is_none());
+        assert!(ts[0].intersect(&Ray::new(&Point::new(0.5f32, -0.5f32, 0f32), &-Vector::unit_z())).is_none());
+    }
+
+    #[test]
+    fn test_triangle_shared_edge_is_watertight() {
+        let ts = TriangleMesh::triangles(&quad(None));
+
+        // rays along the diagonal shared by both triangles must hit at least one of them
+        for ix in 1..1000 {
+            let s = -1f32 + (ix as f32) / 500f32;
+            let o = Point::new(0.3f32, -0.2f32, 0f32);
+            let r = Ray::new(&o, &(Point::new(s, s, 5f32) - o));
+            assert!(ts.iter().any(|t| t.intersect(&r).is_some()));
+        }
+    }
+
+    #[test]
+    fn test_triangle_shading_normals() {
+        let n = vec![Normal::new(0f32, 0f32, -1f32); 4];
+        let ts = TriangleMesh::triangles(&quad(Some(n)));
+
+        let i = ts[0].intersect(&Ray::new(&Point::new(0.5f32, -0.5f32, 0f32), &Vector::unit_z())).unwrap();
+        assert_eq!(i.context.shading.n, Normal::new(0f32, 0f32, -1f32));
+        assert_eq!(i.context.n, Normal::new(0f32, 0f32, -1f32));
+        assert!(i.context.shading.dpdu.dot(&i.context.shading.n.to_vector()).abs() < 1e-5f32);
+    }
+
+    #[test]
+    fn test_triangle_mesh_transform() {
+        let m = Arc::new(quad(None).translate(&Vector::new(0f32, 0f32, 5f32)));
+        let t = Triangle::new(m, 0);
+        let i = t.intersect(&Ray::new(&Point::new(0.5f32, -0.5f32, 0f32), &Vector::unit_z())).unwrap();
+        assert!((i.time - 10f32).abs() < 1e-5f32);
+        assert!((t.world_bound().min().z - 10f32).abs() < 1e-5f32);
+    }
+}
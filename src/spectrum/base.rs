@@ -0,0 +1,49 @@
+// Spectra for working out colours, not for rendering with: materials, lights,
+// the film and the integrators all carry RGB Colors, and multiply
+// reflectances per channel.  Sampled spectra are for turning physical data,
+// such as the emission of a blackbody, into the RGB the renderer uses.
+
+use std::ops::{Add, Mul};
+
+use crate::color::Color;
+
+pub trait Spectrum : Copy + Send + Sync + Add<Output=Self> + Mul<Output=Self> + Mul<f32, Output=Self> {
+    // A reflectance (or other unitless) spectrum with the given linear sRGB colour.
+    fn from_rgb(c : &Color) -> Self;
+
+    fn to_xyz(&self) -> [f32; 3];
+
+    fn to_rgb(&self) -> Color {
+        xyz_to_rgb(&self.to_xyz())
+    }
+
+    fn y(&self) -> f32 {
+        self.to_xyz()[1]
+    }
+
+    fn is_black(&self) -> bool;
+}
+
+// Linear sRGB (D65 white) to and from CIE XYZ.
+pub fn xyz_to_rgb(xyz : &[f32; 3]) -> Color {
+    Color::new( 3.240479f32 * xyz[0] - 1.53715f32 * xyz[1] - 0.498535f32 * xyz[2],
+               -0.969256f32 * xyz[0] + 1.875991f32 * xyz[1] + 0.041556f32 * xyz[2],
+                0.055648f32 * xyz[0] - 0.204043f32 * xyz[1] + 1.057311f32 * xyz[2])
+}
+
+pub fn rgb_to_xyz(c : &Color) -> [f32; 3] {
+    [0.412453f32 * c.r + 0.357580f32 * c.g + 0.180423f32 * c.b,
+     0.212671f32 * c.r + 0.715160f32 * c.g + 0.072169f32 * c.b,
+     0.019334f32 * c.r + 0.119193f32 * c.g + 0.950227f32 * c.b]
+}
+
+#[test]
+fn test_xyz_round_trip() {
+    let c = Color::new(0.2f32, 0.5f32, 0.9f32);
+    assert_eq!(xyz_to_rgb(&rgb_to_xyz(&c)), c);
+
+    // the sRGB white point is D65
+    let w = rgb_to_xyz(&Color::white());
+    let (x, y) = (w[0] / (w[0] + w[1] + w[2]), w[1] / (w[0] + w[1] + w[2]));
+    assert!((x - 0.3127f32).abs() < 1e-3 && (y - 0.3290f32).abs() < 1e-3);
+}
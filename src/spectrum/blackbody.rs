@@ -0,0 +1,55 @@
+use crate::color::Color;
+use crate::spectrum::{Spectrum, SampledSpectrum};
+
+// Planck's law: the spectral radiance of a blackbody at the given temperature
+// in kelvin, at a wavelength in nanometres, in W / (sr m^2 m).
+pub fn blackbody(lambda : f32, temperature : f32) -> f32 {
+    if temperature <= 0f32 {
+        return 0f32;
+    }
+
+    let c  = 299792458f64;
+    let h  = 6.62606957e-34f64;
+    let kb = 1.3806488e-23f64;
+
+    let l = lambda as f64 * 1e-9f64;
+    let t = temperature as f64;
+    ((2f64 * h * c * c) / (l.powi(5) * (((h * c) / (l * kb * t)).exp() - 1f64))) as f32
+}
+
+// The linear sRGB colour of a blackbody at the given temperature in kelvin,
+// scaled to unit luminance, for giving lights a physically based tint.
+pub fn blackbody_color(temperature : f32) -> Color {
+    let s = SampledSpectrum::blackbody(temperature);
+    let c = s.to_rgb();
+    c / s.y()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wien_peak() {
+        let t = 5000f32;
+        let peak = 2.897772e6f32 / t;
+        assert!(blackbody(peak, t) > blackbody(peak - 10f32, t));
+        assert!(blackbody(peak, t) > blackbody(peak + 10f32, t));
+        assert_eq!(blackbody(500f32, 0f32), 0f32);
+    }
+
+    #[test]
+    fn test_blackbody_color() {
+        // 6504K is close to, but not exactly, the sRGB (D65) white point
+        let d65 = blackbody_color(6504f32);
+        assert!((d65.r - d65.g).abs() < 0.1f32 && (d65.b - d65.g).abs() < 0.1f32, "{}", d65);
+
+        // incandescent light is orange, skylight is blue
+        let warm = blackbody_color(2700f32);
+        assert!(warm.r > warm.g && warm.g > warm.b);
+        let cool = blackbody_color(10000f32);
+        assert!(cool.b > cool.g && cool.g > cool.r);
+
+        assert!((warm.luminance() - 1f32).abs() < 1e-3f32);
+    }
+}
@@ -0,0 +1,31 @@
+// The CIE 1931 2° colour matching functions, using the multi-lobe Gaussian fit
+// from Wyman, Sloan and Shirley, "Simple Analytic Approximations to the CIE
+// XYZ Color Matching Functions" (JCGT 2013).  The fit is within the accuracy
+// of the tabulated data for rendering purposes and needs no tables.
+
+fn lobe(lambda : f32, mu : f32, sigma_lo : f32, sigma_hi : f32) -> f32 {
+    let t = (lambda - mu) / if lambda < mu { sigma_lo } else { sigma_hi };
+    (-0.5f32 * t * t).exp()
+}
+
+// Wavelengths are in nanometres.
+pub fn cie_x(lambda : f32) -> f32 {
+    1.056f32 * lobe(lambda, 599.8f32, 37.9f32, 31.0f32) + 0.362f32 * lobe(lambda, 442.0f32, 16.0f32, 26.7f32) - 0.065f32 * lobe(lambda, 501.1f32, 20.4f32, 26.2f32)
+}
+
+pub fn cie_y(lambda : f32) -> f32 {
+    0.821f32 * lobe(lambda, 568.8f32, 46.9f32, 40.5f32) + 0.286f32 * lobe(lambda, 530.9f32, 16.3f32, 31.1f32)
+}
+
+pub fn cie_z(lambda : f32) -> f32 {
+    1.217f32 * lobe(lambda, 437.0f32, 11.8f32, 36.0f32) + 0.681f32 * lobe(lambda, 459.0f32, 26.0f32, 13.8f32)
+}
+
+#[test]
+fn test_matching_functions() {
+    // y-bar is the photopic luminosity function, peaking at 1 near 555nm
+    assert!((cie_y(555f32) - 1f32).abs() < 0.01f32);
+    assert!(cie_y(555f32) > cie_y(540f32) && cie_y(555f32) > cie_y(570f32));
+    assert!(cie_x(600f32) > 1f32 && cie_z(445f32) > 1.7f32);
+    assert!(cie_x(800f32) < 1e-3f32 && cie_y(350f32) < 1e-3f32);
+}
@@ -0,0 +1,10 @@
+pub mod base;
+pub mod blackbody;
+pub mod cie;
+pub mod rgb;
+pub mod sampled;
+
+pub use base::*;
+pub use blackbody::*;
+pub use cie::*;
+pub use sampled::*;
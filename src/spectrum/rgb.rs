@@ -0,0 +1,26 @@
+use crate::color::Color;
+use crate::spectrum::{Spectrum, rgb_to_xyz};
+
+// The RGB spectrum: three samples, one per sRGB primary.  Cheap, and what the
+// renderer carries throughout.
+impl Spectrum for Color {
+    fn from_rgb(c : &Color) -> Color {
+        *c
+    }
+
+    fn to_xyz(&self) -> [f32; 3] {
+        rgb_to_xyz(self)
+    }
+
+    fn to_rgb(&self) -> Color {
+        *self
+    }
+
+    fn y(&self) -> f32 {
+        self.luminance()
+    }
+
+    fn is_black(&self) -> bool {
+        Color::is_black(self)
+    }
+}
@@ -0,0 +1,291 @@
+use std::default::Default;
+use std::ops::{Index, Add, Sub, Mul, Div};
+use std::sync::OnceLock;
+
+use crate::color::Color;
+use crate::spectrum::{Spectrum, cie_x, cie_y, cie_z, blackbody};
+
+pub const SAMPLED_LAMBDA_START : f32 = 400f32;
+pub const SAMPLED_LAMBDA_END   : f32 = 700f32;
+pub const SPECTRAL_SAMPLES     : usize = 60;
+
+// A spectrum as the average value over each of SPECTRAL_SAMPLES equal bins
+// between SAMPLED_LAMBDA_START and SAMPLED_LAMBDA_END nanometres.
+#[derive(Copy, Clone, Debug)]
+pub struct SampledSpectrum {
+    pub c : [f32; SPECTRAL_SAMPLES],
+}
+
+impl SampledSpectrum {
+    pub fn new(v : f32) -> SampledSpectrum {
+        SampledSpectrum { c: [v; SPECTRAL_SAMPLES] }
+    }
+
+    pub fn zero() -> SampledSpectrum {
+        SampledSpectrum::new(0f32)
+    }
+
+    // Bin i covers [lambda(i), lambda(i + 1)).
+    pub fn lambda(i : usize) -> f32 {
+        SAMPLED_LAMBDA_START + (SAMPLED_LAMBDA_END - SAMPLED_LAMBDA_START) * (i as f32) / (SPECTRAL_SAMPLES as f32)
+    }
+
+    // Averages f over each bin.
+    pub fn from_fn<F : Fn(f32) -> f32>(f : F) -> SampledSpectrum {
+        const STEPS : usize = 8;
+        let mut s = SampledSpectrum::zero();
+        for i in 0..SPECTRAL_SAMPLES {
+            let (l0, l1) = (SampledSpectrum::lambda(i), SampledSpectrum::lambda(i + 1));
+            let sum : f32 = (0..STEPS).map(|k| f(l0 + (l1 - l0) * (k as f32 + 0.5f32) / (STEPS as f32))).sum();
+            s.c[i] = sum / (STEPS as f32);
+        }
+        s
+    }
+
+    // Resamples a piecewise-linear spectrum given as (wavelength, value)
+    // pairs sorted by wavelength.  Values beyond either end are held constant.
+    pub fn from_samples(lambdas : &[f32], values : &[f32]) -> SampledSpectrum {
+        assert!(!lambdas.is_empty() && lambdas.len() == values.len());
+        let n = lambdas.len();
+        SampledSpectrum::from_fn(|l| {
+            if l <= lambdas[0] {
+                values[0]
+            } else if l >= lambdas[n - 1] {
+                values[n - 1]
+            } else {
+                let i = lambdas.iter().position(|&x| x > l).unwrap() - 1;
+                let t = (l - lambdas[i]) / (lambdas[i + 1] - lambdas[i]);
+                values[i] * (1f32 - t) + values[i + 1] * t
+            }
+        })
+    }
+
+    // Emission of a blackbody at the given temperature in kelvin, scaled so
+    // its peak (by Wien's displacement law) is 1.
+    pub fn blackbody(temperature : f32) -> SampledSpectrum {
+        let peak = blackbody(2.897772e6f32 / temperature, temperature);
+        SampledSpectrum::from_fn(|l| blackbody(l, temperature) / peak)
+    }
+
+    pub fn max_component(&self) -> f32 {
+        self.c.iter().cloned().fold(f32::NEG_INFINITY, f32::max)
+    }
+
+    pub fn clamp(&self, lo : f32, hi : f32) -> SampledSpectrum {
+        self.map(|v| v.clamp(lo, hi))
+    }
+
+    pub fn sqrt(&self) -> SampledSpectrum {
+        self.map(f32::sqrt)
+    }
+
+    pub fn exp(&self) -> SampledSpectrum {
+        self.map(f32::exp)
+    }
+
+    fn map<F : Fn(f32) -> f32>(&self, f : F) -> SampledSpectrum {
+        let mut s = *self;
+        for v in s.c.iter_mut() {
+            *v = f(*v);
+        }
+        s
+    }
+
+    fn zip<F : Fn(f32, f32) -> f32>(&self, o : &SampledSpectrum, f : F) -> SampledSpectrum {
+        let mut s = *self;
+        for (v, w) in s.c.iter_mut().zip(o.c.iter()) {
+            *v = f(*v, *w);
+        }
+        s
+    }
+}
+
+struct SpectrumTables {
+    x            : SampledSpectrum,
+    y            : SampledSpectrum,
+    z            : SampledSpectrum,
+    y_integral   : f32,
+    white        : SampledSpectrum,
+    cyan         : SampledSpectrum,
+    magenta      : SampledSpectrum,
+    yellow       : SampledSpectrum,
+    red          : SampledSpectrum,
+    green        : SampledSpectrum,
+    blue         : SampledSpectrum,
+}
+
+// Smits, "An RGB-to-Spectrum Conversion for Reflectances" (JGT 1999): smooth
+// basis spectra for white and the six primaries and secondaries, sampled at
+// ten evenly spaced wavelengths from 380nm to 720nm.
+const SMITS_WHITE   : [f32; 10] = [1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000];
+const SMITS_CYAN    : [f32; 10] = [0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000];
+const SMITS_MAGENTA : [f32; 10] = [1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959];
+const SMITS_YELLOW  : [f32; 10] = [0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840];
+const SMITS_RED     : [f32; 10] = [0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149];
+const SMITS_GREEN   : [f32; 10] = [0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025];
+const SMITS_BLUE    : [f32; 10] = [1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496];
+
+fn tables() -> &'static SpectrumTables {
+    static TABLES : OnceLock<SpectrumTables> = OnceLock::new();
+    TABLES.get_or_init(|| {
+        let smits_lambdas : Vec<f32> = (0..10).map(|i| 380f32 + 340f32 * (i as f32) / 9f32).collect();
+        let smits = |v : &[f32; 10]| SampledSpectrum::from_samples(&smits_lambdas, v);
+
+        // the integral of y-bar over the whole visible range, at 1nm steps
+        let y_integral = (360..=830).map(|l| cie_y(l as f32)).sum();
+
+        SpectrumTables {
+            x:          SampledSpectrum::from_fn(cie_x),
+            y:          SampledSpectrum::from_fn(cie_y),
+            z:          SampledSpectrum::from_fn(cie_z),
+            y_integral: y_integral,
+            white:      smits(&SMITS_WHITE),
+            cyan:       smits(&SMITS_CYAN),
+            magenta:    smits(&SMITS_MAGENTA),
+            yellow:     smits(&SMITS_YELLOW),
+            red:        smits(&SMITS_RED),
+            green:      smits(&SMITS_GREEN),
+            blue:       smits(&SMITS_BLUE),
+        }
+    })
+}
+
+impl Spectrum for SampledSpectrum {
+    // Smits' method: white up to the smallest component, then the secondary
+    // shared by the two larger components, then the primary of the largest.
+    fn from_rgb(c : &Color) -> SampledSpectrum {
+        let t = tables();
+        let (r, g, b) = (c.r, c.g, c.b);
+
+        let s = if r <= g && r <= b {
+            if g <= b {
+                t.white * r + t.cyan * (g - r) + t.blue * (b - g)
+            } else {
+                t.white * r + t.cyan * (b - r) + t.green * (g - b)
+            }
+        } else if g <= r && g <= b {
+            if r <= b {
+                t.white * g + t.magenta * (r - g) + t.blue * (b - r)
+            } else {
+                t.white * g + t.magenta * (b - g) + t.red * (r - b)
+            }
+        } else if r <= g {
+            t.white * b + t.yellow * (r - b) + t.green * (g - r)
+        } else {
+            t.white * b + t.yellow * (g - b) + t.red * (r - g)
+        };
+
+        // as in pbrt, keep reflectances a little under 1 and never negative
+        (s * 0.94f32).clamp(0f32, f32::INFINITY)
+    }
+
+    fn to_xyz(&self) -> [f32; 3] {
+        let t = tables();
+        let scale = (SAMPLED_LAMBDA_END - SAMPLED_LAMBDA_START) / (SPECTRAL_SAMPLES as f32 * t.y_integral);
+        let dot = |m : &SampledSpectrum| self.c.iter().zip(m.c.iter()).map(|(a, b)| a * b).sum::<f32>() * scale;
+        [dot(&t.x), dot(&t.y), dot(&t.z)]
+    }
+
+    fn is_black(&self) -> bool {
+        self.c.iter().all(|&v| v == 0f32)
+    }
+}
+
+impl Index<usize> for SampledSpectrum {
+    type Output = f32;
+    fn index(&self, index : usize) -> &f32 {
+        &self.c[index]
+    }
+}
+
+impl Default for SampledSpectrum {
+    fn default() -> SampledSpectrum {
+        SampledSpectrum::zero()
+    }
+}
+
+impl Add<SampledSpectrum> for SampledSpectrum {
+    type Output=SampledSpectrum;
+    fn add(self, s : SampledSpectrum) -> SampledSpectrum {
+        self.zip(&s, |a, b| a + b)
+    }
+}
+
+impl Sub<SampledSpectrum> for SampledSpectrum {
+    type Output=SampledSpectrum;
+    fn sub(self, s : SampledSpectrum) -> SampledSpectrum {
+        self.zip(&s, |a, b| a - b)
+    }
+}
+
+impl Mul<SampledSpectrum> for SampledSpectrum {
+    type Output=SampledSpectrum;
+    fn mul(self, s : SampledSpectrum) -> SampledSpectrum {
+        self.zip(&s, |a, b| a * b)
+    }
+}
+
+impl Mul<f32> for SampledSpectrum {
+    type Output=SampledSpectrum;
+    fn mul(self, s : f32) -> SampledSpectrum {
+        self.map(|a| a * s)
+    }
+}
+
+impl Mul<SampledSpectrum> for f32 {
+    type Output=SampledSpectrum;
+    fn mul(self, s : SampledSpectrum) -> SampledSpectrum {
+        s * self
+    }
+}
+
+impl Div<f32> for SampledSpectrum {
+    type Output=SampledSpectrum;
+    fn div(self, s : f32) -> SampledSpectrum {
+        self.map(|a| a / s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn approx_eq(a : &SampledSpectrum, b : &SampledSpectrum, tolerance : f32) -> bool {
+        a.c.iter().zip(b.c.iter()).all(|(x, y)| (x - y).abs() <= tolerance)
+    }
+
+    #[test]
+    fn test_from_samples() {
+        assert!(approx_eq(&SampledSpectrum::from_samples(&[500f32], &[0.3f32]), &SampledSpectrum::new(0.3f32), 1e-6f32));
+        let ramp = SampledSpectrum::from_samples(&[400f32, 700f32], &[0f32, 3f32]);
+        assert!(approx_eq(&ramp, &SampledSpectrum::from_fn(|l| (l - 400f32) / 100f32), 1e-4f32));
+    }
+
+    #[test]
+    fn test_constant_spectrum_is_equal_energy_white() {
+        let xyz = SampledSpectrum::new(1f32).to_xyz();
+        // the 400-700nm range carries nearly all of the response
+        assert!((xyz[1] - 1f32).abs() < 0.03f32);
+        assert!((xyz[0] - xyz[1]).abs() < 0.03f32 && (xyz[2] - xyz[1]).abs() < 0.03f32);
+    }
+
+    #[test]
+    fn test_from_rgb_keeps_hue() {
+        let white = SampledSpectrum::from_rgb(&Color::white()).to_rgb();
+        for &c in [Color::new(0.8f32, 0.1f32, 0.1f32), Color::new(0.1f32, 0.6f32, 0.2f32), Color::new(0.2f32, 0.3f32, 0.7f32), Color::gray(0.5f32)].iter() {
+            // relative to the white reflectance, which is not exactly (1, 1, 1)
+            // because the conversion integrates against an equal-energy illuminant
+            let rgb = SampledSpectrum::from_rgb(&c).to_rgb().div_c(&white);
+            assert!((rgb.r - c.r).abs() < 0.08f32 && (rgb.g - c.g).abs() < 0.08f32 && (rgb.b - c.b).abs() < 0.08f32, "{} became {}", c, rgb);
+        }
+    }
+
+    #[test]
+    fn test_reflectances_multiply() {
+        // a red filter on a blue surface reflects (nearly) nothing
+        let r = SampledSpectrum::from_rgb(&Color::new(1f32, 0f32, 0f32));
+        let b = SampledSpectrum::from_rgb(&Color::new(0f32, 0f32, 1f32));
+        assert!((r * b).y() < 0.02f32);
+        assert!(r.y() > 0.1f32 && b.y() > 0.02f32);
+    }
+}
@@ -0,0 +1,48 @@
+use std::sync::Arc;
+
+use crate::shapes::SurfaceContext;
+use crate::textures::{Texture, TextureMapping2D};
+
+// Alternates between two textures on the unit squares of the (s, t) plane.
+pub struct CheckerboardTexture<T> {
+    mapping : Box<dyn TextureMapping2D>,
+    tex1    : Arc<dyn Texture<T>>,
+    tex2    : Arc<dyn Texture<T>>,
+}
+
+impl<T> CheckerboardTexture<T> {
+    pub fn new(mapping : Box<dyn TextureMapping2D>, tex1 : Arc<dyn Texture<T>>, tex2 : Arc<dyn Texture<T>>) -> CheckerboardTexture<T> {
+        CheckerboardTexture { mapping: mapping, tex1: tex1, tex2: tex2 }
+    }
+}
+
+impl<T> Texture<T> for CheckerboardTexture<T> {
+    fn evaluate(&self, context : &SurfaceContext) -> T {
+        let c = self.mapping.map(*context);
+        if (c.s.floor() as i32 + c.t.floor() as i32) % 2 == 0 {
+            self.tex1.evaluate(context)
+        } else {
+            self.tex2.evaluate(context)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::{Point, Normal, Vector};
+    use crate::textures::{ConstantTexture, UVMapping2D};
+
+    #[test]
+    fn test_checkerboard() {
+        let tex = CheckerboardTexture::new(Box::new(UVMapping2D::new(4f32, 4f32, 0f32, 0f32)), Arc::new(ConstantTexture::new(1f32)), Arc::new(ConstantTexture::new(0f32)));
+        let at = |u : f32, v : f32| {
+            let sc = SurfaceContext::new(Point::origin(), Normal::new(0f32, 0f32, 1f32), (u, v), (Vector::unit_x(), Vector::unit_y()), (Normal::new(0f32, 0f32, 0f32), Normal::new(0f32, 0f32, 0f32)));
+            tex.evaluate(&sc)
+        };
+        assert_eq!(at(0.1f32, 0.1f32), 1f32);
+        assert_eq!(at(0.3f32, 0.1f32), 0f32);
+        assert_eq!(at(0.3f32, 0.3f32), 1f32);
+        assert_eq!(at(0.1f32, 0.9f32), 0f32);
+    }
+}
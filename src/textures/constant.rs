@@ -0,0 +1,19 @@
+use crate::shapes::SurfaceContext;
+use crate::textures::Texture;
+
+#[derive(Copy, Clone, Debug)]
+pub struct ConstantTexture<T> {
+    pub value : T,
+}
+
+impl<T : Copy> ConstantTexture<T> {
+    pub fn new(value : T) -> ConstantTexture<T> {
+        ConstantTexture { value: value }
+    }
+}
+
+impl<T : Copy + Send + Sync> Texture<T> for ConstantTexture<T> {
+    fn evaluate(&self, _context : &SurfaceContext) -> T {
+        self.value
+    }
+}
@@ -1,3 +1,9 @@
+pub mod checkerboard;
+pub mod constant;
+pub mod texture;
 pub mod texture_mapping;
 
+pub use checkerboard::*;
+pub use constant::*;
+pub use texture::*;
 pub use texture_mapping::*;
@@ -0,0 +1,5 @@
+use crate::shapes::SurfaceContext;
+
+pub trait Texture<T> : Send + Sync {
+    fn evaluate(&self, context : &SurfaceContext) -> T;
+}
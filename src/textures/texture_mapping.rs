@@ -16,7 +16,7 @@ impl TextureContext {
     }
 }
 
-pub trait TextureMapping2D {
+pub trait TextureMapping2D : Send + Sync {
     fn map(&self, s : SurfaceContext) -> TextureContext;
 }
 